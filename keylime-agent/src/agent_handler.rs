@@ -5,6 +5,7 @@ use crate::common::JsonWrapper;
 use crate::{tpm, Error as KeylimeError, QuoteData};
 use actix_web::{http, web, HttpRequest, HttpResponse, Responder};
 use base64::{engine::general_purpose, Engine as _};
+use keylime::agent_data::{ClockChange, TpmClockInfo};
 use log::*;
 use serde::{Deserialize, Serialize};
 
@@ -15,6 +16,10 @@ pub(crate) struct AgentInfo {
     pub tpm_enc_alg: String,
     pub tpm_sign_alg: String,
     pub ak_handle: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tpm_clock_info: Option<TpmClockInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tpm_clock_change: Option<ClockChange>,
 }
 
 // This is an Info request which gets some information about this keylime agent
@@ -25,12 +30,18 @@ async fn info(
 ) -> impl Responder {
     debug!("Returning agent information");
 
+    // must unwrap here due to lock mechanism
+    // https://github.com/rust-lang-nursery/failure/issues/192
+    let agent_data = data.agent_data.lock().unwrap(); //#[allow_ci]
+
     let mut info = AgentInfo {
         agent_uuid: data.agent_uuid.clone(),
         tpm_hash_alg: data.hash_alg.to_string(),
         tpm_enc_alg: data.enc_alg.to_string(),
         tpm_sign_alg: data.sign_alg.to_string(),
         ak_handle: data.ak_handle.value(),
+        tpm_clock_info: agent_data.tpm_clock_info,
+        tpm_clock_change: agent_data.tpm_clock_change,
     };
 
     let response = JsonWrapper::success(info);
@@ -86,11 +97,19 @@ mod tests {
 
     #[actix_rt::test]
     async fn test_agent_info() {
-        let (mut quotedata, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let mut quotedata = QuoteData::fixture().await.unwrap(); //#[allow_ci]
         quotedata.hash_alg = keylime::algorithms::HashAlgorithm::Sha256;
         quotedata.enc_alg = keylime::algorithms::EncryptionAlgorithm::Rsa;
         quotedata.sign_alg = keylime::algorithms::SignAlgorithm::RsaSsa;
         quotedata.agent_uuid = "DEADBEEF".to_string();
+        let clock_info = TpmClockInfo {
+            clock: 1000,
+            reset_count: 1,
+            restart_count: 0,
+            safe: true,
+        };
+        quotedata.agent_data.get_mut().unwrap().tpm_clock_info = //#[allow_ci]
+            Some(clock_info);
         let data = web::Data::new(quotedata);
         let mut app = test::init_service(
             App::new()
@@ -111,6 +130,8 @@ mod tests {
         assert_eq!(result.results.tpm_hash_alg.as_str(), "sha256");
         assert_eq!(result.results.tpm_enc_alg.as_str(), "rsa");
         assert_eq!(result.results.tpm_sign_alg.as_str(), "rsassa");
+        assert_eq!(result.results.tpm_clock_info, Some(clock_info));
+        assert_eq!(result.results.tpm_clock_change, None);
 
         // Explicitly drop QuoteData to cleanup keys
         drop(data);
@@ -203,7 +203,7 @@ mod tests {
     #[actix_rt::test]
     #[cfg(feature = "testing")]
     async fn test_default_version() {
-        let (fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
         let quotedata = web::Data::new(fixture);
         let mut app = test::init_service(
             App::new()
@@ -231,7 +231,7 @@ mod tests {
         // Get the first supported API version
         let first = SUPPORTED_API_VERSIONS[0].to_string();
 
-        let (mut fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let mut fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
 
         // Set the API version with only the first supported version
         fixture.api_versions = vec![first];
@@ -261,7 +261,7 @@ mod tests {
     #[actix_rt::test]
     #[cfg(feature = "testing")]
     async fn test_misconfigured_version() {
-        let (mut fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let mut fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
 
         // Set the API version with empty Vec
         fixture.api_versions = vec![];
@@ -18,6 +18,8 @@ pub(crate) enum Error {
     },
     #[error("Keylime TPM error: {0}")]
     Tpm(#[from] keylime::tpm::TpmError),
+    #[error("Agent data error")]
+    AgentData(#[from] keylime::agent_data::AgentDataError),
     #[error("Invalid request")]
     #[allow(unused)]
     InvalidRequest,
@@ -99,6 +101,8 @@ pub(crate) enum Error {
     Receiver(String),
     #[error("List parser error")]
     ListParser(#[from] keylime::list_parser::ListParsingError),
+    #[error("IMA filter error")]
+    ImaFilter(#[from] keylime::ima::ImaFilterError),
     #[error("Zip error: {0}")]
     Zip(#[from] zip::result::ZipError),
     #[error("Certificate generation error")]
@@ -142,7 +142,7 @@ mod tests {
 
     #[cfg(feature = "testing")]
     async fn test_default(resource: Resource, allow: &str) {
-        let (fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
         let quotedata = web::Data::new(fixture);
         let mut app = test::init_service(
             App::new().app_data(quotedata).service(resource),
@@ -598,7 +598,6 @@ pub(crate) fn configure_keys_endpoints(cfg: &mut web::ServiceConfig) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    #[cfg(feature = "testing")]
     use crate::crypto::testing::{
         encrypt_aead, pkey_pub_from_pem, rsa_oaep_encrypt,
     };
@@ -656,7 +655,6 @@ mod tests {
         (ukey, vkey, k)
     }
 
-    #[cfg(feature = "testing")]
     fn prepare_encrypted_keys(
         key_len: usize,
         payload: Option<EncryptedData>,
@@ -871,10 +869,9 @@ mod tests {
         assert_eq!(result.code, 405);
     }
 
-    #[cfg(feature = "testing")]
     async fn test_u_or_v_key(key_len: usize, payload: Option<&[u8]>) {
         let test_config = KeylimeConfig::default();
-        let (mut fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let mut fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
 
         // Create temporary working directory and secure mount
         let temp_workdir = tempfile::tempdir().unwrap(); //#[allow_ci]
@@ -1068,22 +1065,19 @@ mod tests {
         drop(quotedata);
     }
 
-    #[cfg(feature = "testing")]
     #[actix_rt::test]
     async fn test_u_or_v_key_short() {
         test_u_or_v_key(AES_128_KEY_LEN, None).await;
     }
 
-    #[cfg(feature = "testing")]
     #[actix_rt::test]
     async fn test_u_or_v_key_long() {
         test_u_or_v_key(AES_256_KEY_LEN, None).await;
     }
 
-    #[cfg(feature = "testing")]
     #[actix_rt::test]
     async fn test_pubkey() {
-        let (fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
         let quotedata = web::Data::new(fixture);
         let mut app = test::init_service(
             App::new()
@@ -32,10 +32,8 @@
 #![allow(unused, missing_docs)]
 
 mod agent_handler;
-mod agent_registration;
 mod api;
 mod common;
-mod config;
 mod error;
 mod errors_handler;
 mod keys_handler;
@@ -47,7 +45,6 @@ mod revocation;
 mod secure_mount;
 
 use actix_web::{dev::Service, http, middleware, rt, web, App, HttpServer};
-use agent_registration::AgentRegistration;
 use base64::{engine::general_purpose, Engine as _};
 use clap::{Arg, Command as ClapApp};
 use common::*;
@@ -57,9 +54,12 @@ use futures::{
     try_join,
 };
 use keylime::{
-    crypto::{self, x509::CertificateBuilder},
+    agent_data::AgentData,
+    agent_registration::{self, AgentRegistration},
+    config,
+    crypto::{self, hash_ek_pubkey, x509::CertificateBuilder},
     device_id::{DeviceID, DeviceIDBuilder},
-    ima::MeasurementList,
+    ima::{ImaFilter, MeasurementList},
     list_parser::parse_list,
     registrar_client::RegistrarClientBuilder,
     serialization,
@@ -103,6 +103,8 @@ static NOTFOUND: &[u8] = b"Not Found";
 // handle quotes.
 #[derive(Debug)]
 pub struct QuoteData<'a> {
+    agent_data: Mutex<AgentData>,
+    agent_data_path: Option<PathBuf>,
     agent_uuid: String,
     ak_handle: KeyHandle,
     allow_payload_revocation_actions: bool,
@@ -117,16 +119,35 @@ pub struct QuoteData<'a> {
     )>,
     measuredboot_ml_file: Option<Mutex<fs::File>>,
     payload_tx: mpsc::Sender<payloads::PayloadMessage>,
+    pcr_banks: Vec<keylime::algorithms::HashAlgorithm>,
     priv_key: PKey<Private>,
     pub_key: PKey<Public>,
     revocation_tx: mpsc::Sender<revocation::RevocationMessage>,
     secure_mount: PathBuf,
     secure_size: String,
     sign_alg: keylime::algorithms::SignAlgorithm,
-    tpmcontext: Mutex<tpm::Context<'a>>,
+    tpmcontext: Mutex<Box<dyn tpm::TpmBackend + Send + 'a>>,
     work_dir: PathBuf,
 }
 
+/// Flushes the EK created by the agent when dropped, so that the transient
+/// EK is not left loaded in the TPM when the agent fails before or during
+/// the registration
+struct TransientEk<'a> {
+    ctx: tpm::Context<'a>,
+    handle: Option<KeyHandle>,
+}
+
+impl Drop for TransientEk<'_> {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            if let Err(e) = self.ctx.flush_context(handle.into()) {
+                error!("Failed to flush EK: {}", e);
+            }
+        }
+    }
+}
+
 #[actix_web::main]
 async fn main() -> Result<()> {
     // Print --help information
@@ -140,7 +161,7 @@ async fn main() -> Result<()> {
     pretty_env_logger::init();
 
     // Load config
-    let mut config = config::KeylimeConfig::new()?;
+    let mut config = config::KeylimeConfig::new(api::SUPPORTED_API_VERSIONS)?;
 
     // load path for IMA logfile
     #[cfg(test)]
@@ -336,12 +357,53 @@ async fn main() -> Result<()> {
         config.agent.tpm_signing_alg.as_ref(),
     )?;
 
+    // Select the PCR banks to quote, which must be allocated in the TPM
+    let tpm_pcr_banks = parse_list(&config.agent.tpm_pcr_banks)?
+        .into_iter()
+        .map(|bank| {
+            keylime::algorithms::HashAlgorithm::try_from(unquote(bank))
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let pcr_banks = tpm::quote_banks(tpm_hash_alg, &tpm_pcr_banks);
+    let allocated_banks = ctx.get_pcr_banks()?;
+    if let Some(bank) = pcr_banks.iter().find(|bank| {
+        !allocated_banks
+            .iter()
+            .any(|allocated| allocated.hash_alg == **bank)
+    }) {
+        error!("PCR bank {bank} is not allocated in the TPM");
+        return Err(Error::Configuration(
+            config::KeylimeConfigError::Generic(format!(
+                "PCR bank {bank} is not allocated in the TPM"
+            )),
+        ));
+    }
+    info!(
+        "Quoting PCR banks: {}",
+        pcr_banks
+            .iter()
+            .map(|bank| bank.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
     // Gather EK values and certs
     let ek_result = match config.agent.ek_handle.as_ref() {
         "" => ctx.create_ek(tpm_encryption_alg, None)?,
         s => ctx.create_ek(tpm_encryption_alg, Some(s))?,
     };
 
+    // Flush the EK once the agent is registered, or on any error before
+    // that, if we created it
+    let ek_guard = TransientEk {
+        ctx: ctx.clone(),
+        handle: config
+            .agent
+            .ek_handle
+            .is_empty()
+            .then_some(ek_result.key_handle),
+    };
+
     // Calculate the SHA-256 hash of the public key in PEM format
     let ek_hash = hash_ek_pubkey(ek_result.public.clone())?;
 
@@ -356,6 +418,7 @@ async fn main() -> Result<()> {
     let agent_uuid = config.agent.uuid.clone();
 
     // Try to load persistent Agent data
+    let mut old_clock = (None, None);
     let old_ak = match config.agent.agent_data_path.as_ref() {
         "" => {
             info!("Agent Data path not set in the configuration file");
@@ -372,6 +435,11 @@ async fn main() -> Result<()> {
                             ek_hash.as_bytes(),
                         ) {
                             true => {
+                                // Keep tracking the clock of the same TPM
+                                old_clock = (
+                                    data.tpm_clock_info,
+                                    data.tpm_clock_change,
+                                );
                                 let ak_result = data.get_ak()?;
                                 match ctx
                                     .load_ak(ek_result.key_handle, &ak_result)
@@ -429,17 +497,27 @@ async fn main() -> Result<()> {
     };
 
     // Store new AgentData
-    let agent_data_new = AgentData::create(
+    let mut agent_data_new = AgentData::create(
         tpm_hash_alg,
         tpm_signing_alg,
         &ak,
         ek_hash.as_bytes(),
     )?;
+    (
+        agent_data_new.tpm_clock_info,
+        agent_data_new.tpm_clock_change,
+    ) = old_clock;
 
-    match config.agent.agent_data_path.as_ref() {
-        "" => info!("Agent Data not stored"),
-        path => agent_data_new.store(Path::new(&path))?,
-    }
+    let agent_data_path = match config.agent.agent_data_path.as_ref() {
+        "" => {
+            info!("Agent Data not stored");
+            None
+        }
+        path => {
+            agent_data_new.store(Path::new(&path))?;
+            Some(PathBuf::from(path))
+        }
+    };
 
     info!("Agent UUID: {}", agent_uuid);
 
@@ -617,18 +695,18 @@ async fn main() -> Result<()> {
         api_versions: api_versions.clone(),
         agent: config.agent.clone(),
         agent_uuid: agent_uuid.clone(),
+        contact_ip: Some(config.agent.contact_ip.clone()),
+        contact_port: Some(config.agent.contact_port),
         mtls_cert,
         device_id,
         attest,
         signature,
         ak_handle,
     };
-    match agent_registration::register_agent(aa, &mut ctx).await {
-        Ok(()) => (),
-        Err(e) => {
-            error!("Failed to register agent: {}", e);
-        }
+    if let Err(e) = agent_registration::register_agent(aa, &mut ctx).await {
+        error!("Failed to register agent: {}", e);
     }
+    drop(ek_guard);
 
     let (mut payload_tx, mut payload_rx) =
         mpsc::channel::<payloads::PayloadMessage>(1);
@@ -676,25 +754,59 @@ async fn main() -> Result<()> {
     ))
     .map_err(Error::from);
 
+    // Parse the entries to exclude when sending the IMA measurement list
+    let ima_ml_exclude_paths =
+        parse_list(&config.agent.ima_ml_exclude_paths)?
+            .into_iter()
+            .map(unquote)
+            .collect::<Vec<_>>();
+    let ima_ml_exclude_templates =
+        parse_list(&config.agent.ima_ml_exclude_templates)?
+            .into_iter()
+            .map(unquote)
+            .collect::<Vec<_>>();
+    let ima_filter = if ima_ml_exclude_paths.is_empty()
+        && ima_ml_exclude_templates.is_empty()
+    {
+        None
+    } else {
+        info!(
+            "Excluding IMA measurement list entries matching paths [{}] and templates [{}]",
+            ima_ml_exclude_paths.join(", "),
+            ima_ml_exclude_templates.join(", ")
+        );
+        Some(ImaFilter::new(
+            &ima_ml_exclude_paths,
+            &ima_ml_exclude_templates,
+            &pcr_banks,
+        )?)
+    };
+
+    let mut ima_ml = MeasurementList::new();
+    ima_ml.set_filter(ima_filter);
+
     let quotedata = web::Data::new(QuoteData {
+        agent_data: Mutex::new(agent_data_new),
+        agent_data_path,
         agent_uuid: agent_uuid.clone(),
         ak_handle,
         allow_payload_revocation_actions,
         api_versions: api_versions.clone(),
         enc_alg: tpm_encryption_alg,
         hash_alg: tpm_hash_alg,
-        ima_ml: Mutex::new(MeasurementList::new()),
+        ima_ml: Mutex::new(ima_ml),
         ima_ml_file,
         keys_tx: keys_tx.clone(),
         measuredboot_ml_file,
         payload_tx: payload_tx.clone(),
+        pcr_banks,
         priv_key: nk_priv,
         pub_key: nk_pub,
         revocation_tx: revocation_tx.clone(),
         secure_mount: PathBuf::from(&mount),
         secure_size,
         sign_alg: tpm_signing_alg,
-        tpmcontext: Mutex::new(ctx),
+        tpmcontext: Mutex::new(Box::new(ctx)),
         work_dir,
     });
 
@@ -887,17 +999,20 @@ fn read_in_file(path: String) -> std::io::Result<String> {
     Ok(contents)
 }
 
-#[cfg(feature = "testing")]
+/// Removes the quotes around the items of the lists in the configuration
+fn unquote(s: &str) -> &str {
+    s.trim_matches(|c| c == '"' || c == '\'')
+}
+
 #[cfg(test)]
 mod testing {
     use super::*;
     use crate::{config::KeylimeConfig, crypto::CryptoError};
     use thiserror::Error;
 
-    use std::sync::{Arc, Mutex, OnceLock};
-    use tokio::sync::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard};
+    use std::sync::Mutex;
 
-    use keylime::tpm::testing::lock_tests;
+    use keylime::tpm::{mock::MockTpm, TpmBackend};
 
     #[derive(Error, Debug)]
     pub(crate) enum MainTestError {
@@ -941,13 +1056,19 @@ mod testing {
     }
 
     impl QuoteData<'_> {
-        pub(crate) async fn fixture() -> std::result::Result<
-            (Self, AsyncMutexGuard<'static, ()>),
-            MainTestError,
-        > {
-            let mutex = lock_tests().await;
+        /// Creates the agent data backed by a mock TPM, so the tests using
+        /// it can run in parallel without a TPM
+        pub(crate) async fn fixture(
+        ) -> std::result::Result<Self, MainTestError> {
+            Self::fixture_with_tpm(MockTpm::new()).await
+        }
+
+        /// Creates the fixture on top of the given mock TPM, e.g. with PCRs
+        /// already extended
+        pub(crate) async fn fixture_with_tpm(
+            mut ctx: MockTpm,
+        ) -> std::result::Result<Self, MainTestError> {
             let test_config = KeylimeConfig::default();
-            let mut ctx = tpm::Context::new()?;
 
             let tpm_encryption_alg =
                 keylime::algorithms::EncryptionAlgorithm::try_from(
@@ -975,6 +1096,15 @@ mod testing {
             let ak_handle =
                 ctx.load_ak(ek_result.key_handle, &ak_result).unwrap(); //#[allow_ci]
 
+            let ek_hash = hash_ek_pubkey(ek_result.public.clone())?;
+            let agent_data = AgentData::create(
+                tpm_hash_alg,
+                tpm_signing_alg,
+                &ak_result,
+                ek_hash.as_bytes(),
+            )
+            .unwrap(); //#[allow_ci]
+
             ctx.flush_context(ek_result.key_handle.into()).unwrap(); //#[allow_ci]
 
             let rsa_key_path = Path::new(env!("CARGO_MANIFEST_DIR"))
@@ -1035,32 +1165,32 @@ mod testing {
                 .map(|&s| s.to_string())
                 .collect::<Vec<String>>();
 
-            Ok((
-                QuoteData {
-                    api_versions,
-                    tpmcontext: Mutex::new(ctx),
-                    priv_key: nk_priv,
-                    pub_key: nk_pub,
-                    ak_handle,
-                    keys_tx,
-                    payload_tx,
-                    revocation_tx,
-                    hash_alg: keylime::algorithms::HashAlgorithm::Sha256,
-                    enc_alg: keylime::algorithms::EncryptionAlgorithm::Rsa,
-                    sign_alg: keylime::algorithms::SignAlgorithm::RsaSsa,
-                    agent_uuid: test_config.agent.uuid,
-                    allow_payload_revocation_actions: test_config
-                        .agent
-                        .allow_payload_revocation_actions,
-                    secure_size: test_config.agent.secure_size,
-                    work_dir,
-                    ima_ml_file,
-                    measuredboot_ml_file,
-                    ima_ml: Mutex::new(MeasurementList::new()),
-                    secure_mount,
-                },
-                mutex,
-            ))
+            Ok(QuoteData {
+                agent_data: Mutex::new(agent_data),
+                agent_data_path: None,
+                api_versions,
+                tpmcontext: Mutex::new(Box::new(ctx)),
+                priv_key: nk_priv,
+                pub_key: nk_pub,
+                ak_handle,
+                keys_tx,
+                payload_tx,
+                pcr_banks: vec![keylime::algorithms::HashAlgorithm::Sha256],
+                revocation_tx,
+                hash_alg: keylime::algorithms::HashAlgorithm::Sha256,
+                enc_alg: keylime::algorithms::EncryptionAlgorithm::Rsa,
+                sign_alg: keylime::algorithms::SignAlgorithm::RsaSsa,
+                agent_uuid: test_config.agent.uuid,
+                allow_payload_revocation_actions: test_config
+                    .agent
+                    .allow_payload_revocation_actions,
+                secure_size: test_config.agent.secure_size,
+                work_dir,
+                ima_ml_file,
+                measuredboot_ml_file,
+                ima_ml: Mutex::new(MeasurementList::new()),
+                secure_mount,
+            })
         }
     }
 }
@@ -1083,4 +1213,11 @@ mod tests {
             String::from("Hello World!\n")
         );
     }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("\"sha256\""), "sha256");
+        assert_eq!(unquote("'/tmp/**'"), "/tmp/**");
+        assert_eq!(unquote("ima-buf"), "ima-buf");
+    }
 }
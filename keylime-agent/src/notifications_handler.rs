@@ -137,7 +137,7 @@ mod tests {
             PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/actions"),
         );
 
-        let (mut fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let mut fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
 
         // Replace the channels on the fixture with some local ones
         let (mut revocation_tx, mut revocation_rx) =
@@ -13,23 +13,13 @@ use std::{
     ptr,
 };
 
+pub(crate) use keylime::permissions::{get_euid, get_gid, get_uid};
+
 pub(crate) struct UserIds {
     passwd: libc::passwd,
     group: libc::group,
 }
 
-pub(crate) fn get_gid() -> gid_t {
-    unsafe { libc::getgid() }
-}
-
-pub(crate) fn get_uid() -> uid_t {
-    unsafe { libc::getuid() }
-}
-
-pub(crate) fn get_euid() -> uid_t {
-    unsafe { libc::geteuid() }
-}
-
 impl TryFrom<&str> for UserIds {
     type Error = Error;
 
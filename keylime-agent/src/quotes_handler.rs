@@ -5,16 +5,33 @@ use crate::common::JsonWrapper;
 use crate::crypto;
 use crate::serialization::serialize_maybe_base64;
 use crate::{tpm, Error as KeylimeError, QuoteData};
-use actix_web::{http, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{
+    http,
+    web::{self, Bytes},
+    HttpRequest, HttpResponse, Responder,
+};
 use base64::{engine::general_purpose, Engine as _};
+use futures::stream;
+use keylime::{
+    agent_data::TpmClockInfo,
+    ima::{MeasurementListCursor, MeasurementListEntries},
+    uefi::{self, EventLogError},
+};
 use log::*;
 use serde::{Deserialize, Serialize};
 use std::{
     fs::{read, read_to_string},
-    io::{Read, Seek},
+    io::{self, BufWriter, Read, Seek, Write},
 };
+use tokio::sync::mpsc;
 use tss_esapi::structures::PcrSlot;
 
+/// Number of chunks of the response sent ahead of the client
+const IMA_ML_CHANNEL_SIZE: usize = 64;
+
+/// Number of measurement list entries read at once
+const IMA_ML_BATCH_SIZE: u64 = 256;
+
 #[derive(Deserialize)]
 pub struct Ident {
     nonce: String,
@@ -26,12 +43,16 @@ pub struct Integ {
     mask: String,
     partial: String,
     ima_ml_entry: Option<String>,
+    ima_ml_count: Option<String>,
+    ima_ml_excluded: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub(crate) struct KeylimeQuote {
     pub quote: String, // 'r' + quote + sig + pcrblob
     pub hash_alg: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pcr_banks: Vec<String>,
     pub enc_alg: String,
     pub sign_alg: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -42,6 +63,10 @@ pub(crate) struct KeylimeQuote {
     pub mb_measurement_list: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ima_measurement_list_entry: Option<u64>,
+    /// Current number of entries in the IMA measurement list, allowing the
+    /// verifier to know whether more entries are available
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ima_measurement_list_count: Option<u64>,
 }
 
 // This is a Quote request from the tenant, which does not check
@@ -81,18 +106,22 @@ async fn identity(
 
     debug!("Calling Identity Quote with nonce: {}", param.nonce);
 
+    // The identity quote only includes PCR#16 of the bank of hash_alg
     // must unwrap here due to lock mechanism
     // https://github.com/rust-lang-nursery/failure/issues/192
-    let mut context = data.tpmcontext.lock().unwrap(); //#[allow_ci]
-
-    let tpm_quote = match context.quote(
-        param.nonce.as_bytes(),
-        0,
-        &data.pub_key,
-        data.ak_handle,
-        data.hash_alg,
-        data.sign_alg,
-    ) {
+    let result = data
+        .tpmcontext
+        .lock()
+        .unwrap() //#[allow_ci]
+        .quote(
+            param.nonce.as_bytes(),
+            &[],
+            &data.pub_key,
+            data.ak_handle,
+            data.hash_alg,
+            data.sign_alg,
+        );
+    let tpm_quote = match result {
         Ok(quote) => quote,
         Err(e) => {
             debug!("Unable to retrieve quote: {:?}", e);
@@ -105,9 +134,12 @@ async fn identity(
         }
     };
 
+    track_clock_info(&data, &tpm_quote).await;
+
     let mut quote = KeylimeQuote {
         quote: tpm_quote,
         hash_alg: data.hash_alg.to_string(),
+        pcr_banks: vec![data.hash_alg.to_string()],
         enc_alg: data.enc_alg.to_string(),
         sign_alg: data.sign_alg.to_string(),
         ..Default::default()
@@ -139,7 +171,7 @@ async fn identity(
 async fn integrity(
     req: HttpRequest,
     param: web::Query<Integ>,
-    data: web::Data<QuoteData<'_>>,
+    data: web::Data<QuoteData<'static>>,
 ) -> impl Responder {
     // nonce, mask can only be in alphanumerical format
     if !param.nonce.chars().all(char::is_alphanumeric) {
@@ -227,19 +259,59 @@ async fn integrity(
         Some(idx) => idx.parse::<u64>().unwrap_or(0),
     };
 
+    // If a count was provided, at most that number of entries is returned so
+    // that large lists can be retrieved in chunks
+    let max_entries = match &param.ima_ml_count {
+        None => None,
+        Some(count) => match count.parse::<u64>() {
+            Ok(count) => Some(count),
+            Err(_) => {
+                warn!("Get quote returning 400 response. ima_ml_count should be a non-negative integer: {}", count);
+                return HttpResponse::BadRequest().json(JsonWrapper::error(
+                    400,
+                    format!(
+                        "ima_ml_count should be a non-negative integer: {count}"
+                    ),
+                ));
+            }
+        },
+    };
+
+    // Entries are only replaced by the IMA filter if the verifier accepts
+    // them, see ima::EXCLUDED_ENTRIES_FORMAT
+    let exclude_entries = match param.ima_ml_excluded.as_deref() {
+        None | Some("0") => false,
+        Some("1") => true,
+        Some(excluded) => {
+            warn!("Get quote returning 400 response. ima_ml_excluded should be '0' or '1': {}", excluded);
+            return HttpResponse::BadRequest().json(JsonWrapper::error(
+                400,
+                format!("ima_ml_excluded should be '0' or '1': {excluded}"),
+            ));
+        }
+    };
+
+    // Generate the ID quote. The TPM context is only locked for the quote, as
+    // the measurement list is streamed after the handler returns.
     // must unwrap here due to lock mechanism
     // https://github.com/rust-lang-nursery/failure/issues/192
-    let mut context = data.tpmcontext.lock().unwrap(); //#[allow_ci]
-
-    // Generate the ID quote.
-    let tpm_quote = match context.quote(
-        param.nonce.as_bytes(),
-        mask,
-        &data.pub_key,
-        data.ak_handle,
-        data.hash_alg,
-        data.sign_alg,
-    ) {
+    let result = data
+        .tpmcontext
+        .lock()
+        .unwrap() //#[allow_ci]
+        .quote(
+            param.nonce.as_bytes(),
+            &data
+                .pcr_banks
+                .iter()
+                .map(|bank| (*bank, mask))
+                .collect::<Vec<_>>(),
+            &data.pub_key,
+            data.ak_handle,
+            data.hash_alg,
+            data.sign_alg,
+        );
+    let tpm_quote = match result {
         Ok(tpm_quote) => tpm_quote,
         Err(e) => {
             debug!("Unable to retrieve quote: {:?}", e);
@@ -252,9 +324,12 @@ async fn integrity(
         }
     };
 
+    track_clock_info(&data, &tpm_quote).await;
+
     let id_quote = KeylimeQuote {
         quote: tpm_quote,
         hash_alg: data.hash_alg.to_string(),
+        pcr_banks: data.pcr_banks.iter().map(|b| b.to_string()).collect(),
         enc_alg: data.enc_alg.to_string(),
         sign_alg: data.sign_alg.to_string(),
         ..Default::default()
@@ -277,7 +352,25 @@ async fn integrity(
                     );
                 }
                 mb_measurement_list = match f.read_to_end(&mut ml) {
-                    Ok(_) => Some(general_purpose::STANDARD.encode(ml)),
+                    Ok(_) => {
+                        match check_mb_measurement_list(&ml, &id_quote.quote)
+                        {
+                            Ok(()) => {
+                                Some(general_purpose::STANDARD.encode(ml))
+                            }
+                            Err(e @ EventLogError::PcrMismatch(_)) => {
+                                warn!("TPM2 event log is not consistent with the quote, not sending it: {}", e);
+                                None
+                            }
+                            Err(e) => {
+                                debug!(
+                                "Unable to check TPM2 event log against the quote: {}",
+                                e
+                            );
+                                Some(general_purpose::STANDARD.encode(ml))
+                            }
+                        }
+                    }
                     Err(e) => {
                         warn!("Could not read TPM2 event log: {}", e);
                         None
@@ -297,45 +390,174 @@ async fn integrity(
         _ => (),
     }
 
-    // Generate the measurement list
-    let (ima_measurement_list, ima_measurement_list_entry, num_entries) =
-        if let Some(ima_file) = &data.ima_ml_file {
-            let mut ima_ml = data.ima_ml.lock().unwrap(); //#[allow_ci]
-            match ima_ml.read(
-                &mut ima_file.lock().unwrap(), //#[allow_ci]
-                nth_entry,
-            ) {
-                Ok(result) => {
-                    (Some(result.0), Some(result.1), Some(result.2))
-                }
-                Err(e) => {
-                    debug!("Unable to read measurement list: {:?}", e);
-                    return HttpResponse::InternalServerError().json(
-                        JsonWrapper::error(
-                            500,
-                            "Unable to retrieve quote".to_string(),
-                        ),
-                    );
-                }
-            }
-        } else {
-            (None, None, None)
-        };
-
     // Generate the final quote based on the ID quote
-    let quote = KeylimeQuote {
+    let mut quote = KeylimeQuote {
         pubkey,
-        ima_measurement_list,
         mb_measurement_list,
-        ima_measurement_list_entry,
         ..id_quote
     };
 
+    // The measurement list is streamed into the response as it is read, so
+    // that it is never held in memory
+    if data.ima_ml_file.is_some() {
+        let cursor = {
+            let data = data.clone();
+            tokio::task::spawn_blocking(move || {
+                get_ima_ml_cursor(
+                    &data,
+                    nth_entry,
+                    max_entries,
+                    exclude_entries,
+                )
+            })
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e)))
+        };
+        let cursor = match cursor {
+            Ok(cursor) => cursor,
+            Err(e) => {
+                debug!("Unable to read measurement list: {:?}", e);
+                return HttpResponse::InternalServerError().json(
+                    JsonWrapper::error(
+                        500,
+                        "Unable to retrieve quote".to_string(),
+                    ),
+                );
+            }
+        };
+        quote.ima_measurement_list_entry = Some(cursor.nth_entry());
+        quote.ima_measurement_list_count = Some(cursor.num_entries());
+
+        let (body_tx, body_rx) = mpsc::channel(IMA_ML_CHANNEL_SIZE);
+        let data = data.clone();
+        // The task is detached, it ends when the list is read or the
+        // response is dropped
+        drop(tokio::task::spawn_blocking(move || {
+            send_ima_ml(&data, &quote, cursor, &body_tx)
+        }));
+        let body = stream::unfold(body_rx, |mut body_rx| async move {
+            body_rx.recv().await.map(|chunk| (chunk, body_rx))
+        });
+
+        info!("GET integrity quote returning 200 response");
+        return HttpResponse::Ok()
+            .content_type(http::header::ContentType::json())
+            .streaming(body);
+    }
+
     let response = JsonWrapper::success(quote);
     info!("GET integrity quote returning 200 response");
     HttpResponse::Ok().json(response)
 }
 
+/// Quote serialized with the IMA measurement list read while it is written
+#[derive(Serialize)]
+struct StreamedQuote<'a, E> {
+    #[serde(flatten)]
+    quote: &'a KeylimeQuote,
+    ima_measurement_list: E,
+}
+
+/// Writer sending the data written to it as chunks of the response
+struct ResponseWriter<'a>(&'a mpsc::Sender<io::Result<Bytes>>);
+
+impl Write for ResponseWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The client went away if the response is no longer received
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "response was dropped",
+                )
+            })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Creates the cursor for the entries of the IMA measurement list requested.
+/// This blocks and must not run in the async executor
+fn get_ima_ml_cursor(
+    data: &QuoteData<'_>,
+    nth_entry: u64,
+    max_entries: Option<u64>,
+    exclude_entries: bool,
+) -> io::Result<MeasurementListCursor> {
+    let Some(ima_file) = &data.ima_ml_file else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "IMA measurement list not available",
+        ));
+    };
+
+    // must unwrap here due to lock mechanism
+    // https://github.com/rust-lang-nursery/failure/issues/192
+    let mut ima_ml = data.ima_ml.lock().unwrap(); //#[allow_ci]
+    let mut ima_file = ima_file.lock().unwrap(); //#[allow_ci]
+    ima_ml.cursor(&mut ima_file, nth_entry, max_entries, exclude_entries)
+}
+
+/// Serializes the response with the entries of the IMA measurement list of
+/// the cursor, sending it in chunks to body_tx. The list is read in batches
+/// of IMA_ML_BATCH_SIZE entries and only locked while a batch is read, so
+/// that a client slow to receive the response does not block the other
+/// requests. This blocks and must not run in the async executor.
+///
+/// As the response status was already sent, a failure to read the list
+/// aborts the response, leaving the client with a truncated body.
+fn send_ima_ml(
+    data: &QuoteData<'_>,
+    quote: &KeylimeQuote,
+    mut cursor: MeasurementListCursor,
+    body_tx: &mpsc::Sender<io::Result<Bytes>>,
+) {
+    let Some(ima_file) = &data.ima_ml_file else {
+        return;
+    };
+
+    let entries = MeasurementListEntries::new(|| {
+        // must unwrap here due to lock mechanism
+        // https://github.com/rust-lang-nursery/failure/issues/192
+        let mut ima_ml = data.ima_ml.lock().unwrap(); //#[allow_ci]
+        let mut ima_file = ima_file.lock().unwrap(); //#[allow_ci]
+        ima_ml.read_batch(&mut ima_file, &mut cursor, IMA_ML_BATCH_SIZE)
+    });
+    let response = JsonWrapper {
+        code: 200,
+        status: String::from("Success"),
+        results: StreamedQuote {
+            quote,
+            ima_measurement_list: &entries,
+        },
+    };
+
+    let mut writer = BufWriter::new(ResponseWriter(body_tx));
+    let result = serde_json::to_writer(&mut writer, &response)
+        .map_err(io::Error::from)
+        .and_then(|()| entries.take_error().map_or(Ok(()), Err));
+    // The end of the response is still buffered, and is only sent if the
+    // list was read completely
+    let (_, buffered) = writer.into_parts();
+    match (result, buffered) {
+        (Ok(()), Ok(buffered)) => {
+            let _ = body_tx.blocking_send(Ok(Bytes::from(buffered)));
+        }
+        (Err(e), _) if e.kind() == io::ErrorKind::BrokenPipe => {
+            debug!("Measurement list not sent: {}", e);
+        }
+        (Err(e), _) => {
+            warn!("Unable to read measurement list: {}", e);
+            let _ = body_tx.blocking_send(Err(e));
+        }
+        (Ok(()), Err(_)) => (),
+    }
+}
+
 /// Handles the default case for the /quotes scope
 async fn quotes_default(req: HttpRequest) -> impl Responder {
     let error;
@@ -376,17 +598,125 @@ pub(crate) fn configure_quotes_endpoints(cfg: &mut web::ServiceConfig) {
         .default_service(web::to(quotes_default));
 }
 
-#[cfg(feature = "testing")]
+/// Records the TPM clock information of the quote in the agent data and
+/// reports TPM resets and restarts, as well as changes which a genuine TPM
+/// never makes
+async fn track_clock_info(data: &QuoteData<'_>, quote: &str) {
+    let clock_info = match tpm::get_quote_clock_info(quote) {
+        Ok(clock_info) => TpmClockInfo::from(&clock_info),
+        Err(e) => {
+            warn!("Unable to get the clock information from the quote: {e}");
+            return;
+        }
+    };
+
+    let to_store = {
+        // must unwrap here due to lock mechanism
+        // https://github.com/rust-lang-nursery/failure/issues/192
+        let mut agent_data = data.agent_data.lock().unwrap(); //#[allow_ci]
+        let first = agent_data.tpm_clock_info.is_none();
+        let change = agent_data.update_clock_info(clock_info);
+        match change {
+            Some(change) if change.is_suspicious() => {
+                error!("Unexpected change of the TPM clock information ({change:?}): {clock_info:?}");
+            }
+            Some(change) => {
+                warn!(
+                    "TPM clock information changed ({change:?}): {clock_info:?}"
+                );
+            }
+            None => {}
+        }
+
+        // The clock only moves forward between changes, so the stored
+        // information is enough to detect changes after a restart
+        match &data.agent_data_path {
+            Some(path) if first || change.is_some() => {
+                Some((agent_data.clone(), path.clone()))
+            }
+            _ => None,
+        }
+    };
+
+    if let Some((agent_data, path)) = to_store {
+        match web::block(move || agent_data.store(&path)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Unable to store the agent data: {e}"),
+            Err(e) => warn!("Unable to store the agent data: {e}"),
+        }
+    }
+}
+
+/// PCRs extended by the firmware, which are fully described by the measured
+/// boot log. Later components may extend the other PCRs without recording it
+/// in the log, like systemd-pcrphase extending PCR 11
+const FIRMWARE_PCRS: std::ops::RangeInclusive<u32> = 0..=7;
+
+/// Replays the measured boot log against the PCR values included in the
+/// quote. Only the firmware PCRs extended by the events in the log are
+/// checked
+fn check_mb_measurement_list(
+    ml: &[u8],
+    quote: &str,
+) -> Result<(), EventLogError> {
+    let log = uefi::EventLog::parse(ml)?;
+    let banks = match tpm::get_quoted_pcrs(quote) {
+        Ok(banks) => banks,
+        Err(e) => {
+            debug!("Unable to get the PCR values from the quote: {}", e);
+            return Ok(());
+        }
+    };
+    for (hash_alg, mut pcrs) in banks {
+        pcrs.retain(|pcr_index, _| FIRMWARE_PCRS.contains(pcr_index));
+        uefi::check_pcrs(&log, hash_alg, &pcrs)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use actix_web::body::MessageBody;
     use actix_web::{test, web, App};
-    use keylime::{crypto::testing::pkey_pub_from_pem, tpm};
+    use keylime::{
+        algorithms::HashAlgorithm, crypto::testing::pkey_pub_from_pem,
+        ima::ImaFilter, tpm, tpm::mock::MockTpm,
+    };
     use serde_json::{json, Value};
+    use std::{fs::File, path::Path, pin::Pin, sync::Mutex, time::Duration};
+
+    #[actix_rt::test]
+    async fn test_check_mb_measurement_list() {
+        let test_data = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../keylime/test-data");
+        let ml =
+            read(test_data.join("uefi/binary_bios_measurements")).unwrap(); //#[allow_ci]
+        let quote = read_to_string(test_data.join("test-quote.txt")).unwrap(); //#[allow_ci]
+
+        // The PCRs in the quote are not extended by the events in the log
+        assert!(check_mb_measurement_list(&ml, quote.trim_end()).is_ok());
+
+        // A log which cannot be parsed is not reported as inconsistent
+        let result = check_mb_measurement_list(&ml[..64], quote.trim_end());
+        assert!(matches!(result, Err(EventLogError::Truncated { .. })));
+
+        // The log is sent with a quote of the firmware PCRs extended by the
+        // events in the log
+        let fixture = mb_log_fixture(&[]).await;
+        assert_eq!(
+            get_mb_measurement_list(fixture, "0xff").await,
+            Some(general_purpose::STANDARD.encode(&ml))
+        );
+
+        // But not once a firmware PCR was extended by one more event
+        let fixture = mb_log_fixture(&[(7, &[0xaa; 32])]).await;
+        assert_eq!(get_mb_measurement_list(fixture, "0xff").await, None);
+    }
 
     #[actix_rt::test]
     async fn test_identity() {
-        let (fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
         let quotedata = web::Data::new(fixture);
         let mut app = test::init_service(
             App::new()
@@ -416,21 +746,32 @@ mod tests {
 
         let mut context = quotedata.tpmcontext.lock().unwrap(); //#[allow_ci]
         tpm::testing::check_quote(
-            &mut context,
+            &mut **context,
             quotedata.ak_handle,
             &result.results.quote,
             b"1234567890ABCDEFHIJ",
         )
         .expect("unable to verify quote");
+        drop(context);
+
+        // The clock information of the quote is recorded
+        let clock_info =
+            tpm::get_quote_clock_info(&result.results.quote).unwrap(); //#[allow_ci]
+        let agent_data = quotedata.agent_data.lock().unwrap(); //#[allow_ci]
+        assert_eq!(
+            agent_data.tpm_clock_info,
+            Some(TpmClockInfo::from(&clock_info))
+        );
+        assert_eq!(agent_data.tpm_clock_change, None);
 
         // Explicitly drop QuoteData to cleanup keys
-        drop(context);
+        drop(agent_data);
         drop(quotedata);
     }
 
     #[actix_rt::test]
     async fn test_integrity_pre() {
-        let (fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
         let quotedata = web::Data::new(fixture);
         let mut app = test::init_service(
             App::new()
@@ -451,6 +792,7 @@ mod tests {
         let result: JsonWrapper<KeylimeQuote> =
             test::read_body_json(resp).await;
         assert_eq!(result.results.hash_alg.as_str(), "sha256");
+        assert_eq!(result.results.pcr_banks, vec!["sha256"]);
         assert_eq!(result.results.enc_alg.as_str(), "rsa");
         assert_eq!(result.results.sign_alg.as_str(), "rsassa");
         assert!(
@@ -473,7 +815,7 @@ mod tests {
 
                     let mut context = quotedata.tpmcontext.lock().unwrap(); //#[allow_ci]
                     tpm::testing::check_quote(
-                        &mut context,
+                        &mut **context,
                         quotedata.ak_handle,
                         &result.results.quote,
                         b"1234567890ABCDEFHIJ",
@@ -490,9 +832,52 @@ mod tests {
         drop(quotedata);
     }
 
+    #[actix_rt::test]
+    async fn test_integrity_pcr_banks() {
+        let mut fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        fixture.pcr_banks = vec![HashAlgorithm::Sha256, HashAlgorithm::Sha1];
+        let quotedata = web::Data::new(fixture);
+        let mut app = test::init_service(
+            App::new()
+                .app_data(quotedata.clone())
+                .route("/vX.Y/quotes/integrity", web::get().to(integrity)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(
+                "/vX.Y/quotes/integrity?nonce=1234567890ABCDEFHIJ&mask=0x408000&partial=1",
+            )
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let result: JsonWrapper<KeylimeQuote> =
+            test::read_body_json(resp).await;
+        assert_eq!(result.results.hash_alg.as_str(), "sha256");
+        assert_eq!(result.results.pcr_banks, vec!["sha256", "sha1"]);
+
+        // Both banks are quoted, and PCR 16 only in the sha256 bank
+        let banks = tpm::get_quoted_pcrs(&result.results.quote).unwrap(); //#[allow_ci]
+        assert_eq!(banks.len(), 2);
+        let (hash_alg, pcrs) = &banks[1];
+        assert_eq!(*hash_alg, HashAlgorithm::Sha1);
+        assert_eq!(pcrs.keys().copied().collect::<Vec<_>>(), vec![15, 22]);
+
+        let mut context = quotedata.tpmcontext.lock().unwrap(); //#[allow_ci]
+        tpm::testing::check_quote(
+            &mut **context,
+            quotedata.ak_handle,
+            &result.results.quote,
+            b"1234567890ABCDEFHIJ",
+        )
+        .expect("unable to verify quote");
+    }
+
     #[actix_rt::test]
     async fn test_integrity_post() {
-        let (fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
         let quotedata = web::Data::new(fixture);
         let mut app = test::init_service(
             App::new()
@@ -536,7 +921,7 @@ mod tests {
 
         let mut context = quotedata.tpmcontext.lock().unwrap(); //#[allow_ci]
         tpm::testing::check_quote(
-            &mut context,
+            &mut **context,
             quotedata.ak_handle,
             &result.results.quote,
             b"1234567890ABCDEFHIJ",
@@ -548,9 +933,120 @@ mod tests {
         drop(quotedata);
     }
 
+    #[actix_rt::test]
+    async fn test_integrity_ima_ml_count() {
+        let fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let quotedata = web::Data::new(fixture);
+        let mut app = test::init_service(
+            App::new()
+                .app_data(quotedata.clone())
+                .route("/vX.Y/quotes/integrity", web::get().to(integrity)),
+        )
+        .await;
+
+        // Request only the second entry of the list
+        let req = test::TestRequest::get()
+            .uri(
+                "/vX.Y/quotes/integrity?nonce=1234567890ABCDEFHIJ&mask=0x408000&partial=1&ima_ml_entry=1&ima_ml_count=1",
+            )
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let result: JsonWrapper<KeylimeQuote> =
+            test::read_body_json(resp).await;
+        assert_eq!(result.results.ima_measurement_list_entry, Some(1));
+
+        if let Some(ima_mutex) = &quotedata.ima_ml_file {
+            let mut ima_ml_file = ima_mutex.lock().unwrap(); //#[allow_ci]
+            ima_ml_file.rewind().unwrap(); //#[allow_ci]
+            let mut ima_ml = String::new();
+            let _ = ima_ml_file.read_to_string(&mut ima_ml).unwrap(); //#[allow_ci]
+            let second_entry = ima_ml.split_inclusive('\n').nth(1).unwrap(); //#[allow_ci]
+            assert_eq!(
+                result.results.ima_measurement_list.unwrap().as_str(), //#[allow_ci]
+                second_entry
+            );
+            // The number of entries in the whole list is reported
+            assert_eq!(
+                result.results.ima_measurement_list_count,
+                Some(ima_ml.lines().count() as u64)
+            );
+            assert!(ima_ml.lines().count() > 2);
+        } else {
+            panic!("IMA file was None"); //#[allow_ci]
+        }
+
+        // The count must be a non-negative integer
+        let req = test::TestRequest::get()
+            .uri(
+                "/vX.Y/quotes/integrity?nonce=1234567890ABCDEFHIJ&mask=0x408000&partial=1&ima_ml_count=-1",
+            )
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+        // Explicitly drop QuoteData to cleanup keys
+        drop(quotedata);
+    }
+
+    #[actix_rt::test]
+    async fn test_integrity_ima_ml_excluded() {
+        let fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let filter =
+            ImaFilter::new(&["/init"], &[], &[HashAlgorithm::Sha256])
+                .unwrap(); //#[allow_ci]
+        fixture.ima_ml.lock().unwrap().set_filter(Some(filter)); //#[allow_ci]
+        let quotedata = web::Data::new(fixture);
+        let mut app = test::init_service(
+            App::new()
+                .app_data(quotedata.clone())
+                .route("/vX.Y/quotes/integrity", web::get().to(integrity)),
+        )
+        .await;
+
+        // The entries are not replaced unless requested
+        let req = test::TestRequest::get()
+            .uri(
+                "/vX.Y/quotes/integrity?nonce=1234567890ABCDEFHIJ&mask=0x408000&partial=1&ima_ml_entry=1&ima_ml_count=1",
+            )
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let result: JsonWrapper<KeylimeQuote> =
+            test::read_body_json(resp).await;
+        let ima_ml = result.results.ima_measurement_list.unwrap(); //#[allow_ci]
+        assert!(ima_ml.ends_with(" /init\n"), "{ima_ml}");
+
+        let req = test::TestRequest::get()
+            .uri(
+                "/vX.Y/quotes/integrity?nonce=1234567890ABCDEFHIJ&mask=0x408000&partial=1&ima_ml_entry=1&ima_ml_count=1&ima_ml_excluded=1",
+            )
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let result: JsonWrapper<KeylimeQuote> =
+            test::read_body_json(resp).await;
+        let ima_ml = result.results.ima_measurement_list.unwrap(); //#[allow_ci]
+        assert!(ima_ml.contains(" excluded sha256:"), "{ima_ml}");
+
+        let req = test::TestRequest::get()
+            .uri(
+                "/vX.Y/quotes/integrity?nonce=1234567890ABCDEFHIJ&mask=0x408000&partial=1&ima_ml_excluded=yes",
+            )
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+        // Explicitly drop QuoteData to cleanup keys
+        drop(quotedata);
+    }
+
     #[actix_rt::test]
     async fn test_missing_ima_file() {
-        let (mut fixture, mutex) = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        let mut fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
 
         // Remove the IMA log file from the context
         fixture.ima_ml_file = None;
@@ -575,11 +1071,209 @@ mod tests {
             test::read_body_json(resp).await;
         assert!(result.results.ima_measurement_list.is_none());
         assert!(result.results.ima_measurement_list_entry.is_none());
+        assert!(result.results.ima_measurement_list_count.is_none());
+
+        // Explicitly drop QuoteData to cleanup keys
+        drop(quotedata);
+    }
+
+    /// Creates an IMA log with more entries than what is sent ahead of the
+    /// client
+    fn large_ima_log() -> tempfile::NamedTempFile {
+        let mut ima_log = tempfile::NamedTempFile::new().unwrap(); //#[allow_ci]
+        for i in 0..20000 {
+            writeln!(
+                ima_log,
+                "10 {i:040x} ima-ng sha256:{i:064x} /usr/lib/file-{i}"
+            )
+            .unwrap(); //#[allow_ci]
+        }
+        ima_log
+    }
+
+    #[actix_rt::test]
+    async fn test_integrity_slow_client() {
+        let ima_log = large_ima_log();
+        let mut fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        fixture.ima_ml_file =
+            Some(Mutex::new(File::open(ima_log.path()).unwrap())); //#[allow_ci]
+        let quotedata = web::Data::new(fixture);
+        let mut app = test::init_service(
+            App::new()
+                .app_data(quotedata.clone())
+                .route("/vX.Y/quotes/integrity", web::get().to(integrity)),
+        )
+        .await;
+        let uri = "/vX.Y/quotes/integrity?nonce=1234567890ABCDEFHIJ&mask=0x408000&partial=1";
 
+        // The body of the first response is never read
+        let slow_resp = test::call_service(
+            &app,
+            test::TestRequest::get().uri(uri).to_request(),
+        )
+        .await;
+        assert!(slow_resp.status().is_success());
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+
+        // This does not prevent other requests from reading the list
+        let body = actix_rt::time::timeout(Duration::from_secs(10), async {
+            let resp = test::call_service(
+                &app,
+                test::TestRequest::get().uri(uri).to_request(),
+            )
+            .await;
+            assert!(resp.status().is_success());
+            test::read_body(resp).await
+        })
+        .await
+        .expect("request blocked by a client not reading the response");
+        let result: JsonWrapper<KeylimeQuote> =
+            serde_json::from_slice(&body).unwrap(); //#[allow_ci]
+        assert_eq!(
+            result.results.ima_measurement_list.unwrap().lines().count(), //#[allow_ci]
+            20000
+        );
+
+        drop(slow_resp);
         // Explicitly drop QuoteData to cleanup keys
         drop(quotedata);
     }
 
+    #[actix_rt::test]
+    async fn test_integrity_ima_ml_read_error() {
+        let ima_log = large_ima_log();
+        let mut fixture = QuoteData::fixture().await.unwrap(); //#[allow_ci]
+        fixture.ima_ml_file =
+            Some(Mutex::new(File::open(ima_log.path()).unwrap())); //#[allow_ci]
+        let quotedata = web::Data::new(fixture);
+        let mut app = test::init_service(
+            App::new()
+                .app_data(quotedata.clone())
+                .route("/vX.Y/quotes/integrity", web::get().to(integrity)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(
+                "/vX.Y/quotes/integrity?nonce=1234567890ABCDEFHIJ&mask=0x408000&partial=1",
+            )
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        // The list is truncated beyond what was read ahead of the client,
+        // so reading it fails after the status of the response was sent
+        let len = ima_log.as_file().metadata().unwrap().len(); //#[allow_ci]
+        ima_log.as_file().set_len(len / 2).unwrap(); //#[allow_ci]
+
+        // The response is then aborted, leaving a truncated body
+        let mut body = resp.into_body();
+        let mut received = Vec::new();
+        let error = loop {
+            match std::future::poll_fn(|cx| {
+                MessageBody::poll_next(Pin::new(&mut body), cx)
+            })
+            .await
+            {
+                Some(Ok(chunk)) => received.extend_from_slice(&chunk),
+                Some(Err(e)) => break Some(e),
+                None => break None,
+            }
+        };
+        assert!(error.is_some());
+        let received = String::from_utf8(received).unwrap(); //#[allow_ci]
+        assert!(received.starts_with(r#"{"code":200,"status":"Success","#));
+        assert!(received.contains(r#""ima_measurement_list":"10 "#));
+        assert!(serde_json::from_str::<Value>(&received).is_err());
+
+        // Explicitly drop QuoteData to cleanup keys
+        drop(quotedata);
+    }
+
+    /// Creates the fixture with the measured boot log, on a mock TPM where
+    /// the events of the log and then the `extra` digests were extended
+    async fn mb_log_fixture(extra: &[(usize, &[u8])]) -> QuoteData<'static> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../keylime/test-data/uefi/binary_bios_measurements");
+        let log = uefi::EventLog::from_file(&path).unwrap(); //#[allow_ci]
+        let mut ctx = MockTpm::new();
+        for event in &log.events {
+            if let Some(digest) = event.digest(HashAlgorithm::Sha256) {
+                if event.event_type != uefi::EventType::NoAction {
+                    ctx.pcr_extend(
+                        HashAlgorithm::Sha256,
+                        event.pcr_index as usize,
+                        digest,
+                    )
+                    .unwrap(); //#[allow_ci]
+                }
+            }
+        }
+        for (pcr_index, digest) in extra {
+            ctx.pcr_extend(HashAlgorithm::Sha256, *pcr_index, digest)
+                .unwrap(); //#[allow_ci]
+        }
+
+        let mut fixture = QuoteData::fixture_with_tpm(ctx).await.unwrap(); //#[allow_ci]
+        fixture.measuredboot_ml_file =
+            Some(Mutex::new(File::open(&path).unwrap())); //#[allow_ci]
+        fixture
+    }
+
+    /// Requests an integrity quote of the PCRs in `mask`, returning the
+    /// measured boot log sent with it
+    async fn get_mb_measurement_list(
+        fixture: QuoteData<'static>,
+        mask: &str,
+    ) -> Option<String> {
+        let quotedata = web::Data::new(fixture);
+        let mut app = test::init_service(
+            App::new()
+                .app_data(quotedata.clone())
+                .route("/vX.Y/quotes/integrity", web::get().to(integrity)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/vX.Y/quotes/integrity?nonce=1234567890ABCDEFHIJ&mask={mask}&partial=1"
+            ))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let result: JsonWrapper<KeylimeQuote> =
+            test::read_body_json(resp).await;
+        result.results.mb_measurement_list
+    }
+
+    #[actix_rt::test]
+    async fn test_integrity_mb_measurement_list() {
+        let ml = read(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../keylime/test-data/uefi/binary_bios_measurements"),
+        )
+        .unwrap(); //#[allow_ci]
+
+        // Quote more than 8 PCRs, where PCRs 8 and 11 were extended by
+        // components not recording it in the log, like systemd-pcrphase
+        let fixture =
+            mb_log_fixture(&[(8, &[0xaa; 32]), (11, &[0xbb; 32])]).await;
+        assert_eq!(
+            get_mb_measurement_list(fixture, "0xfff").await,
+            Some(general_purpose::STANDARD.encode(&ml))
+        );
+
+        // The log is not sent when a firmware PCR conflicts with it
+        let fixture = mb_log_fixture(&[(4, &[0xaa; 32])]).await;
+        assert_eq!(get_mb_measurement_list(fixture, "0xfff").await, None);
+
+        // Also when quoting only the firmware PCRs
+        let fixture = mb_log_fixture(&[(0, &[0xaa; 32])]).await;
+        assert_eq!(get_mb_measurement_list(fixture, "0xff").await, None);
+    }
+
     #[actix_rt::test]
     async fn test_keys_default() {
         let mut app = test::init_service(
@@ -0,0 +1,366 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+
+// Generator of synthetic IMA measurement lists, for load testing.
+
+use crate::{ImaEmulatorError, Result};
+use clap::ValueEnum;
+use keylime::algorithms::HashAlgorithm;
+use keylime::ima;
+use log::*;
+use openssl::{
+    bn::BigNumContext,
+    ec::PointConversionForm,
+    hash::{hash, Hasher, MessageDigest},
+    md::Md,
+    pkey::{Id, PKey, Private},
+    pkey_ctx::PkeyCtx,
+};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Type of the IMA signature, EVM_IMA_XATTR_DIGSIG
+const IMA_XATTR_DIGSIG: u8 = 0x03;
+/// Version of the IMA signature format
+const IMA_SIGNATURE_VERSION: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Template {
+    #[value(name = "ima-ng")]
+    ImaNg,
+    #[value(name = "ima-sig")]
+    ImaSig,
+}
+
+impl Template {
+    fn name(&self) -> &'static str {
+        match self {
+            Template::ImaNg => "ima-ng",
+            Template::ImaSig => "ima-sig",
+        }
+    }
+}
+
+/// Generates the lines of an ASCII measurement list
+pub(crate) struct Generator {
+    template: Template,
+    ima_hash_alg: HashAlgorithm,
+    file_hash_alg: HashAlgorithm,
+    signing_key: Option<PKey<Private>>,
+}
+
+impl Generator {
+    /// Creates a generator
+    ///
+    /// # Arguments
+    ///
+    /// * template (Template): The template of the entries
+    /// * ima_hash_alg (HashAlgorithm): The algorithm of the template hashes
+    /// * file_hash_alg (HashAlgorithm): The algorithm of the file digests
+    /// * signing_key (Option<PKey<Private>>): The key to sign the file
+    ///   digests with, for the ima-sig template
+    pub(crate) fn new(
+        template: Template,
+        ima_hash_alg: HashAlgorithm,
+        file_hash_alg: HashAlgorithm,
+        signing_key: Option<PKey<Private>>,
+    ) -> Result<Self> {
+        if signing_key.is_some() && template != Template::ImaSig {
+            return Err(ImaEmulatorError::Other(
+                "A signing key can only be used with the ima-sig template"
+                    .to_string(),
+            ));
+        }
+        Ok(Generator {
+            template,
+            ima_hash_alg,
+            file_hash_alg,
+            signing_key,
+        })
+    }
+
+    /// Generates the line for a file with the given digest
+    pub(crate) fn line(&self, path: &str, digest: &[u8]) -> Result<String> {
+        let mut event = format!(
+            "{}:{} {}",
+            self.file_hash_alg,
+            hex::encode(digest),
+            path
+        );
+        if self.template == Template::ImaSig {
+            event.push(' ');
+            // The boot_aggregate is never signed
+            if let (Some(key), false) =
+                (&self.signing_key, path == "boot_aggregate")
+            {
+                event.push_str(&hex::encode(sign(
+                    key,
+                    self.file_hash_alg,
+                    digest,
+                )?));
+            }
+        }
+        let entry = ima::Entry::from_event(
+            ima::IMA_PCR,
+            self.template.name(),
+            &event,
+            self.ima_hash_alg,
+        )?;
+        Ok(format!(
+            "{} {} {} {}",
+            entry.pcr_index,
+            hex::encode(entry.template_hash.value()),
+            entry.template_name,
+            event
+        ))
+    }
+
+    /// Generates the line for a file, calculating the digest of its content
+    pub(crate) fn file_line(&self, path: &Path) -> Result<String> {
+        let mut hasher = Hasher::new(self.file_hash_alg.into())?;
+        let _ = io::copy(&mut File::open(path)?, &mut hasher)?;
+        let digest = hasher.finish()?;
+        self.line(&path.display().to_string(), &digest)
+    }
+
+    /// Generates the line for the nth synthetic file. The path and content
+    /// of the file are derived from the seed, so that the same list is
+    /// generated for the same seed
+    pub(crate) fn synthetic_line(
+        &self,
+        seed: u64,
+        nth: usize,
+    ) -> Result<String> {
+        let content = hash(
+            MessageDigest::sha256(),
+            format!("{seed}:{nth}").as_bytes(),
+        )?;
+        let path = format!(
+            "/synthetic/{}/{}",
+            hex::encode(&content[..1]),
+            hex::encode(&content[1..8])
+        );
+        let digest = hash(self.file_hash_alg.into(), &content)?;
+        self.line(&path, &digest)
+    }
+}
+
+/// Signs the file digest in the IMA signature format version 2, as set in
+/// the security.ima extended attribute by evmctl
+fn sign(
+    key: &PKey<Private>,
+    hash_alg: HashAlgorithm,
+    digest: &[u8],
+) -> Result<Vec<u8>> {
+    let mut ctx = PkeyCtx::new(key)?;
+    ctx.sign_init()?;
+    let md = Md::from_nid(MessageDigest::from(hash_alg).type_()).ok_or_else(
+        || {
+            ImaEmulatorError::Other(format!(
+                "Unsupported signature algorithm {hash_alg}"
+            ))
+        },
+    )?;
+    ctx.set_signature_md(md)?;
+    let mut signature = vec![];
+    let _ = ctx.sign_to_vec(digest, &mut signature)?;
+
+    let mut value = vec![
+        IMA_XATTR_DIGSIG,
+        IMA_SIGNATURE_VERSION,
+        ima_hash_algo(hash_alg),
+    ];
+    value.extend_from_slice(&keyid(key)?);
+    value.extend_from_slice(&(signature.len() as u16).to_be_bytes());
+    value.extend_from_slice(&signature);
+    Ok(value)
+}
+
+/// The identifier of the hash algorithm in the kernel's hash_info.h
+fn ima_hash_algo(hash_alg: HashAlgorithm) -> u8 {
+    match hash_alg {
+        HashAlgorithm::Sha1 => 2,
+        HashAlgorithm::Sha256 => 4,
+        HashAlgorithm::Sha384 => 5,
+        HashAlgorithm::Sha512 => 6,
+        HashAlgorithm::Sm3_256 => 17,
+    }
+}
+
+/// The key identifier, the last 4 bytes of the SHA-1 digest of the public
+/// key, in PKCS#1 format for RSA keys and as an uncompressed point for EC
+/// keys
+fn keyid(key: &PKey<Private>) -> Result<[u8; 4]> {
+    let public = match key.id() {
+        Id::RSA => key.rsa()?.public_key_to_der_pkcs1()?,
+        Id::EC => {
+            let ec = key.ec_key()?;
+            let mut ctx = BigNumContext::new()?;
+            ec.public_key().to_bytes(
+                ec.group(),
+                PointConversionForm::UNCOMPRESSED,
+                &mut ctx,
+            )?
+        }
+        _ => {
+            return Err(ImaEmulatorError::Other(
+                "Only RSA and EC signing keys are supported".to_string(),
+            ))
+        }
+    };
+    let digest = hash(MessageDigest::sha1(), &public)?;
+    let mut keyid = [0u8; 4];
+    keyid.copy_from_slice(&digest[16..20]);
+    Ok(keyid)
+}
+
+/// Lists up to count regular files in the directory and its subdirectories,
+/// in a stable order
+pub(crate) fn walk_dir(dir: &Path, count: usize) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.canonicalize()?];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                warn!("Skipping directory {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        entries.sort();
+        // Subdirectories are pushed in reverse to be visited in order
+        for path in entries.iter().rev() {
+            let Ok(metadata) = fs::symlink_metadata(path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                dirs.push(path.clone());
+            }
+        }
+        for path in entries {
+            if files.len() == count {
+                return Ok(files);
+            }
+            if fs::symlink_metadata(&path).is_ok_and(|m| m.is_file()) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keylime::ima::{replay_ascii_measurement_list, ImaReplay};
+    use openssl::{pkey_ctx::PkeyCtx, rsa::Rsa};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn generate_synthetic_test() {
+        let generator = Generator::new(
+            Template::ImaNg,
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Sha256,
+            None,
+        )
+        .unwrap(); //#[allow_ci]
+        let lines = (0..100)
+            .map(|n| generator.synthetic_line(7, n))
+            .collect::<Result<Vec<String>>>()
+            .unwrap(); //#[allow_ci]
+
+        // The same list is generated for the same seed
+        assert_eq!(lines[3], generator.synthetic_line(7, 3).unwrap()); //#[allow_ci]
+        assert_ne!(lines[3], generator.synthetic_line(8, 3).unwrap()); //#[allow_ci]
+        assert!(lines[0].starts_with("10 "));
+        assert!(lines[0].contains(" ima-ng sha256:"));
+
+        // The list can be replayed
+        let list = lines.join("\n");
+        let replay = replay_ascii_measurement_list(
+            list.as_bytes(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha1, HashAlgorithm::Sha256],
+        )
+        .unwrap(); //#[allow_ci]
+        assert_eq!(replay.entries(), 100);
+    }
+
+    #[test]
+    fn generate_signed_test() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap(); //#[allow_ci]
+        assert!(Generator::new(
+            Template::ImaNg,
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Sha256,
+            Some(key.clone()),
+        )
+        .is_err());
+
+        let generator = Generator::new(
+            Template::ImaSig,
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Sha256,
+            Some(key.clone()),
+        )
+        .unwrap(); //#[allow_ci]
+
+        let line = generator.line("boot_aggregate", &[0u8; 32]).unwrap(); //#[allow_ci]
+        assert!(line.ends_with(" boot_aggregate "));
+
+        let digest = hash(MessageDigest::sha256(), b"content").unwrap(); //#[allow_ci]
+        let line = generator.line("/usr/bin/file", &digest).unwrap(); //#[allow_ci]
+        let mut replay =
+            ImaReplay::new(HashAlgorithm::Sha1, &[HashAlgorithm::Sha256]);
+        let _ = replay.extend_ascii(&line).unwrap(); //#[allow_ci]
+
+        // The signature verifies against the file digest
+        let signature =
+            hex::decode(line.rsplit_once(' ').unwrap().1).unwrap(); //#[allow_ci]
+        assert_eq!(&signature[..3], &[0x03, 0x02, 0x04]);
+        assert_eq!(&signature[3..7], &keyid(&key).unwrap()); //#[allow_ci]
+        let size = u16::from_be_bytes([signature[7], signature[8]]) as usize;
+        assert_eq!(size + 9, signature.len());
+        let mut ctx = PkeyCtx::new(&key).unwrap(); //#[allow_ci]
+        ctx.verify_init().unwrap(); //#[allow_ci]
+        ctx.set_signature_md(Md::sha256()).unwrap(); //#[allow_ci]
+        assert!(ctx.verify(&digest, &signature[9..]).unwrap()); //#[allow_ci]
+
+        let entry = ima::Entry::try_from(line.as_str()).unwrap(); //#[allow_ci]
+        assert_eq!(entry.event_data.path(), "/usr/bin/file");
+    }
+
+    #[test]
+    fn walk_dir_test() {
+        let dir = tempfile::tempdir().unwrap(); //#[allow_ci]
+        fs::create_dir(dir.path().join("b")).unwrap(); //#[allow_ci]
+        fs::write(dir.path().join("b/2"), b"2").unwrap(); //#[allow_ci]
+        fs::write(dir.path().join("a"), b"1").unwrap(); //#[allow_ci]
+        fs::write(dir.path().join("c"), b"3").unwrap(); //#[allow_ci]
+
+        let root = dir.path().canonicalize().unwrap(); //#[allow_ci]
+        let files = walk_dir(dir.path(), 10).unwrap(); //#[allow_ci]
+        assert_eq!(files, [root.join("a"), root.join("c"), root.join("b/2")]);
+        let files = walk_dir(dir.path(), 1).unwrap(); //#[allow_ci]
+        assert_eq!(files, [root.join("a")]);
+
+        let generator = Generator::new(
+            Template::ImaNg,
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Sha256,
+            None,
+        )
+        .unwrap(); //#[allow_ci]
+        let line = generator.file_line(&files[0]).unwrap(); //#[allow_ci]
+        let digest = hash(MessageDigest::sha256(), b"1").unwrap(); //#[allow_ci]
+        assert!(line.ends_with(&format!(
+            "sha256:{} {}",
+            hex::encode(digest),
+            files[0].display()
+        )));
+    }
+}
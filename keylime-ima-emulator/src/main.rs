@@ -3,18 +3,22 @@
 
 use keylime::algorithms::HashAlgorithm;
 use keylime::ima;
-use openssl::hash::{hash, MessageDigest};
+use openssl::{
+    hash::{Hasher, MessageDigest},
+    pkey::PKey,
+};
 
 use log::*;
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use inotify::{EventMask, Inotify, WatchMask};
 use signal_hook::consts::SIGINT;
 use signal_hook::consts::SIGTERM;
-use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter, SeekFrom};
+use std::os::fd::AsRawFd;
 use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -23,6 +27,9 @@ use std::sync::{
 
 use thiserror::Error;
 
+mod generate;
+mod measured_boot;
+
 use tss_esapi::{
     abstraction::pcr,
     handles::PcrHandle,
@@ -48,79 +55,137 @@ enum ImaEmulatorError {
     IoError(#[from] std::io::Error),
     #[error("Integer parsing error")]
     ParseInt(#[from] std::num::ParseIntError),
+    #[error("IMA replay error")]
+    ImaReplay(#[from] ima::ImaReplayError),
+    #[error("UEFI event log error")]
+    EventLog(#[from] keylime::uefi::EventLogError),
     #[error("{0}")]
     Other(String),
 }
 
 type Result<T> = std::result::Result<T, ImaEmulatorError>;
 
+/// Creates the context for the TPM emulator set in the TCTI environment
+/// variable
+fn tpm_context() -> Result<Context> {
+    let tcti =
+        match Tcti::from_environment_variable() {
+            Ok(tcti) => tcti,
+            Err(_) => return Err(ImaEmulatorError::Other(
+                "This stub requires TCTI environment variable set properly"
+                    .to_string(),
+            )),
+        };
+
+    let mut context = Context::new(tcti)?;
+
+    if !tss_esapi::utils::get_tpm_vendor(&mut context)?.contains("SW") {
+        return Err(ImaEmulatorError::Other(
+            "This stub should only be used with a TPM emulator".to_string(),
+        ));
+    }
+    Ok(context)
+}
+
+/// Returns true if PCR 10 was not extended in any of the banks
+fn ima_pcrs_clean(pcrs: &[(HashAlgorithm, Digest)]) -> bool {
+    pcrs.iter().all(|(pcr_hash_alg, digest)| {
+        let pcr_digest: MessageDigest = (*pcr_hash_alg).into();
+        digest.value() == vec![0x00u8; pcr_digest.size()]
+    })
+}
+
+/// Position in the measurement list, after the last complete line read
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct MlPosition {
+    /// Offset in bytes from the start of the list
+    offset: u64,
+    /// Number of entries before the offset
+    entries: usize,
+}
+
+/// Reads the next entry of the measurement list, advancing the position
+/// past it. Empty lines are skipped.
+///
+/// Returns None at the end of the list, including when the last line is not
+/// terminated by a newline, as it may still be being written. That line is
+/// read again from the same position on the next call.
+fn ml_next_line(
+    reader: &mut impl BufRead,
+    position: &mut MlPosition,
+) -> Result<Option<String>> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if line.pop() != Some(b'\n') {
+            return Ok(None);
+        }
+        position.offset += read as u64;
+        if line.is_empty() {
+            continue;
+        }
+        position.entries += 1;
+        return match String::from_utf8(line) {
+            Ok(line) => Ok(Some(line)),
+            Err(e) => {
+                Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+                    .into())
+            }
+        };
+    }
+}
+
+/// Extends the entries of the measurement list starting from position into
+/// PCR 10, with a single extension for all the banks in pcr_hash_algs.
+///
+/// If search_pcrs is given, the PCR is not extended and the position at
+/// which the replayed values of all the banks match search_pcrs is returned
+/// instead.
 fn ml_extend(
     context: &mut Context,
     ml: &Path,
-    mut position: usize,
+    mut position: MlPosition,
     ima_hash_alg: HashAlgorithm,
-    pcr_hash_alg: HashAlgorithm,
-    search_hash: Option<&Digest>,
-) -> Result<usize> {
-    let f = File::open(ml)?;
+    pcr_hash_algs: &[HashAlgorithm],
+    search_pcrs: Option<&[(HashAlgorithm, Digest)]>,
+) -> Result<MlPosition> {
+    let mut f = File::open(ml)?;
+    let _ = f.seek(SeekFrom::Start(position.offset))?;
     let mut reader = BufReader::new(f);
-    let ima_digest: MessageDigest = ima_hash_alg.into();
-    let ima_start_hash = ima::Digest::start(ima_hash_alg);
-    let pcr_digest: MessageDigest = pcr_hash_alg.into();
-    let mut running_hash = ima::Digest::start(pcr_hash_alg);
-    let ff_hash = ima::Digest::ff(pcr_hash_alg);
-    for line in reader.by_ref().lines().skip(position) {
-        let line = line?;
-        if line.is_empty() {
-            continue;
-        }
-
+    let mut replay = ima::ImaReplay::new(ima_hash_alg, pcr_hash_algs);
+    while let Some(line) = ml_next_line(&mut reader, &mut position)? {
         let entry: ima::Entry = line.as_str().try_into()?;
 
-        position += 1;
-
-        // Set correct hash for time of measure, time of use (ToMToU) errors
-        // and if a file is already opened for write.
-        // https://elixir.bootlin.com/linux/v5.12.12/source/security/integrity/ima/ima_main.c#L101
-        let pcr_template_hash = if entry.template_hash == ima_start_hash {
-            Digest::try_from(ff_hash.value())
-        } else {
-            let mut event_data = vec![];
-            entry.event_data.encode(&mut event_data)?;
-            let pcr_event_hash = hash(pcr_digest, &event_data)?;
-            let ima_event_hash = hash(ima_digest, &event_data)?;
-            if ima_event_hash.as_ref() != entry.template_hash.value() {
-                return Err(ImaEmulatorError::Other(
-                    "IMA template hash doesn't match".to_string(),
-                ));
-            }
-            Digest::try_from(pcr_event_hash.as_ref())
-        }?;
+        let template_hashes = replay.extend(&entry)?;
+        if template_hashes.is_empty() {
+            // The entry was not measured into PCR 10
+            continue;
+        }
 
-        match search_hash {
+        match search_pcrs {
             None => {
-                println!(
-                    "extending hash {} for {}",
-                    hex::encode(pcr_template_hash.value()),
-                    entry.event_data.path(),
-                );
                 let mut vals = DigestValues::new();
-                vals.set(pcr_hash_alg.into(), pcr_template_hash);
+                for (pcr_hash_alg, template_hash) in template_hashes {
+                    println!(
+                        "extending {} hash {} for {}",
+                        pcr_hash_alg,
+                        hex::encode(template_hash.value()),
+                        entry.event_data.path(),
+                    );
+                    vals.set(
+                        pcr_hash_alg.into(),
+                        Digest::try_from(template_hash.value())?,
+                    );
+                }
                 context.execute_with_nullauth_session(|ctx| {
                     ctx.pcr_extend(PcrHandle::Pcr10, vals)
                 })?;
             }
-            Some(search_hash) => {
-                let mut hasher = openssl::hash::Hasher::new(pcr_digest)?;
-                hasher.update(running_hash.value())?;
-                hasher.update(&pcr_template_hash)?;
-                running_hash =
-                    ima::Digest::new(pcr_hash_alg, &hasher.finish()?)?;
-                let digest = Digest::try_from(running_hash.value())?;
-                let mut vals = DigestValues::new();
-                vals.set(pcr_hash_alg.into(), digest.clone());
-
-                if digest == *search_hash {
+            Some(search_pcrs) => {
+                if search_pcrs.iter().all(|(pcr_hash_alg, digest)| {
+                    replay.check_pcr(*pcr_hash_alg, digest).is_ok()
+                }) {
                     println!(
                         "Located last IMA file updated: {}",
                         entry.event_data.path()
@@ -131,7 +196,7 @@ fn ml_extend(
         }
     }
 
-    if search_hash.is_some() {
+    if search_pcrs.is_some() {
         return Err(ImaEmulatorError::Other(
             "Unable to find current measurement list position, Resetting the TPM emulator may be neccesary".to_string()));
     }
@@ -139,106 +204,482 @@ fn ml_extend(
     Ok(position)
 }
 
+/// Extends the entries appended to the measurement list, waiting for them
+/// with inotify as `tail -f` does, until shutdown is set.
+///
+/// Note that inotify does not report the entries added by the kernel to the
+/// securityfs measurement list, which must be polled instead.
+fn ml_follow(
+    context: &mut Context,
+    ml: &Path,
+    mut position: MlPosition,
+    ima_hash_alg: HashAlgorithm,
+    pcr_hash_algs: &[HashAlgorithm],
+    shutdown: &AtomicBool,
+) -> Result<MlPosition> {
+    let mut inotify = Inotify::init()?;
+    let _ = inotify.watches().add(
+        ml,
+        WatchMask::MODIFY
+            | WatchMask::CLOSE_WRITE
+            | WatchMask::DELETE_SELF
+            | WatchMask::MOVE_SELF,
+    )?;
+    let mut buffer = [0u8; 4096];
+
+    // Extend the entries added before the watch was set
+    position =
+        ml_extend(context, ml, position, ima_hash_alg, pcr_hash_algs, None)?;
+
+    while !shutdown.load(Ordering::SeqCst) {
+        let mut fds = libc::pollfd {
+            fd: inotify.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // Wake up periodically to check for the shutdown
+        if unsafe { libc::poll(&mut fds, 1, 200) } < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err.into());
+        }
+        if fds.revents & libc::POLLIN == 0 {
+            continue;
+        }
+
+        let mut removed = false;
+        for event in inotify.read_events(&mut buffer)? {
+            if event
+                .mask
+                .intersects(EventMask::DELETE_SELF | EventMask::MOVE_SELF)
+            {
+                removed = true;
+            }
+        }
+        position = ml_extend(
+            context,
+            ml,
+            position,
+            ima_hash_alg,
+            pcr_hash_algs,
+            None,
+        )?;
+        if removed {
+            return Err(ImaEmulatorError::Other(format!(
+                "{} was removed or moved",
+                ml.display()
+            )));
+        }
+    }
+
+    Ok(position)
+}
+
+/// Calculates the boot_aggregate from PCRs 0-7 of the bank, and PCRs 8-9 for
+/// banks other than SHA-1, as the kernel does
+fn boot_aggregate(
+    context: &mut Context,
+    hash_alg: HashAlgorithm,
+) -> Result<Vec<u8>> {
+    let slots = [
+        PcrSlot::Slot0,
+        PcrSlot::Slot1,
+        PcrSlot::Slot2,
+        PcrSlot::Slot3,
+        PcrSlot::Slot4,
+        PcrSlot::Slot5,
+        PcrSlot::Slot6,
+        PcrSlot::Slot7,
+        PcrSlot::Slot8,
+        PcrSlot::Slot9,
+    ];
+    let slots = match hash_alg {
+        HashAlgorithm::Sha1 => &slots[..8],
+        _ => &slots[..],
+    };
+    let pcr_list = PcrSelectionListBuilder::new()
+        .with_selection(hash_alg.into(), slots)
+        .build()?;
+    let pcr_data = context
+        .execute_without_session(|ctx| pcr::read_all(ctx, pcr_list))?;
+    let bank = pcr_data.pcr_bank(hash_alg.into()).ok_or_else(|| {
+        ImaEmulatorError::Other(format!("TPM does not have {hash_alg} bank"))
+    })?;
+    let mut hasher = Hasher::new(hash_alg.into())?;
+    for slot in slots {
+        let digest = bank.get_digest(*slot).ok_or_else(|| {
+            ImaEmulatorError::Other(format!("could not read PCR {slot:?}"))
+        })?;
+        hasher.update(digest.value())?;
+    }
+    Ok(hasher.finish()?.to_vec())
+}
+
+/// Generates a synthetic measurement list and extends it into the TPM
+fn generate(
+    args: GenerateArgs,
+    ima_hash_alg: HashAlgorithm,
+    pcr_hash_algs: &[HashAlgorithm],
+) -> Result<()> {
+    let file_hash_alg: HashAlgorithm =
+        args.file_hash_alg.as_str().try_into()?;
+    let signing_key = match &args.signing_key {
+        Some(path) => Some(PKey::private_key_from_pem(&fs::read(path)?)?),
+        None => None,
+    };
+    let generator = generate::Generator::new(
+        args.template,
+        ima_hash_alg,
+        file_hash_alg,
+        signing_key,
+    )?;
+
+    let mut context = if args.no_extend {
+        None
+    } else {
+        let mut context = tpm_context()?;
+        if !ima_pcrs_clean(&read_ima_pcrs(&mut context, pcr_hash_algs)?) {
+            return Err(ImaEmulatorError::Other(
+                "IMA PCR is not empty, Resetting the TPM emulator may be neccesary".to_string(),
+            ));
+        }
+        Some(context)
+    };
+
+    // Without a TPM the kernel records a boot_aggregate of zeros
+    let aggregate = match &mut context {
+        Some(context) => boot_aggregate(context, file_hash_alg)?,
+        None => {
+            let digest: MessageDigest = file_hash_alg.into();
+            vec![0x00u8; digest.size()]
+        }
+    };
+
+    let mut output = BufWriter::new(File::create(&args.output)?);
+    writeln!(output, "{}", generator.line("boot_aggregate", &aggregate)?)?;
+    match &args.dir {
+        Some(dir) => {
+            for path in generate::walk_dir(dir, args.count)? {
+                match generator.file_line(&path) {
+                    Ok(line) => writeln!(output, "{line}")?,
+                    Err(e) => {
+                        warn!("Skipping file {}: {}", path.display(), e)
+                    }
+                }
+            }
+        }
+        None => {
+            for nth in 0..args.count {
+                writeln!(
+                    output,
+                    "{}",
+                    generator.synthetic_line(args.seed, nth)?
+                )?;
+            }
+        }
+    }
+    output.flush()?;
+    drop(output);
+
+    if let Some(context) = &mut context {
+        let position = ml_extend(
+            context,
+            &args.output,
+            MlPosition::default(),
+            ima_hash_alg,
+            pcr_hash_algs,
+            None,
+        )?;
+        println!(
+            "Extended {} entries of {}",
+            position.entries,
+            args.output.display()
+        );
+    }
+    Ok(())
+}
+
+/// Extends a recorded UEFI event log into the TPM and writes it to the
+/// output, to be read by the agent as the measured boot log
+fn measured_boot(args: MeasuredBootArgs) -> Result<()> {
+    let data = fs::read(&args.event_log)?;
+    let log = keylime::uefi::EventLog::parse(&data)?;
+
+    let mut context = tpm_context()?;
+    let hash_algs = measured_boot::extend_event_log(&mut context, &log)?;
+    println!(
+        "PCRs of the {} banks match {}",
+        hash_algs
+            .iter()
+            .map(|hash_alg| hash_alg.to_string())
+            .collect::<Vec<String>>()
+            .join(", "),
+        args.event_log.display()
+    );
+
+    if let Some(output) = &args.output {
+        fs::write(output, &data)?;
+        println!("Wrote the event log to {}", output.display());
+    }
+    Ok(())
+}
+
+/// Reads the value of PCR 10 for each of the banks
+fn read_ima_pcrs(
+    context: &mut Context,
+    pcr_hash_algs: &[HashAlgorithm],
+) -> Result<Vec<(HashAlgorithm, Digest)>> {
+    let mut builder = PcrSelectionListBuilder::new();
+    for pcr_hash_alg in pcr_hash_algs {
+        builder = builder
+            .with_selection((*pcr_hash_alg).into(), &[PcrSlot::Slot10]);
+    }
+    let pcr_list = builder.build()?;
+    let pcr_data = context
+        .execute_without_session(|ctx| pcr::read_all(ctx, pcr_list))?;
+    pcr_hash_algs
+        .iter()
+        .map(|pcr_hash_alg| {
+            let digest = pcr_data
+                .pcr_bank((*pcr_hash_alg).into())
+                .ok_or_else(|| {
+                    ImaEmulatorError::Other(format!(
+                        "IMA slot does not have {pcr_hash_alg} bank",
+                    ))
+                })?
+                .get_digest(PcrSlot::Slot10)
+                .ok_or_else(|| {
+                    ImaEmulatorError::Other(
+                        "could not read value from IMA PCR".to_string(),
+                    )
+                })?;
+            Ok((*pcr_hash_alg, digest.clone()))
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Poll the measurement list for new entries, as required for the
+    /// securityfs measurement list
+    Poll,
+    /// Watch the measurement list for appended entries with inotify
+    Follow,
+    /// Extend the entries of the measurement list and exit, e.g. to replay
+    /// a recorded measurement list
+    Once,
+}
+
+#[derive(clap::Args)]
+struct GenerateArgs {
+    /// Path of the generated ASCII measurement list
+    #[clap(long, short = 'o')]
+    output: PathBuf,
+    /// Directory whose files are measured, instead of synthetic files
+    #[clap(long, short = 'd')]
+    dir: Option<PathBuf>,
+    /// Number of entries to generate after the boot_aggregate, or maximum
+    /// number of files to measure in the directory
+    #[clap(long, short = 'n', default_value_t = 1000)]
+    count: usize,
+    /// Seed for the paths and contents of the synthetic files
+    #[clap(long, short = 's', default_value_t = 0)]
+    seed: u64,
+    /// Template of the entries
+    #[clap(long, short = 't', value_enum, default_value_t = generate::Template::ImaNg)]
+    template: generate::Template,
+    /// Algorithm of the file digests
+    #[clap(long, default_value = "sha256")]
+    file_hash_alg: String,
+    /// PEM private key (RSA or EC) to sign the file digests with, for the
+    /// ima-sig template
+    #[clap(long, short = 'k')]
+    signing_key: Option<PathBuf>,
+    /// Only write the measurement list, without extending the TPM
+    #[clap(long)]
+    no_extend: bool,
+}
+
+#[derive(clap::Args)]
+struct MeasuredBootArgs {
+    /// Path of the recorded UEFI event log, in the binary crypto agile
+    /// format
+    #[clap(long, short = 'e')]
+    event_log: PathBuf,
+    /// Path where the event log is written after extending it, to be set
+    /// as the 'measuredboot_ml_path' option of the agent
+    #[clap(long, short = 'o')]
+    output: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a synthetic measurement list and extend it into the TPM
+    Generate(GenerateArgs),
+    /// Extend a recorded UEFI event log into the PCRs of all the banks of
+    /// the TPM allocated in the log
+    MeasuredBoot(MeasuredBootArgs),
+}
+
 #[derive(Parser)]
 #[clap(about)]
 struct Args {
-    #[clap(long = "hash_algs", short = 'a', default_value = "sha1")]
+    /// PCR banks to extend, all in a single extension of PCR 10
+    #[clap(
+        long = "hash_algs",
+        short = 'a',
+        default_value = "sha1",
+        value_delimiter = ','
+    )]
     hash_algs: Vec<String>,
     #[clap(long, short = 'i', default_value = "sha1")]
     ima_hash_alg: String,
     #[clap(long, short = 'f', default_value = IMA_ML)]
     ima_log: PathBuf,
+    #[clap(long, short = 'm', value_enum, default_value_t = Mode::Poll)]
+    mode: Mode,
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
 fn main() -> std::result::Result<(), ImaEmulatorError> {
     let args = Args::parse();
 
-    let tcti =
-        match Tcti::from_environment_variable() {
-            Ok(tcti) => tcti,
-            Err(_) => return Err(ImaEmulatorError::Other(
-                "This stub requires TCTI environment variable set properly"
-                    .to_string(),
-            )),
-        };
-
-    let mut context = Context::new(tcti)?;
-
-    if !tss_esapi::utils::get_tpm_vendor(&mut context)?.contains("SW") {
-        return Err(ImaEmulatorError::Other(
-            "This stub should only be used with a TPM emulator".to_string(),
-        ));
-    }
-
     let ima_hash_alg: HashAlgorithm =
         args.ima_hash_alg.as_str().try_into()?;
-    let mut positions = HashMap::new();
+    let mut pcr_hash_algs: Vec<HashAlgorithm> = Vec::new();
     for pcr_hash_alg in args.hash_algs {
         let pcr_hash_alg: HashAlgorithm = pcr_hash_alg.as_str().try_into()?;
-        positions.insert(pcr_hash_alg, 0usize);
-    }
-
-    for (pcr_hash_alg, position) in positions.iter_mut() {
-        // check if pcr is clean
-        let pcr_list = PcrSelectionListBuilder::new()
-            .with_selection((*pcr_hash_alg).into(), &[PcrSlot::Slot10])
-            .build()?;
-        let pcr_data = context
-            .execute_without_session(|ctx| pcr::read_all(ctx, pcr_list))?;
-        let digest = pcr_data
-            .pcr_bank((*pcr_hash_alg).into())
-            .ok_or_else(|| {
-                ImaEmulatorError::Other(format!(
-                    "IMA slot does not have {} bank",
-                    *pcr_hash_alg,
-                ))
-            })?
-            .get_digest(PcrSlot::Slot10)
-            .ok_or_else(|| {
-                ImaEmulatorError::Other(
-                    "could not read value from IMA PCR".to_string(),
-                )
-            })?;
+        if !pcr_hash_algs.contains(&pcr_hash_alg) {
+            pcr_hash_algs.push(pcr_hash_alg);
+        }
+    }
 
-        let pcr_digest: MessageDigest = (*pcr_hash_alg).into();
-        let pcr_start_hash = vec![0x00u8; pcr_digest.size()];
-        if digest.value() != pcr_start_hash {
-            log::warn!("IMA PCR is not empty, trying to find the last updated file in the measurement list...");
-            *position = ml_extend(
-                &mut context,
-                &args.ima_log,
-                *position,
-                ima_hash_alg,
-                *pcr_hash_alg,
-                Some(digest),
-            )?;
+    match args.command {
+        Some(Command::Generate(generate_args)) => {
+            return generate(generate_args, ima_hash_alg, &pcr_hash_algs);
+        }
+        Some(Command::MeasuredBoot(measured_boot_args)) => {
+            return measured_boot(measured_boot_args);
         }
+        None => {}
+    }
+
+    let mut context = tpm_context()?;
+
+    // check if pcr is clean, otherwise find the position in the measurement
+    // list matching all the banks
+    let mut position = MlPosition::default();
+    let pcrs = read_ima_pcrs(&mut context, &pcr_hash_algs)?;
+    if !ima_pcrs_clean(&pcrs) {
+        log::warn!("IMA PCR is not empty, trying to find the last updated file in the measurement list...");
+        position = ml_extend(
+            &mut context,
+            &args.ima_log,
+            position,
+            ima_hash_alg,
+            &pcr_hash_algs,
+            Some(&pcrs),
+        )?;
+    }
+
+    if args.mode == Mode::Once {
+        position = ml_extend(
+            &mut context,
+            &args.ima_log,
+            position,
+            ima_hash_alg,
+            &pcr_hash_algs,
+            None,
+        )?;
+        println!(
+            "Extended {} entries of {}",
+            position.entries,
+            args.ima_log.display()
+        );
+        return Ok(());
     }
 
     let shutdown_marker = Arc::new(AtomicBool::new(false));
     signal_hook::flag::register(SIGINT, Arc::clone(&shutdown_marker))?;
     signal_hook::flag::register(SIGTERM, Arc::clone(&shutdown_marker))?;
     println!("Monitoring {}", args.ima_log.display());
-    while !shutdown_marker.load(Ordering::SeqCst) {
-        for (pcr_hash_alg, position) in positions.iter_mut() {
-            *position = ml_extend(
+    if args.mode == Mode::Follow {
+        let _ = ml_follow(
+            &mut context,
+            &args.ima_log,
+            position,
+            ima_hash_alg,
+            &pcr_hash_algs,
+            &shutdown_marker,
+        )?;
+    } else {
+        while !shutdown_marker.load(Ordering::SeqCst) {
+            position = ml_extend(
                 &mut context,
                 &args.ima_log,
-                *position,
+                position,
                 ima_hash_alg,
-                *pcr_hash_alg,
+                &pcr_hash_algs,
                 None,
-                ).expect("Error extending position {position} on PCR bank {pcr_hash_alg}");
-        }
+            )?;
 
-        // FIXME: We could poll IMA_ML as in the python implementation, though
-        // the file is not pollable:
-        // https://github.com/torvalds/linux/blob/master/security/integrity/ima/ima_fs.c#L267
-        // Better idea might be to check the "runtime_measurements_count" file.
-        let duration = std::time::Duration::from_millis(200);
-        std::thread::sleep(duration);
+            // FIXME: We could poll IMA_ML as in the python implementation, though
+            // the file is not pollable:
+            // https://github.com/torvalds/linux/blob/master/security/integrity/ima/ima_fs.c#L267
+            // Better idea might be to check the "runtime_measurements_count" file.
+            let duration = std::time::Duration::from_millis(200);
+            std::thread::sleep(duration);
+        }
     }
     println!("Shutting down keylime IMA emulator");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn ml_next_line_test() {
+        let mut position = MlPosition::default();
+        let mut reader = Cursor::new(b"first\n\nsecond\npart".to_vec());
+        assert_eq!(
+            ml_next_line(&mut reader, &mut position).unwrap(), //#[allow_ci]
+            Some("first".to_string())
+        );
+        assert_eq!(
+            ml_next_line(&mut reader, &mut position).unwrap(), //#[allow_ci]
+            Some("second".to_string())
+        );
+        // The line without a newline is not complete yet
+        assert_eq!(ml_next_line(&mut reader, &mut position).unwrap(), None); //#[allow_ci]
+        assert_eq!(
+            position,
+            MlPosition {
+                offset: 14,
+                entries: 2
+            }
+        );
+
+        // The line is read once completed, continuing from the position
+        let mut reader = Cursor::new(b"first\n\nsecond\npartial\n".to_vec());
+        reader.set_position(position.offset);
+        assert_eq!(
+            ml_next_line(&mut reader, &mut position).unwrap(), //#[allow_ci]
+            Some("partial".to_string())
+        );
+        assert_eq!(ml_next_line(&mut reader, &mut position).unwrap(), None); //#[allow_ci]
+        assert_eq!(
+            position,
+            MlPosition {
+                offset: 22,
+                entries: 3
+            }
+        );
+    }
+}
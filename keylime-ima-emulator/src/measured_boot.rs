@@ -0,0 +1,268 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+
+// Extension of a recorded UEFI event log into the PCRs of a TPM emulator.
+
+use crate::{ImaEmulatorError, Result};
+use keylime::algorithms::HashAlgorithm;
+use keylime::uefi::{self, EventLog, EventLogError, EventType};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use tss_esapi::{
+    abstraction::pcr,
+    constants::CapabilityType,
+    handles::PcrHandle,
+    structures::{
+        CapabilityData, Digest, DigestValues, PcrSelectionListBuilder,
+        PcrSlot,
+    },
+    Context,
+};
+
+/// Handles of the PCRs of the platform, indexed by PCR number
+const PCR_HANDLES: [PcrHandle; 24] = [
+    PcrHandle::Pcr0,
+    PcrHandle::Pcr1,
+    PcrHandle::Pcr2,
+    PcrHandle::Pcr3,
+    PcrHandle::Pcr4,
+    PcrHandle::Pcr5,
+    PcrHandle::Pcr6,
+    PcrHandle::Pcr7,
+    PcrHandle::Pcr8,
+    PcrHandle::Pcr9,
+    PcrHandle::Pcr10,
+    PcrHandle::Pcr11,
+    PcrHandle::Pcr12,
+    PcrHandle::Pcr13,
+    PcrHandle::Pcr14,
+    PcrHandle::Pcr15,
+    PcrHandle::Pcr16,
+    PcrHandle::Pcr17,
+    PcrHandle::Pcr18,
+    PcrHandle::Pcr19,
+    PcrHandle::Pcr20,
+    PcrHandle::Pcr21,
+    PcrHandle::Pcr22,
+    PcrHandle::Pcr23,
+];
+
+/// Gets the handle of the PCR with the given index
+fn pcr_handle(pcr_index: u32) -> Result<PcrHandle> {
+    PCR_HANDLES.get(pcr_index as usize).copied().ok_or_else(|| {
+        ImaEmulatorError::Other(format!("Invalid PCR index {pcr_index}"))
+    })
+}
+
+/// Gets the banks declared in the event log which are allocated in the TPM,
+/// in the order of the event log
+pub(crate) fn common_banks(
+    log_hash_algs: &[HashAlgorithm],
+    tpm_hash_algs: &[HashAlgorithm],
+) -> Vec<HashAlgorithm> {
+    log_hash_algs
+        .iter()
+        .filter(|hash_alg| tpm_hash_algs.contains(hash_alg))
+        .copied()
+        .collect()
+}
+
+/// Gets the algorithms of the PCR banks allocated in the TPM
+fn tpm_banks(context: &mut Context) -> Result<Vec<HashAlgorithm>> {
+    let (capabilities, _) =
+        context.get_capability(CapabilityType::AssignedPcr, 0, 1)?;
+    let CapabilityData::AssignedPcr(selection_list) = capabilities else {
+        return Err(ImaEmulatorError::Other(
+            "Unexpected capability data for the PCR banks".to_string(),
+        ));
+    };
+    Ok(selection_list
+        .get_selections()
+        .iter()
+        .filter(|selection| !selection.is_empty())
+        .filter_map(|selection| {
+            HashAlgorithm::try_from(selection.hashing_algorithm()).ok()
+        })
+        .collect())
+}
+
+/// Reads the PCRs with the given indices from each of the banks
+fn read_pcrs(
+    context: &mut Context,
+    hash_algs: &[HashAlgorithm],
+    pcr_indices: &[u32],
+) -> Result<Vec<BTreeMap<u32, Vec<u8>>>> {
+    let slots = pcr_indices
+        .iter()
+        .map(|pcr_index| {
+            let slot = 1u32.checked_shl(*pcr_index).ok_or_else(|| {
+                ImaEmulatorError::Other(format!(
+                    "Invalid PCR index {pcr_index}"
+                ))
+            })?;
+            Ok(PcrSlot::try_from(slot)?)
+        })
+        .collect::<Result<Vec<PcrSlot>>>()?;
+    let mut builder = PcrSelectionListBuilder::new();
+    for hash_alg in hash_algs {
+        builder = builder.with_selection((*hash_alg).into(), &slots);
+    }
+    let pcr_data = context.execute_without_session(|ctx| {
+        pcr::read_all(ctx, builder.build()?)
+    })?;
+
+    hash_algs
+        .iter()
+        .map(|hash_alg| {
+            let bank =
+                pcr_data.pcr_bank((*hash_alg).into()).ok_or_else(|| {
+                    ImaEmulatorError::Other(format!(
+                        "TPM does not have {hash_alg} bank"
+                    ))
+                })?;
+            pcr_indices
+                .iter()
+                .zip(&slots)
+                .map(|(pcr_index, slot)| {
+                    let digest = bank.get_digest(*slot).ok_or_else(|| {
+                        ImaEmulatorError::Other(format!(
+                            "could not read PCR {pcr_index} of {hash_alg} bank"
+                        ))
+                    })?;
+                    Ok((*pcr_index, digest.value().to_vec()))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Extends the events of the UEFI event log into the PCRs of the TPM
+/// emulator, with a single extension for all the banks in common between
+/// the event log and the TPM.
+///
+/// Nothing is extended if the PCRs already match the event log, so that the
+/// same log can be replayed on every start. Otherwise, the PCRs with events
+/// in the log must not have been extended yet.
+///
+/// # Returns
+///
+/// The algorithms of the banks which were checked against the event log
+pub(crate) fn extend_event_log(
+    context: &mut Context,
+    log: &EventLog,
+) -> Result<Vec<HashAlgorithm>> {
+    let log_hash_algs = log.hash_algorithms();
+    let tpm_hash_algs = tpm_banks(context)?;
+    let hash_algs = common_banks(&log_hash_algs, &tpm_hash_algs);
+    for hash_alg in &log_hash_algs {
+        if !hash_algs.contains(hash_alg) {
+            println!("Skipping {hash_alg} digests, the bank is not allocated in the TPM");
+        }
+    }
+    for hash_alg in &tpm_hash_algs {
+        if !log_hash_algs.contains(hash_alg) {
+            println!("{hash_alg} bank is not extended, the event log has no digests for it");
+        }
+    }
+    if hash_algs.is_empty() {
+        return Err(ImaEmulatorError::Other(
+            "The event log has no digests for the PCR banks of the TPM"
+                .to_string(),
+        ));
+    }
+
+    // The TPM emulator is started from locality 0, so PCR 0 cannot match a
+    // log recorded on a platform started from another locality
+    match log.startup_locality() {
+        None | Some(0) => {}
+        Some(locality) => {
+            return Err(ImaEmulatorError::Other(format!(
+                "The event log was recorded with startup locality {locality}, the TPM emulator starts from locality 0"
+            )));
+        }
+    }
+
+    let replayed = hash_algs
+        .iter()
+        .map(|hash_alg| uefi::replay(log, *hash_alg))
+        .collect::<std::result::Result<Vec<_>, EventLogError>>()?;
+    let pcr_indices: Vec<u32> = replayed
+        .first()
+        .map(|r| r.keys().copied().collect())
+        .unwrap_or_default();
+    let current = read_pcrs(context, &hash_algs, &pcr_indices)?;
+    if current == replayed {
+        println!("PCRs already match the event log");
+        return Ok(hash_algs);
+    }
+    if current
+        .iter()
+        .flat_map(|pcrs| pcrs.values())
+        .flatten()
+        .any(|b| *b != 0)
+    {
+        return Err(ImaEmulatorError::Other(
+            "Measured boot PCRs are not empty, Resetting the TPM emulator may be neccesary".to_string(),
+        ));
+    }
+
+    for (index, event) in log.events.iter().enumerate() {
+        // EV_NO_ACTION events are informative and not extended
+        if event.event_type == EventType::NoAction {
+            continue;
+        }
+        let mut vals = DigestValues::new();
+        for hash_alg in &hash_algs {
+            let digest = event.digest(*hash_alg).ok_or(
+                EventLogError::MissingDigest {
+                    index,
+                    hash_alg: *hash_alg,
+                },
+            )?;
+            vals.set((*hash_alg).into(), Digest::try_from(digest)?);
+        }
+        println!(
+            "extending PCR {} with event #{} ({})",
+            event.pcr_index, index, event.event_type
+        );
+        let handle = pcr_handle(event.pcr_index)?;
+        context.execute_with_nullauth_session(|ctx| {
+            ctx.pcr_extend(handle, vals)
+        })?;
+    }
+
+    let current = read_pcrs(context, &hash_algs, &pcr_indices)?;
+    for (hash_alg, pcrs) in hash_algs.iter().zip(&current) {
+        uefi::check_pcrs(log, *hash_alg, pcrs)?;
+    }
+    Ok(hash_algs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_banks_test() {
+        assert_eq!(
+            common_banks(
+                &[HashAlgorithm::Sha1, HashAlgorithm::Sha256],
+                &[HashAlgorithm::Sha256, HashAlgorithm::Sha384],
+            ),
+            vec![HashAlgorithm::Sha256]
+        );
+        assert!(common_banks(
+            &[HashAlgorithm::Sha1],
+            &[HashAlgorithm::Sha256]
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn pcr_handle_test() {
+        assert_eq!(pcr_handle(0).unwrap(), PcrHandle::Pcr0); //#[allow_ci]
+        assert_eq!(pcr_handle(7).unwrap(), PcrHandle::Pcr7); //#[allow_ci]
+        assert_eq!(pcr_handle(23).unwrap(), PcrHandle::Pcr23); //#[allow_ci]
+        assert!(pcr_handle(24).is_err());
+    }
+}
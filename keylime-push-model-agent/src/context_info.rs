@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+use crate::struct_filler::AttestationRequestFillerFromTpm;
+use keylime::{
+    agent_data::AgentData,
+    algorithms::{EncryptionAlgorithm, HashAlgorithm, SignAlgorithm},
+    crypto, tpm,
+};
+use log::*;
+use std::error::Error;
+use std::path::Path;
+use tss_esapi::handles::KeyHandle;
+
+/// ContextInfo holds the TPM context and the keys used by the push model
+/// agent during its lifetime
+pub struct ContextInfo {
+    pub tpm_context: tpm::Context<'static>,
+    pub ek_result: tpm::EKResult,
+    pub ek_hash: String,
+    pub ak_handle: KeyHandle,
+    pub ak: tpm::AKResult,
+    pub tpm_hash_alg: HashAlgorithm,
+    pub tpm_signing_alg: SignAlgorithm,
+    /// Whether the EK was created by the agent, and not loaded from a
+    /// persistent handle
+    pub ek_transient: bool,
+}
+
+impl ContextInfo {
+    /// Creates the EK and loads the AK into the TPM. The AK persisted in the
+    /// agent data file is reused if it is still valid, otherwise a new AK is
+    /// created and persisted
+    ///
+    /// # Arguments
+    ///
+    /// * tpm_encryption_alg (EncryptionAlgorithm): The EK algorithm
+    /// * tpm_hash_alg (HashAlgorithm): The AK hash algorithm
+    /// * tpm_signing_alg (SignAlgorithm): The AK signing algorithm
+    /// * ek_handle (Option<&str>): The handle of a persistent EK to use. If
+    ///   not provided, a new EK is generated
+    /// * agent_data_path (Option<&Path>): The file where the AK is persisted.
+    ///   If not provided, the AK is not persisted
+    pub fn new(
+        tpm_encryption_alg: EncryptionAlgorithm,
+        tpm_hash_alg: HashAlgorithm,
+        tpm_signing_alg: SignAlgorithm,
+        ek_handle: Option<&str>,
+        agent_data_path: Option<&Path>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut tpm_context = tpm::Context::new()?;
+        let ek_result =
+            tpm_context.create_ek(tpm_encryption_alg, ek_handle)?;
+        let ek_transient = ek_handle.is_none();
+
+        let (ek_hash, ak_handle, ak) = match load_ak(
+            &mut tpm_context,
+            &ek_result,
+            tpm_hash_alg,
+            tpm_signing_alg,
+            agent_data_path,
+        ) {
+            Ok(keys) => keys,
+            Err(e) => {
+                // Do not leave the EK we created loaded in the TPM
+                if ek_transient {
+                    if let Err(e) =
+                        tpm_context.flush_context(ek_result.key_handle.into())
+                    {
+                        warn!("Failed to flush EK: {e}");
+                    }
+                }
+                return Err(e);
+            }
+        };
+
+        Ok(ContextInfo {
+            tpm_context,
+            ek_result,
+            ek_hash,
+            ak_handle,
+            ak,
+            tpm_hash_alg,
+            tpm_signing_alg,
+            ek_transient,
+        })
+    }
+
+    /// Creates a filler for the attestation request describing the TPM and
+    /// the AK
+    pub fn get_filler(
+        &mut self,
+    ) -> Result<AttestationRequestFillerFromTpm, Box<dyn Error>> {
+        AttestationRequestFillerFromTpm::new(
+            &mut self.tpm_context,
+            self.ak_handle,
+            &self.ak.public,
+        )
+    }
+
+    /// Flushes the AK and, if the agent created it, the EK
+    pub fn flush(mut self) {
+        if let Err(e) = self.tpm_context.flush_context(self.ak_handle.into())
+        {
+            warn!("Failed to flush AK: {e}");
+        }
+        if !self.ek_transient {
+            return;
+        }
+        if let Err(e) = self
+            .tpm_context
+            .flush_context(self.ek_result.key_handle.into())
+        {
+            warn!("Failed to flush EK: {e}");
+        }
+    }
+}
+
+/// Loads the AK under the EK, reusing the AK persisted in the agent data
+/// file if it is still valid. Otherwise a new AK is created and persisted.
+/// Returns the hash of the EK with the handle of the AK
+fn load_ak(
+    tpm_context: &mut tpm::Context<'static>,
+    ek_result: &tpm::EKResult,
+    tpm_hash_alg: HashAlgorithm,
+    tpm_signing_alg: SignAlgorithm,
+    agent_data_path: Option<&Path>,
+) -> Result<(String, KeyHandle, tpm::AKResult), Box<dyn Error>> {
+    // Calculate the SHA-256 hash of the public key in PEM format
+    let ek_hash = crypto::hash_ek_pubkey(ek_result.public.clone())?;
+
+    let old_ak = agent_data_path.and_then(|path| {
+        load_persisted_ak(
+            tpm_context,
+            ek_result.key_handle,
+            path,
+            tpm_hash_alg,
+            tpm_signing_alg,
+            &ek_hash,
+        )
+    });
+
+    // Use old AK or generate a new one and update the agent data
+    let (ak_handle, ak) = match old_ak {
+        Some((ak_handle, ak)) => (ak_handle, ak),
+        None => {
+            let ak = tpm_context.create_ak(
+                ek_result.key_handle,
+                tpm_hash_alg,
+                tpm_signing_alg,
+            )?;
+            let ak_handle = tpm_context.load_ak(ek_result.key_handle, &ak)?;
+            (ak_handle, ak)
+        }
+    };
+
+    match agent_data_path {
+        Some(path) => AgentData::create(
+            tpm_hash_alg,
+            tpm_signing_alg,
+            &ak,
+            ek_hash.as_bytes(),
+        )?
+        .store(path)?,
+        None => info!("Agent Data not stored"),
+    }
+
+    Ok((ek_hash, ak_handle, ak))
+}
+
+/// Loads the AK persisted in the agent data file, if it exists and is valid
+/// for the current configuration and EK
+fn load_persisted_ak(
+    tpm_context: &mut tpm::Context<'static>,
+    ek_handle: KeyHandle,
+    path: &Path,
+    tpm_hash_alg: HashAlgorithm,
+    tpm_signing_alg: SignAlgorithm,
+    ek_hash: &str,
+) -> Option<(KeyHandle, tpm::AKResult)> {
+    if !path.exists() {
+        info!("Agent Data not found in: {}", path.display());
+        return None;
+    }
+    let data = match AgentData::load(path) {
+        Ok(data) => data,
+        Err(e) => {
+            warn!("Could not load agent data: {}", e);
+            return None;
+        }
+    };
+    if !data.valid(tpm_hash_alg, tpm_signing_alg, ek_hash.as_bytes()) {
+        warn!(
+            "Not using old {} because it is not valid with current configuration",
+            path.display()
+        );
+        return None;
+    }
+    let ak = match data.get_ak() {
+        Ok(ak) => ak,
+        Err(e) => {
+            warn!("Could not get AK from agent data: {}", e);
+            return None;
+        }
+    };
+    match tpm_context.load_ak(ek_handle, &ak) {
+        Ok(ak_handle) => {
+            info!("Loaded old AK key from {}", path.display());
+            Some((ak_handle, ak))
+        }
+        Err(e) => {
+            warn!("Loading old AK key from {} failed: {}", path.display(), e);
+            None
+        }
+    }
+}
@@ -0,0 +1,519 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+use crate::context_info::ContextInfo;
+use base64::{engine::general_purpose, Engine as _};
+use keylime::{
+    algorithms::{HashAlgorithm, SignAlgorithm},
+    ima::{
+        ImaFilter, MeasurementList, MeasurementListEntries,
+        EXCLUDED_ENTRIES_FORMAT,
+    },
+    structures,
+};
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+
+/// Number of IMA log entries read at once
+const IMA_LOG_BATCH_SIZE: u64 = 1024;
+
+/// IMA log entries of the collected evidence, read from the log while the
+/// evidence is serialized
+pub type ImaLogEntries<'a> =
+    MeasurementListEntries<Box<dyn FnMut() -> io::Result<Vec<String>> + 'a>>;
+
+/// EvidenceCollector gathers the evidence requested by the verifier in the
+/// attestation response
+pub struct EvidenceCollector {
+    ima_ml: RefCell<MeasurementList>,
+    ima_log_path: String,
+    uefi_log_path: String,
+}
+
+impl EvidenceCollector {
+    pub fn new(ima_log_path: &str, uefi_log_path: &str) -> Self {
+        EvidenceCollector {
+            ima_ml: RefCell::new(MeasurementList::new()),
+            ima_log_path: ima_log_path.to_string(),
+            uefi_log_path: uefi_log_path.to_string(),
+        }
+    }
+
+    /// Sets the filter applied to the IMA log entries when the verifier
+    /// requests the log in the EXCLUDED_ENTRIES_FORMAT format
+    pub fn set_ima_filter(&mut self, filter: Option<ImaFilter>) {
+        self.ima_ml.get_mut().set_filter(filter);
+    }
+
+    /// Collects the evidence requested by the verifier and builds the body
+    /// of the evidence submission, to be serialized by serialize_evidence()
+    ///
+    /// # Arguments
+    ///
+    /// * evidence_requested (&[structures::EvidenceRequested]): The evidence
+    ///   requested by the verifier, with the chosen parameters
+    /// * context_info (Option<&mut ContextInfo>): The TPM context and keys,
+    ///   required when a TPM quote is requested
+    pub fn collect(
+        &self,
+        evidence_requested: &[structures::EvidenceRequested],
+        mut context_info: Option<&mut ContextInfo>,
+    ) -> Result<
+        structures::EvidenceHandlingRequest<ImaLogEntries<'_>>,
+        Box<dyn Error>,
+    > {
+        let mut evidence_collected = Vec::new();
+        for requested in evidence_requested {
+            let data = match (
+                requested.evidence_class.as_str(),
+                requested.evidence_type.as_str(),
+            ) {
+                ("certification", "tpm_quote") => self.collect_tpm_quote(
+                    requested.chosen_parameters.as_ref(),
+                    context_info.as_deref_mut(),
+                )?,
+                ("log", "uefi_log") => self.collect_uefi_log()?,
+                ("log", "ima_log") => self
+                    .collect_ima_log(requested.chosen_parameters.as_ref())?,
+                (class, type_) => {
+                    return Err(format!(
+                        "Unsupported evidence {class}/{type_} requested"
+                    )
+                    .into());
+                }
+            };
+            evidence_collected.push(structures::EvidenceCollected {
+                evidence_class: requested.evidence_class.clone(),
+                data,
+            });
+        }
+
+        Ok(structures::EvidenceHandlingRequest {
+            data: structures::EvidenceHandlingRequestData {
+                type_: "attestation".to_string(),
+                attributes: structures::EvidenceHandlingRequestAttributes {
+                    evidence_collected,
+                },
+            },
+        })
+    }
+
+    fn collect_tpm_quote<E>(
+        &self,
+        chosen_parameters: Option<&structures::ChosenParameters>,
+        context_info: Option<&mut ContextInfo>,
+    ) -> Result<structures::EvidenceData<E>, Box<dyn Error>> {
+        let Some(context_info) = context_info else {
+            return Err(
+                "TPM quote requested but TPM usage is disabled".into()
+            );
+        };
+        let Some(structures::ChosenParameters::Parameters(params)) =
+            chosen_parameters
+        else {
+            return Err("Missing chosen parameters for TPM quote".into());
+        };
+        let Some(challenge) = &params.challenge else {
+            return Err("Missing challenge for TPM quote".into());
+        };
+        if let Some(key) = &params.certification_key {
+            let ak_name = context_info
+                .tpm_context
+                .get_name(context_info.ak_handle.into())?;
+            check_certification_key(key, &hex::encode(ak_name.value()))?;
+        }
+
+        let hash_alg = match &params.hash_algorithm {
+            Some(alg) => HashAlgorithm::try_from(alg.as_str())?,
+            None => context_info.tpm_hash_alg,
+        };
+        let sign_alg = match &params.signature_scheme {
+            Some(alg) => SignAlgorithm::try_from(alg.as_str())?,
+            None => context_info.tpm_signing_alg,
+        };
+
+        // Quote the PCRs selected in each bank, or all the PCRs of the bank
+        // of the hash algorithm if none are selected
+        let selection = match &params.selected_subjects {
+            Some(subjects) => get_selection(subjects)?,
+            None => {
+                let pcrs = context_info
+                    .tpm_context
+                    .get_pcr_banks()?
+                    .into_iter()
+                    .find(|bank| bank.hash_alg == hash_alg)
+                    .map(|bank| bank.pcrs)
+                    .unwrap_or_default();
+                vec![(hash_alg, get_mask(&pcrs)?)]
+            }
+        };
+
+        let quote = context_info.tpm_context.quote_without_nk(
+            challenge.as_bytes(),
+            &selection,
+            context_info.ak_handle,
+            hash_alg,
+            sign_alg,
+        )?;
+        let (message, signature, subject_data) = split_quote(&quote)?;
+
+        Ok(structures::EvidenceData::TpmQuote {
+            subject_data: subject_data.to_string(),
+            message: message.to_string(),
+            signature: signature.to_string(),
+        })
+    }
+
+    fn collect_uefi_log<E>(
+        &self,
+    ) -> Result<structures::EvidenceData<E>, Box<dyn Error>> {
+        let uefi_log = std::fs::read(&self.uefi_log_path).map_err(|e| {
+            format!("Failed to read UEFI log {}: {e}", self.uefi_log_path)
+        })?;
+        Ok(structures::EvidenceData::UefiLog {
+            entries: general_purpose::STANDARD.encode(uefi_log),
+        })
+    }
+
+    fn collect_ima_log(
+        &self,
+        chosen_parameters: Option<&structures::ChosenParameters>,
+    ) -> Result<structures::EvidenceData<ImaLogEntries<'_>>, Box<dyn Error>>
+    {
+        let (starting_offset, entry_count, exclude) = match chosen_parameters
+        {
+            Some(structures::ChosenParameters::Offset(params)) => (
+                params.starting_offset.map(u64::try_from).transpose()?,
+                params.entry_count.map(u64::try_from).transpose()?,
+                params.format == EXCLUDED_ENTRIES_FORMAT,
+            ),
+            _ => (None, None, false),
+        };
+
+        let mut ima_file = File::open(&self.ima_log_path).map_err(|e| {
+            format!("Failed to open IMA log {}: {e}", self.ima_log_path)
+        })?;
+        let mut cursor = self.ima_ml.borrow_mut().cursor(
+            &mut ima_file,
+            starting_offset.unwrap_or(0),
+            entry_count,
+            exclude,
+        )?;
+        let starting_offset = Some(cursor.nth_entry());
+        let entry_count = cursor.entry_count();
+        let ima_ml = &self.ima_ml;
+        let read_batch: Box<dyn FnMut() -> _> = Box::new(move || {
+            ima_ml.borrow_mut().read_batch(
+                &mut ima_file,
+                &mut cursor,
+                IMA_LOG_BATCH_SIZE,
+            )
+        });
+
+        Ok(structures::EvidenceData::ImaLog {
+            starting_offset,
+            entry_count,
+            entries: MeasurementListEntries::new(read_batch),
+        })
+    }
+}
+
+/// Serializes the evidence returned by EvidenceCollector::collect() into the
+/// body of the evidence submission. The IMA log entries are read from the
+/// log into the body as they are serialized, so that the log is never held
+/// in memory besides the body, and the body is discarded if the log could
+/// not be read completely
+pub fn serialize_evidence(
+    evidence: &structures::EvidenceHandlingRequest<ImaLogEntries<'_>>,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let body = serde_json::to_vec(evidence)?;
+    for collected in &evidence.data.attributes.evidence_collected {
+        if let structures::EvidenceData::ImaLog { entries, .. } =
+            &collected.data
+        {
+            if let Some(e) = entries.take_error() {
+                return Err(format!("Failed to read IMA log: {e}").into());
+            }
+        }
+    }
+    Ok(body)
+}
+
+/// Checks that the certification key chosen by the verifier is the AK, which
+/// is identified by the hex encoded name advertised in the capabilities
+fn check_certification_key(
+    key: &structures::CertificationKey,
+    ak_name: &str,
+) -> Result<(), Box<dyn Error>> {
+    if !key.local_identifier.eq_ignore_ascii_case(ak_name) {
+        return Err(format!(
+            "Certification key '{}' chosen for TPM quote does not match the AK '{ak_name}'",
+            key.local_identifier
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Gets the PCR selection mask of each bank with selected PCRs
+fn get_selection(
+    subjects: &structures::ShaValues,
+) -> Result<Vec<(HashAlgorithm, u32)>, Box<dyn Error>> {
+    let selection = [
+        (HashAlgorithm::Sha1, &subjects.sha1),
+        (HashAlgorithm::Sha256, &subjects.sha256),
+        (HashAlgorithm::Sha384, &subjects.sha384),
+    ]
+    .into_iter()
+    .filter(|(_, pcrs)| !pcrs.is_empty())
+    .map(|(hash_alg, pcrs)| Ok((hash_alg, get_mask(pcrs)?)))
+    .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    if selection.is_empty() {
+        return Err("No PCRs selected for TPM quote".into());
+    }
+    Ok(selection)
+}
+
+/// Converts a list of PCR indexes into a PCR selection mask
+fn get_mask(pcrs: &[u8]) -> Result<u32, Box<dyn Error>> {
+    if pcrs.is_empty() {
+        return Err("No PCRs selected for TPM quote".into());
+    }
+    pcrs.iter().try_fold(0u32, |mask, pcr| {
+        if *pcr > 23 {
+            return Err(format!("Invalid PCR index {pcr}").into());
+        }
+        Ok(mask | (1 << pcr))
+    })
+}
+
+/// Splits a quote string into the base64 encoded TPMS_ATTEST, TPMT_SIGNATURE
+/// and PCR blob
+fn split_quote(quote: &str) -> Result<(&str, &str, &str), Box<dyn Error>> {
+    let mut split = quote
+        .strip_prefix('r')
+        .ok_or("Quote string does not start with 'r'")?
+        .split(':');
+    match (split.next(), split.next(), split.next(), split.next()) {
+        (Some(message), Some(signature), Some(pcrs), None) => {
+            Ok((message, signature, pcrs))
+        }
+        _ => Err("Malformed quote string".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Write;
+
+    const IMA_LOG: &str = "0-entry\n1-entry\n2-entry\n";
+    const IMA_ML_FILTERED: &str = "10 c156ebdcbfcd28fe1060ef4cdec0aab04d3a9b63 ima-ng sha1:19f13b42c2745066347e76454788c0fe083643f3 /init
+10 790ff4fe72889b071a0f7585112710be6d0084fe ima-ng sha1:c90333979f56f38bbd41b81806015b0de502f3cc /bin/sh
+";
+
+    fn get_ima_log_params(
+        starting_offset: Option<i32>,
+        entry_count: Option<i32>,
+    ) -> structures::ChosenParameters {
+        structures::ChosenParameters::Offset(structures::LogParameters {
+            format: "text/plain".to_string(),
+            starting_offset,
+            entry_count,
+        })
+    }
+
+    #[test]
+    fn get_mask_test() {
+        assert_eq!(get_mask(&[0, 1, 10]).unwrap(), 0x403); //#[allow_ci]
+        assert!(get_mask(&[]).is_err());
+        assert!(get_mask(&[24]).is_err());
+    }
+
+    #[test]
+    fn get_selection_test() {
+        let subjects = structures::ShaValues {
+            sha1: vec![0, 1],
+            sha256: vec![],
+            sha384: vec![10],
+        };
+        assert_eq!(
+            get_selection(&subjects).unwrap(), //#[allow_ci]
+            vec![(HashAlgorithm::Sha1, 0x3), (HashAlgorithm::Sha384, 0x400)]
+        );
+        assert!(get_selection(&structures::ShaValues::default()).is_err());
+        let subjects = structures::ShaValues {
+            sha256: vec![24],
+            ..Default::default()
+        };
+        assert!(get_selection(&subjects).is_err());
+    }
+
+    #[test]
+    fn check_certification_key_test() {
+        let mut key = structures::CertificationKey {
+            key_algorithm: "rsa".to_string(),
+            key_class: "asymmetric".to_string(),
+            key_size: 2048,
+            server_identifier: "ak".to_string(),
+            local_identifier: "000B1234ABCD".to_string(),
+            public: "".to_string(),
+        };
+        assert!(check_certification_key(&key, "000b1234abcd").is_ok());
+
+        key.local_identifier = "000b5678".to_string();
+        let err = check_certification_key(&key, "000b1234abcd").unwrap_err(); //#[allow_ci]
+        assert!(err.to_string().contains("does not match the AK"));
+    }
+
+    #[test]
+    fn split_quote_test() {
+        let (message, signature, pcrs) =
+            split_quote("rAA==:BB==:CC==").unwrap(); //#[allow_ci]
+        assert_eq!(message, "AA==");
+        assert_eq!(signature, "BB==");
+        assert_eq!(pcrs, "CC==");
+        assert!(split_quote("AA==:BB==:CC==").is_err());
+        assert!(split_quote("rAA==:BB==").is_err());
+        assert!(split_quote("rAA==:BB==:CC==:DD==").is_err());
+    }
+
+    #[test]
+    fn collect_logs_test() {
+        let mut ima_log = tempfile::NamedTempFile::new().unwrap(); //#[allow_ci]
+        ima_log.write_all(IMA_LOG.as_bytes()).unwrap(); //#[allow_ci]
+        let mut uefi_log = tempfile::NamedTempFile::new().unwrap(); //#[allow_ci]
+        uefi_log.write_all(&[0x00, 0x01, 0x02]).unwrap(); //#[allow_ci]
+
+        let collector = EvidenceCollector::new(
+            ima_log.path().to_str().unwrap(), //#[allow_ci]
+            uefi_log.path().to_str().unwrap(), //#[allow_ci]
+        );
+        let evidence_requested = vec![
+            structures::EvidenceRequested {
+                evidence_class: "log".to_string(),
+                evidence_type: "uefi_log".to_string(),
+                chosen_parameters: None,
+            },
+            structures::EvidenceRequested {
+                evidence_class: "log".to_string(),
+                evidence_type: "ima_log".to_string(),
+                chosen_parameters: Some(get_ima_log_params(Some(1), Some(1))),
+            },
+        ];
+        let evidence = collector.collect(&evidence_requested, None).unwrap(); //#[allow_ci]
+        let body = serialize_evidence(&evidence).unwrap(); //#[allow_ci]
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(), //#[allow_ci]
+            json!({
+                "data": {
+                    "type": "attestation",
+                    "attributes": {
+                        "evidence_collected": [
+                            {
+                                "evidence_class": "log",
+                                "evidence_type": "uefi_log",
+                                "data": {
+                                    "entries": "AAEC",
+                                }
+                            },
+                            {
+                                "evidence_class": "log",
+                                "evidence_type": "ima_log",
+                                "data": {
+                                    "starting_offset": 1,
+                                    "entry_count": 1,
+                                    "entries": "1-entry\n",
+                                }
+                            }
+                        ]
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn serialize_ima_log_read_error_test() {
+        let mut ima_log = tempfile::NamedTempFile::new().unwrap(); //#[allow_ci]
+        ima_log.write_all(IMA_LOG.as_bytes()).unwrap(); //#[allow_ci]
+        let collector =
+            EvidenceCollector::new(ima_log.path().to_str().unwrap(), ""); //#[allow_ci]
+        let evidence_requested = vec![structures::EvidenceRequested {
+            evidence_class: "log".to_string(),
+            evidence_type: "ima_log".to_string(),
+            chosen_parameters: Some(get_ima_log_params(None, None)),
+        }];
+        let evidence = collector.collect(&evidence_requested, None).unwrap(); //#[allow_ci]
+
+        // The log is truncated before the entries collected are read
+        ima_log.as_file().set_len(8).unwrap(); //#[allow_ci]
+        let e = serialize_evidence(&evidence).unwrap_err(); //#[allow_ci]
+        assert!(e.to_string().starts_with("Failed to read IMA log"), "{e}");
+    }
+
+    #[test]
+    fn collect_ima_log_excluded_test() {
+        let mut ima_log = tempfile::NamedTempFile::new().unwrap(); //#[allow_ci]
+        ima_log.write_all(IMA_ML_FILTERED.as_bytes()).unwrap(); //#[allow_ci]
+        let mut collector =
+            EvidenceCollector::new(ima_log.path().to_str().unwrap(), ""); //#[allow_ci]
+        let filter =
+            ImaFilter::new(&["/init"], &[], &[HashAlgorithm::Sha256])
+                .unwrap(); //#[allow_ci]
+        collector.set_ima_filter(Some(filter));
+
+        let get_entries = |collector: &EvidenceCollector, format: &str| {
+            let evidence_requested = vec![structures::EvidenceRequested {
+                evidence_class: "log".to_string(),
+                evidence_type: "ima_log".to_string(),
+                chosen_parameters: Some(
+                    structures::ChosenParameters::Offset(
+                        structures::LogParameters {
+                            format: format.to_string(),
+                            starting_offset: None,
+                            entry_count: None,
+                        },
+                    ),
+                ),
+            }];
+            let evidence =
+                collector.collect(&evidence_requested, None).unwrap(); //#[allow_ci]
+            let body = serialize_evidence(&evidence).unwrap(); //#[allow_ci]
+            let body: serde_json::Value =
+                serde_json::from_slice(&body).unwrap(); //#[allow_ci]
+            body["data"]["attributes"]["evidence_collected"][0]["data"]
+                ["entries"]
+                .as_str()
+                .unwrap() //#[allow_ci]
+                .to_string()
+        };
+
+        // The entries are only replaced if the verifier requests it
+        assert_eq!(get_entries(&collector, "text/plain"), IMA_ML_FILTERED);
+        let entries = get_entries(&collector, EXCLUDED_ENTRIES_FORMAT);
+        assert!(entries.contains(" excluded sha256:"), "{entries}");
+        assert!(!entries.contains("/init"), "{entries}");
+    }
+
+    #[test]
+    fn collect_unsupported_test() {
+        let collector = EvidenceCollector::new("", "");
+        let quote_requested = vec![structures::EvidenceRequested {
+            evidence_class: "certification".to_string(),
+            evidence_type: "tpm_quote".to_string(),
+            chosen_parameters: None,
+        }];
+        // TPM quotes cannot be generated without a TPM context
+        assert!(collector.collect(&quote_requested, None).is_err());
+
+        let unknown_requested = vec![structures::EvidenceRequested {
+            evidence_class: "log".to_string(),
+            evidence_type: "unknown_log".to_string(),
+            chosen_parameters: None,
+        }];
+        assert!(collector.collect(&unknown_requested, None).is_err());
+    }
+}
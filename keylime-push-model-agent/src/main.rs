@@ -1,16 +1,68 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright 2025 Keylime Authors
+use crate::context_info::ContextInfo;
+use crate::evidence::{serialize_evidence, EvidenceCollector};
+use crate::retry::{RetriesExhausted, RetryConfig};
+use crate::session::AttestationSession;
 use crate::struct_filler::StructureFiller;
 use clap::Parser;
+use keylime::{
+    algorithms::{EncryptionAlgorithm, HashAlgorithm, SignAlgorithm},
+    config::{AgentConfig, KeylimeConfig},
+    ima::ImaFilter,
+    list_parser::parse_list,
+    structures, tpm,
+};
+use log::*;
 use std::error::Error;
 use std::fs::File;
 use std::io::Read;
+use std::path::Path;
 use std::time::Duration;
+mod context_info;
+mod evidence;
+mod mtls;
+mod registration;
+mod retry;
+mod session;
 mod struct_filler;
 
 const DEFAULT_TIMEOUT_MILLIS: &str = "5000";
 const HTTPS_PREFIX: &str = "https://";
 const DEFAULT_API_VERSION: &str = "v3.0";
+// API versions enabled to register the agent with the registrar
+const REGISTRAR_API_VERSIONS: &[&str] = &["2.1", "2.2"];
+// Exit code used when the agent stops due to an attestation failure
+const EXIT_CODE_ATTESTATION_FAILED: i32 = 1;
+// Exit code used when all the attempts to negotiate the evidence failed
+const EXIT_CODE_NEGOTIATION_RETRIES_EXHAUSTED: i32 = 2;
+// Exit code used when all the attempts to submit the evidence failed
+const EXIT_CODE_EVIDENCE_RETRIES_EXHAUSTED: i32 = 3;
+
+/// Error that stops the agent with the given exit code
+#[derive(Debug)]
+struct ExitError {
+    code: i32,
+    source: Box<dyn Error>,
+}
+
+impl std::fmt::Display for ExitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Error for ExitError {}
+
+/// Converts the error into an ExitError with the given code if all the
+/// attempts to communicate with the verifier failed
+fn exit_on_retries_exhausted(e: Box<dyn Error>, code: i32) -> Box<dyn Error> {
+    if e.is::<RetriesExhausted>() {
+        Box::new(ExitError { code, source: e })
+    } else {
+        e
+    }
+}
 
 fn get_api_version(args: &Args) -> String {
     if args.api_version.is_some() {
@@ -35,19 +87,17 @@ fn get_https_client(args: &Args) -> Result<reqwest::Client, Box<dyn Error>> {
     let mut buf = Vec::new();
     File::open(args.certificate.clone())?.read_to_end(&mut buf)?;
     let cert = reqwest::Certificate::from_pem(&buf)?;
-    if args.insecure.is_some() && args.insecure.unwrap() {
-        return Ok(reqwest::Client::builder()
-            .connection_verbose(true)
-            .add_root_certificate(cert)
-            .danger_accept_invalid_certs(true)
-            .timeout(Duration::from_millis(args.timeout))
-            .build()?);
-    }
-    Ok(reqwest::Client::builder()
+    let mut builder = reqwest::Client::builder()
         .connection_verbose(true)
         .add_root_certificate(cert)
-        .timeout(Duration::from_millis(args.timeout))
-        .build()?)
+        .timeout(Duration::from_millis(args.timeout));
+    if let Some(identity) = &args.client_identity {
+        builder = builder.identity(identity.clone());
+    }
+    if args.insecure.is_some() && args.insecure.unwrap() {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder.build()?)
 }
 
 fn get_client(args: &Args) -> Result<reqwest::Client, Box<dyn Error>> {
@@ -59,13 +109,23 @@ fn get_client(args: &Args) -> Result<reqwest::Client, Box<dyn Error>> {
         .build()?)
 }
 
-fn get_attestation_filler_request(args: &Args) -> Box<dyn StructureFiller> {
-    if args.json_file.is_none() {
-        return Box::new(struct_filler::AttestationRequestFillerFromCode {});
+fn get_attestation_filler_request(
+    args: &Args,
+    context_info: Option<&mut ContextInfo>,
+) -> Result<Box<dyn StructureFiller>, Box<dyn Error>> {
+    if let Some(json_file) = &args.json_file {
+        return Ok(Box::new(
+            struct_filler::AttestationRequestFillerFromFile {
+                file_path: json_file.clone(),
+            },
+        ));
+    }
+    match context_info {
+        Some(context_info) => Ok(Box::new(context_info.get_filler()?)),
+        None => {
+            Ok(Box::new(struct_filler::AttestationRequestFillerFromCode {}))
+        }
     }
-    Box::new(struct_filler::AttestationRequestFillerFromFile {
-        file_path: args.json_file.clone().unwrap(),
-    })
 }
 
 fn get_request_builder_from_method(
@@ -94,49 +154,112 @@ fn get_request_builder_from_method(
 
 async fn send_attestation_request(
     args: &Args,
-) -> Result<String, Box<dyn Error>> {
-    let filler = get_attestation_filler_request(args);
+    context_info: Option<&mut ContextInfo>,
+    session: &mut AttestationSession,
+) -> Result<structures::AttestationResponse, Box<dyn Error>> {
+    let filler = get_attestation_filler_request(args, context_info)?;
     let request = filler.get_attestation_request();
-    let serialized = serde_json::to_string(&request).unwrap();
-    println!("Serialized Request: {}", serialized);
+    let serialized = serde_json::to_string(&request)?;
+    debug!("Serialized attestation request: {} bytes", serialized.len());
     let reqb = get_request_builder_from_method(args)?;
 
-    let response = reqb
+    let reqb = reqb
         .header("Content-Type", "application/json")
         .header("Content-Length", serialized.len().to_string())
         .body(serialized)
-        .timeout(Duration::from_millis(args.timeout))
-        .send()
-        .await?;
-    println!("Response code:{}", response.status());
-    println!("Response headers: {:?}", response.headers());
-    let response_body = response.text().await?;
-    if !response_body.is_empty() {
-        println!("Response body: {}", response_body);
+        .timeout(Duration::from_millis(args.timeout));
+    session.negotiate(reqb, args.retry_config.as_ref()).await
+}
+
+async fn send_evidence(
+    args: &Args,
+    body: Vec<u8>,
+    session: &mut AttestationSession,
+) -> Result<structures::EvidenceHandlingResponse, Box<dyn Error>> {
+    debug!("Serialized evidence: {} bytes", body.len());
+    let reqb = get_client(args)?
+        .patch(session.evidence_url().as_str())
+        .header("Content-Type", "application/json")
+        .header("Content-Length", body.len().to_string())
+        .body(body)
+        .timeout(Duration::from_millis(args.timeout));
+    session
+        .submit_evidence(reqb, args.retry_config.as_ref())
+        .await
+}
+
+/// Performs one attestation round: negotiates the evidence with the
+/// verifier and submits the evidence it requested
+async fn attest(
+    args: &Args,
+    mut context_info: Option<&mut ContextInfo>,
+    collector: &EvidenceCollector,
+    session: &mut AttestationSession,
+) -> Result<(), Box<dyn Error>> {
+    let response =
+        send_attestation_request(args, context_info.as_deref_mut(), session)
+            .await
+            .map_err(|e| {
+                exit_on_retries_exhausted(
+                    e,
+                    EXIT_CODE_NEGOTIATION_RETRIES_EXHAUSTED,
+                )
+            })?;
+    let evidence_requested = &response.data.attributes.evidence_requested;
+    if evidence_requested.is_empty() {
+        return Err("Verifier did not request any evidence".into());
     }
-    Ok(response_body)
+    let body = {
+        let evidence = collector.collect(evidence_requested, context_info)?;
+        serialize_evidence(&evidence)?
+    };
+    let response = send_evidence(args, body, session).await.map_err(|e| {
+        exit_on_retries_exhausted(e, EXIT_CODE_EVIDENCE_RETRIES_EXHAUSTED)
+    })?;
+    println!(
+        "Evidence evaluation: {} (stage: {})",
+        response.data.attributes.evaluation, response.data.attributes.stage
+    );
+    Ok(())
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, ignore_errors = true)]
-struct Args {
+struct CliArgs {
     /// API version
     /// Default: "v3.0"
     #[arg(long, default_value = DEFAULT_API_VERSION)]
     api_version: Option<String>,
+    /// Interval between attestations in seconds. If 0, the agent attests
+    /// only once
+    /// Overrides the 'attestation_interval_seconds' configuration option
+    #[arg(long)]
+    attestation_interval_seconds: Option<u64>,
+    /// Avoid TPM usage, sending hard-coded capabilities instead
+    #[arg(long, action, default_missing_value = "true")]
+    avoid_tpm: Option<bool>,
     /// certificate file
-    #[arg(short, long, default_value = "/etc/keylime/ca/cacert.crt")]
-    certificate: String,
+    /// Overrides the 'verifier_ca_cert' configuration option
+    #[arg(short, long)]
+    certificate: Option<String>,
     /// json file
     #[arg(short, long, default_missing_value = "")]
     json_file: Option<String>,
     /// identifier
-    /// Default: 12345678
-    #[arg(long, default_value = "12345678")]
-    id: String,
+    /// Overrides the 'uuid' configuration option
+    #[arg(long)]
+    id: Option<String>,
+    /// IMA measurement list path
+    /// Overrides the 'ima_ml_path' configuration option
+    #[arg(long)]
+    ima_ml_path: Option<String>,
     /// insecure
     #[arg(long, action, default_missing_value = "true")]
     insecure: Option<bool>,
+    /// Maximum number of retries when the verifier is unavailable
+    /// Overrides the 'exponential_backoff_max_retries' configuration option
+    #[arg(long)]
+    max_retries: Option<u32>,
     /// Method
     /// Default: "POST"
     #[arg(long, default_value = "POST")]
@@ -145,48 +268,335 @@ struct Args {
     /// Default: 5000
     #[arg(long, default_value = DEFAULT_TIMEOUT_MILLIS)]
     timeout: u64,
+    /// UEFI event log path
+    /// Overrides the 'measuredboot_ml_path' configuration option
+    #[arg(long)]
+    uefi_log_path: Option<String>,
     /// Verifier URL
-    #[arg(short, long, default_value = "https://127.0.0.1:8881")]
+    /// Overrides the 'verifier_url' configuration option
+    #[arg(short, long)]
+    verifier_url: Option<String>,
+}
+
+/// The settings used by the agent, obtained from the command line arguments
+/// and the configuration
+#[derive(Clone, Debug)]
+struct Args {
+    api_version: Option<String>,
+    attestation_interval_seconds: u64,
+    avoid_tpm: Option<bool>,
+    certificate: String,
+    client_identity: Option<reqwest::Identity>,
+    json_file: Option<String>,
+    id: String,
+    ima_ml_path: String,
+    insecure: Option<bool>,
+    method: Option<String>,
+    retry_config: Option<RetryConfig>,
+    timeout: u64,
+    uefi_log_path: String,
     verifier_url: String,
 }
 
-async fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+impl Args {
+    /// Merges the command line arguments with the configuration, giving
+    /// precedence to the values set in the command line
+    ///
+    /// Disabling the verifier certificate validation is only allowed if
+    /// mutual TLS was explicitly disabled in the configuration
+    fn new(
+        cli: CliArgs,
+        config: &AgentConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        if cli.insecure.unwrap_or(false) && config.enable_agent_mtls {
+            return Err("The insecure option requires disabling mTLS by setting 'enable_agent_mtls' as 'false'".into());
+        }
+        Ok(Args {
+            api_version: cli.api_version,
+            attestation_interval_seconds: cli
+                .attestation_interval_seconds
+                .unwrap_or(config.attestation_interval_seconds),
+            avoid_tpm: cli.avoid_tpm,
+            certificate: cli
+                .certificate
+                .unwrap_or_else(|| config.verifier_ca_cert.clone()),
+            client_identity: None,
+            json_file: cli.json_file,
+            id: cli.id.unwrap_or_else(|| config.uuid.clone()),
+            ima_ml_path: cli
+                .ima_ml_path
+                .unwrap_or_else(|| config.ima_ml_path.clone()),
+            insecure: cli.insecure,
+            method: cli.method,
+            retry_config: Some(RetryConfig {
+                max_retries: cli
+                    .max_retries
+                    .unwrap_or(config.exponential_backoff_max_retries),
+                initial_delay_ms: config.exponential_backoff_initial_delay,
+                max_delay_ms: config.exponential_backoff_max_delay,
+            }),
+            timeout: cli.timeout,
+            uefi_log_path: cli
+                .uefi_log_path
+                .unwrap_or_else(|| config.measuredboot_ml_path.clone()),
+            verifier_url: cli
+                .verifier_url
+                .unwrap_or_else(|| config.verifier_url.clone()),
+        })
+    }
+}
+
+/// Creates the TPM context using the algorithms, EK handle and agent data
+/// path from the configuration
+fn get_context_info(
+    config: &AgentConfig,
+) -> Result<ContextInfo, Box<dyn Error>> {
+    let tpm_encryption_alg =
+        EncryptionAlgorithm::try_from(config.tpm_encryption_alg.as_str())?;
+    let tpm_hash_alg = HashAlgorithm::try_from(config.tpm_hash_alg.as_str())?;
+    let tpm_signing_alg =
+        SignAlgorithm::try_from(config.tpm_signing_alg.as_str())?;
+    let ek_handle = match config.ek_handle.as_str() {
+        "" => None,
+        s => Some(s),
+    };
+    let agent_data_path = match config.agent_data_path.as_str() {
+        "" => None,
+        s => Some(Path::new(s)),
+    };
+    ContextInfo::new(
+        tpm_encryption_alg,
+        tpm_hash_alg,
+        tpm_signing_alg,
+        ek_handle,
+        agent_data_path,
+    )
+}
+
+/// Removes the quotes around the items of the lists in the configuration
+fn unquote(s: &str) -> &str {
+    s.trim_matches(|c| c == '"' || c == '\'')
+}
+
+/// Creates the filter for the IMA log entries from the exclusion lists in
+/// the configuration. The excluded entries carry the template hashes for the
+/// banks given by 'tpm_hash_alg' and 'tpm_pcr_banks'
+fn get_ima_filter(
+    config: &AgentConfig,
+) -> Result<Option<ImaFilter>, Box<dyn Error>> {
+    let paths = parse_list(&config.ima_ml_exclude_paths)?
+        .into_iter()
+        .map(unquote)
+        .collect::<Vec<_>>();
+    let templates = parse_list(&config.ima_ml_exclude_templates)?
+        .into_iter()
+        .map(unquote)
+        .collect::<Vec<_>>();
+    if paths.is_empty() && templates.is_empty() {
+        return Ok(None);
+    }
+    let tpm_pcr_banks = parse_list(&config.tpm_pcr_banks)?
+        .into_iter()
+        .map(|bank| HashAlgorithm::try_from(unquote(bank)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let banks = tpm::quote_banks(
+        HashAlgorithm::try_from(config.tpm_hash_alg.as_str())?,
+        &tpm_pcr_banks,
+    );
+    Ok(Some(ImaFilter::new(&paths, &templates, &banks)?))
+}
+
+/// Replaces the 'hash_ek' keyword in the agent identifier with the hash of
+/// the EK public key. This can only be done once the TPM is available
+fn resolve_agent_id(
+    args: &mut Args,
+    context_info: Option<&ContextInfo>,
+) -> Result<(), Box<dyn Error>> {
+    if args.id != "hash_ek" {
+        return Ok(());
+    }
+    match context_info {
+        Some(context_info) => {
+            args.id = context_info.ek_hash.clone();
+            Ok(())
+        }
+        None => Err("The 'hash_ek' agent UUID requires the TPM".into()),
+    }
+}
+
+/// Completes the settings that depend on the TPM and registers the agent
+async fn setup(
+    args: &Args,
+    config: &AgentConfig,
+    context_info: Option<&mut ContextInfo>,
+) -> Result<Args, Box<dyn Error>> {
+    let mut args = args.clone();
+    resolve_agent_id(&mut args, context_info.as_deref())?;
+    // The mTLS certificate is registered so that it can be trusted by the
+    // verifier
+    let mtls_identity = if config.enable_agent_mtls {
+        if !args.verifier_url.starts_with(HTTPS_PREFIX) {
+            warn!("mTLS is enabled, but the verifier URL does not use HTTPS");
+        }
+        Some(mtls::get_key_and_cert(config, &args.id)?)
+    } else {
+        None
+    };
+    // The verifier can only trust the AK once the registrar has activated it
+    if let Some(context_info) = context_info {
+        let mtls_cert = mtls_identity.as_ref().map(|(_, cert)| cert.clone());
+        if let Err(e) = registration::register_agent(
+            context_info,
+            config,
+            &args.id,
+            mtls_cert,
+        )
+        .await
+        {
+            eprintln!("Error registering agent: {}", e);
+            return Err(e);
+        }
+    }
+    if let Some((key, cert)) = mtls_identity {
+        args.client_identity = Some(mtls::get_client_identity(&key, &cert)?);
+    }
+    Ok(args)
+}
+
+async fn run(
+    args: &Args,
+    config: &AgentConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let ima_filter = get_ima_filter(config)?;
+    let mut context_info = if args.avoid_tpm.unwrap_or(false) {
+        None
+    } else {
+        match get_context_info(config) {
+            Ok(context_info) => Some(context_info),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return Err(e);
+            }
+        }
+    };
+    let args = match setup(args, config, context_info.as_mut()).await {
+        Ok(args) => args,
+        Err(e) => {
+            if let Some(context_info) = context_info {
+                context_info.flush();
+            }
+            return Err(e);
+        }
+    };
+    println!("Agent ID: {}", args.id);
     println!("API version: {}", get_api_version(&args));
     println!("Verifier URL: {}", args.verifier_url);
     println!("Timeout: {}", args.timeout);
     println!("Certificate file: {}", args.certificate);
     println!("Insecure: {}", args.insecure.unwrap_or(false));
-    let res = send_attestation_request(&args).await;
-    match res {
-        Ok(_) => println!("Request sent successfully"),
-        Err(e) => eprintln!("Error: {}", e),
+    let mut collector =
+        EvidenceCollector::new(&args.ima_ml_path, &args.uefi_log_path);
+    collector.set_ima_filter(ima_filter);
+    let mut session =
+        AttestationSession::new(&get_attestation_request_url(&args));
+    let default_interval =
+        Duration::from_secs(args.attestation_interval_seconds);
+    loop {
+        let res =
+            attest(&args, context_info.as_mut(), &collector, &mut session)
+                .await;
+        match res {
+            Ok(_) => match session.session_id() {
+                Some(id) => {
+                    println!("Evidence sent successfully (attestation {id})")
+                }
+                None => println!("Evidence sent successfully"),
+            },
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                // Stop if the verifier could not be reached after all the
+                // attempts or if no other attestation is scheduled
+                if e.is::<ExitError>()
+                    || args.attestation_interval_seconds == 0
+                {
+                    if let Some(context_info) = context_info {
+                        context_info.flush();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        if args.attestation_interval_seconds == 0 {
+            break;
+        }
+        // The verifier can request a different interval for the next round
+        let interval = session.next_attestation_delay(default_interval);
+        if interval != default_interval {
+            info!(
+                "Next attestation in {} seconds, as requested by the verifier",
+                interval.as_secs()
+            );
+        }
+        actix_web::rt::time::sleep(interval).await;
+    }
+    if let Some(context_info) = context_info {
+        context_info.flush();
     }
     Ok(())
 }
 
 #[actix_web::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    run().await
+    pretty_env_logger::init();
+    let cli = CliArgs::parse();
+    let config = KeylimeConfig::new(REGISTRAR_API_VERSIONS)?;
+    let args = Args::new(cli, &config.agent)?;
+    if let Err(e) = run(&args, &config.agent).await {
+        let code = match e.downcast_ref::<ExitError>() {
+            Some(exit_error) => exit_error.code,
+            None => EXIT_CODE_ATTESTATION_FAILED,
+        };
+        std::process::exit(code);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use keylime::config;
     const TEST_TIMEOUT_MILLIS: u64 = 100;
 
-    #[actix_rt::test]
-    async fn send_attestation_request_test() {
-        if (send_attestation_request(&Args {
-            api_version: Some("v3.0".to_string()),
+    /// Arguments for the tests, with a verifier which is not reachable
+    fn test_args() -> Args {
+        Args {
+            api_version: None,
+            avoid_tpm: Some(true),
             verifier_url: "http://1.2.3.4:5678".to_string(),
             timeout: TEST_TIMEOUT_MILLIS,
             certificate: "/tmp/does_not_exist.pem".to_string(),
+            client_identity: None,
             insecure: Some(false),
             id: "12345678".to_string(),
             json_file: None,
             method: None,
-        })
+            retry_config: None,
+            ima_ml_path: config::DEFAULT_IMA_ML_PATH.to_string(),
+            uefi_log_path: config::DEFAULT_MEASUREDBOOT_ML_PATH.to_string(),
+            attestation_interval_seconds: 0,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn send_attestation_request_test() {
+        if (send_attestation_request(
+            &Args {
+                api_version: Some("v3.0".to_string()),
+                ..test_args()
+            },
+            None,
+            &mut AttestationSession::default(),
+        )
         .await)
             .is_ok()
         {
@@ -196,16 +606,17 @@ mod tests {
 
     #[actix_rt::test]
     async fn send_attestation_request_test_no_cert_file() {
-        match send_attestation_request(&Args {
-            api_version: Some("v3.0".to_string()),
-            verifier_url: "https://1.2.3.4:5678".to_string(),
-            timeout: TEST_TIMEOUT_MILLIS,
-            certificate: "/tmp/unexisting_cert_file".to_string(),
-            insecure: Some(true),
-            id: "12345678".to_string(),
-            json_file: None,
-            method: None,
-        })
+        match send_attestation_request(
+            &Args {
+                api_version: Some("v3.0".to_string()),
+                verifier_url: "https://1.2.3.4:5678".to_string(),
+                certificate: "/tmp/unexisting_cert_file".to_string(),
+                insecure: Some(true),
+                ..test_args()
+            },
+            None,
+            &mut AttestationSession::default(),
+        )
         .await
         {
             Ok(_) => unreachable!(),
@@ -224,16 +635,16 @@ mod tests {
             .unwrap();
         assert!(output.status.success());
 
-        match send_attestation_request(&Args {
-            api_version: Some("3.0".to_string()),
-            verifier_url: "https://1.2.3.4:5678/".to_string(),
-            timeout: TEST_TIMEOUT_MILLIS,
-            certificate: "/tmp/test_cert_file".to_string(),
-            insecure: Some(false),
-            id: "12345678".to_string(),
-            json_file: None,
-            method: None,
-        })
+        match send_attestation_request(
+            &Args {
+                api_version: Some("3.0".to_string()),
+                verifier_url: "https://1.2.3.4:5678/".to_string(),
+                certificate: "/tmp/test_cert_file".to_string(),
+                ..test_args()
+            },
+            None,
+            &mut AttestationSession::default(),
+        )
         .await
         {
             Ok(_) => unreachable!(),
@@ -241,16 +652,17 @@ mod tests {
                 assert_eq!(e.to_string(), "builder error")
             }
         }
-        match send_attestation_request(&Args {
-            api_version: Some("3.0".to_string()),
-            verifier_url: "https://1.2.3.4:5678/".to_string(),
-            timeout: TEST_TIMEOUT_MILLIS,
-            certificate: "/tmp/test_cert_file".to_string(),
-            insecure: Some(true),
-            id: "12345678".to_string(),
-            json_file: None,
-            method: None,
-        })
+        match send_attestation_request(
+            &Args {
+                api_version: Some("3.0".to_string()),
+                verifier_url: "https://1.2.3.4:5678/".to_string(),
+                certificate: "/tmp/test_cert_file".to_string(),
+                insecure: Some(true),
+                ..test_args()
+            },
+            None,
+            &mut AttestationSession::default(),
+        )
         .await
         {
             Ok(_) => unreachable!(),
@@ -258,19 +670,21 @@ mod tests {
                 assert!(e.to_string().contains("builder error"))
             }
         }
-        match send_attestation_request(&Args {
-            api_version: Some("3.0".to_string()),
-            verifier_url: "https://1.2.3.4:5678/".to_string(),
-            timeout: TEST_TIMEOUT_MILLIS,
-            certificate: "/tmp/test_cert_file".to_string(),
-            insecure: Some(true),
-            id: "12345678".to_string(),
-            json_file: Some(
-                "./tests/evidence_supported_attestation_request.json"
-                    .to_string(),
-            ),
-            method: None,
-        })
+        match send_attestation_request(
+            &Args {
+                api_version: Some("3.0".to_string()),
+                verifier_url: "https://1.2.3.4:5678/".to_string(),
+                certificate: "/tmp/test_cert_file".to_string(),
+                insecure: Some(true),
+                json_file: Some(
+                    "./tests/evidence_supported_attestation_request.json"
+                        .to_string(),
+                ),
+                ..test_args()
+            },
+            None,
+            &mut AttestationSession::default(),
+        )
         .await
         {
             Ok(_) => unreachable!(),
@@ -291,16 +705,14 @@ mod tests {
         // array with the different methods:
         let methods = vec!["DELETE", "GET", "PATCH", "POST", "PUT"];
         for method in methods {
-            match send_attestation_request(&Args {
-                api_version: None,
-                verifier_url: "http://1.2.3.4:5678".to_string(),
-                timeout: TEST_TIMEOUT_MILLIS,
-                certificate: "/tmp/does_not_exists.pem".to_string(),
-                insecure: Some(false),
-                id: "12345678".to_string(),
-                json_file: None,
-                method: Some(method.to_string()),
-            })
+            match send_attestation_request(
+                &Args {
+                    method: Some(method.to_string()),
+                    ..test_args()
+                },
+                None,
+                &mut AttestationSession::default(),
+            )
             .await
             {
                 Ok(_) => unreachable!(),
@@ -314,14 +726,8 @@ mod tests {
     #[actix_rt::test]
     async fn get_attestation_request_url_test() {
         let url = get_attestation_request_url(&Args {
-            api_version: None,
             verifier_url: "https://1.2.3.4:5678/".to_string(),
-            timeout: TEST_TIMEOUT_MILLIS,
-            certificate: "/tmp/does_not_exists.pem".to_string(),
-            insecure: Some(false),
-            id: "12345678".to_string(),
-            json_file: None,
-            method: None,
+            ..test_args()
         });
         assert_eq!(
             url,
@@ -329,9 +735,95 @@ mod tests {
         );
     } // get_attestation_request_url_test
 
+    #[test]
+    fn args_from_config_test() {
+        let config = KeylimeConfig::default();
+        let cli = CliArgs::parse_from(["keylime_push_model_agent"]);
+        let args = Args::new(cli, &config.agent).unwrap(); //#[allow_ci]
+        assert_eq!(args.id, config.agent.uuid);
+        assert_eq!(args.verifier_url, config.agent.verifier_url);
+        assert_eq!(args.certificate, config.agent.verifier_ca_cert);
+        assert_eq!(args.ima_ml_path, config.agent.ima_ml_path);
+        assert_eq!(args.uefi_log_path, config.agent.measuredboot_ml_path);
+        assert_eq!(
+            args.attestation_interval_seconds,
+            config.agent.attestation_interval_seconds
+        );
+
+        // The command line arguments take precedence over the configuration
+        let cli = CliArgs::parse_from([
+            "keylime_push_model_agent",
+            "--id",
+            "cli_id",
+            "--verifier-url",
+            "http://1.2.3.4:5678",
+            "--certificate",
+            "/tmp/cli_cert.pem",
+            "--attestation-interval-seconds",
+            "0",
+        ]);
+        let args = Args::new(cli, &config.agent).unwrap(); //#[allow_ci]
+        assert_eq!(args.id, "cli_id");
+        assert_eq!(args.verifier_url, "http://1.2.3.4:5678");
+        assert_eq!(args.certificate, "/tmp/cli_cert.pem");
+        assert_eq!(args.attestation_interval_seconds, 0);
+        assert_eq!(args.ima_ml_path, config.agent.ima_ml_path);
+    }
+
+    #[test]
+    fn args_insecure_test() {
+        let mut config = KeylimeConfig::default();
+        let cli =
+            CliArgs::parse_from(["keylime_push_model_agent", "--insecure"]);
+        // The insecure option cannot be combined with the default mTLS
+        assert!(Args::new(cli, &config.agent).is_err());
+
+        config.agent.enable_agent_mtls = false;
+        let cli =
+            CliArgs::parse_from(["keylime_push_model_agent", "--insecure"]);
+        let args = Args::new(cli, &config.agent).unwrap(); //#[allow_ci]
+        assert_eq!(args.insecure, Some(true));
+    }
+
+    #[test]
+    fn get_ima_filter_test() {
+        let mut config = KeylimeConfig::default();
+        assert!(get_ima_filter(&config.agent).unwrap().is_none()); //#[allow_ci]
+
+        config.agent.ima_ml_exclude_paths =
+            "\"/tmp/**\", /var/log/*".to_string();
+        config.agent.tpm_pcr_banks = "sha384".to_string();
+        let filter = get_ima_filter(&config.agent).unwrap().unwrap(); //#[allow_ci]
+        assert!(!filter.is_empty());
+
+        config.agent.tpm_pcr_banks = "invalid".to_string();
+        assert!(get_ima_filter(&config.agent).is_err());
+    }
+
+    #[test]
+    fn resolve_agent_id_test() {
+        let config = KeylimeConfig::default();
+        let cli = CliArgs::parse_from(["keylime_push_model_agent"]);
+        let mut args = Args::new(cli, &config.agent).unwrap(); //#[allow_ci]
+        args.id = "12345678".to_string();
+        assert!(resolve_agent_id(&mut args, None).is_ok());
+        assert_eq!(args.id, "12345678");
+
+        // The EK hash cannot be obtained without the TPM
+        args.id = "hash_ek".to_string();
+        assert!(resolve_agent_id(&mut args, None).is_err());
+    }
+
     #[actix_rt::test]
     async fn run_test() {
-        let res = run();
-        assert!(res.await.is_ok());
+        let args = test_args();
+        let mut config = KeylimeConfig::default();
+        config.agent.enable_agent_mtls = false;
+        let res = run(&args, &config.agent).await;
+        let Err(e) = res else {
+            panic!("Expected the verifier to be unreachable"); //#[allow_ci]
+        };
+        let exit_error = e.downcast_ref::<ExitError>().unwrap(); //#[allow_ci]
+        assert_eq!(exit_error.code, EXIT_CODE_NEGOTIATION_RETRIES_EXHAUSTED);
     }
 }
@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+use keylime::{config::AgentConfig, crypto};
+use log::*;
+use openssl::{
+    pkey::{PKey, Private},
+    x509::X509,
+};
+use std::error::Error;
+use std::path::Path;
+
+/// Loads the agent key pair set in the 'server_key' configuration option,
+/// generating and storing a new one if it does not exist
+fn get_key(config: &AgentConfig) -> Result<PKey<Private>, Box<dyn Error>> {
+    let key_password = Some(config.server_key_password.as_str());
+    match config.server_key.as_str() {
+        "" => {
+            debug!(
+                "The server_key option was not set in the configuration file"
+            );
+            debug!("Generating new key pair");
+            let (_, private) = crypto::rsa_generate_pair(2048)?;
+            Ok(private)
+        }
+        path => {
+            let key_path = Path::new(path);
+            if key_path.exists() {
+                debug!(
+                    "Loading existing key pair from {}",
+                    key_path.display()
+                );
+                let (_, private) =
+                    crypto::load_key_pair(key_path, key_password)?;
+                Ok(private)
+            } else {
+                debug!("Generating new key pair");
+                let (_, private) = crypto::rsa_generate_pair(2048)?;
+                crypto::write_key_pair(&private, key_path, key_password)?;
+                Ok(private)
+            }
+        }
+    }
+}
+
+/// Loads the agent certificate set in the 'server_cert' configuration
+/// option, generating and storing a new one if it does not exist
+fn get_cert(
+    config: &AgentConfig,
+    key: &PKey<Private>,
+    agent_uuid: &str,
+) -> Result<X509, Box<dyn Error>> {
+    let contact_ips = vec![config.contact_ip.as_str()];
+    match config.server_cert.as_str() {
+        "" => {
+            debug!("The server_cert option was not set in the configuration file");
+            Ok(crypto::x509::CertificateBuilder::new()
+                .private_key(key)
+                .common_name(agent_uuid)
+                .add_ips(contact_ips)
+                .build()?)
+        }
+        path => {
+            let cert_path = Path::new(path);
+            if cert_path.exists() {
+                debug!(
+                    "Loading existing mTLS certificate from {}",
+                    cert_path.display()
+                );
+                Ok(crypto::load_x509_pem(cert_path)?)
+            } else {
+                debug!("Generating new mTLS certificate");
+                let cert = crypto::x509::CertificateBuilder::new()
+                    .private_key(key)
+                    .common_name(agent_uuid)
+                    .add_ips(contact_ips)
+                    .build()?;
+                crypto::write_x509(&cert, cert_path)?;
+                Ok(cert)
+            }
+        }
+    }
+}
+
+/// Loads the agent mTLS key pair and certificate, generating and storing
+/// them if they do not exist. The agent mTLS key and certificate are the same
+/// used by the pull model agent
+///
+/// # Arguments
+///
+/// * config (&AgentConfig): The agent configuration
+/// * agent_uuid (&str): The agent identifier, used as the certificate common
+///   name when a new certificate is generated
+pub fn get_key_and_cert(
+    config: &AgentConfig,
+    agent_uuid: &str,
+) -> Result<(PKey<Private>, X509), Box<dyn Error>> {
+    let key = get_key(config)?;
+    let cert = get_cert(config, &key, agent_uuid)?;
+    Ok((key, cert))
+}
+
+/// Gets the identity presented by the agent to authenticate to the verifier
+/// using mutual TLS
+///
+/// # Arguments
+///
+/// * key (&PKey<Private>): The agent mTLS private key
+/// * cert (&X509): The agent mTLS certificate
+pub fn get_client_identity(
+    key: &PKey<Private>,
+    cert: &X509,
+) -> Result<reqwest::Identity, Box<dyn Error>> {
+    Ok(reqwest::Identity::from_pkcs8_pem(
+        &cert.to_pem()?,
+        &key.private_key_to_pem_pkcs8()?,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keylime::config::KeylimeConfig;
+
+    #[test]
+    fn get_key_and_cert_test() {
+        let temp_dir = tempfile::tempdir().unwrap(); //#[allow_ci]
+        let key_path = temp_dir.path().join("key.pem");
+        let cert_path = temp_dir.path().join("cert.pem");
+        let mut config = KeylimeConfig::default().agent;
+        config.server_key = key_path.display().to_string();
+        config.server_cert = cert_path.display().to_string();
+        config.server_key_password = "password".to_string();
+
+        // The key and certificate are generated and stored
+        let (key, cert) = get_key_and_cert(&config, "test_uuid").unwrap(); //#[allow_ci]
+        let result = get_client_identity(&key, &cert);
+        assert!(result.is_ok(), "{result:?}");
+        assert!(key_path.exists());
+        assert!(cert_path.exists());
+
+        // The stored key and certificate are reused
+        let (_, reloaded) = get_key_and_cert(&config, "other_uuid").unwrap(); //#[allow_ci]
+        assert_eq!(cert.to_der().unwrap(), reloaded.to_der().unwrap()); //#[allow_ci]
+
+        // A wrong password prevents loading the stored key
+        config.server_key_password = "wrong".to_string();
+        assert!(get_key_and_cert(&config, "test_uuid").is_err());
+    }
+
+    #[test]
+    fn get_key_and_cert_ephemeral_test() {
+        let mut config = KeylimeConfig::default().agent;
+        config.server_key = "".to_string();
+        config.server_cert = "".to_string();
+        let (key, cert) = get_key_and_cert(&config, "test_uuid").unwrap(); //#[allow_ci]
+        assert!(get_client_identity(&key, &cert).is_ok());
+    }
+}
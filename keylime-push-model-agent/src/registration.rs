@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+use crate::context_info::ContextInfo;
+use keylime::{
+    agent_registration::{self, AgentRegistration},
+    config::{self, AgentConfig},
+    device_id::{DeviceID, DeviceIDBuilder},
+    list_parser::parse_list,
+    tpm,
+};
+use log::*;
+use openssl::x509::X509;
+use std::error::Error;
+use tss_esapi::structures::Data;
+
+/// Obtains the IAK and IDevID, if enabled in the configuration
+fn get_device_id(
+    config: &AgentConfig,
+    ctx: &mut tpm::Context<'_>,
+) -> Result<Option<DeviceID>, Box<dyn Error>> {
+    if !config.enable_iak_idevid {
+        return Ok(None);
+    }
+
+    let mut builder = DeviceIDBuilder::new()
+        .iak_handle(&config.iak_handle)
+        .iak_password(&config.iak_password)
+        .iak_default_template(config::DEFAULT_IAK_IDEVID_TEMPLATE)
+        .iak_template(&config.iak_idevid_template)
+        .iak_asym_alg(&config.iak_idevid_asymmetric_alg)
+        .iak_hash_alg(&config.iak_idevid_name_alg)
+        .idevid_handle(&config.idevid_handle)
+        .idevid_cert_path(&config.idevid_cert)
+        .idevid_password(&config.idevid_password)
+        .idevid_default_template(config::DEFAULT_IAK_IDEVID_TEMPLATE)
+        .idevid_template(&config.iak_idevid_template)
+        .idevid_asym_alg(&config.iak_idevid_asymmetric_alg)
+        .idevid_hash_alg(&config.iak_idevid_name_alg);
+
+    if !config.iak_cert.is_empty() {
+        builder = builder.iak_cert_path(&config.iak_cert);
+    }
+
+    if !config.idevid_cert.is_empty() {
+        builder = builder.idevid_cert_path(&config.idevid_cert);
+    }
+
+    Ok(Some(builder.build(ctx)?))
+}
+
+/// Registers the agent with the registrar and activates it by proving the
+/// AK is bound to the EK through the credential activation
+///
+/// # Arguments
+///
+/// * context_info (&mut ContextInfo): The TPM context holding the EK and AK
+/// * config (&AgentConfig): The agent configuration
+/// * agent_uuid (&str): The agent identifier
+/// * mtls_cert (Option<X509>): The agent mTLS certificate, if mTLS is enabled
+pub async fn register_agent(
+    context_info: &mut ContextInfo,
+    config: &AgentConfig,
+    agent_uuid: &str,
+    mtls_cert: Option<X509>,
+) -> Result<(), Box<dyn Error>> {
+    let mut device_id = get_device_id(config, &mut context_info.tpm_context)?;
+
+    let (attest, signature) = if let Some(dev_id) = &mut device_id {
+        let qualifying_data = Data::try_from(agent_uuid.as_bytes())?;
+        let (attest, signature) = dev_id.certify(
+            qualifying_data,
+            context_info.ak_handle,
+            &mut context_info.tpm_context,
+        )?;
+        info!("AK certified with IAK.");
+        (Some(attest), Some(signature))
+    } else {
+        (None, None)
+    };
+
+    let aa = AgentRegistration {
+        ak: context_info.ak.clone(),
+        ek_result: context_info.ek_result.clone(),
+        api_versions: parse_list(&config.api_versions)?
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        agent: config.clone(),
+        agent_uuid: agent_uuid.to_string(),
+        // The verifier does not contact the agent in the push model
+        contact_ip: None,
+        contact_port: None,
+        mtls_cert,
+        device_id,
+        attest,
+        signature,
+        ak_handle: context_info.ak_handle,
+    };
+    agent_registration::register_agent(aa, &mut context_info.tpm_context)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+use log::*;
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// The retry policy used when communicating with the verifier
+#[derive(Clone, Debug)]
+pub struct RetryConfig {
+    /// Maximum number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on each following retry
+    pub initial_delay_ms: u64,
+    /// Upper bound for the delay between retries
+    pub max_delay_ms: u64,
+}
+
+/// Error returned when all the attempts to send a request failed
+#[derive(Debug)]
+pub struct RetriesExhausted {
+    pub attempts: u32,
+    pub last_error: String,
+}
+
+impl fmt::Display for RetriesExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Giving up after {} attempt(s): {}",
+            self.attempts, self.last_error
+        )
+    }
+}
+
+impl Error for RetriesExhausted {}
+
+/// Returns true if the verifier may accept the request later
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Parses the Retry-After header value. Only the delay in seconds format is
+/// supported, HTTP dates are ignored
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+/// Calculates the delay before the given retry, starting from 0. The delay
+/// grows exponentially up to the maximum and a random jitter in the upper
+/// half of the delay is applied to avoid agents retrying in lockstep
+fn get_backoff_delay(config: &RetryConfig, retry: u32) -> Duration {
+    let delay = config
+        .initial_delay_ms
+        .saturating_mul(2u64.saturating_pow(retry))
+        .min(config.max_delay_ms);
+    let half = delay / 2;
+    let mut buf = [0u8; 8];
+    let jitter = match openssl::rand::rand_bytes(&mut buf) {
+        Ok(_) => u64::from_ne_bytes(buf) % (delay - half + 1),
+        Err(_) => 0,
+    };
+    Duration::from_millis(half + jitter)
+}
+
+/// Calculates the delay before the given retry, using the delay requested
+/// by the verifier in the Retry-After header if present. The requested delay
+/// is limited to the maximum, so that the verifier cannot stall the agent
+fn get_retry_delay(
+    config: &RetryConfig,
+    retry: u32,
+    retry_after: Option<Duration>,
+) -> Duration {
+    match retry_after {
+        Some(delay) => delay.min(Duration::from_millis(config.max_delay_ms)),
+        None => get_backoff_delay(config, retry),
+    }
+}
+
+/// Sends the request, retrying according to the retry policy on connection
+/// failures and on responses indicating the verifier is unavailable. The
+/// Retry-After header sent by the verifier, up to the maximum delay, takes
+/// precedence over the calculated backoff delay
+///
+/// # Arguments
+///
+/// * request (reqwest::RequestBuilder): The request to send
+/// * retry_config (Option<&RetryConfig>): The retry policy. If None, a single
+///   attempt is made
+pub async fn send_with_retry(
+    request: reqwest::RequestBuilder,
+    retry_config: Option<&RetryConfig>,
+) -> Result<reqwest::Response, Box<dyn Error>> {
+    let max_retries = retry_config.map(|c| c.max_retries).unwrap_or(0);
+    let mut retry = 0;
+    loop {
+        let Some(attempt) = request.try_clone() else {
+            return Err("The request cannot be cloned for retrying".into());
+        };
+        let (last_error, retry_after) = match attempt.send().await {
+            Ok(response) if is_retryable_status(response.status()) => {
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_retry_after);
+                (
+                    format!("Unexpected response code {}", response.status()),
+                    retry_after,
+                )
+            }
+            Ok(response) => return Ok(response),
+            Err(e) if e.is_builder() => return Err(e.into()),
+            Err(e) => (e.to_string(), None),
+        };
+
+        let Some(config) = retry_config.filter(|_| retry < max_retries)
+        else {
+            return Err(Box::new(RetriesExhausted {
+                attempts: retry + 1,
+                last_error,
+            }));
+        };
+        let delay = get_retry_delay(config, retry, retry_after);
+        warn!(
+            "Request failed: {}. Retrying in {} ms ({}/{})",
+            last_error,
+            delay.as_millis(),
+            retry + 1,
+            max_retries
+        );
+        actix_web::rt::time::sleep(delay).await;
+        retry += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retryable_status_test() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable_status(StatusCode::OK));
+    }
+
+    #[test]
+    fn parse_retry_after_test() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 5 "), Some(Duration::from_secs(5)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+
+    #[test]
+    fn backoff_delay_test() {
+        let config = RetryConfig {
+            max_retries: 10,
+            initial_delay_ms: 100,
+            max_delay_ms: 1000,
+        };
+        for retry in 0..10 {
+            let expected = (100u64 << retry).min(1000);
+            let delay = get_backoff_delay(&config, retry).as_millis() as u64;
+            assert!(delay >= expected / 2, "{delay} < {}", expected / 2);
+            assert!(delay <= expected, "{delay} > {expected}");
+        }
+
+        // Huge retry counts must not overflow
+        let delay = get_backoff_delay(&config, 200).as_millis() as u64;
+        assert!(delay <= 1000);
+    }
+
+    #[test]
+    fn retry_delay_test() {
+        let config = RetryConfig {
+            max_retries: 10,
+            initial_delay_ms: 100,
+            max_delay_ms: 1000,
+        };
+        // The Retry-After delay is used up to the maximum delay
+        assert_eq!(
+            get_retry_delay(&config, 0, Some(Duration::from_millis(500))),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            get_retry_delay(&config, 0, Some(Duration::from_secs(3600))),
+            Duration::from_millis(1000)
+        );
+        // Without Retry-After the backoff delay is used
+        let delay = get_retry_delay(&config, 0, None);
+        assert!(delay <= Duration::from_millis(100));
+    }
+
+    #[actix_rt::test]
+    async fn retries_exhausted_test() {
+        let config = RetryConfig {
+            max_retries: 2,
+            initial_delay_ms: 1,
+            max_delay_ms: 2,
+        };
+        let request = reqwest::Client::new()
+            .get("http://1.2.3.4:5678")
+            .timeout(Duration::from_millis(10));
+        let result = send_with_retry(request, Some(&config)).await;
+        let Err(e) = result else {
+            panic!("Expected an error"); //#[allow_ci]
+        };
+        let exhausted = e.downcast_ref::<RetriesExhausted>().unwrap(); //#[allow_ci]
+        assert_eq!(exhausted.attempts, 3);
+
+        // Without a retry policy a single attempt is made
+        let request = reqwest::Client::new()
+            .get("http://1.2.3.4:5678")
+            .timeout(Duration::from_millis(10));
+        let e = send_with_retry(request, None).await.unwrap_err(); //#[allow_ci]
+        let exhausted = e.downcast_ref::<RetriesExhausted>().unwrap(); //#[allow_ci]
+        assert_eq!(exhausted.attempts, 1);
+    }
+}
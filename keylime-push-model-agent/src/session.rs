@@ -0,0 +1,361 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+use crate::retry::{self, RetryConfig};
+use keylime::structures::{AttestationResponse, EvidenceHandlingResponse};
+use log::*;
+use reqwest::header::LOCATION;
+use std::error::Error;
+use std::time::Duration;
+
+/// An attestation session with the verifier
+///
+/// The session keeps the state returned by the verifier when the evidence is
+/// negotiated, so that the evidence is submitted to the attestation created
+/// by the verifier, and the interval requested by the verifier for the next
+/// attestation
+#[derive(Debug, Default)]
+pub struct AttestationSession {
+    attestation_url: String,
+    session_id: Option<String>,
+    location: Option<String>,
+    next_attestation: Option<Duration>,
+}
+
+impl AttestationSession {
+    /// Creates a session for the agent attestations endpoint
+    ///
+    /// # Arguments
+    ///
+    /// * attestation_url (&str): The URL used to negotiate the evidence,
+    ///   e.g. https://verifier/v3.0/agents/<uuid>/attestations
+    pub fn new(attestation_url: &str) -> Self {
+        AttestationSession {
+            attestation_url: attestation_url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The identifier of the attestation created by the verifier, if any
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    /// Gets the URL where the evidence should be submitted. The location
+    /// returned by the verifier takes precedence, followed by the attestation
+    /// identifier. If the verifier returned neither, the latest attestation
+    /// is used
+    pub fn evidence_url(&self) -> String {
+        if let Some(location) = &self.location {
+            return location.clone();
+        }
+        let base = self.attestation_url.trim_end_matches('/');
+        match &self.session_id {
+            Some(id) => format!("{base}/{id}"),
+            None => format!("{base}/latest"),
+        }
+    }
+
+    /// Gets the delay before the next attestation. The interval requested by
+    /// the verifier in the last evidence response takes precedence over the
+    /// given default
+    pub fn next_attestation_delay(&self, default: Duration) -> Duration {
+        self.next_attestation.unwrap_or(default)
+    }
+
+    /// Sends the capabilities negotiation request, starting a new session
+    ///
+    /// # Arguments
+    ///
+    /// * request (reqwest::RequestBuilder): The negotiation request
+    /// * retry_config (Option<&RetryConfig>): The retry policy
+    pub async fn negotiate(
+        &mut self,
+        request: reqwest::RequestBuilder,
+        retry_config: Option<&RetryConfig>,
+    ) -> Result<AttestationResponse, Box<dyn Error>> {
+        // The state from the previous session is no longer valid
+        self.session_id = None;
+        self.location = None;
+        self.next_attestation = None;
+
+        let response = retry::send_with_retry(request, retry_config).await?;
+        let location = match response.headers().get(LOCATION) {
+            Some(value) => Some(self.resolve_location(value.to_str()?)?),
+            None => None,
+        };
+        let body = get_response_body(response).await?;
+        let response: AttestationResponse = serde_json::from_str(&body)?;
+
+        self.location = location;
+        self.session_id = response.data.id.clone();
+        debug!(
+            "Attestation session started (id: {:?}, evidence URL: {})",
+            self.session_id,
+            self.evidence_url()
+        );
+        Ok(response)
+    }
+
+    /// Sends the evidence to the URL obtained from the negotiation, which
+    /// must be used to build the request
+    ///
+    /// # Arguments
+    ///
+    /// * request (reqwest::RequestBuilder): The evidence handling request
+    /// * retry_config (Option<&RetryConfig>): The retry policy
+    pub async fn submit_evidence(
+        &mut self,
+        request: reqwest::RequestBuilder,
+        retry_config: Option<&RetryConfig>,
+    ) -> Result<EvidenceHandlingResponse, Box<dyn Error>> {
+        let response = retry::send_with_retry(request, retry_config).await?;
+        let body = get_response_body(response).await?;
+        let response: EvidenceHandlingResponse = serde_json::from_str(&body)?;
+
+        if self.session_id.is_none() {
+            self.session_id = response.data.id.clone();
+        }
+        self.next_attestation = response
+            .data
+            .attributes
+            .seconds_to_next_attestation
+            .map(Duration::from_secs);
+        Ok(response)
+    }
+
+    /// Resolves the location returned by the verifier, which can be relative
+    /// to the attestations endpoint
+    fn resolve_location(
+        &self,
+        location: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let base = reqwest::Url::parse(&self.attestation_url)?;
+        Ok(base.join(location)?.to_string())
+    }
+}
+
+/// Gets the body of the response, failing if the request was not successful
+async fn get_response_body(
+    response: reqwest::Response,
+) -> Result<String, Box<dyn Error>> {
+    let status = response.status();
+    debug!("Response code: {status}");
+    let response_body = response.text().await?;
+    if !status.is_success() {
+        return Err(format!("Unexpected response code {status}").into());
+    }
+    Ok(response_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const ATTESTATIONS_PATH: &str = "/v3.0/agents/12345678/attestations";
+
+    fn negotiation_response(id: Option<&str>) -> serde_json::Value {
+        let mut data = json!({
+            "type": "attestation",
+            "attributes": {
+                "stage": "awaiting_evidence",
+                "evidence_requested": [{
+                    "evidence_class": "certification",
+                    "evidence_type": "tpm_quote",
+                }],
+            },
+        });
+        if let Some(id) = id {
+            data["id"] = json!(id);
+        }
+        json!({ "data": data })
+    }
+
+    fn evidence_response(seconds: Option<u64>) -> serde_json::Value {
+        let mut attributes = json!({
+            "stage": "evaluating_evidence",
+            "evaluation": "pending",
+        });
+        if let Some(seconds) = seconds {
+            attributes["seconds_to_next_attestation"] = json!(seconds);
+        }
+        json!({
+            "data": {
+                "type": "attestation",
+                "attributes": attributes,
+            }
+        })
+    }
+
+    #[test]
+    fn evidence_url_test() {
+        let mut session =
+            AttestationSession::new("http://1.2.3.4:5678/attestations/");
+        assert_eq!(
+            session.evidence_url(),
+            "http://1.2.3.4:5678/attestations/latest"
+        );
+        session.session_id = Some("3".to_string());
+        assert_eq!(
+            session.evidence_url(),
+            "http://1.2.3.4:5678/attestations/3"
+        );
+        session.location = Some("http://1.2.3.4:5678/other/3".to_string());
+        assert_eq!(session.evidence_url(), "http://1.2.3.4:5678/other/3");
+    }
+
+    #[test]
+    fn next_attestation_delay_test() {
+        let mut session = AttestationSession::new("http://1.2.3.4:5678");
+        let default = Duration::from_secs(60);
+        assert_eq!(session.next_attestation_delay(default), default);
+        session.next_attestation = Some(Duration::from_secs(5));
+        assert_eq!(
+            session.next_attestation_delay(default),
+            Duration::from_secs(5)
+        );
+    }
+
+    #[actix_rt::test]
+    async fn session_with_location_test() {
+        let mock_server = MockServer::start().await;
+        let location = format!("{ATTESTATIONS_PATH}/7");
+        Mock::given(method("POST"))
+            .and(path(ATTESTATIONS_PATH))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .insert_header("Location", location.as_str())
+                    .set_body_json(negotiation_response(Some("7"))),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path(location.as_str()))
+            .respond_with(
+                ResponseTemplate::new(202)
+                    .set_body_json(evidence_response(Some(30))),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let url = format!("{}{ATTESTATIONS_PATH}", mock_server.uri());
+        let client = reqwest::Client::new();
+        let mut session = AttestationSession::new(&url);
+        let response = session.negotiate(client.post(&url), None).await;
+        assert!(response.is_ok(), "{response:?}");
+        assert_eq!(session.session_id(), Some("7"));
+        assert_eq!(
+            session.evidence_url(),
+            format!("{}{location}", mock_server.uri())
+        );
+
+        let response = session
+            .submit_evidence(client.patch(session.evidence_url()), None)
+            .await;
+        assert!(response.is_ok(), "{response:?}");
+        assert_eq!(
+            session.next_attestation_delay(Duration::from_secs(60)),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[actix_rt::test]
+    async fn session_without_location_test() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(ATTESTATIONS_PATH))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .set_body_json(negotiation_response(None)),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path(format!("{ATTESTATIONS_PATH}/latest")))
+            .respond_with(
+                ResponseTemplate::new(202)
+                    .set_body_json(evidence_response(None)),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let url = format!("{}{ATTESTATIONS_PATH}", mock_server.uri());
+        let client = reqwest::Client::new();
+        let mut session = AttestationSession::new(&url);
+        // Leftover state from a previous session must be discarded
+        session.next_attestation = Some(Duration::from_secs(5));
+        session.session_id = Some("1".to_string());
+        let response = session.negotiate(client.post(&url), None).await;
+        assert!(response.is_ok(), "{response:?}");
+        assert_eq!(session.session_id(), None);
+
+        let response = session
+            .submit_evidence(client.patch(session.evidence_url()), None)
+            .await;
+        assert!(response.is_ok(), "{response:?}");
+        assert_eq!(
+            session.next_attestation_delay(Duration::from_secs(60)),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[actix_rt::test]
+    async fn negotiation_retry_after_test() {
+        let mock_server = MockServer::start().await;
+        // The verifier is busy and asks to retry immediately
+        Mock::given(method("POST"))
+            .and(path(ATTESTATIONS_PATH))
+            .respond_with(
+                ResponseTemplate::new(429).insert_header("Retry-After", "0"),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(ATTESTATIONS_PATH))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .set_body_json(negotiation_response(Some("2"))),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let url = format!("{}{ATTESTATIONS_PATH}", mock_server.uri());
+        let retry_config = RetryConfig {
+            max_retries: 1,
+            initial_delay_ms: 60000,
+            max_delay_ms: 60000,
+        };
+        let mut session = AttestationSession::new(&url);
+        let response = session
+            .negotiate(reqwest::Client::new().post(&url), Some(&retry_config))
+            .await;
+        assert!(response.is_ok(), "{response:?}");
+        assert_eq!(session.session_id(), Some("2"));
+    }
+
+    #[actix_rt::test]
+    async fn negotiation_error_test() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(ATTESTATIONS_PATH))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(&mock_server)
+            .await;
+
+        let url = format!("{}{ATTESTATIONS_PATH}", mock_server.uri());
+        let mut session = AttestationSession::new(&url);
+        let response = session
+            .negotiate(reqwest::Client::new().post(&url), None)
+            .await;
+        let e = response.unwrap_err(); //#[allow_ci]
+        assert!(e.to_string().contains("400"), "{e}");
+    }
+}
@@ -1,4 +1,16 @@
-use keylime::structures;
+use base64::{engine::general_purpose, Engine as _};
+use keylime::{
+    algorithms::{EncryptionAlgorithm, HashAlgorithm, SignAlgorithm},
+    structures, tpm,
+};
+use std::error::Error;
+use tss_esapi::{
+    handles::KeyHandle,
+    structures::{Public, PublicBuffer},
+    traits::Marshall,
+};
+
+const PROC_STAT: &str = "/proc/stat";
 
 // Implement a structure filler for the attestation request
 // by using polimorphism and traits, with next options:
@@ -28,6 +40,146 @@ impl StructureFiller for AttestationRequestFillerFromFile {
     }
 }
 
+pub struct AttestationRequestFillerFromTpm {
+    capabilities: structures::Capabilities,
+    boot_time: String,
+}
+
+impl AttestationRequestFillerFromTpm {
+    /// Creates a filler describing the TPM and the AK used for the quotes
+    ///
+    /// # Arguments
+    ///
+    /// * tpm_context (&mut tpm::Context): The TPM context
+    /// * ak_handle (KeyHandle): The handle of the loaded AK
+    /// * ak_public (&Public): The public part of the loaded AK
+    pub fn new(
+        tpm_context: &mut tpm::Context<'_>,
+        ak_handle: KeyHandle,
+        ak_public: &Public,
+    ) -> Result<Self, Box<dyn Error>> {
+        let banks = tpm_context.get_pcr_banks()?;
+        let (key_algorithm, key_size) = tpm::get_public_key_info(ak_public)?;
+
+        // Only advertise the signing schemes usable with the AK
+        let signature_schemes = tpm_context
+            .get_supported_sign_algorithms()?
+            .into_iter()
+            .filter(|alg| match key_algorithm {
+                EncryptionAlgorithm::Rsa => {
+                    matches!(
+                        alg,
+                        SignAlgorithm::RsaSsa | SignAlgorithm::RsaPss
+                    )
+                }
+                EncryptionAlgorithm::Ecc => matches!(
+                    alg,
+                    SignAlgorithm::EcDsa | SignAlgorithm::EcSchnorr
+                ),
+            })
+            .map(|alg| alg.to_string())
+            .collect();
+
+        let get_pcrs = |hash_alg: HashAlgorithm| -> Vec<u8> {
+            banks
+                .iter()
+                .find(|bank| bank.hash_alg == hash_alg)
+                .map(|bank| bank.pcrs.clone())
+                .unwrap_or_default()
+        };
+
+        let ak_name = tpm_context.get_name(ak_handle.into())?;
+        let ak_public =
+            PublicBuffer::try_from(ak_public.clone())?.marshall()?;
+
+        let capabilities = structures::Capabilities {
+            component_version: "2.0".to_string(),
+            hash_algorithms: banks
+                .iter()
+                .map(|bank| bank.hash_alg.to_string())
+                .collect(),
+            signature_schemes,
+            available_subjects: structures::ShaValues {
+                sha1: get_pcrs(HashAlgorithm::Sha1),
+                sha256: get_pcrs(HashAlgorithm::Sha256),
+                sha384: get_pcrs(HashAlgorithm::Sha384),
+            },
+            certification_keys: vec![structures::CertificationKey {
+                local_identifier: hex::encode(ak_name.value()),
+                key_algorithm: key_algorithm.to_string(),
+                key_class: "asymmetric".to_string(),
+                key_size,
+                server_identifier: "ak".to_string(),
+                public: general_purpose::STANDARD.encode(ak_public),
+            }],
+        };
+
+        Ok(AttestationRequestFillerFromTpm {
+            capabilities,
+            boot_time: get_boot_time(PROC_STAT)?,
+        })
+    }
+}
+
+impl StructureFiller for AttestationRequestFillerFromTpm {
+    fn get_attestation_request(&self) -> structures::AttestationRequest {
+        structures::AttestationRequest {
+            data: structures::RequestData {
+                type_: "attestation".to_string(),
+                attributes: structures::Attributes {
+                    evidence_supported: vec![
+                        structures::EvidenceSupported::Certification {
+                            evidence_type: "tpm_quote".to_string(),
+                            capabilities: self.capabilities.clone(),
+                        },
+                    ],
+                    system_info: structures::SystemInfo {
+                        boot_time: self.boot_time.clone(),
+                    },
+                },
+            },
+        }
+    }
+}
+
+/// Reads the boot time from the `btime` entry of the given proc stat file
+/// and formats it in RFC 3339 format
+fn get_boot_time(proc_stat: &str) -> Result<String, Box<dyn Error>> {
+    let stat = std::fs::read_to_string(proc_stat)?;
+    let btime = stat
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .ok_or(format!("Missing btime entry in {proc_stat}"))?
+        .trim()
+        .parse::<u64>()?;
+    Ok(seconds_to_rfc3339(btime))
+}
+
+/// Formats the seconds since the UNIX epoch as an RFC 3339 UTC timestamp
+fn seconds_to_rfc3339(seconds: u64) -> String {
+    let days = seconds / 86400;
+    let rem = seconds % 86400;
+
+    // Convert the days since epoch into a civil date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
 fn get_attestation_request_from_file(
     json_file: String,
 ) -> structures::AttestationRequest {
@@ -51,6 +203,7 @@ fn get_attestation_request_from_code() -> structures::AttestationRequest {
                             available_subjects: structures::ShaValues {
                                 sha1: vec![0x04, 0x05, 0x06],
                                 sha256: vec![0x01, 0x02, 0x03],
+                                ..Default::default()
                             },
                             certification_keys: vec![
                                 structures::CertificationKey {
@@ -77,6 +230,81 @@ fn get_attestation_request_from_code() -> structures::AttestationRequest {
 mod tests {
 
     use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn seconds_to_rfc3339_test() {
+        assert_eq!(seconds_to_rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(seconds_to_rfc3339(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(seconds_to_rfc3339(1731428477), "2024-11-12T16:21:17Z");
+    }
+
+    #[test]
+    fn get_boot_time_test() {
+        let mut stat = tempfile::NamedTempFile::new().unwrap(); //#[allow_ci]
+        writeln!(stat, "cpu  1 2 3 4\nbtime 1731428477\nprocesses 42")
+            .unwrap(); //#[allow_ci]
+        let path = stat.path().to_str().unwrap(); //#[allow_ci]
+        assert_eq!(get_boot_time(path).unwrap(), "2024-11-12T16:21:17Z"); //#[allow_ci]
+
+        let mut stat = tempfile::NamedTempFile::new().unwrap(); //#[allow_ci]
+        writeln!(stat, "cpu  1 2 3 4").unwrap(); //#[allow_ci]
+        let path = stat.path().to_str().unwrap(); //#[allow_ci]
+        assert!(get_boot_time(path).is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "testing")]
+    async fn get_attestation_request_filler_from_tpm_test() {
+        let _mutex = tpm::testing::lock_tests().await;
+        let mut ctx = tpm::Context::new().unwrap(); //#[allow_ci]
+        let ek_result = ctx
+            .create_ek(EncryptionAlgorithm::Rsa, None)
+            .expect("Failed to create EK");
+        let ak = ctx
+            .create_ak(
+                ek_result.key_handle,
+                HashAlgorithm::Sha256,
+                SignAlgorithm::RsaSsa,
+            )
+            .expect("Failed to create AK");
+        let ak_handle = ctx
+            .load_ak(ek_result.key_handle, &ak)
+            .expect("Failed to load AK");
+
+        let filler = AttestationRequestFillerFromTpm::new(
+            &mut ctx, ak_handle, &ak.public,
+        );
+
+        // Cleanup created keys
+        let _ = ctx.flush_context(ak_handle.into());
+        let _ = ctx.flush_context(ek_result.key_handle.into());
+
+        let req = filler.unwrap().get_attestation_request(); //#[allow_ci]
+        assert_eq!(req.data.type_, "attestation");
+        assert_eq!(req.data.attributes.evidence_supported.len(), 1);
+        match req.data.attributes.evidence_supported.first() {
+            Some(structures::EvidenceSupported::Certification {
+                evidence_type,
+                capabilities,
+            }) => {
+                assert_eq!(evidence_type, "tpm_quote");
+                assert!(capabilities
+                    .hash_algorithms
+                    .contains(&"sha256".to_string()));
+                assert!(capabilities
+                    .signature_schemes
+                    .contains(&"rsassa".to_string()));
+                assert!(!capabilities.available_subjects.sha256.is_empty());
+                let certification_key =
+                    capabilities.certification_keys.first().unwrap(); //#[allow_ci]
+                assert_eq!(certification_key.key_algorithm, "rsa");
+                assert_eq!(certification_key.key_size, 2048);
+                assert_eq!(certification_key.server_identifier, "ak");
+            }
+            _ => panic!("Expected Certification"), //#[allow_ci]
+        }
+    }
 
     #[test]
     fn get_attestation_request_test() {
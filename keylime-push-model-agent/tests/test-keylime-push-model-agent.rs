@@ -24,12 +24,21 @@ mod tests {
     #[test]
     fn connection_error_test() -> Result<(), Box<dyn std::error::Error>> {
         let mut cmd = Command::cargo_bin(KEYLIME_PUSH_MODEL_AGENT_BINARY)?;
-        cmd.arg("-v")
+        // The keylime directory set in the configuration must exist
+        cmd.env("KEYLIME_DIR", std::env::temp_dir())
+            .env("KEYLIME_AGENT_ENABLE_AGENT_MTLS", "false")
+            .arg("-v")
             .arg("http://1.2.3.4:5678")
             .arg("--timeout")
-            .arg("10");
+            .arg("10")
+            .arg("--avoid-tpm")
+            .arg("--attestation-interval-seconds")
+            .arg("0")
+            .arg("--max-retries")
+            .arg("0");
+        // Exit code used when the evidence negotiation cannot be completed
         cmd.assert()
-            .success()
+            .code(2)
             .stderr(predicate::str::contains(ERROR_SENDING_REQUEST));
         Ok(())
     }
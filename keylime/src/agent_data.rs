@@ -0,0 +1,350 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+
+use crate::{
+    algorithms::{HashAlgorithm, SignAlgorithm},
+    tpm,
+};
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, fs::File, path::Path};
+use tempfile::NamedTempFile;
+use thiserror::Error;
+use tss_esapi::{
+    structures::{ClockInfo, Private, Public},
+    traits::{Marshall, UnMarshall},
+};
+
+#[derive(Error, Debug)]
+pub enum AgentDataError {
+    /// Failed to read or write the agent data file
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
+
+    /// Failed to serialize or deserialize the agent data
+    #[error("Serde error")]
+    Serde(#[from] serde_json::Error),
+
+    /// Failed to marshall or unmarshall the AK
+    #[error("TSS error")]
+    Tss(#[from] tss_esapi::Error),
+}
+
+/// Clock information of the TPM observed in a quote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TpmClockInfo {
+    /// Time in milliseconds during which the TPM has been powered
+    pub clock: u64,
+    /// Number of TPM Reset events, e.g. reboots
+    pub reset_count: u32,
+    /// Number of TPM Restart or TPM Resume events since the last TPM Reset
+    pub restart_count: u32,
+    /// Whether no clock value greater than the current one was reported
+    pub safe: bool,
+}
+
+impl From<&ClockInfo> for TpmClockInfo {
+    fn from(clock_info: &ClockInfo) -> Self {
+        TpmClockInfo {
+            clock: clock_info.clock(),
+            reset_count: clock_info.reset_count(),
+            restart_count: clock_info.restart_count(),
+            safe: clock_info.safe(),
+        }
+    }
+}
+
+/// Change of the TPM clock information between two quotes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockChange {
+    /// The reset count increased, the TPM was reset, e.g. by a reboot
+    Reset,
+    /// The restart count increased, the TPM was restarted or resumed
+    Restart,
+    /// The reset count went backward, which a TPM never does
+    ResetCountBackward,
+    /// The restart count went backward without a TPM Reset
+    RestartCountBackward,
+    /// The clock went backward without a TPM Reset or Restart
+    ClockBackward,
+}
+
+impl ClockChange {
+    /// Detects the change of the TPM clock information from `previous` to
+    /// `current`, if any
+    pub fn detect(
+        previous: &TpmClockInfo,
+        current: &TpmClockInfo,
+    ) -> Option<Self> {
+        use std::cmp::Ordering::*;
+
+        match (
+            current.reset_count.cmp(&previous.reset_count),
+            current.restart_count.cmp(&previous.restart_count),
+        ) {
+            (Less, _) => Some(ClockChange::ResetCountBackward),
+            (Greater, _) => Some(ClockChange::Reset),
+            (Equal, Less) => Some(ClockChange::RestartCountBackward),
+            (Equal, Greater) => Some(ClockChange::Restart),
+            (Equal, Equal) if current.clock < previous.clock => {
+                Some(ClockChange::ClockBackward)
+            }
+            (Equal, Equal) => None,
+        }
+    }
+
+    /// Whether the change cannot happen on a genuine TPM, and may be evidence
+    /// of tampering
+    pub fn is_suspicious(&self) -> bool {
+        !matches!(self, ClockChange::Reset | ClockChange::Restart)
+    }
+}
+
+// TPM data and agent related that can be persisted and loaded on agent startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentData {
+    pub ak_hash_alg: HashAlgorithm,
+    pub ak_sign_alg: SignAlgorithm,
+    ak_public: Vec<u8>,
+    ak_private: Vec<u8>,
+    ek_hash: Vec<u8>,
+    /// The TPM clock information of the last quote
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tpm_clock_info: Option<TpmClockInfo>,
+    /// The last change detected in the TPM clock information
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tpm_clock_change: Option<ClockChange>,
+}
+
+impl AgentData {
+    pub fn create(
+        ak_hash_alg: HashAlgorithm,
+        ak_sign_alg: SignAlgorithm,
+        ak: &tpm::AKResult,
+        ek_hash: &[u8],
+    ) -> Result<Self, AgentDataError> {
+        let ak_public = ak.public.marshall()?;
+        let ak_private: Vec<u8> = ak.private.to_vec();
+        let ek_hash: Vec<u8> = ek_hash.to_vec();
+        Ok(Self {
+            ak_hash_alg,
+            ak_sign_alg,
+            ak_public,
+            ak_private,
+            ek_hash,
+            tpm_clock_info: None,
+            tpm_clock_change: None,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, AgentDataError> {
+        let file = File::open(path)?;
+        let data: Self = serde_json::from_reader(file)?;
+        Ok(data)
+    }
+
+    /// Stores the agent data in the given file. The data is written to a
+    /// temporary file in the same directory which then replaces the file, so
+    /// that the previously stored data is kept if writing fails
+    pub fn store(&self, path: &Path) -> Result<(), AgentDataError> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut file = NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.as_file().sync_all()?;
+        file.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    pub fn get_ak(&self) -> Result<tpm::AKResult, AgentDataError> {
+        let public = Public::unmarshall(&self.ak_public)?;
+        let private = Private::try_from(self.ak_private.clone())?;
+
+        Ok(tpm::AKResult { public, private })
+    }
+
+    pub fn valid(
+        &self,
+        hash_alg: HashAlgorithm,
+        sign_alg: SignAlgorithm,
+        ek_hash: &[u8],
+    ) -> bool {
+        hash_alg == self.ak_hash_alg
+            && sign_alg == self.ak_sign_alg
+            && ek_hash.to_vec() == self.ek_hash
+    }
+
+    /// Records the TPM clock information of a new quote
+    ///
+    /// # Returns
+    ///
+    /// The change from the clock information of the previous quote, if any,
+    /// which is also kept as the last detected change
+    pub fn update_clock_info(
+        &mut self,
+        clock_info: TpmClockInfo,
+    ) -> Option<ClockChange> {
+        let change = self
+            .tpm_clock_info
+            .and_then(|previous| ClockChange::detect(&previous, &clock_info));
+        if change.is_some() {
+            self.tpm_clock_change = change;
+        }
+        self.tpm_clock_info = Some(clock_info);
+        change
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock_info(
+        clock: u64,
+        reset_count: u32,
+        restart_count: u32,
+    ) -> TpmClockInfo {
+        TpmClockInfo {
+            clock,
+            reset_count,
+            restart_count,
+            safe: true,
+        }
+    }
+
+    #[test]
+    fn test_clock_change() {
+        let previous = clock_info(1000, 2, 1);
+        let cases = [
+            (clock_info(2000, 2, 1), None),
+            (clock_info(10, 3, 0), Some(ClockChange::Reset)),
+            (clock_info(2000, 2, 2), Some(ClockChange::Restart)),
+            (
+                clock_info(2000, 1, 1),
+                Some(ClockChange::ResetCountBackward),
+            ),
+            (
+                clock_info(2000, 2, 0),
+                Some(ClockChange::RestartCountBackward),
+            ),
+            (clock_info(500, 2, 1), Some(ClockChange::ClockBackward)),
+        ];
+        for (current, expected) in cases {
+            assert_eq!(ClockChange::detect(&previous, &current), expected);
+        }
+        assert!(!ClockChange::Reset.is_suspicious());
+        assert!(ClockChange::ResetCountBackward.is_suspicious());
+    }
+
+    #[test]
+    fn test_update_clock_info() {
+        use crate::{algorithms::EncryptionAlgorithm, tpm::mock::MockTpm};
+        use tpm::TpmBackend;
+
+        let mut tpm = MockTpm::new();
+        let ek = tpm.create_ek(EncryptionAlgorithm::Rsa, None).unwrap(); //#[allow_ci]
+        let ak = tpm
+            .create_ak(
+                ek.key_handle,
+                HashAlgorithm::Sha256,
+                SignAlgorithm::RsaSsa,
+            )
+            .unwrap(); //#[allow_ci]
+        let mut agent_data = AgentData::create(
+            HashAlgorithm::Sha256,
+            SignAlgorithm::RsaSsa,
+            &ak,
+            b"ek_hash",
+        )
+        .unwrap(); //#[allow_ci]
+
+        assert_eq!(
+            agent_data.update_clock_info(clock_info(1000, 2, 1)),
+            None
+        );
+        assert_eq!(
+            agent_data.update_clock_info(clock_info(10, 3, 0)),
+            Some(ClockChange::Reset)
+        );
+        // The last detected change is kept until another change happens
+        assert_eq!(agent_data.update_clock_info(clock_info(20, 3, 0)), None);
+        assert_eq!(agent_data.tpm_clock_change, Some(ClockChange::Reset));
+        assert_eq!(agent_data.tpm_clock_info, Some(clock_info(20, 3, 0)));
+
+        // The clock information is persisted
+        let temp_dir = tempfile::tempdir().unwrap(); //#[allow_ci]
+        let path = temp_dir.path().join("agent_data.json");
+        agent_data.store(&path).unwrap(); //#[allow_ci]
+        let loaded = AgentData::load(&path).unwrap(); //#[allow_ci]
+        assert_eq!(loaded.tpm_clock_info, agent_data.tpm_clock_info);
+        assert_eq!(loaded.tpm_clock_change, Some(ClockChange::Reset));
+
+        // Storing again replaces the file without leaving temporary files
+        let _ = agent_data.update_clock_info(clock_info(5, 4, 0));
+        agent_data.store(&path).unwrap(); //#[allow_ci]
+        let loaded = AgentData::load(&path).unwrap(); //#[allow_ci]
+        assert_eq!(loaded.tpm_clock_info, Some(clock_info(5, 4, 0)));
+        let entries = std::fs::read_dir(temp_dir.path()).unwrap().count(); //#[allow_ci]
+        assert_eq!(entries, 1);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "testing")]
+    async fn test_agent_data() {
+        use crate::{algorithms::EncryptionAlgorithm, crypto};
+
+        let _mutex = tpm::testing::lock_tests().await;
+        let mut ctx = tpm::Context::new().unwrap(); //#[allow_ci]
+
+        let ek_result = ctx
+            .create_ek(EncryptionAlgorithm::Rsa, None)
+            .expect("Failed to create EK");
+
+        let ek_hash = crypto::hash_ek_pubkey(ek_result.public.clone())
+            .expect("Failed to get pubkey");
+
+        let ak = ctx
+            .create_ak(
+                ek_result.key_handle,
+                HashAlgorithm::Sha256,
+                SignAlgorithm::RsaSsa,
+            )
+            .expect("Failed to create AK");
+
+        let agent_data_test = AgentData::create(
+            HashAlgorithm::Sha256,
+            SignAlgorithm::RsaSsa,
+            &ak,
+            ek_hash.as_bytes(),
+        )
+        .expect("Failed to create agent data");
+
+        assert!(agent_data_test.valid(
+            HashAlgorithm::Sha256,
+            SignAlgorithm::RsaSsa,
+            ek_hash.as_bytes(),
+        ));
+        assert!(!agent_data_test.valid(
+            HashAlgorithm::Sha384,
+            SignAlgorithm::RsaSsa,
+            ek_hash.as_bytes(),
+        ));
+
+        // Store and load the agent data, checking the AK is preserved
+        let temp_dir = tempfile::tempdir().unwrap(); //#[allow_ci]
+        let path = temp_dir.path().join("agent_data.json");
+        agent_data_test.store(&path).unwrap(); //#[allow_ci]
+        let loaded = AgentData::load(&path).unwrap(); //#[allow_ci]
+        let loaded_ak = loaded.get_ak().unwrap(); //#[allow_ci]
+        assert_eq!(loaded_ak.public, ak.public);
+
+        // Cleanup created keys
+        let ak_handle = ctx
+            .load_ak(ek_result.key_handle, &ak)
+            .expect("Failed to load AK");
+        let _ = ctx.flush_context(ak_handle.into());
+        let _ = ctx.flush_context(ek_result.key_handle.into());
+    }
+}
@@ -1,37 +1,83 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright 2025 Keylime Authors
-use crate::config;
-use crate::error::{Error, Result};
-use base64::{engine::general_purpose, Engine as _};
-use keylime::{
-    crypto::{self, x509::CertificateBuilder},
-    registrar_client::RegistrarClientBuilder,
-    tpm::{self, IAKResult, IDevIDResult},
+
+use crate::{
+    config::AgentConfig,
+    crypto::{self, CryptoError},
+    device_id::DeviceID,
+    registrar_client::{
+        RegistrarClientBuilder, RegistrarClientBuilderError,
+        RegistrarClientError,
+    },
+    tpm::{self, TpmError},
 };
+use base64::{engine::general_purpose, Engine as _};
 use log::{error, info};
 use openssl::x509::X509;
-use tss_esapi::handles::KeyHandle;
-use tss_esapi::structures::PublicBuffer;
-use tss_esapi::traits::Marshall;
+use thiserror::Error;
+use tss_esapi::{
+    handles::KeyHandle,
+    structures::{Attest, PublicBuffer, Signature},
+    traits::Marshall,
+};
+
+#[derive(Error, Debug)]
+pub enum AgentRegistrationError {
+    /// IAK and IDevID are enabled, but the data to register them is missing
+    #[error("IDevID and IAK are enabled but could not be generated")]
+    IAKIDevIDMissing,
+
+    /// Failed to compute the authentication tag
+    #[error("Failed to compute the authentication tag")]
+    AuthTag(#[source] CryptoError),
+
+    /// Failed to build the registrar client
+    #[error("Failed to build the registrar client")]
+    RegistrarClientBuilder(#[from] RegistrarClientBuilderError),
+
+    /// Failed to register or activate the agent
+    #[error("Failed to register or activate the agent")]
+    RegistrarClient(#[from] RegistrarClientError),
+
+    /// Failed to activate the credential
+    #[error("Failed to activate the credential")]
+    ActivateCredential(#[source] TpmError),
 
+    /// Failed to marshall a TPM structure
+    #[error("Failed to marshall TPM structure")]
+    Marshall(#[from] tss_esapi::Error),
+}
+
+/// Holds the keys and the information the agent sends to the registrar
 #[derive(Debug)]
 pub struct AgentRegistration {
     pub ak: tpm::AKResult,
     pub ek_result: tpm::EKResult,
     pub api_versions: Vec<String>,
-    pub agent: config::AgentConfig,
+    pub agent: AgentConfig,
     pub agent_uuid: String,
+    /// The IP and port where the agent can be contacted. Not registered if
+    /// not set, like for agents which do not serve requests
+    pub contact_ip: Option<String>,
+    pub contact_port: Option<u32>,
     pub mtls_cert: Option<X509>,
-    pub device_id: Option<keylime::device_id::DeviceID>,
-    pub attest: Option<tss_esapi::structures::Attest>,
-    pub signature: Option<tss_esapi::structures::Signature>,
+    pub device_id: Option<DeviceID>,
+    pub attest: Option<Attest>,
+    pub signature: Option<Signature>,
     pub ak_handle: KeyHandle,
 }
 
+/// Registers the agent with the registrar and activates it by proving the
+/// AK is bound to the EK through the credential activation
+///
+/// # Arguments
+///
+/// * aa (AgentRegistration): The keys and information to register
+/// * ctx (&mut tpm::Context): The TPM context where the AK and EK are loaded
 pub async fn register_agent(
-    mut aa: AgentRegistration,
-    mut ctx: &mut tpm::Context<'_>,
-) -> Result<()> {
+    aa: AgentRegistration,
+    ctx: &mut tpm::Context<'_>,
+) -> Result<(), AgentRegistrationError> {
     let iak_pub;
     let idevid_pub;
     let ak_pub = &PublicBuffer::try_from(aa.ak.public)?.marshall()?;
@@ -47,9 +93,15 @@ pub async fn register_agent(
         )
         .registrar_ip(aa.agent.registrar_ip.clone())
         .registrar_port(aa.agent.registrar_port)
-        .uuid(&aa.agent_uuid)
-        .ip(aa.agent.contact_ip.clone())
-        .port(aa.agent.contact_port);
+        .uuid(&aa.agent_uuid);
+
+    if let Some(ip) = aa.contact_ip {
+        builder = builder.ip(ip);
+    }
+
+    if let Some(port) = aa.contact_port {
+        builder = builder.port(port);
+    }
 
     if let Some(mtls_cert) = aa.mtls_cert {
         builder = builder.mtls_cert(mtls_cert);
@@ -66,12 +118,7 @@ pub async fn register_agent(
             (&aa.device_id, aa.attest, aa.signature)
         else {
             error!("IDevID and IAK are enabled but could not be generated");
-            return Err(Error::Configuration(
-                config::KeylimeConfigError::Generic(
-                    "IDevID and IAK are enabled but could not be generated"
-                        .to_string(),
-                ),
-            ));
+            return Err(AgentRegistrationError::IAKIDevIDMissing);
         };
 
         iak_pub =
@@ -103,20 +150,14 @@ pub async fn register_agent(
 
     info!("SUCCESS: Agent {} registered", &aa.agent_uuid);
 
-    let key = ctx.activate_credential(
-        keyblob,
-        aa.ak_handle,
-        aa.ek_result.key_handle,
-    )?;
-
-    // Flush EK if we created it
-    if aa.agent.ek_handle.is_empty() {
-        ctx.flush_context(aa.ek_result.key_handle.into())?;
-    }
+    let key = ctx
+        .activate_credential(keyblob, aa.ak_handle, aa.ek_result.key_handle)
+        .map_err(AgentRegistrationError::ActivateCredential)?;
 
     let mackey = general_purpose::STANDARD.encode(key.value());
     let auth_tag =
-        crypto::compute_hmac(mackey.as_bytes(), aa.agent_uuid.as_bytes())?;
+        crypto::compute_hmac(mackey.as_bytes(), aa.agent_uuid.as_bytes())
+            .map_err(AgentRegistrationError::AuthTag)?;
     let auth_tag = hex::encode(&auth_tag);
 
     registrar_client.activate_agent(&auth_tag).await?;
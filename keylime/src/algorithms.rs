@@ -75,6 +75,25 @@ impl From<HashAlgorithm> for HashingAlgorithm {
     }
 }
 
+impl TryFrom<HashingAlgorithm> for HashAlgorithm {
+    type Error = AlgorithmError;
+
+    fn try_from(
+        hashing_algorithm: HashingAlgorithm,
+    ) -> Result<Self, Self::Error> {
+        match hashing_algorithm {
+            HashingAlgorithm::Sha1 => Ok(HashAlgorithm::Sha1),
+            HashingAlgorithm::Sha256 => Ok(HashAlgorithm::Sha256),
+            HashingAlgorithm::Sha384 => Ok(HashAlgorithm::Sha384),
+            HashingAlgorithm::Sha512 => Ok(HashAlgorithm::Sha512),
+            HashingAlgorithm::Sm3_256 => Ok(HashAlgorithm::Sm3_256),
+            _ => Err(AlgorithmError::UnsupportedHashingAlgorithm(format!(
+                "{hashing_algorithm:?}"
+            ))),
+        }
+    }
+}
+
 impl From<HashAlgorithm> for MessageDigest {
     fn from(hash_algorithm: HashAlgorithm) -> Self {
         match hash_algorithm {
@@ -1,21 +1,20 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright 2022 Keylime Authors
 
-use crate::{api::SUPPORTED_API_VERSIONS, permissions, tpm};
-use config::{
-    builder::DefaultState, Config, ConfigBuilder, ConfigError, Environment,
-    File, FileFormat, Map, Source, Value, ValueKind::Table,
-};
-use glob::glob;
-use keylime::{
-    algorithms::{EncryptionAlgorithm, HashAlgorithm, SignAlgorithm},
+use crate::{
     hostname_parser::{parse_hostname, HostnameParsingError},
     ip_parser::{parse_ip, IpParsingError},
     list_parser::{parse_list, ListParsingError},
+    permissions,
     version::{self, GetErrorInput},
 };
+use config::{
+    builder::DefaultState, Config, ConfigBuilder, ConfigError, Environment,
+    File, FileFormat, Map, Source, Value,
+};
+use glob::glob;
 use log::*;
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::{
     collections::HashMap,
@@ -64,6 +63,7 @@ pub static DEFAULT_ALLOW_PAYLOAD_REVOCATION_ACTIONS: bool = true;
 pub static DEFAULT_TPM_HASH_ALG: &str = "sha256";
 pub static DEFAULT_TPM_ENCRYPTION_ALG: &str = "rsa";
 pub static DEFAULT_TPM_SIGNING_ALG: &str = "rsassa";
+pub static DEFAULT_TPM_PCR_BANKS: &str = "";
 pub static DEFAULT_EK_HANDLE: &str = "generate";
 pub static DEFAULT_ENABLE_IAK_IDEVID: bool = false;
 pub static DEFAULT_IAK_IDEVID_ASYMMETRIC_ALG: &str = "rsa";
@@ -77,8 +77,18 @@ pub static DEFAULT_RUN_AS: &str = "keylime:tss";
 pub static DEFAULT_AGENT_DATA_PATH: &str = "agent_data.json";
 pub static DEFAULT_IMA_ML_PATH: &str =
     "/sys/kernel/security/ima/ascii_runtime_measurements";
+pub static DEFAULT_IMA_ML_EXCLUDE_PATHS: &str = "";
+pub static DEFAULT_IMA_ML_EXCLUDE_TEMPLATES: &str = "";
 pub static DEFAULT_MEASUREDBOOT_ML_PATH: &str =
     "/sys/kernel/security/tpm0/binary_bios_measurements";
+// Options used only by the push model agent
+pub static DEFAULT_VERIFIER_URL: &str = "https://127.0.0.1:8881";
+// The DEFAULT_VERIFIER_CA_CERT is relative from KEYLIME_DIR
+pub static DEFAULT_VERIFIER_CA_CERT: &str = "cv_ca/cacert.crt";
+pub static DEFAULT_ATTESTATION_INTERVAL_SECONDS: u64 = 60;
+pub static DEFAULT_EXPONENTIAL_BACKOFF_MAX_RETRIES: u32 = 5;
+pub static DEFAULT_EXPONENTIAL_BACKOFF_INITIAL_DELAY: u64 = 10000;
+pub static DEFAULT_EXPONENTIAL_BACKOFF_MAX_DELAY: u64 = 300000;
 pub static DEFAULT_CONFIG: &str = "/etc/keylime/agent.conf";
 pub static DEFAULT_CONFIG_SYS: &str = "/usr/etc/keylime/agent.conf";
 
@@ -149,10 +159,11 @@ pub enum KeylimeConfigError {
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
-pub(crate) struct AgentConfig {
+pub struct AgentConfig {
     pub agent_data_path: String,
     pub allow_payload_revocation_actions: bool,
     pub api_versions: String,
+    pub attestation_interval_seconds: u64,
     pub contact_ip: String,
     pub contact_port: u32,
     pub dec_payload_file: String,
@@ -162,6 +173,9 @@ pub(crate) struct AgentConfig {
     pub enable_insecure_payload: bool,
     pub enable_revocation_notifications: bool,
     pub enc_keyname: String,
+    pub exponential_backoff_initial_delay: u64,
+    pub exponential_backoff_max_delay: u64,
+    pub exponential_backoff_max_retries: u32,
     pub extract_payload_zip: bool,
     pub iak_cert: String,
     pub iak_handle: String,
@@ -172,6 +186,8 @@ pub(crate) struct AgentConfig {
     pub idevid_cert: String,
     pub idevid_handle: String,
     pub idevid_password: String,
+    pub ima_ml_exclude_paths: String,
+    pub ima_ml_exclude_templates: String,
     pub ima_ml_path: String,
     pub ip: String,
     pub keylime_dir: String,
@@ -193,9 +209,12 @@ pub(crate) struct AgentConfig {
     pub tpm_encryption_alg: String,
     pub tpm_hash_alg: String,
     pub tpm_ownerpassword: String,
+    pub tpm_pcr_banks: String,
     pub tpm_signing_alg: String,
     pub trusted_client_ca: String,
     pub uuid: String,
+    pub verifier_ca_cert: String,
+    pub verifier_url: String,
     pub version: String,
 }
 
@@ -238,18 +257,25 @@ impl Source for EnvConfig {
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
-pub(crate) struct KeylimeConfig {
+pub struct KeylimeConfig {
     pub agent: AgentConfig,
 }
 
 impl KeylimeConfig {
-    pub fn new() -> Result<Self, KeylimeConfigError> {
+    /// Load the configuration, enabling the API versions set in the
+    /// 'api_versions' option among the supported_api_versions
+    pub fn new(
+        supported_api_versions: &[&str],
+    ) -> Result<Self, KeylimeConfigError> {
         // Get the base configuration file from the environment variable or the default locations
         let config: KeylimeConfig =
             config_get_setting()?.build()?.try_deserialize()?;
 
+        // Validate that keylime_dir exists
+        let _ = check_keylime_dir(&config.agent.keylime_dir)?;
+
         // Replace keywords with actual values
-        config_translate_keywords(&config)
+        config_translate_keywords(&config, supported_api_versions)
     }
 }
 
@@ -328,6 +354,8 @@ impl Default for AgentConfig {
             allow_payload_revocation_actions:
                 DEFAULT_ALLOW_PAYLOAD_REVOCATION_ACTIONS,
             api_versions: DEFAULT_API_VERSIONS.to_string(),
+            attestation_interval_seconds:
+                DEFAULT_ATTESTATION_INTERVAL_SECONDS,
             contact_ip: DEFAULT_CONTACT_IP.to_string(),
             contact_port: DEFAULT_CONTACT_PORT,
             dec_payload_file: DEFAULT_DEC_PAYLOAD_FILE.to_string(),
@@ -338,6 +366,12 @@ impl Default for AgentConfig {
             enable_revocation_notifications:
                 DEFAULT_ENABLE_REVOCATION_NOTIFICATIONS,
             enc_keyname: DEFAULT_ENC_KEYNAME.to_string(),
+            exponential_backoff_initial_delay:
+                DEFAULT_EXPONENTIAL_BACKOFF_INITIAL_DELAY,
+            exponential_backoff_max_delay:
+                DEFAULT_EXPONENTIAL_BACKOFF_MAX_DELAY,
+            exponential_backoff_max_retries:
+                DEFAULT_EXPONENTIAL_BACKOFF_MAX_RETRIES,
             extract_payload_zip: DEFAULT_EXTRACT_PAYLOAD_ZIP,
             iak_cert: "default".to_string(),
             iak_handle: DEFAULT_IAK_HANDLE.to_string(),
@@ -349,6 +383,9 @@ impl Default for AgentConfig {
             idevid_cert: "default".to_string(),
             idevid_handle: DEFAULT_IDEVID_HANDLE.to_string(),
             idevid_password: DEFAULT_IDEVID_PASSWORD.to_string(),
+            ima_ml_exclude_paths: DEFAULT_IMA_ML_EXCLUDE_PATHS.to_string(),
+            ima_ml_exclude_templates: DEFAULT_IMA_ML_EXCLUDE_TEMPLATES
+                .to_string(),
             ima_ml_path: "default".to_string(),
             ip: DEFAULT_IP.to_string(),
             keylime_dir: DEFAULT_KEYLIME_DIR.to_string(),
@@ -373,9 +410,12 @@ impl Default for AgentConfig {
             tpm_encryption_alg: DEFAULT_TPM_ENCRYPTION_ALG.to_string(),
             tpm_hash_alg: DEFAULT_TPM_HASH_ALG.to_string(),
             tpm_ownerpassword: DEFAULT_TPM_OWNERPASSWORD.to_string(),
+            tpm_pcr_banks: DEFAULT_TPM_PCR_BANKS.to_string(),
             tpm_signing_alg: DEFAULT_TPM_SIGNING_ALG.to_string(),
             trusted_client_ca: "default".to_string(),
             uuid: DEFAULT_UUID.to_string(),
+            verifier_ca_cert: "default".to_string(),
+            verifier_url: DEFAULT_VERIFIER_URL.to_string(),
             version: CONFIG_VERSION.to_string(),
         }
     }
@@ -387,8 +427,10 @@ impl Default for KeylimeConfig {
             agent: AgentConfig::default(),
         };
 
-        // The default config should never fail to translate keywords
-        config_translate_keywords(&c).unwrap() //#[allow_ci]
+        // The default config should never fail to translate keywords. The
+        // supported API versions are only known when loading the
+        // configuration, so the 'api_versions' option is left untranslated
+        config_translate_keywords(&c, &[]).unwrap() //#[allow_ci]
     }
 }
 
@@ -404,7 +446,7 @@ fn config_get_setting(
     if let Ok(env_cfg) = env::var("KEYLIME_AGENT_CONFIG") {
         if !env_cfg.is_empty() {
             let path = Path::new(&env_cfg);
-            if (path.exists()) {
+            if path.exists() {
                 builder = builder.add_source(
                     File::new(&env_cfg, FileFormat::Toml).required(true),
                 )
@@ -467,41 +509,24 @@ fn config_get_setting(
 }
 
 /// Replace the options that support keywords with the final value
+///
+/// The 'api_versions' option is checked against the supported_api_versions,
+/// and left unchanged if none are given
 fn config_translate_keywords(
     config: &KeylimeConfig,
+    supported_api_versions: &[&str],
 ) -> Result<KeylimeConfig, KeylimeConfigError> {
     let uuid = get_uuid(&config.agent.uuid);
 
-    let env_keylime_dir = env::var("KEYLIME_DIR").ok();
-    let keylime_dir = match env_keylime_dir {
-        Some(ref dir) => {
-            if dir.is_empty() {
-                match &config.agent.keylime_dir {
-                    s => Path::new(s),
-                    _ => Path::new(DEFAULT_KEYLIME_DIR),
-                }
-            } else {
-                Path::new(dir)
-            }
-        }
-        None => match &config.agent.keylime_dir {
-            s => Path::new(s),
-            _ => Path::new(DEFAULT_KEYLIME_DIR),
-        },
-    };
-
-    // Validate that keylime_dir exists
-    #[cfg(not(test))]
-    let keylime_dir = &keylime_dir.canonicalize().map_err(|e| {
-        KeylimeConfigError::MissingKeylimeDir {
-            path: keylime_dir.display().to_string(),
-            source: e,
-        }
-    })?;
+    // Use the canonical path when the directory exists. Its existence is
+    // validated when the configuration is loaded in KeylimeConfig::new()
+    let keylime_dir = get_keylime_dir(&config.agent.keylime_dir);
+    let keylime_dir = keylime_dir.canonicalize().unwrap_or(keylime_dir);
+    let keylime_dir = keylime_dir.as_path();
 
     let root_path = Path::new("/");
 
-    let mut agent_data_path = config_get_file_path(
+    let agent_data_path = config_get_file_path(
         "agent_data_path",
         &config.agent.agent_data_path,
         keylime_dir,
@@ -509,7 +534,7 @@ fn config_translate_keywords(
         false,
     );
 
-    let mut ima_ml_path = config_get_file_path(
+    let ima_ml_path = config_get_file_path(
         "ima_ml_path",
         &config.agent.ima_ml_path,
         root_path,
@@ -517,7 +542,7 @@ fn config_translate_keywords(
         false,
     );
 
-    let mut measuredboot_ml_path = config_get_file_path(
+    let measuredboot_ml_path = config_get_file_path(
         "measuredboot_ml_path",
         &config.agent.measuredboot_ml_path,
         root_path,
@@ -525,7 +550,7 @@ fn config_translate_keywords(
         false,
     );
 
-    let mut server_key = config_get_file_path(
+    let server_key = config_get_file_path(
         "server_key",
         &config.agent.server_key,
         keylime_dir,
@@ -533,7 +558,7 @@ fn config_translate_keywords(
         false,
     );
 
-    let mut server_cert = config_get_file_path(
+    let server_cert = config_get_file_path(
         "server_cert",
         &config.agent.server_cert,
         keylime_dir,
@@ -556,7 +581,15 @@ fn config_translate_keywords(
             .collect::<Vec<_>>()
             .join(", ");
 
-    let mut iak_cert = config_get_file_path(
+    let verifier_ca_cert = config_get_file_path(
+        "verifier_ca_cert",
+        &config.agent.verifier_ca_cert,
+        keylime_dir,
+        DEFAULT_VERIFIER_CA_CERT,
+        false,
+    );
+
+    let iak_cert = config_get_file_path(
         "iak_cert",
         &config.agent.iak_cert,
         keylime_dir,
@@ -564,7 +597,7 @@ fn config_translate_keywords(
         true,
     );
 
-    let mut idevid_cert = config_get_file_path(
+    let idevid_cert = config_get_file_path(
         "idevid_cert",
         &config.agent.idevid_cert,
         keylime_dir,
@@ -608,31 +641,34 @@ fn config_translate_keywords(
     // If the "default" keyword is used, use all the supported versions
     // If the "latest" keyword is used, use only the latest version
     let api_versions: String = match config.agent.api_versions.as_ref() {
-        "default" => SUPPORTED_API_VERSIONS
+        _ if supported_api_versions.is_empty() => {
+            config.agent.api_versions.clone()
+        }
+        "default" => supported_api_versions
             .iter()
             .map(|&s| s.to_string())
             .collect::<Vec<String>>()
             .join(", "),
         "latest" => {
             if let Some(version) =
-                SUPPORTED_API_VERSIONS.iter().map(|&s| s.to_string()).last()
+                supported_api_versions.iter().map(|&s| s.to_string()).last()
             {
                 version
             } else {
                 unreachable!();
             }
         }
-        versions => {
+        _versions => {
             let parsed: Vec<String> = match parse_list(
                 &config.agent.api_versions,
             ) {
                 Ok(list) => {
                     let mut filtered_versions = list
                     .iter()
-                    .inspect(|e| { if !SUPPORTED_API_VERSIONS.contains(e) {
+                    .inspect(|e| { if !supported_api_versions.contains(e) {
                         warn!("Skipping API version \"{e}\" obtained from 'api_versions' configuration option")
                     }})
-                    .filter(|e| SUPPORTED_API_VERSIONS.contains(e))
+                    .filter(|e| supported_api_versions.contains(e))
                     .map(|&s| version::Version::from_str(s))
                     .inspect(|err| if let Err(e) = err {
                         warn!("Skipping API version \"{}\" obtained from 'api_versions' configuration option", e.input());
@@ -651,15 +687,15 @@ fn config_translate_keywords(
                         .map(|v| v.to_string())
                         .collect::<Vec<String>>()
                 }
-                Err(e) => {
+                Err(_e) => {
                     warn!("Failed to parse list from 'api_versions' configuration option; using default supported versions");
-                    SUPPORTED_API_VERSIONS.iter().map(|&s| s.into()).collect()
+                    supported_api_versions.iter().map(|&s| s.into()).collect()
                 }
             };
 
             if parsed.is_empty() {
                 warn!("No supported version found in 'api_versions' configuration option; using default supported versions");
-                SUPPORTED_API_VERSIONS
+                supported_api_versions
                     .iter()
                     .map(|&s| s.to_string())
                     .collect::<Vec<String>>()
@@ -692,7 +728,8 @@ fn config_translate_keywords(
                 value_b: "empty".into(),
             });
         }
-        let actions_dir = match config.agent.revocation_actions_dir.as_ref() {
+        let _actions_dir = match config.agent.revocation_actions_dir.as_ref()
+        {
             "" => {
                 error!("The option 'enable_revocation_notifications' is set as 'true' but the revocation actions directory was set as empty in 'revocation_actions_dir'");
                 return Err(KeylimeConfigError::IncompatibleOptions {
@@ -706,7 +743,7 @@ fn config_translate_keywords(
         };
     }
 
-    let mut revocation_cert = config_get_file_path(
+    let revocation_cert = config_get_file_path(
         "revocation_cert",
         &config.agent.revocation_cert,
         keylime_dir,
@@ -732,11 +769,38 @@ fn config_translate_keywords(
             server_key,
             trusted_client_ca,
             uuid,
+            verifier_ca_cert,
             ..config.agent.clone()
         },
     })
 }
 
+/// Get the keylime directory, giving precedence to the KEYLIME_DIR
+/// environment variable over the 'keylime_dir' configuration option
+fn get_keylime_dir(keylime_dir_config: &str) -> PathBuf {
+    match env::var("KEYLIME_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match keylime_dir_config {
+            "" => PathBuf::from(DEFAULT_KEYLIME_DIR),
+            s => PathBuf::from(s),
+        },
+    }
+}
+
+/// Check that the keylime directory obtained with get_keylime_dir() exists,
+/// returning its canonical path
+fn check_keylime_dir(
+    keylime_dir_config: &str,
+) -> Result<PathBuf, KeylimeConfigError> {
+    let keylime_dir = get_keylime_dir(keylime_dir_config);
+    keylime_dir.canonicalize().map_err(|e| {
+        KeylimeConfigError::MissingKeylimeDir {
+            path: keylime_dir.display().to_string(),
+            source: e,
+        }
+    })
+}
+
 /// Expand a file path from the configuration file.
 ///
 /// If the string is set as "default", return the provided default path relative from the provided work_dir.
@@ -800,6 +864,8 @@ fn get_uuid(agent_uuid_config: &str) -> String {
 mod tests {
     use super::*;
 
+    static SUPPORTED_API_VERSIONS: &[&str] = &["2.1", "2.2"];
+
     #[test]
     fn test_default() {
         let default = KeylimeConfig::default();
@@ -808,7 +874,7 @@ mod tests {
             agent: AgentConfig::default(),
         };
 
-        let result = config_translate_keywords(&c);
+        let result = config_translate_keywords(&c, &[]);
         assert!(result.is_ok());
         let expected = result.unwrap(); //#[allow_ci]
         assert_eq!(expected, default);
@@ -827,7 +893,7 @@ mod tests {
 
         let c = KeylimeConfig { agent: modified };
 
-        let result = config_translate_keywords(&c);
+        let result = config_translate_keywords(&c, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
         let result = result.unwrap(); //#[allow_ci]
         let resulting_ip = result.agent.ip;
@@ -842,7 +908,7 @@ mod tests {
     fn get_revocation_cert_path_default() {
         let test_config = KeylimeConfig::default();
         let revocation_cert_path = test_config.agent.revocation_cert.clone();
-        let mut expected = Path::new(&test_config.agent.keylime_dir)
+        let expected = Path::new(&test_config.agent.keylime_dir)
             .join("secure/unzipped")
             .join(DEFAULT_REVOCATION_CERT)
             .display()
@@ -850,35 +916,95 @@ mod tests {
         assert_eq!(revocation_cert_path, expected);
     }
 
+    #[test]
+    fn test_get_keylime_dir() {
+        // The KEYLIME_DIR environment variable takes precedence
+        if env::var("KEYLIME_DIR").is_ok_and(|dir| !dir.is_empty()) {
+            return;
+        }
+        assert_eq!(
+            get_keylime_dir("/test/keylime"),
+            Path::new("/test/keylime")
+        );
+        assert_eq!(get_keylime_dir(""), Path::new(DEFAULT_KEYLIME_DIR));
+    }
+
+    #[test]
+    fn test_check_keylime_dir() {
+        if env::var("KEYLIME_DIR").is_ok_and(|dir| !dir.is_empty()) {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap(); //#[allow_ci]
+        let path = dir.path().to_str().unwrap(); //#[allow_ci]
+        assert_eq!(
+            check_keylime_dir(path).unwrap(), //#[allow_ci]
+            dir.path().canonicalize().unwrap()  //#[allow_ci]
+        );
+
+        let missing = dir.path().join("missing");
+        let result = check_keylime_dir(missing.to_str().unwrap()); //#[allow_ci]
+        assert!(matches!(
+            result,
+            Err(KeylimeConfigError::MissingKeylimeDir { .. })
+        ));
+    }
+
+    #[test]
+    fn test_translate_missing_keylime_dir() {
+        if env::var("KEYLIME_DIR").is_ok_and(|dir| !dir.is_empty()) {
+            return;
+        }
+        // A missing keylime_dir is only rejected when loading the
+        // configuration, the paths relative to it are translated as is
+        let test_config = KeylimeConfig {
+            agent: AgentConfig {
+                keylime_dir: "/nonexistent/keylime".to_string(),
+                ..Default::default()
+            },
+        };
+        let result = config_translate_keywords(&test_config, &[]);
+        assert!(result.is_ok());
+        let config = result.unwrap(); //#[allow_ci]
+        assert_eq!(
+            config.agent.server_key,
+            Path::new("/nonexistent/keylime")
+                .join(DEFAULT_SERVER_KEY)
+                .display()
+                .to_string()
+        );
+    }
+
     #[test]
     fn get_revocation_cert_path_absolute() {
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 revocation_cert: "/test/cert.crt".to_string(),
                 ..Default::default()
             },
         };
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
         let test_config = result.unwrap(); //#[allow_ci]
         let revocation_cert_path = test_config.agent.revocation_cert;
-        let mut expected = Path::new("/test/cert.crt").display().to_string();
+        let expected = Path::new("/test/cert.crt").display().to_string();
         assert_eq!(revocation_cert_path, expected);
     }
 
     #[test]
     fn get_revocation_cert_path_relative() {
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 revocation_cert: "cert.crt".to_string(),
                 ..Default::default()
             },
         };
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
         let test_config = result.unwrap(); //#[allow_ci]
         let revocation_cert_path = test_config.agent.revocation_cert.clone();
-        let mut expected = Path::new(&test_config.agent.keylime_dir)
+        let expected = Path::new(&test_config.agent.keylime_dir)
             .join("cert.crt")
             .display()
             .to_string();
@@ -887,17 +1013,18 @@ mod tests {
 
     #[test]
     fn get_revocation_notification_ip_empty() {
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 enable_revocation_notifications: true,
                 revocation_notification_ip: "".to_string(),
                 ..Default::default()
             },
         };
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         // Due to enable_revocation_notifications being set
         assert!(result.is_err());
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 enable_revocation_notifications: false,
                 revocation_notification_ip: "".to_string(),
@@ -906,7 +1033,8 @@ mod tests {
         };
 
         // Now unset enable_revocation_notifications and check that is allowed
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
         let test_config = result.unwrap(); //#[allow_ci]
         assert_eq!(
@@ -917,17 +1045,18 @@ mod tests {
 
     #[test]
     fn get_revocation_cert_empty() {
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 enable_revocation_notifications: true,
                 revocation_cert: "".to_string(),
                 ..Default::default()
             },
         };
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         // Due to enable_revocation_notifications being set
         assert!(result.is_err());
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 enable_revocation_notifications: false,
                 revocation_cert: "".to_string(),
@@ -936,23 +1065,25 @@ mod tests {
         };
 
         // Now unset enable_revocation_notifications and check that is allowed
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
     }
 
     #[test]
     fn get_revocation_actions_dir_empty() {
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 enable_revocation_notifications: true,
                 revocation_actions_dir: "".to_string(),
                 ..Default::default()
             },
         };
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         // Due to enable_revocation_notifications being set
         assert!(result.is_err());
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 enable_revocation_notifications: false,
                 revocation_actions_dir: "".to_string(),
@@ -961,19 +1092,21 @@ mod tests {
         };
 
         // Now unset enable_revocation_notifications and check that is allowed
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
     }
 
     #[test]
     fn test_translate_api_versions_latest_keyword() {
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 api_versions: "latest".to_string(),
                 ..Default::default()
             },
         };
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
         let config = result.unwrap(); //#[allow_ci]
         let version = config.agent.api_versions;
@@ -988,7 +1121,8 @@ mod tests {
     #[test]
     fn test_translate_api_versions_default_keyword() {
         let default = KeylimeConfig::default();
-        let result = config_translate_keywords(&default);
+        let result =
+            config_translate_keywords(&default, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
         let config = result.unwrap(); //#[allow_ci]
         let version = config.agent.api_versions;
@@ -1004,13 +1138,14 @@ mod tests {
     fn test_translate_api_versions_old_supported() {
         let old = SUPPORTED_API_VERSIONS[0];
 
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 api_versions: old.to_string(),
                 ..Default::default()
             },
         };
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
         let config = result.unwrap(); //#[allow_ci]
         let version = config.agent.api_versions;
@@ -1021,13 +1156,14 @@ mod tests {
     fn test_translate_invalid_api_versions_filtered() {
         let old = SUPPORTED_API_VERSIONS[0];
 
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 api_versions: format!("a.b, {old}, c.d"),
                 ..Default::default()
             },
         };
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
         let config = result.unwrap(); //#[allow_ci]
         let version = config.agent.api_versions;
@@ -1038,13 +1174,14 @@ mod tests {
     fn test_translate_invalid_api_versions_fallback_default() {
         let old = SUPPORTED_API_VERSIONS;
 
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 api_versions: "a.b, c.d".to_string(),
                 ..Default::default()
             },
         };
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
         let config = result.unwrap(); //#[allow_ci]
         let version = config.agent.api_versions;
@@ -1061,19 +1198,31 @@ mod tests {
             .collect::<Vec<_>>()
             .join(", ");
 
-        let mut test_config = KeylimeConfig {
+        let test_config = KeylimeConfig {
             agent: AgentConfig {
                 api_versions: reversed,
                 ..Default::default()
             },
         };
-        let result = config_translate_keywords(&test_config);
+        let result =
+            config_translate_keywords(&test_config, SUPPORTED_API_VERSIONS);
         assert!(result.is_ok());
         let config = result.unwrap(); //#[allow_ci]
         let version = config.agent.api_versions;
         assert_eq!(version, old.join(", "));
     }
 
+    #[test]
+    fn test_translate_api_versions_unsupported() {
+        // Without supported versions, the option is kept as is
+        let default = KeylimeConfig::default();
+        assert_eq!(default.agent.api_versions, DEFAULT_API_VERSIONS);
+        let result = config_translate_keywords(&default, &[]);
+        assert!(result.is_ok());
+        let config = result.unwrap(); //#[allow_ci]
+        assert_eq!(config.agent.api_versions, DEFAULT_API_VERSIONS);
+    }
+
     #[test]
     fn test_get_uuid() {
         assert_eq!(get_uuid("hash_ek"), "hash_ek");
@@ -1097,7 +1246,7 @@ mod tests {
         let default = AgentConfig::default();
 
         // Test that the AgentConfig can be used as a source for KeylimeConfig
-        let config: KeylimeConfig = Config::builder()
+        let _config: KeylimeConfig = Config::builder()
             .add_source(default)
             .build()
             .unwrap() //#[allow_ci]
@@ -1110,7 +1259,7 @@ mod tests {
         let default = KeylimeConfig::default();
 
         // Test that the KeylimeConfig can be used as a source for KeylimeConfig
-        let config: KeylimeConfig = Config::builder()
+        let _config: KeylimeConfig = Config::builder()
             .add_source(default)
             .build()
             .unwrap() //#[allow_ci]
@@ -1124,7 +1273,7 @@ mod tests {
         let env_config = EnvConfig::new().unwrap(); //#[allow_ci]
 
         // Test that the EnvConfig can be used as a source for KeylimeConfig
-        let config: KeylimeConfig = Config::builder()
+        let _config: KeylimeConfig = Config::builder()
             .add_source(default)
             .add_source(env_config)
             .build()
@@ -1135,7 +1284,7 @@ mod tests {
 
     #[test]
     fn test_config_get_setting() {
-        let env_config = config_get_setting().unwrap(); //#[allow_ci]
+        let _env_config = config_get_setting().unwrap(); //#[allow_ci]
     }
 
     #[test]
@@ -1144,6 +1293,7 @@ mod tests {
             ("KEYLIME_AGENT_AGENT_DATA_PATH", "override_agent_data_path"),
             ("KEYLIME_AGENT_ALLOW_PAYLOAD_REVOCATION_ACTIONS", "false"),
             ("KEYLIME_AGENT_API_VERSIONS", "latest"),
+            ("KEYLIME_AGENT_ATTESTATION_INTERVAL_SECONDS", "9999"),
             ("KEYLIME_AGENT_CONTACT_IP", "override_contact_ip"),
             ("KEYLIME_AGENT_CONTACT_PORT", "9999"),
             (
@@ -1156,6 +1306,9 @@ mod tests {
             ("KEYLIME_AGENT_ENABLE_INSECURE_PAYLOAD", "true"),
             ("KEYLIME_AGENT_ENABLE_REVOCATION_NOTIFICATIONS", "false"),
             ("KEYLIME_AGENT_ENC_KEYNAME", "override_enc_keyname"),
+            ("KEYLIME_AGENT_EXPONENTIAL_BACKOFF_INITIAL_DELAY", "9999"),
+            ("KEYLIME_AGENT_EXPONENTIAL_BACKOFF_MAX_DELAY", "9999"),
+            ("KEYLIME_AGENT_EXPONENTIAL_BACKOFF_MAX_RETRIES", "9999"),
             ("KEYLIME_AGENT_EXTRACT_PAYLOAD_ZIP", "false"),
             ("KEYLIME_AGENT_IAK_CERT", "override_iak_cert"),
             ("KEYLIME_AGENT_IAK_HANDLE", "override_iak_handle"),
@@ -1175,6 +1328,14 @@ mod tests {
             ("KEYLIME_AGENT_IDEVID_CERT", "override_idevid_cert"),
             ("KEYLIME_AGENT_IDEVID_HANDLE", "override_idevid_handle"),
             ("KEYLIME_AGENT_IDEVID_PASSWORD", "override_idevid_password"),
+            (
+                "KEYLIME_AGENT_IMA_ML_EXCLUDE_PATHS",
+                "override_ima_ml_exclude_paths",
+            ),
+            (
+                "KEYLIME_AGENT_IMA_ML_EXCLUDE_TEMPLATES",
+                "override_ima_ml_exclude_templates",
+            ),
             ("KEYLIME_AGENT_IMA_ML_PATH", "override_ima_ml_path"),
             ("KEYLIME_AGENT_IP", "override_ip"),
             ("KEYLIME_AGENT_KEYLIME_DIR", "override_keylime_dir"),
@@ -1217,12 +1378,18 @@ mod tests {
                 "KEYLIME_AGENT_TPM_OWNERPASSWORD",
                 "override_tpm_ownerpassword",
             ),
+            ("KEYLIME_AGENT_TPM_PCR_BANKS", "override_tpm_pcr_banks"),
             ("KEYLIME_AGENT_TPM_SIGNING_ALG", "override_tpm_signing_alg"),
             (
                 "KEYLIME_AGENT_TRUSTED_CLIENT_CA",
                 "override_trusted_client_ca",
             ),
             ("KEYLIME_AGENT_UUID", "override_uuid"),
+            (
+                "KEYLIME_AGENT_VERIFIER_CA_CERT",
+                "override_verifier_ca_cert",
+            ),
+            ("KEYLIME_AGENT_VERIFIER_URL", "override_verifier_url"),
             ("KEYLIME_AGENT_VERSION", "override_version"),
         ]);
 
@@ -378,6 +378,17 @@ pub fn hash(
         .to_vec())
 }
 
+/// Calculate the SHA-256 hash of the TPM public key in PEM format
+///
+/// This is used as the agent UUID when the configuration option 'uuid' is set as 'hash_ek'
+pub fn hash_ek_pubkey(
+    ek_pub: tss_esapi::structures::Public,
+) -> Result<String, CryptoError> {
+    let pem = tss_pubkey_to_pem(ek_pub)?;
+    let hash = hash(&pem, MessageDigest::sha256())?;
+    Ok(hex::encode(hash))
+}
+
 /// Check an x509 certificate contains a specific public key
 pub fn check_x509_key(
     cert: &X509,
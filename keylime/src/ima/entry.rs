@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright 2022 Keylime Authors
 
-// Parser for IMA ASCII entries.
+// Parser for IMA ASCII and binary entries.
 //
 // Implements the templates (modes) and types as defined in:
 // https://elixir.bootlin.com/linux/latest/source/security/integrity/ima/ima_template.c
@@ -10,7 +10,7 @@
 use crate::algorithms::HashAlgorithm;
 use openssl::hash::MessageDigest;
 use std::convert::{TryFrom, TryInto};
-use std::io::{Error, ErrorKind, Result, Write};
+use std::io::{Error, ErrorKind, Read, Result, Write};
 
 pub trait Encode {
     /// Encodes this type and writes the output to `writer`.
@@ -22,6 +22,45 @@ pub trait EncodeLegacy {
     fn encode_legacy(&self, writer: &mut dyn Write) -> Result<()>;
 }
 
+/// Reads the little endian, length prefixed fields of the binary template
+/// data.
+struct FieldReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> FieldReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "truncated template data",
+            ));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read_field(&mut self) -> Result<&'a [u8]> {
+        let len = u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()); //#[allow_ci]
+        self.read_bytes(len as usize)
+    }
+
+    fn finish(&self) -> Result<()> {
+        if !self.data.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "unexpected data after template fields",
+            ));
+        }
+        Ok(())
+    }
+}
+
 /// Wrapper around a IMA digest value.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Digest {
@@ -71,6 +110,22 @@ impl Digest {
             value: vec![0xffu8; digest.size()],
         }
     }
+
+    /// Decodes the binary d-ng field, the algorithm followed by ":\0" and
+    /// the digest value.
+    fn decode(field: &[u8]) -> Result<Self> {
+        let separator =
+            field.windows(2).position(|w| w == b":\0").ok_or_else(|| {
+                Error::new(ErrorKind::InvalidData, "invalid digest field")
+            })?;
+        let algorithm = std::str::from_utf8(&field[..separator])
+            .ok()
+            .and_then(|a| HashAlgorithm::try_from(a).ok())
+            .ok_or_else(|| {
+                Error::new(ErrorKind::InvalidData, "invalid algorithm")
+            })?;
+        Self::new(algorithm, &field[separator + 2..])
+    }
 }
 
 impl TryFrom<&str> for Digest {
@@ -106,17 +161,14 @@ impl TryFrom<&str> for Digest {
 
 impl Encode for Digest {
     fn encode(&self, writer: &mut dyn Write) -> Result<()> {
-        if self.algorithm == HashAlgorithm::Sha1 {
-            writer.write_all(&(self.value.len() as u32).to_le_bytes())?;
-            writer.write_all(&self.value)?;
-        } else {
-            let algorithm = format!("{}", self.algorithm);
-            let total_len = algorithm.len() + 2 + self.value.len();
-            writer.write_all(&(total_len as u32).to_le_bytes())?;
-            writer.write_all(algorithm.as_bytes())?;
-            writer.write_all(&[58u8, 0u8])?;
-            writer.write_all(&self.value)?;
-        }
+        // The d-ng field is always prefixed with the algorithm, including
+        // SHA-1
+        let algorithm = format!("{}", self.algorithm);
+        let total_len = algorithm.len() + 2 + self.value.len();
+        writer.write_all(&(total_len as u32).to_le_bytes())?;
+        writer.write_all(algorithm.as_bytes())?;
+        writer.write_all(&[58u8, 0u8])?;
+        writer.write_all(&self.value)?;
         Ok(())
     }
 }
@@ -142,6 +194,19 @@ impl TryFrom<&str> for Name {
     }
 }
 
+impl Name {
+    /// Decodes the binary name field, which may be NUL terminated.
+    fn decode(field: &[u8]) -> Result<Self> {
+        let field = field.strip_suffix(&[0u8]).unwrap_or(field);
+        let name = std::str::from_utf8(field).map_err(|_| {
+            Error::new(ErrorKind::InvalidData, "invalid name encoding")
+        })?;
+        Ok(Self {
+            name: name.to_string(),
+        })
+    }
+}
+
 const TCG_EVENT_NAME_LEN_MAX: usize = 255;
 
 impl Encode for Name {
@@ -158,7 +223,13 @@ impl EncodeLegacy for Name {
     fn encode_legacy(&self, writer: &mut dyn Write) -> Result<()> {
         let bytes = self.name.as_bytes();
         writer.write_all(bytes)?;
-        writer.write_all(&vec![0u8; TCG_EVENT_NAME_LEN_MAX - bytes.len()])?;
+        // The name is hashed as a NUL padded buffer of
+        // TCG_EVENT_NAME_LEN_MAX + 1 bytes
+        writer.write_all(&vec![
+            0u8;
+            (TCG_EVENT_NAME_LEN_MAX + 1)
+                .saturating_sub(bytes.len())
+        ])?;
         Ok(())
     }
 }
@@ -174,6 +245,14 @@ impl TryFrom<&str> for Signature {
         let value = hex::decode(value).map_err(|_| {
             Error::new(ErrorKind::InvalidInput, "invalid hex encoding")
         })?;
+        Self::try_from(value)
+    }
+}
+
+impl TryFrom<Vec<u8>> for Signature {
+    type Error = std::io::Error;
+
+    fn try_from(value: Vec<u8>) -> std::result::Result<Self, Self::Error> {
         // basic checks on signature
         if value.len() < 9 {
             return Err(Error::new(
@@ -224,6 +303,207 @@ impl Encode for Buffer {
     }
 }
 
+/// A digest prefixed with its type, as in the d-ngv2 field, e.g.
+/// "ima:sha256:<digest>" or "verity:sha256:<digest>".
+struct DigestV2 {
+    digest_type: String,
+    digest: Digest,
+}
+
+impl TryFrom<&str> for DigestV2 {
+    type Error = std::io::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        let (digest_type, digest) =
+            value.split_once(':').ok_or_else(|| {
+                Error::new(ErrorKind::InvalidInput, "invalid digest type")
+            })?;
+        // Unlike in d-ng, the algorithm is mandatory
+        if !digest.contains(':') {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid digest value",
+            ));
+        }
+        Ok(Self {
+            digest_type: digest_type.to_string(),
+            digest: Digest::try_from(digest)?,
+        })
+    }
+}
+
+impl DigestV2 {
+    /// Decodes the binary d-ngv2 field, the digest type and algorithm
+    /// followed by ":\0" and the digest value.
+    fn decode(field: &[u8]) -> Result<Self> {
+        let separator =
+            field.iter().position(|b| *b == b':').ok_or_else(|| {
+                Error::new(ErrorKind::InvalidData, "invalid digest field")
+            })?;
+        let digest_type =
+            std::str::from_utf8(&field[..separator]).map_err(|_| {
+                Error::new(ErrorKind::InvalidData, "invalid digest type")
+            })?;
+        Ok(Self {
+            digest_type: digest_type.to_string(),
+            digest: Digest::decode(&field[separator + 1..])?,
+        })
+    }
+}
+
+impl Encode for DigestV2 {
+    fn encode(&self, writer: &mut dyn Write) -> Result<()> {
+        let prefix =
+            format!("{}:{}:", self.digest_type, self.digest.algorithm);
+        let total_len = prefix.len() + 1 + self.digest.value.len();
+        writer.write_all(&(total_len as u32).to_le_bytes())?;
+        writer.write_all(prefix.as_bytes())?;
+        writer.write_all(&[0u8])?;
+        writer.write_all(&self.digest.value)?;
+        Ok(())
+    }
+}
+
+/// A digest field which may be empty, as the d-modsig field for files
+/// without an appended signature.
+struct OptionalDigest {
+    digest: Option<Digest>,
+}
+
+impl TryFrom<&str> for OptionalDigest {
+    type Error = std::io::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        let digest = if value.is_empty() {
+            None
+        } else {
+            Some(Digest::try_from(value)?)
+        };
+        Ok(Self { digest })
+    }
+}
+
+impl OptionalDigest {
+    fn decode(field: &[u8]) -> Result<Self> {
+        let digest = if field.is_empty() {
+            None
+        } else {
+            Some(Digest::decode(field)?)
+        };
+        Ok(Self { digest })
+    }
+}
+
+impl Encode for OptionalDigest {
+    fn encode(&self, writer: &mut dyn Write) -> Result<()> {
+        match &self.digest {
+            Some(digest) => digest.encode(writer),
+            None => writer.write_all(&0u32.to_le_bytes()),
+        }
+    }
+}
+
+/// A string field shown as is in the ASCII list, as the xattrnames field.
+struct Text {
+    value: String,
+}
+
+impl TryFrom<&str> for Text {
+    type Error = std::io::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            value: value.to_string(),
+        })
+    }
+}
+
+impl Text {
+    fn decode(field: &[u8]) -> Result<Self> {
+        let value = std::str::from_utf8(field).map_err(|_| {
+            Error::new(ErrorKind::InvalidData, "invalid string encoding")
+        })?;
+        Ok(Self {
+            value: value.to_string(),
+        })
+    }
+}
+
+impl Encode for Text {
+    fn encode(&self, writer: &mut dyn Write) -> Result<()> {
+        writer.write_all(&(self.value.len() as u32).to_le_bytes())?;
+        writer.write_all(self.value.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// An unsigned integer field shown in decimal in the ASCII list, as the
+/// iuid, igid and imode fields. The field is empty if the event is not
+/// related to a file.
+struct Uint {
+    value: Option<u64>,
+    size: usize,
+}
+
+impl Uint {
+    fn parse(value: &str, size: usize) -> Result<Self> {
+        let value = if value.is_empty() {
+            None
+        } else {
+            let value = value.parse::<u64>().map_err(|_| {
+                Error::new(ErrorKind::InvalidInput, "invalid integer")
+            })?;
+            if size < 8 && value >> (size * 8) != 0 {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "integer out of range",
+                ));
+            }
+            Some(value)
+        };
+        Ok(Self { value, size })
+    }
+
+    fn decode(field: &[u8]) -> Result<Self> {
+        let value = match field.len() {
+            0 => None,
+            1 | 2 | 4 | 8 => {
+                let mut bytes = [0u8; 8];
+                bytes[..field.len()].copy_from_slice(field);
+                Some(u64::from_le_bytes(bytes))
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "invalid integer size",
+                ))
+            }
+        };
+        Ok(Self {
+            value,
+            size: field.len(),
+        })
+    }
+}
+
+impl Encode for Uint {
+    fn encode(&self, writer: &mut dyn Write) -> Result<()> {
+        match self.value {
+            Some(value) => {
+                writer.write_all(&(self.size as u32).to_le_bytes())?;
+                writer.write_all(&value.to_le_bytes()[..self.size])?;
+            }
+            None => writer.write_all(&0u32.to_le_bytes())?,
+        }
+        Ok(())
+    }
+}
+
+/// Size of the iuid and igid fields
+const ID_FIELD_SIZE: usize = 4;
+/// Size of the imode field
+const MODE_FIELD_SIZE: usize = 2;
+
 pub trait EventData: Encode {
     fn path(&self) -> &str;
 }
@@ -326,11 +606,7 @@ impl TryFrom<&str> for ImaSig {
 
         let digest = Digest::try_from(tokens[0])?;
         let path = Name::try_from(tokens[1])?;
-        let signature = if !signature.is_empty() {
-            Some(Signature::try_from(signature)?)
-        } else {
-            None
-        };
+        let signature = parse_signature(signature)?;
 
         Ok(Self {
             digest,
@@ -344,12 +620,7 @@ impl Encode for ImaSig {
     fn encode(&self, writer: &mut dyn Write) -> Result<()> {
         self.digest.encode(writer)?;
         self.path.encode(writer)?;
-        if let Some(signature) = &self.signature {
-            signature.encode(writer)?;
-        } else {
-            writer.write_all(&0u32.to_le_bytes())?;
-        }
-        Ok(())
+        encode_signature(&self.signature, writer)
     }
 }
 
@@ -390,10 +661,494 @@ impl Encode for ImaBuf {
     }
 }
 
+struct ImaNgV2 {
+    digest: DigestV2,
+    path: Name,
+}
+
+impl TryFrom<&str> for ImaNgV2 {
+    type Error = std::io::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        let tokens: Vec<&str> = value.splitn(2, ' ').collect();
+        if tokens.len() != 2 {
+            return Err(Error::new(ErrorKind::InvalidInput, value));
+        }
+
+        Ok(Self {
+            digest: DigestV2::try_from(tokens[0])?,
+            path: Name::try_from(tokens[1])?,
+        })
+    }
+}
+
+impl EventData for ImaNgV2 {
+    fn path(&self) -> &str {
+        &self.path.name
+    }
+}
+
+impl Encode for ImaNgV2 {
+    fn encode(&self, writer: &mut dyn Write) -> Result<()> {
+        self.digest.encode(writer)?;
+        self.path.encode(writer)?;
+        Ok(())
+    }
+}
+
+struct ImaSigV2 {
+    digest: DigestV2,
+    path: Name,
+    signature: Option<Signature>,
+}
+
+impl TryFrom<&str> for ImaSigV2 {
+    type Error = std::io::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        // extract signature first
+        let (value, signature) = value
+            .rsplit_once(' ')
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, value))?;
+
+        // parse d-ngv2|n-ng as in ima-ngv2
+        let ng = ImaNgV2::try_from(value)?;
+        Ok(Self {
+            digest: ng.digest,
+            path: ng.path,
+            signature: parse_signature(signature)?,
+        })
+    }
+}
+
+impl EventData for ImaSigV2 {
+    fn path(&self) -> &str {
+        &self.path.name
+    }
+}
+
+impl Encode for ImaSigV2 {
+    fn encode(&self, writer: &mut dyn Write) -> Result<()> {
+        self.digest.encode(writer)?;
+        self.path.encode(writer)?;
+        encode_signature(&self.signature, writer)
+    }
+}
+
+struct ImaModSig {
+    digest: Digest,
+    path: Name,
+    signature: Option<Signature>,
+    modsig_digest: OptionalDigest,
+    modsig: Buffer,
+}
+
+impl TryFrom<&str> for ImaModSig {
+    type Error = std::io::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        // extract sig|d-modsig|modsig first
+        let tokens: Vec<&str> = value.rsplitn(4, ' ').collect();
+        if tokens.len() != 4 {
+            return Err(Error::new(ErrorKind::InvalidInput, value));
+        }
+
+        // parse d-ng|n-ng as in ima-ng
+        let ng = ImaNg::try_from(tokens[3])?;
+        Ok(Self {
+            digest: ng.digest,
+            path: ng.path,
+            signature: parse_signature(tokens[2])?,
+            modsig_digest: OptionalDigest::try_from(tokens[1])?,
+            modsig: Buffer::try_from(tokens[0])?,
+        })
+    }
+}
+
+impl EventData for ImaModSig {
+    fn path(&self) -> &str {
+        &self.path.name
+    }
+}
+
+impl Encode for ImaModSig {
+    fn encode(&self, writer: &mut dyn Write) -> Result<()> {
+        self.digest.encode(writer)?;
+        self.path.encode(writer)?;
+        encode_signature(&self.signature, writer)?;
+        self.modsig_digest.encode(writer)?;
+        self.modsig.encode(writer)?;
+        Ok(())
+    }
+}
+
+struct EvmSig {
+    digest: Digest,
+    path: Name,
+    evm_signature: Buffer,
+    xattr_names: Text,
+    xattr_lengths: Buffer,
+    xattr_values: Buffer,
+    uid: Uint,
+    gid: Uint,
+    mode: Uint,
+}
+
+impl TryFrom<&str> for EvmSig {
+    type Error = std::io::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        // extract the fields following the name first
+        let tokens: Vec<&str> = value.rsplitn(8, ' ').collect();
+        if tokens.len() != 8 {
+            return Err(Error::new(ErrorKind::InvalidInput, value));
+        }
+
+        // parse d-ng|n-ng as in ima-ng
+        let ng = ImaNg::try_from(tokens[7])?;
+        Ok(Self {
+            digest: ng.digest,
+            path: ng.path,
+            evm_signature: Buffer::try_from(tokens[6])?,
+            xattr_names: Text::try_from(tokens[5])?,
+            xattr_lengths: Buffer::try_from(tokens[4])?,
+            xattr_values: Buffer::try_from(tokens[3])?,
+            uid: Uint::parse(tokens[2], ID_FIELD_SIZE)?,
+            gid: Uint::parse(tokens[1], ID_FIELD_SIZE)?,
+            mode: Uint::parse(tokens[0], MODE_FIELD_SIZE)?,
+        })
+    }
+}
+
+impl EventData for EvmSig {
+    fn path(&self) -> &str {
+        &self.path.name
+    }
+}
+
+impl Encode for EvmSig {
+    fn encode(&self, writer: &mut dyn Write) -> Result<()> {
+        self.digest.encode(writer)?;
+        self.path.encode(writer)?;
+        self.evm_signature.encode(writer)?;
+        self.xattr_names.encode(writer)?;
+        self.xattr_lengths.encode(writer)?;
+        self.xattr_values.encode(writer)?;
+        self.uid.encode(writer)?;
+        self.gid.encode(writer)?;
+        self.mode.encode(writer)?;
+        Ok(())
+    }
+}
+
+/// Parses the sig field of the ASCII list, which is empty if the file is not
+/// signed
+fn parse_signature(value: &str) -> Result<Option<Signature>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Signature::try_from(value)?))
+}
+
+/// Decodes the binary sig field, which is empty if the file is not signed
+fn decode_signature(field: &[u8]) -> Result<Option<Signature>> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Signature::try_from(field.to_vec())?))
+}
+
+fn encode_signature(
+    signature: &Option<Signature>,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    match signature {
+        Some(signature) => signature.encode(writer),
+        None => writer.write_all(&0u32.to_le_bytes()),
+    }
+}
+
+impl ImaNg {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = FieldReader::new(data);
+        let digest = Digest::decode(reader.read_field()?)?;
+        let path = Name::decode(reader.read_field()?)?;
+        reader.finish()?;
+        Ok(Self { digest, path })
+    }
+}
+
+impl ImaSig {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = FieldReader::new(data);
+        let digest = Digest::decode(reader.read_field()?)?;
+        let path = Name::decode(reader.read_field()?)?;
+        let signature = decode_signature(reader.read_field()?)?;
+        reader.finish()?;
+        Ok(Self {
+            digest,
+            path,
+            signature,
+        })
+    }
+}
+
+impl ImaBuf {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = FieldReader::new(data);
+        let digest = Digest::decode(reader.read_field()?)?;
+        let name = Name::decode(reader.read_field()?)?;
+        let data = Buffer {
+            value: reader.read_field()?.to_vec(),
+        };
+        reader.finish()?;
+        Ok(Self { digest, name, data })
+    }
+}
+
+impl ImaNgV2 {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = FieldReader::new(data);
+        let digest = DigestV2::decode(reader.read_field()?)?;
+        let path = Name::decode(reader.read_field()?)?;
+        reader.finish()?;
+        Ok(Self { digest, path })
+    }
+}
+
+impl ImaSigV2 {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = FieldReader::new(data);
+        let digest = DigestV2::decode(reader.read_field()?)?;
+        let path = Name::decode(reader.read_field()?)?;
+        let signature = decode_signature(reader.read_field()?)?;
+        reader.finish()?;
+        Ok(Self {
+            digest,
+            path,
+            signature,
+        })
+    }
+}
+
+impl ImaModSig {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = FieldReader::new(data);
+        let digest = Digest::decode(reader.read_field()?)?;
+        let path = Name::decode(reader.read_field()?)?;
+        let signature = decode_signature(reader.read_field()?)?;
+        let modsig_digest = OptionalDigest::decode(reader.read_field()?)?;
+        let modsig = Buffer {
+            value: reader.read_field()?.to_vec(),
+        };
+        reader.finish()?;
+        Ok(Self {
+            digest,
+            path,
+            signature,
+            modsig_digest,
+            modsig,
+        })
+    }
+}
+
+impl EvmSig {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = FieldReader::new(data);
+        let digest = Digest::decode(reader.read_field()?)?;
+        let path = Name::decode(reader.read_field()?)?;
+        let evm_signature = Buffer {
+            value: reader.read_field()?.to_vec(),
+        };
+        let xattr_names = Text::decode(reader.read_field()?)?;
+        let xattr_lengths = Buffer {
+            value: reader.read_field()?.to_vec(),
+        };
+        let xattr_values = Buffer {
+            value: reader.read_field()?.to_vec(),
+        };
+        let uid = Uint::decode(reader.read_field()?)?;
+        let gid = Uint::decode(reader.read_field()?)?;
+        let mode = Uint::decode(reader.read_field()?)?;
+        reader.finish()?;
+        Ok(Self {
+            digest,
+            path,
+            evm_signature,
+            xattr_names,
+            xattr_lengths,
+            xattr_values,
+            uid,
+            gid,
+            mode,
+        })
+    }
+}
+
+/// Size of the template hash in the binary measurement list, which is always
+/// a SHA-1 digest
+const TEMPLATE_HASH_SIZE: u64 = 20;
+
+fn unrecognized_template(template: &str) -> Error {
+    Error::new(
+        ErrorKind::Other,
+        format!("unrecognized template \"{template}\"",),
+    )
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_vec<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>> {
+    // Avoid allocating the length read from the list before the data is
+    // actually available
+    let mut buf = Vec::new();
+    let _ = reader.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "truncated measurement list entry",
+        ));
+    }
+    Ok(buf)
+}
+
 /// Represents a single entry in the IMA measurement list.
 pub struct Entry {
+    pub pcr_index: u32,
+    pub template_name: String,
     pub template_hash: Digest,
     pub event_data: Box<dyn EventData>,
+    template_data: Vec<u8>,
+}
+
+impl Entry {
+    fn new(
+        pcr_index: u32,
+        template_name: &str,
+        template_hash: Digest,
+        event_data: Box<dyn EventData>,
+        template_data: Option<Vec<u8>>,
+    ) -> Result<Self> {
+        let template_data = match template_data {
+            Some(template_data) => template_data,
+            None => {
+                let mut template_data = vec![];
+                event_data.encode(&mut template_data)?;
+                template_data
+            }
+        };
+        Ok(Self {
+            pcr_index,
+            template_name: template_name.to_string(),
+            template_hash,
+            event_data,
+            template_data,
+        })
+    }
+
+    /// Creates an entry from the event data in the format of the ASCII
+    /// measurement list, e.g. "sha256:<digest> /usr/bin/bash" for the
+    /// ima-ng template, calculating the template hash with hash_alg.
+    ///
+    /// The ASCII measurement list line for the entry is the PCR index, the
+    /// template hash, the template name and the event data.
+    pub fn from_event(
+        pcr_index: u32,
+        template_name: &str,
+        event: &str,
+        hash_alg: HashAlgorithm,
+    ) -> Result<Self> {
+        let event_data = parse_event_data(template_name, event)?;
+        let mut template_data = vec![];
+        event_data.encode(&mut template_data)?;
+        let template_hash = Digest::new(
+            hash_alg,
+            &openssl::hash::hash(hash_alg.into(), &template_data)?,
+        )?;
+        Self::new(
+            pcr_index,
+            template_name,
+            template_hash,
+            event_data,
+            Some(template_data),
+        )
+    }
+
+    /// Retrieves the template data, as hashed into the template hash.
+    ///
+    /// For entries read from the binary measurement list this is the data
+    /// exactly as provided by the kernel.
+    pub fn template_data(&self) -> &[u8] {
+        &self.template_data
+    }
+
+    /// Reads an entry of the binary measurement list, as provided in
+    /// /sys/kernel/security/ima/binary_runtime_measurements
+    ///
+    /// Returns None if the end of the list was reached before the entry.
+    pub fn read_binary<R: Read>(reader: &mut R) -> Result<Option<Self>> {
+        let mut pcr_index = [0u8; 4];
+        if reader.read(&mut pcr_index[..1])? == 0 {
+            return Ok(None);
+        }
+        reader.read_exact(&mut pcr_index[1..])?;
+        let pcr_index = u32::from_le_bytes(pcr_index);
+
+        let template_hash = Digest::new(
+            HashAlgorithm::Sha1,
+            &read_vec(reader, TEMPLATE_HASH_SIZE)?,
+        )?;
+        let len = read_u32(reader)?;
+        let template_name = String::from_utf8(read_vec(reader, len.into())?)
+            .map_err(|_| {
+                Error::new(ErrorKind::InvalidData, "invalid template name")
+            })?;
+
+        if template_name == "ima" {
+            // The legacy template has no template data length, and the name
+            // is not padded as when it is hashed
+            let digest = Digest::new(
+                HashAlgorithm::Sha1,
+                &read_vec(reader, TEMPLATE_HASH_SIZE)?,
+            )?;
+            let len = read_u32(reader)?;
+            let path = Name::decode(&read_vec(reader, len.into())?)?;
+            let event_data = Box::new(Ima { digest, path });
+            return Self::new(
+                pcr_index,
+                &template_name,
+                template_hash,
+                event_data,
+                None,
+            )
+            .map(Some);
+        }
+
+        let len = read_u32(reader)?;
+        let template_data = read_vec(reader, len.into())?;
+        let event_data: Box<dyn EventData> = match template_name.as_str() {
+            "ima-ng" => Box::new(ImaNg::decode(&template_data)?),
+            "ima-sig" => Box::new(ImaSig::decode(&template_data)?),
+            "ima-buf" => Box::new(ImaBuf::decode(&template_data)?),
+            "ima-ngv2" => Box::new(ImaNgV2::decode(&template_data)?),
+            "ima-sigv2" => Box::new(ImaSigV2::decode(&template_data)?),
+            "ima-modsig" => Box::new(ImaModSig::decode(&template_data)?),
+            "evm-sig" => Box::new(EvmSig::decode(&template_data)?),
+            template => return Err(unrecognized_template(template)),
+        };
+        Self::new(
+            pcr_index,
+            &template_name,
+            template_hash,
+            event_data,
+            Some(template_data),
+        )
+        .map(Some)
+    }
 }
 
 impl TryFrom<&str> for Entry {
@@ -405,6 +1160,9 @@ impl TryFrom<&str> for Entry {
             return Err(Error::new(ErrorKind::InvalidInput, value));
         }
 
+        let pcr_index = tokens[0].parse::<u32>().map_err(|_| {
+            Error::new(ErrorKind::InvalidInput, "invalid PCR index")
+        })?;
         let template_hash = Digest {
             algorithm: HashAlgorithm::Sha1,
             value: hex::decode(tokens[1]).map_err(|_| {
@@ -412,29 +1170,64 @@ impl TryFrom<&str> for Entry {
             })?,
         };
         let mode = tokens[2];
-        let event = tokens[3];
+        let event_data = parse_event_data(mode, tokens[3])?;
+        Self::new(pcr_index, mode, template_hash, event_data, None)
+    }
+}
 
-        match mode {
-            "ima" => Ok(Self {
-                template_hash,
-                event_data: Box::new(Ima::try_from(event)?),
-            }),
-            "ima-ng" => Ok(Self {
-                template_hash,
-                event_data: Box::new(ImaNg::try_from(event)?),
-            }),
-            "ima-sig" => Ok(Self {
-                template_hash,
-                event_data: Box::new(ImaSig::try_from(event)?),
-            }),
-            "ima-buf" => Ok(Self {
-                template_hash,
-                event_data: Box::new(ImaBuf::try_from(event)?),
-            }),
-            template => Err(Error::new(
-                ErrorKind::Other,
-                format!("unrecognized template \"{template}\"",),
-            )),
+fn parse_event_data(
+    template_name: &str,
+    event: &str,
+) -> Result<Box<dyn EventData>> {
+    let event_data: Box<dyn EventData> = match template_name {
+        "ima" => Box::new(Ima::try_from(event)?),
+        "ima-ng" => Box::new(ImaNg::try_from(event)?),
+        "ima-sig" => Box::new(ImaSig::try_from(event)?),
+        "ima-buf" => Box::new(ImaBuf::try_from(event)?),
+        "ima-ngv2" => Box::new(ImaNgV2::try_from(event)?),
+        "ima-sigv2" => Box::new(ImaSigV2::try_from(event)?),
+        "ima-modsig" => Box::new(ImaModSig::try_from(event)?),
+        "evm-sig" => Box::new(EvmSig::try_from(event)?),
+        template => return Err(unrecognized_template(template)),
+    };
+    Ok(event_data)
+}
+
+/// Iterator over the entries of the binary IMA measurement list.
+///
+/// Iteration stops after the first error, as the position of the following
+/// entry cannot be determined.
+pub struct BinaryEntries<R: Read> {
+    reader: R,
+    done: bool,
+}
+
+impl<R: Read> BinaryEntries<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for BinaryEntries<R> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match Entry::read_binary(&mut self.reader) {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
         }
     }
 }
@@ -456,7 +1249,7 @@ mod tests {
             .expect("unable to encode event data");
         assert_eq!(
             &buf,
-            &hex::decode("6f66d1d8e2fffcc12dfcb78c04b81fe5b8bbae4e2f7573722f62696e2f6b6d6f640000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000").unwrap(), //#[allow_ci]
+            &hex::decode("6f66d1d8e2fffcc12dfcb78c04b81fe5b8bbae4e2f7573722f62696e2f6b6d6f64000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000").unwrap(), //#[allow_ci]
         );
     }
 
@@ -472,7 +1265,7 @@ mod tests {
             .expect("unable to encode event data");
         assert_eq!(
             &buf,
-            &hex::decode("14000000bc026ae66d81713e4e852465e980784dc96651f8190000002f7573722f6c69622f73797374656d642f73797374656d6400").unwrap(), //#[allow_ci]
+            &hex::decode("1a000000736861313a00bc026ae66d81713e4e852465e980784dc96651f8190000002f7573722f6c69622f73797374656d642f73797374656d6400").unwrap(), //#[allow_ci]
         );
     }
 
@@ -488,7 +1281,7 @@ mod tests {
             .expect("unable to encode event data");
         assert_eq!(
             &buf,
-            &hex::decode("140000001cb84b12db45d7da8de58ba6744187db84082f0e0f0000002f7573722f62696e2f7a6d6f72650051000000030202531f402500483046022100bff9c02dc7b270c83cc94bfec10eecd42831de2cdcb04f024369a14623bc3a91022100cc4d015ae932fb98d6846645ed7d1bb1afd4621ec9089bc087126f191886dd31").unwrap(), //#[allow_ci]
+            &hex::decode("1a000000736861313a001cb84b12db45d7da8de58ba6744187db84082f0e0f0000002f7573722f62696e2f7a6d6f72650051000000030202531f402500483046022100bff9c02dc7b270c83cc94bfec10eecd42831de2cdcb04f024369a14623bc3a91022100cc4d015ae932fb98d6846645ed7d1bb1afd4621ec9089bc087126f191886dd31").unwrap(), //#[allow_ci]
         );
     }
 
@@ -520,7 +1313,259 @@ mod tests {
             .expect("unable to encode event data");
         assert_eq!(
             &buf,
-            &hex::decode("140000006e0e6fc8a188ef4f059638949adca4d2219469060e0000006465766963655f726573756d6500ce0000006e616d653d544553543b757569643d43525950542d5645524954592d39656633326535623635623034343234613561386562343436636630653731332d544553543b63617061636974793d303b6d616a6f723d3235333b6d696e6f723d303b6d696e6f725f636f756e743d313b6e756d5f746172676574733d313b6163746976655f7461626c655f686173683d346565383065333365353635643336333430356634303238393436653837623365396563306335383661666639656630656436663561653762656237326431333b").unwrap(), //#[allow_ci]
+            &hex::decode("1a000000736861313a006e0e6fc8a188ef4f059638949adca4d2219469060e0000006465766963655f726573756d6500ce0000006e616d653d544553543b757569643d43525950542d5645524954592d39656633326535623635623034343234613561386562343436636630653731332d544553543b63617061636974793d303b6d616a6f723d3235333b6d696e6f723d303b6d696e6f725f636f756e743d313b6e756d5f746172676574733d313b6163746976655f7461626c655f686173683d346565383065333365353635643336333430356634303238393436653837623365396563306335383661666639656630656436663561653762656237326431333b").unwrap(), //#[allow_ci]
+        );
+    }
+
+    /// Encodes the entry as in the binary measurement list
+    fn to_binary(entry: &Entry) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.extend_from_slice(&entry.pcr_index.to_le_bytes());
+        buf.extend_from_slice(entry.template_hash.value());
+        buf.extend_from_slice(
+            &(entry.template_name.len() as u32).to_le_bytes(),
+        );
+        buf.extend_from_slice(entry.template_name.as_bytes());
+        buf.extend_from_slice(
+            &(entry.template_data().len() as u32).to_le_bytes(),
+        );
+        buf.extend_from_slice(entry.template_data());
+        buf
+    }
+
+    #[test]
+    fn test_parse_binary() {
+        let ascii: Vec<Entry> = [
+            "10 7936eb315fb4e74b99e7d461bc5c96049e1ee092 ima-ng sha1:bc026ae66d81713e4e852465e980784dc96651f8 /usr/lib/systemd/systemd",
+            "10 06e804489a77ddab51b9ef27e17053c0e5d503bd ima-sig sha1:1cb84b12db45d7da8de58ba6744187db84082f0e /usr/bin/zmore 030202531f402500483046022100bff9c02dc7b270c83cc94bfec10eecd42831de2cdcb04f024369a14623bc3a91022100cc4d015ae932fb98d6846645ed7d1bb1afd4621ec9089bc087126f191886dd31",
+            "10 b7862dbbf1383ac6c7cca7f02d981a081aacb1f1 ima-buf sha1:6e0e6fc8a188ef4f059638949adca4d221946906 device_resume 6e616d653d544553543b",
+        ]
+        .iter()
+        .map(|line| Entry::try_from(*line).unwrap()) //#[allow_ci]
+        .collect();
+        let binary = ascii.iter().flat_map(to_binary).collect::<Vec<u8>>();
+
+        let entries = BinaryEntries::new(binary.as_slice())
+            .collect::<Result<Vec<Entry>>>()
+            .expect("unable to parse binary entries");
+        assert_eq!(entries.len(), ascii.len());
+        for (entry, expected) in entries.iter().zip(ascii.iter()) {
+            assert_eq!(entry.pcr_index, 10);
+            assert_eq!(entry.template_name, expected.template_name);
+            assert_eq!(entry.template_hash, expected.template_hash);
+            assert_eq!(entry.event_data.path(), expected.event_data.path());
+            assert_eq!(entry.template_data(), expected.template_data());
+            let mut buf = vec![];
+            entry
+                .event_data
+                .encode(&mut buf)
+                .expect("unable to encode event data");
+            assert_eq!(buf, entry.template_data());
+        }
+    }
+
+    #[test]
+    fn test_parse_binary_path_with_spaces() {
+        let mut template_data = vec![];
+        Digest::new(HashAlgorithm::Sha256, &[0x11; 32])
+            .unwrap() //#[allow_ci]
+            .encode(&mut template_data)
+            .unwrap(); //#[allow_ci]
+        Name::try_from("/tmp/file with  spaces ")
+            .unwrap() //#[allow_ci]
+            .encode(&mut template_data)
+            .unwrap(); //#[allow_ci]
+        let entry = Entry {
+            pcr_index: 11,
+            template_name: "ima-ng".to_string(),
+            template_hash: Digest::start(HashAlgorithm::Sha1),
+            event_data: Box::new(ImaNg::decode(&template_data).unwrap()), //#[allow_ci]
+            template_data,
+        };
+
+        let binary = to_binary(&entry);
+        let parsed = Entry::read_binary(&mut binary.as_slice())
+            .unwrap() //#[allow_ci]
+            .unwrap(); //#[allow_ci]
+        assert_eq!(parsed.pcr_index, 11);
+        assert_eq!(parsed.event_data.path(), "/tmp/file with  spaces ");
+        assert_eq!(parsed.template_data(), entry.template_data());
+    }
+
+    #[test]
+    fn test_parse_binary_ima() {
+        // The legacy template has no template data length and the name is
+        // not padded
+        let mut binary = vec![];
+        binary.extend_from_slice(&10u32.to_le_bytes());
+        binary.extend_from_slice(
+            &hex::decode("d7026dc672344d3ee372217bdbc7395947788671").unwrap(), //#[allow_ci]
+        );
+        binary.extend_from_slice(&3u32.to_le_bytes());
+        binary.extend_from_slice(b"ima");
+        binary.extend_from_slice(
+            &hex::decode("6f66d1d8e2fffcc12dfcb78c04b81fe5b8bbae4e").unwrap(), //#[allow_ci]
+        );
+        binary.extend_from_slice(&13u32.to_le_bytes());
+        binary.extend_from_slice(b"/usr/bin/kmod");
+
+        let mut reader = binary.as_slice();
+        let entry = Entry::read_binary(&mut reader)
+            .unwrap() //#[allow_ci]
+            .unwrap(); //#[allow_ci]
+        let ascii = Entry::try_from("10 d7026dc672344d3ee372217bdbc7395947788671 ima 6f66d1d8e2fffcc12dfcb78c04b81fe5b8bbae4e /usr/bin/kmod").unwrap(); //#[allow_ci]
+        assert_eq!(entry.event_data.path(), "/usr/bin/kmod");
+        assert_eq!(entry.template_data(), ascii.template_data());
+        assert!(Entry::read_binary(&mut reader).unwrap().is_none()); //#[allow_ci]
+    }
+
+    #[test]
+    fn test_parse_binary_invalid() {
+        let entry = Entry::try_from("10 7936eb315fb4e74b99e7d461bc5c96049e1ee092 ima-ng sha1:bc026ae66d81713e4e852465e980784dc96651f8 /usr/lib/systemd/systemd").unwrap(); //#[allow_ci]
+        let binary = to_binary(&entry);
+
+        // Truncated entries stop the iteration
+        let mut entries = BinaryEntries::new(&binary[..binary.len() - 1]);
+        let e = entries.next().unwrap().err().unwrap(); //#[allow_ci]
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+        assert!(entries.next().is_none());
+
+        // Unknown templates are rejected
+        let mut unknown = binary.clone();
+        unknown[28..31].copy_from_slice(b"foo");
+        let e = Entry::read_binary(&mut unknown.as_slice()).err().unwrap(); //#[allow_ci]
+        assert_eq!(e.to_string(), "unrecognized template \"foo-ng\"");
+
+        // Data after the template fields is rejected
+        let mut trailing =
+            binary[..binary.len() - entry.template_data().len() - 4].to_vec();
+        trailing.extend_from_slice(
+            &(entry.template_data().len() as u32 + 1).to_le_bytes(),
+        );
+        trailing.extend_from_slice(entry.template_data());
+        trailing.push(0);
+        assert!(Entry::read_binary(&mut trailing.as_slice()).is_err());
+    }
+
+    /// Checks the template data of the entry, its template hash and that it
+    /// is parsed identically from the binary list
+    fn check_template(line: &str, path: &str, template_data: &str) {
+        let entry = Entry::try_from(line).expect("unable to parse template");
+        assert_eq!(entry.event_data.path(), path);
+        assert_eq!(
+            hex::encode(entry.template_data()),
+            template_data,
+            "{line}"
+        );
+        let template_hash =
+            openssl::hash::hash(MessageDigest::sha1(), entry.template_data())
+                .unwrap(); //#[allow_ci]
+        assert_eq!(entry.template_hash.value(), template_hash.as_ref());
+
+        let binary = to_binary(&entry);
+        let parsed = Entry::read_binary(&mut binary.as_slice())
+            .expect("unable to parse binary template")
+            .unwrap(); //#[allow_ci]
+        assert_eq!(parsed.template_name, entry.template_name);
+        assert_eq!(parsed.event_data.path(), path);
+        let mut buf = vec![];
+        parsed
+            .event_data
+            .encode(&mut buf)
+            .expect("unable to encode event data");
+        assert_eq!(buf, entry.template_data());
+    }
+
+    #[test]
+    fn test_parse_ima_ngv2() {
+        check_template(
+            "10 faa23cd9438b4476155b437d355051112ba6b09e ima-ngv2 ima:sha256:37d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c2 /usr/bin/bash",
+            "/usr/bin/bash",
+            "2c000000696d613a7368613235363a0037d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c20e0000002f7573722f62696e2f6261736800",
         );
+        // The digest type and algorithm are mandatory
+        assert!(Entry::try_from("10 faa23cd9438b4476155b437d355051112ba6b09e ima-ngv2 sha256:37d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c2 /usr/bin/bash").is_err());
+    }
+
+    #[test]
+    fn test_parse_ima_sigv2() {
+        check_template(
+            "10 018fb26415161f06840c7d07f1e6034543d1f080 ima-sigv2 verity:sha256:37d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c2 /usr/bin/zmore 030202531f402500483046022100bff9c02dc7b270c83cc94bfec10eecd42831de2cdcb04f024369a14623bc3a91022100cc4d015ae932fb98d6846645ed7d1bb1afd4621ec9089bc087126f191886dd31",
+            "/usr/bin/zmore",
+            "2f0000007665726974793a7368613235363a0037d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c20f0000002f7573722f62696e2f7a6d6f72650051000000030202531f402500483046022100bff9c02dc7b270c83cc94bfec10eecd42831de2cdcb04f024369a14623bc3a91022100cc4d015ae932fb98d6846645ed7d1bb1afd4621ec9089bc087126f191886dd31",
+        );
+    }
+
+    #[test]
+    fn test_parse_ima_modsig() {
+        // Appended signature without IMA signature
+        check_template(
+            "10 ebf3dd065aecd3c8c0442632dcc185c5f8a67674 ima-modsig sha256:37d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c2 /usr/lib/modules/test.ko  sha256:120970d812836f19888625587a4606a5ad23cef31c8684e601771552548fc6b9 308201aa06092a864886f70d010702",
+            "/usr/lib/modules/test.ko",
+            "280000007368613235363a0037d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c2190000002f7573722f6c69622f6d6f64756c65732f746573742e6b6f0000000000280000007368613235363a00120970d812836f19888625587a4606a5ad23cef31c8684e601771552548fc6b90f000000308201aa06092a864886f70d010702",
+        );
+        // IMA signature without appended signature
+        check_template(
+            "10 2daec974d0906d91a79dafb7aa8a3929efb8bedb ima-modsig sha256:37d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c2 /usr/bin/zmore 030202531f402500483046022100bff9c02dc7b270c83cc94bfec10eecd42831de2cdcb04f024369a14623bc3a91022100cc4d015ae932fb98d6846645ed7d1bb1afd4621ec9089bc087126f191886dd31  ",
+            "/usr/bin/zmore",
+            "280000007368613235363a0037d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c20f0000002f7573722f62696e2f7a6d6f72650051000000030202531f402500483046022100bff9c02dc7b270c83cc94bfec10eecd42831de2cdcb04f024369a14623bc3a91022100cc4d015ae932fb98d6846645ed7d1bb1afd4621ec9089bc087126f191886dd310000000000000000",
+        );
+    }
+
+    #[test]
+    fn test_parse_evm_sig() {
+        check_template(
+            "10 8e9de0221855bf11d01a680087dcd5fcfe185ea2 evm-sig sha256:37d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c2 /usr/bin/evm file 050204aabbccdd0002 security.selinux|security.ima 0400000003000000 61626300040401 0 1000 33261",
+            "/usr/bin/evm file",
+            "280000007368613235363a0037d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c2120000002f7573722f62696e2f65766d2066696c650009000000050204aabbccdd00021d00000073656375726974792e73656c696e75787c73656375726974792e696d610800000004000000030000000700000061626300040401040000000000000004000000e803000002000000ed81",
+        );
+        // Events not related to a file have empty fields
+        check_template(
+            "10 3a3b7bbbcae13c0810ff26bc38e90f79736045db evm-sig sha256:0000000000000000000000000000000000000000000000000000000000000000 boot_aggregate       ",
+            "boot_aggregate",
+            "280000007368613235363a0000000000000000000000000000000000000000000000000000000000000000000f000000626f6f745f6167677265676174650000000000000000000000000000000000000000000000000000000000",
+        );
+        // The mode does not fit in its field
+        assert!(Entry::try_from("10 8e9de0221855bf11d01a680087dcd5fcfe185ea2 evm-sig sha256:37d2b12d5d9abc2a364ef9448767ee03938e383c0284193477dc7618f4b7c6c2 /usr/bin/evm 050204aabbccdd0002 security.ima 03000000 040401 0 0 65536").is_err());
+    }
+
+    #[test]
+    fn test_from_event() {
+        let event = "sha1:e646273f60f682341af5bac85d986f9f8f2babe7 /usr/lib/os-release";
+        let entry =
+            Entry::from_event(10, "ima-ng", event, HashAlgorithm::Sha1)
+                .expect("unable to create ima-ng entry");
+        assert_eq!(entry.pcr_index, 10);
+        assert_eq!(entry.template_name, "ima-ng");
+        assert_eq!(entry.event_data.path(), "/usr/lib/os-release");
+        assert_eq!(
+            hex::encode(entry.template_hash.value()),
+            "17594ffbe7d26484b2fd11403ae18c125ee5389c"
+        );
+
+        let entry =
+            Entry::from_event(10, "ima-ng", event, HashAlgorithm::Sha256)
+                .expect("unable to create ima-ng entry");
+        assert_eq!(
+            hex::encode(entry.template_hash.value()),
+            "46b5459dba3f4c8fd855f524a52f90b7a7f612a6679d93d2f403352d668db832"
+        );
+
+        // The entry is parsed back from its ASCII line
+        let line = format!(
+            "10 {} ima-ng {event}",
+            hex::encode(entry.template_hash.value())
+        );
+        let parsed = Entry::try_from(line.as_str())
+            .expect("unable to parse ima-ng template");
+        assert_eq!(parsed.template_data(), entry.template_data());
+
+        assert!(Entry::from_event(
+            10,
+            "ima-unknown",
+            event,
+            HashAlgorithm::Sha1
+        )
+        .is_err());
     }
 }
@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+
+// Filter for the entries of the IMA measurement list sent to the verifier.
+
+use super::{Digest, Entry};
+use crate::algorithms::HashAlgorithm;
+use glob::Pattern;
+use openssl::hash::hash;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::io::{Error, ErrorKind, Result};
+use thiserror::Error;
+
+/// Template name used for the entries replaced by the filter
+pub const EXCLUDED_TEMPLATE: &str = "excluded";
+
+/// Format of the IMA measurement list including entries replaced by the
+/// filter. The filter is only applied when the verifier requests this
+/// format, as verifiers unaware of it cannot parse the replaced entries
+pub const EXCLUDED_ENTRIES_FORMAT: &str = "text/plain+excluded";
+
+#[derive(Error, Debug)]
+pub enum ImaFilterError {
+    /// A path pattern is not a valid glob
+    #[error("Invalid IMA path pattern {pattern}")]
+    Pattern {
+        pattern: String,
+        #[source]
+        source: glob::PatternError,
+    },
+}
+
+/// Excludes entries of the IMA measurement list by path or template name
+///
+/// The excluded entries are not removed from the list, as the verifier
+/// still needs to replay them to validate PCR 10. Instead, each excluded
+/// entry is replaced by a line containing only the PCR index, the template
+/// hash and the template hashes calculated with the algorithm of each PCR
+/// bank:
+///
+/// `10 <template hash> excluded <algorithm>:<PCR bank template hash> ...`
+///
+/// Entries recording violations and entries which cannot be parsed are
+/// never excluded.
+#[derive(Clone, Debug)]
+pub struct ImaFilter {
+    paths: Vec<Pattern>,
+    templates: Vec<String>,
+    pcr_hash_algs: Vec<HashAlgorithm>,
+}
+
+impl ImaFilter {
+    /// Creates a filter for the given path globs and template names
+    ///
+    /// # Arguments
+    ///
+    /// * paths (&[&str]): Globs matching the paths of the excluded entries
+    /// * templates (&[&str]): Names of the templates of the excluded entries
+    /// * pcr_hash_algs (&[HashAlgorithm]): The algorithms of the PCR banks
+    ///   the verifier replays the measurement list against
+    pub fn new(
+        paths: &[&str],
+        templates: &[&str],
+        pcr_hash_algs: &[HashAlgorithm],
+    ) -> std::result::Result<Self, ImaFilterError> {
+        let paths = paths
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern).map_err(|source| {
+                    ImaFilterError::Pattern {
+                        pattern: pattern.to_string(),
+                        source,
+                    }
+                })
+            })
+            .collect::<std::result::Result<Vec<Pattern>, ImaFilterError>>()?;
+        Ok(ImaFilter {
+            paths,
+            templates: templates.iter().map(|t| t.to_string()).collect(),
+            pcr_hash_algs: pcr_hash_algs.to_vec(),
+        })
+    }
+
+    /// Returns true if the filter does not exclude any entry
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.templates.is_empty()
+    }
+
+    /// Returns true if the entry is excluded
+    pub fn excludes(&self, entry: &Entry) -> bool {
+        if entry.template_hash.value().iter().all(|b| *b == 0) {
+            return false;
+        }
+        self.templates.iter().any(|t| *t == entry.template_name)
+            || self
+                .paths
+                .iter()
+                .any(|p| p.matches(entry.event_data.path()))
+    }
+
+    /// Applies the filter to a line of the ASCII measurement list
+    ///
+    /// # Returns
+    ///
+    /// The line unchanged, or the replacement if the entry is excluded
+    pub fn apply<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let Ok(entry) = Entry::try_from(line) else {
+            return Cow::Borrowed(line);
+        };
+        if !self.excludes(&entry) {
+            return Cow::Borrowed(line);
+        }
+        let mut excluded = format!(
+            "{} {} {EXCLUDED_TEMPLATE}",
+            entry.pcr_index,
+            hex::encode(entry.template_hash.value()),
+        );
+        for alg in &self.pcr_hash_algs {
+            let Ok(pcr_template_hash) =
+                hash((*alg).into(), entry.template_data())
+            else {
+                return Cow::Borrowed(line);
+            };
+            excluded.push_str(&format!(
+                " {alg}:{}",
+                hex::encode(pcr_template_hash)
+            ));
+        }
+        Cow::Owned(excluded)
+    }
+}
+
+/// An entry replaced by the filter, as parsed from the ASCII measurement list
+pub struct ExcludedEntry {
+    pub pcr_index: u32,
+    pub template_hash: Digest,
+    /// The template hashes calculated with the algorithm of each PCR bank
+    pub pcr_template_hashes: Vec<Digest>,
+}
+
+impl ExcludedEntry {
+    /// Parses a line of the ASCII measurement list
+    ///
+    /// # Returns
+    ///
+    /// None if the line is not an entry replaced by the filter
+    pub fn parse(line: &str) -> Option<Result<Self>> {
+        let tokens: Vec<&str> = line.split(' ').collect();
+        if tokens.len() < 4 || tokens[2] != EXCLUDED_TEMPLATE {
+            return None;
+        }
+        Some(Self::from_tokens(tokens[0], tokens[1], &tokens[3..]))
+    }
+
+    fn from_tokens(
+        pcr_index: &str,
+        template_hash: &str,
+        pcr_template_hashes: &[&str],
+    ) -> Result<Self> {
+        let pcr_index = pcr_index.parse::<u32>().map_err(|_| {
+            Error::new(ErrorKind::InvalidInput, "invalid PCR index")
+        })?;
+        // Validate the length of the digests
+        let template_hash = Digest::try_from(template_hash)?;
+        let template_hash =
+            Digest::new(template_hash.algorithm, template_hash.value())?;
+        let pcr_template_hashes = pcr_template_hashes
+            .iter()
+            .map(|pcr_template_hash| {
+                let pcr_template_hash = Digest::try_from(*pcr_template_hash)?;
+                Digest::new(
+                    pcr_template_hash.algorithm,
+                    pcr_template_hash.value(),
+                )
+            })
+            .collect::<Result<Vec<Digest>>>()?;
+        Ok(ExcludedEntry {
+            pcr_index,
+            template_hash,
+            pcr_template_hashes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OS_RELEASE: &str = "10 17594ffbe7d26484b2fd11403ae18c125ee5389c ima-ng sha1:e646273f60f682341af5bac85d986f9f8f2babe7 /usr/lib/os-release";
+
+    #[test]
+    fn filter_test() {
+        let filter = ImaFilter::new(
+            &["/usr/lib/**", "/tmp/*"],
+            &[],
+            &[HashAlgorithm::Sha256],
+        )
+        .unwrap(); //#[allow_ci]
+        assert!(!filter.is_empty());
+
+        let filtered = filter.apply(OS_RELEASE);
+        assert_eq!(filtered, "10 17594ffbe7d26484b2fd11403ae18c125ee5389c excluded sha256:46b5459dba3f4c8fd855f524a52f90b7a7f612a6679d93d2f403352d668db832");
+        let excluded = ExcludedEntry::parse(&filtered).unwrap().unwrap(); //#[allow_ci]
+        assert_eq!(excluded.pcr_index, 10);
+        assert_eq!(excluded.pcr_template_hashes.len(), 1);
+        assert_eq!(
+            excluded.pcr_template_hashes[0].algorithm,
+            HashAlgorithm::Sha256
+        );
+
+        // Other paths, violations and unknown lines are kept
+        let line = "10 c156ebdcbfcd28fe1060ef4cdec0aab04d3a9b63 ima-ng sha1:19f13b42c2745066347e76454788c0fe083643f3 /init";
+        assert_eq!(filter.apply(line), line);
+        let line = "10 0000000000000000000000000000000000000000 ima-ng sha1:0000000000000000000000000000000000000000 /tmp/file";
+        assert_eq!(filter.apply(line), line);
+        assert_eq!(filter.apply("invalid"), "invalid");
+        assert!(ExcludedEntry::parse(line).is_none());
+    }
+
+    #[test]
+    fn filter_templates_test() {
+        let filter =
+            ImaFilter::new(&[], &["ima-ng"], &[HashAlgorithm::Sha1]).unwrap(); //#[allow_ci]
+        assert_eq!(
+            filter.apply(OS_RELEASE),
+            "10 17594ffbe7d26484b2fd11403ae18c125ee5389c excluded sha1:17594ffbe7d26484b2fd11403ae18c125ee5389c"
+        );
+
+        let filter =
+            ImaFilter::new(&[], &[], &[HashAlgorithm::Sha1]).unwrap(); //#[allow_ci]
+        assert!(filter.is_empty());
+        assert_eq!(filter.apply(OS_RELEASE), OS_RELEASE);
+
+        assert!(ImaFilter::new(&["/usr/[lib"], &[], &[HashAlgorithm::Sha1])
+            .is_err());
+    }
+
+    #[test]
+    fn filter_banks_test() {
+        let filter = ImaFilter::new(
+            &["/usr/lib/**"],
+            &[],
+            &[HashAlgorithm::Sha256, HashAlgorithm::Sha384],
+        )
+        .unwrap(); //#[allow_ci]
+        let filtered = filter.apply(OS_RELEASE);
+        let excluded = ExcludedEntry::parse(&filtered).unwrap().unwrap(); //#[allow_ci]
+        let algorithms = excluded
+            .pcr_template_hashes
+            .iter()
+            .map(|digest| digest.algorithm)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            algorithms,
+            vec![HashAlgorithm::Sha256, HashAlgorithm::Sha384]
+        );
+
+        // Invalid template hashes are rejected
+        let invalid = format!("{filtered} sha256:1234");
+        assert!(ExcludedEntry::parse(&invalid).unwrap().is_err());
+    }
+}
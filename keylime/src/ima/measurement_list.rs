@@ -1,46 +1,85 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright 2021 Keylime Authors
 
+use super::ImaFilter;
+use serde::{Serialize, Serializer};
 use std::{
-    collections::HashSet,
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
     fs::File,
-    io::{prelude::*, Error, SeekFrom},
+    io::{prelude::*, BufReader, Error, ErrorKind, SeekFrom},
 };
 
-/// MeasurementList models the IMA measurement lists's last two known
-/// numbers of entries in the log and filesizes at that point
+/// Number of entries between two checkpoints of the measurement list
+const CHECKPOINT_INTERVAL: u64 = 1024;
+
+/// Maximum number of checkpoints kept for the measurement list
+const MAX_CHECKPOINTS: usize = 4096;
+
+/// MeasurementList models the IMA measurement list through an ordered index
+/// of checkpoints, mapping entry numbers to the offsets of the entries in the
+/// file.
+///
+/// A checkpoint is kept every CHECKPOINT_INTERVAL entries, plus one for the
+/// last known end of the list, so reading from any entry only needs to skip
+/// less than CHECKPOINT_INTERVAL entries. When MAX_CHECKPOINTS is reached,
+/// the checkpoints for the oldest entries are dropped first, as the verifier
+/// usually requests the most recent entries.
 #[derive(Debug)]
 pub struct MeasurementList {
-    entries: HashSet<(u64, u64)>,
+    checkpoints: BTreeMap<u64, u64>,
+    filter: Option<ImaFilter>,
 }
 
 impl MeasurementList {
     pub fn new() -> Self {
         Self {
-            entries: HashSet::new(),
+            checkpoints: BTreeMap::new(),
+            filter: None,
         }
     }
 
+    /// Set the filter applied to the entries read from the measurement list
+    /// by readers with exclude_entries() set. Excluded entries are replaced,
+    /// not removed, so the entry numbers are not affected by the filter.
+    pub fn set_filter(&mut self, filter: Option<ImaFilter>) {
+        self.filter = filter.filter(|f| !f.is_empty());
+    }
+
     pub fn reset(&mut self) {
-        self.entries = HashSet::new();
+        self.checkpoints = BTreeMap::new();
     }
 
-    fn update(&mut self, num_entries: u64, filesize: u64) -> Option<bool> {
-        if self.entries.len() > 32 {
-            let e = *self.entries.iter().next()?;
-            let _ = self.entries.remove(&e);
+    fn checkpoint(&mut self, nth_entry: u64, offset: u64) {
+        if nth_entry == 0 {
+            return;
+        }
+        let _ = self.checkpoints.insert(nth_entry, offset);
+        while self.checkpoints.len() > MAX_CHECKPOINTS {
+            let _ = self.checkpoints.pop_first();
         }
-        Some(self.entries.insert((num_entries, filesize)))
     }
 
-    fn find(&self, nth_entry: u64) -> (u64, u64) {
-        self.entries.iter().fold((0, 0), |best, entry| {
-            if entry.0 > best.0 && entry.0 < nth_entry {
-                *entry
-            } else {
-                best
+    /// Records the last known end of the list, replacing the previous one
+    fn update_end(&mut self, num_entries: u64, offset: u64) {
+        if let Some((&last, _)) = self.checkpoints.last_key_value() {
+            if !last.is_multiple_of(CHECKPOINT_INTERVAL)
+                && last != num_entries
+            {
+                let _ = self.checkpoints.remove(&last);
             }
-        })
+        }
+        self.checkpoint(num_entries, offset);
+    }
+
+    /// Finds the closest checkpoint at or before the nth_entry
+    fn find(&self, nth_entry: u64) -> (u64, u64) {
+        self.checkpoints
+            .range(..=nth_entry)
+            .next_back()
+            .map(|(entry, offset)| (*entry, *offset))
+            .unwrap_or((0, 0))
     }
 
     /// Read the IMA measurement list starting from a given entry.
@@ -55,34 +94,133 @@ impl MeasurementList {
         ima_file: &mut File,
         nth_entry: u64,
     ) -> Result<(String, u64, u64), Error> {
-        // Try to find the closest entry to the nth_entry
-        let (mut num_entries, filesize) = self.find(nth_entry);
+        self.read_bounded(ima_file, nth_entry, None)
+    }
 
-        let mut ml = None;
-        let mut filedata = String::new();
-        let _ = ima_file.seek(SeekFrom::Start(filesize))?;
-        let _ = ima_file.read_to_string(&mut filedata)?;
-        let mut offset: usize = 0;
+    /// Read at most max_entries entries of the IMA measurement list starting
+    /// from a given entry, allowing large lists to be delivered in chunks.
+    /// If max_entries is None, the list is read until the end.
+    /// The starting entry is handled as in read(), and the same values are
+    /// returned. The current number of entries in the file allows the client
+    /// to know whether more entries are available.
+    pub fn read_bounded(
+        &mut self,
+        ima_file: &mut File,
+        nth_entry: u64,
+        max_entries: Option<u64>,
+    ) -> Result<(String, u64, u64), Error> {
+        let mut reader = self.reader(ima_file, nth_entry, max_entries)?;
+        let mut ml = String::new();
+        for line in reader.by_ref() {
+            ml.push_str(&line?);
+        }
+        let nth_entry = reader.nth_entry();
+        let num_entries = reader.finish()?;
+        Ok((ml, nth_entry, num_entries))
+    }
 
-        loop {
-            if nth_entry == num_entries {
-                ml = Some(&filedata[offset..]);
-            }
-            let s = &filedata[offset..];
-            let idx = match s.find('\n') {
-                None => break,
-                Some(i) => i,
-            };
-            offset = offset + idx + 1;
-            num_entries += 1;
+    /// Create a reader producing the entries of the IMA measurement list one
+    /// at a time, so that the list does not need to be held in memory.
+    /// The starting entry and max_entries are handled as in read_bounded().
+    pub fn reader<'a>(
+        &'a mut self,
+        ima_file: &'a mut File,
+        nth_entry: u64,
+        max_entries: Option<u64>,
+    ) -> Result<MeasurementListReader<'a>, Error> {
+        // Start from the closest checkpoint to the nth_entry
+        let (entry, offset) = self.find(nth_entry);
+        let _ = ima_file.seek(SeekFrom::Start(offset))?;
+        let mut reader = MeasurementListReader {
+            ml: self,
+            file: BufReader::new(ima_file),
+            buf: Vec::new(),
+            nth_entry,
+            entry,
+            offset,
+            last_entry: max_entries.map(|max| nth_entry.saturating_add(max)),
+            exclude: false,
+        };
+
+        while reader.entry < nth_entry && reader.next_line()? {}
+
+        if reader.entry != nth_entry {
+            // The entry is beyond the next one, read from the beginning
+            reader.nth_entry = 0;
+            reader.entry = 0;
+            reader.offset = 0;
+            reader.last_entry = max_entries;
+            let _ = reader.file.seek(SeekFrom::Start(0))?;
         }
+        Ok(reader)
+    }
 
-        let _ = self.update(num_entries, filesize + offset as u64);
+    /// Create a cursor to read the IMA measurement list in batches with
+    /// read_batch(), so that the list and the file do not need to be held
+    /// between the batches.
+    /// The starting entry and max_entries are handled as in read_bounded().
+    /// The list is scanned to its end to find the current number of entries,
+    /// and only the entries available at this point are read by the cursor.
+    pub fn cursor(
+        &mut self,
+        ima_file: &mut File,
+        nth_entry: u64,
+        max_entries: Option<u64>,
+        exclude: bool,
+    ) -> Result<MeasurementListCursor, Error> {
+        let reader = self.reader(ima_file, nth_entry, max_entries)?;
+        let nth_entry = reader.nth_entry;
+        let offset = reader.offset;
+        let last_entry = reader.last_entry;
+        let num_entries = reader.finish()?;
+        Ok(MeasurementListCursor {
+            nth_entry,
+            entry: nth_entry,
+            offset,
+            last_entry: last_entry
+                .map_or(num_entries, |last| last.min(num_entries)),
+            num_entries,
+            exclude,
+        })
+    }
 
-        match ml {
-            None => self.read(ima_file, 0),
-            Some(slice) => Ok((String::from(slice), nth_entry, num_entries)),
+    /// Read the next batch of at most max_entries entries of the cursor,
+    /// advancing it. An empty batch is returned once all the entries of the
+    /// cursor were read.
+    pub fn read_batch(
+        &mut self,
+        ima_file: &mut File,
+        cursor: &mut MeasurementListCursor,
+        max_entries: u64,
+    ) -> Result<Vec<String>, Error> {
+        let _ = ima_file.seek(SeekFrom::Start(cursor.offset))?;
+        let mut reader = MeasurementListReader {
+            ml: self,
+            file: BufReader::new(ima_file),
+            buf: Vec::new(),
+            nth_entry: cursor.nth_entry,
+            entry: cursor.entry,
+            offset: cursor.offset,
+            last_entry: Some(
+                cursor
+                    .last_entry
+                    .min(cursor.entry.saturating_add(max_entries)),
+            ),
+            exclude: cursor.exclude,
+        };
+        let batch = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+        cursor.entry = reader.entry;
+        cursor.offset = reader.offset;
+        if batch.is_empty() && max_entries > 0 && !cursor.is_done() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                format!(
+                    "measurement list ended at entry {}, before entry {}",
+                    cursor.entry, cursor.last_entry
+                ),
+            ));
         }
+        Ok(batch)
     }
 }
 
@@ -92,9 +230,205 @@ impl Default for MeasurementList {
     }
 }
 
+/// Reader for the entries of the IMA measurement list, created by
+/// MeasurementList::reader()
+///
+/// The reader is an iterator over the entries, each including its trailing
+/// new line. An incomplete last line, which the kernel may still be writing,
+/// is not returned.
+pub struct MeasurementListReader<'a> {
+    ml: &'a mut MeasurementList,
+    file: BufReader<&'a mut File>,
+    buf: Vec<u8>,
+    nth_entry: u64,
+    entry: u64,
+    offset: u64,
+    last_entry: Option<u64>,
+    exclude: bool,
+}
+
+impl MeasurementListReader<'_> {
+    /// The entry from where the list is read
+    pub fn nth_entry(&self) -> u64 {
+        self.nth_entry
+    }
+
+    /// Applies the filter of the MeasurementList to the entries, if enabled.
+    /// This must only be enabled if the verifier accepts the entries
+    /// replaced by the filter, see EXCLUDED_ENTRIES_FORMAT
+    pub fn exclude_entries(mut self, enabled: bool) -> Self {
+        self.exclude = enabled;
+        self
+    }
+
+    /// Reads the next complete line into the buffer, recording checkpoints
+    ///
+    /// # Returns
+    ///
+    /// false if there are no more complete lines
+    fn next_line(&mut self) -> Result<bool, Error> {
+        self.buf.clear();
+        let read = self.file.read_until(b'\n', &mut self.buf)?;
+        if read == 0 || self.buf.last() != Some(&b'\n') {
+            return Ok(false);
+        }
+        self.entry += 1;
+        self.offset += read as u64;
+        if self.entry.is_multiple_of(CHECKPOINT_INTERVAL) {
+            self.ml.checkpoint(self.entry, self.offset);
+        }
+        Ok(true)
+    }
+
+    /// Skips the remaining entries to find the current number of entries in
+    /// the list, which is returned
+    pub fn finish(mut self) -> Result<u64, Error> {
+        while self.next_line()? {}
+        self.ml.update_end(self.entry, self.offset);
+        Ok(self.entry)
+    }
+}
+
+/// Position of a read of the IMA measurement list done in batches, created by
+/// MeasurementList::cursor()
+#[derive(Debug, Clone)]
+pub struct MeasurementListCursor {
+    nth_entry: u64,
+    entry: u64,
+    offset: u64,
+    last_entry: u64,
+    num_entries: u64,
+    exclude: bool,
+}
+
+impl MeasurementListCursor {
+    /// The entry from where the list is read
+    pub fn nth_entry(&self) -> u64 {
+        self.nth_entry
+    }
+
+    /// The number of entries read by the cursor
+    pub fn entry_count(&self) -> u64 {
+        self.last_entry - self.nth_entry
+    }
+
+    /// The number of entries in the list when the cursor was created
+    pub fn num_entries(&self) -> u64 {
+        self.num_entries
+    }
+
+    /// Whether all the entries of the cursor were read
+    pub fn is_done(&self) -> bool {
+        self.entry >= self.last_entry
+    }
+}
+
+/// Serializes the entries of the IMA measurement list as a single string,
+/// reading them in batches from read_batch while they are written, so that
+/// the list is never held in memory. read_batch returns an empty batch once
+/// all the entries were read, see MeasurementList::read_batch().
+///
+/// The serialization cannot fail on a read error without leaving the output
+/// incomplete, so the string is instead ended with the entries read so far,
+/// and the error is kept to be returned by take_error(), which must be
+/// checked after serializing. If the output was already sent, e.g. in a
+/// streamed response after its status, the receiver is left with a truncated
+/// list and has to be notified by aborting the transfer.
+pub struct MeasurementListEntries<F> {
+    read_batch: RefCell<F>,
+    error: RefCell<Option<Error>>,
+}
+
+impl<F> MeasurementListEntries<F>
+where
+    F: FnMut() -> Result<Vec<String>, Error>,
+{
+    pub fn new(read_batch: F) -> Self {
+        Self {
+            read_batch: RefCell::new(read_batch),
+            error: RefCell::new(None),
+        }
+    }
+
+    /// Takes the error which ended the serialization early, if any
+    pub fn take_error(&self) -> Option<Error> {
+        self.error.borrow_mut().take()
+    }
+}
+
+impl<F> Serialize for MeasurementListEntries<F>
+where
+    F: FnMut() -> Result<Vec<String>, Error>,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // The string is written through Display so that the serializer
+        // escapes the entries as they are read
+        serializer.collect_str(&EntriesDisplay(self))
+    }
+}
+
+struct EntriesDisplay<'a, F>(&'a MeasurementListEntries<F>);
+
+impl<F> fmt::Display for EntriesDisplay<'_, F>
+where
+    F: FnMut() -> Result<Vec<String>, Error>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut read_batch = self.0.read_batch.borrow_mut();
+        loop {
+            match (read_batch)() {
+                Ok(batch) if batch.is_empty() => return Ok(()),
+                Ok(batch) => {
+                    for line in batch {
+                        f.write_str(&line)?;
+                    }
+                }
+                // Returning fmt::Error for anything but a failed write is
+                // not supported by the serializers
+                Err(e) => {
+                    *self.0.error.borrow_mut() = Some(e);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for MeasurementListReader<'_> {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.last_entry == Some(self.entry) {
+            return None;
+        }
+        match self.next_line() {
+            Ok(true) => (),
+            Ok(false) => return None,
+            Err(e) => return Some(Err(e)),
+        }
+        let line = match std::str::from_utf8(&self.buf) {
+            Ok(line) => line,
+            Err(e) => {
+                return Some(Err(Error::new(ErrorKind::InvalidData, e)))
+            }
+        };
+        let line = match &self.ml.filter {
+            Some(filter) if self.exclude => {
+                filter.apply(line.trim_end_matches('\n')).into_owned() + "\n"
+            }
+            _ => line.to_string(),
+        };
+        Some(Ok(line))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::algorithms::HashAlgorithm;
     use tempfile::NamedTempFile;
 
     #[test]
@@ -130,4 +464,305 @@ mod tests {
         assert_eq!(nth_entry, 0);
         assert_eq!(ml.find("0-entry").unwrap(), 0); //#[allow_ci]
     }
+
+    #[test]
+    fn read_bounded_measurement_list_test() {
+        let mut ima_ml = MeasurementList::new();
+
+        let filedata = "0-entry\n1-entry\n2-entry\n3-entry\n";
+        let mut tf = NamedTempFile::new().unwrap(); //#[allow_ci]
+        tf.write_all(filedata.as_bytes()).unwrap(); //#[allow_ci]
+        tf.flush().unwrap(); //#[allow_ci]
+
+        let mut ima_file = File::open(tf.path()).unwrap(); //#[allow_ci]
+
+        // Read the list in chunks of 2 entries
+        let (ml, nth_entry, num_entries) =
+            ima_ml.read_bounded(&mut ima_file, 0, Some(2)).unwrap(); //#[allow_ci]
+        assert_eq!(ml, "0-entry\n1-entry\n");
+        assert_eq!(nth_entry, 0);
+        assert_eq!(num_entries, 4);
+
+        let (ml, nth_entry, num_entries) =
+            ima_ml.read_bounded(&mut ima_file, 2, Some(2)).unwrap(); //#[allow_ci]
+        assert_eq!(ml, "2-entry\n3-entry\n");
+        assert_eq!(nth_entry, 2);
+        assert_eq!(num_entries, 4);
+
+        // The limit can exceed the number of available entries
+        let (ml, _, _) =
+            ima_ml.read_bounded(&mut ima_file, 3, Some(10)).unwrap(); //#[allow_ci]
+        assert_eq!(ml, "3-entry\n");
+
+        // No entries are read with a limit of 0
+        let (ml, nth_entry, _) =
+            ima_ml.read_bounded(&mut ima_file, 1, Some(0)).unwrap(); //#[allow_ci]
+        assert_eq!(ml, "");
+        assert_eq!(nth_entry, 1);
+
+        // Without a limit the list is read until the end
+        let (ml, _, _) = ima_ml.read_bounded(&mut ima_file, 1, None).unwrap(); //#[allow_ci]
+        assert_eq!(ml, "1-entry\n2-entry\n3-entry\n");
+
+        // An entry beyond the next one reads the list from the beginning,
+        // keeping the limit
+        let (ml, nth_entry, _) =
+            ima_ml.read_bounded(&mut ima_file, 9, Some(1)).unwrap(); //#[allow_ci]
+        assert_eq!(ml, "0-entry\n");
+        assert_eq!(nth_entry, 0);
+    }
+
+    #[test]
+    fn read_filtered_measurement_list_test() {
+        let mut ima_ml = MeasurementList::new();
+        ima_ml.set_filter(Some(
+            ImaFilter::new(&["/usr/lib/**"], &[], &[HashAlgorithm::Sha1])
+                .unwrap(), //#[allow_ci]
+        ));
+
+        let filedata = "10 c156ebdcbfcd28fe1060ef4cdec0aab04d3a9b63 ima-ng sha1:19f13b42c2745066347e76454788c0fe083643f3 /init
+10 17594ffbe7d26484b2fd11403ae18c125ee5389c ima-ng sha1:e646273f60f682341af5bac85d986f9f8f2babe7 /usr/lib/os-release
+10 790ff4fe72889b071a0f7585112710be6d0084fe ima-ng sha1:c90333979f56f38bbd41b81806015b0de502f3cc /bin/sh
+";
+        let mut tf = NamedTempFile::new().unwrap(); //#[allow_ci]
+        tf.write_all(filedata.as_bytes()).unwrap(); //#[allow_ci]
+        tf.flush().unwrap(); //#[allow_ci]
+
+        let mut ima_file = File::open(tf.path()).unwrap(); //#[allow_ci]
+
+        // The filter is only applied if requested
+        let (ml, _, _) = ima_ml.read(&mut ima_file, 0).unwrap(); //#[allow_ci]
+        assert_eq!(ml, filedata);
+
+        let reader = ima_ml
+            .reader(&mut ima_file, 1, Some(2))
+            .unwrap() //#[allow_ci]
+            .exclude_entries(true);
+        let nth_entry = reader.nth_entry();
+        let ml = reader.collect::<Result<String, _>>().unwrap(); //#[allow_ci]
+        assert_eq!(ml, "10 17594ffbe7d26484b2fd11403ae18c125ee5389c excluded sha1:17594ffbe7d26484b2fd11403ae18c125ee5389c
+10 790ff4fe72889b071a0f7585112710be6d0084fe ima-ng sha1:c90333979f56f38bbd41b81806015b0de502f3cc /bin/sh
+");
+        assert_eq!(nth_entry, 1);
+
+        // An empty filter leaves the list unchanged
+        ima_ml.set_filter(Some(
+            ImaFilter::new(&[], &[], &[HashAlgorithm::Sha1]).unwrap(), //#[allow_ci]
+        ));
+        let ml = ima_ml
+            .reader(&mut ima_file, 0, None)
+            .unwrap() //#[allow_ci]
+            .exclude_entries(true)
+            .collect::<Result<String, _>>()
+            .unwrap(); //#[allow_ci]
+        assert_eq!(ml, filedata);
+    }
+
+    #[test]
+    fn checkpoints_test() {
+        let mut ima_ml = MeasurementList::new();
+
+        let filedata = (0..3000)
+            .map(|i| format!("{i}-entry\n"))
+            .collect::<String>();
+        let mut tf = NamedTempFile::new().unwrap(); //#[allow_ci]
+        tf.write_all(filedata.as_bytes()).unwrap(); //#[allow_ci]
+        tf.flush().unwrap(); //#[allow_ci]
+
+        let mut ima_file = File::open(tf.path()).unwrap(); //#[allow_ci]
+
+        let (ml, _, num_entries) =
+            ima_ml.read_bounded(&mut ima_file, 0, Some(1)).unwrap(); //#[allow_ci]
+        assert_eq!(ml, "0-entry\n");
+        assert_eq!(num_entries, 3000);
+
+        // Checkpoints are kept every CHECKPOINT_INTERVAL entries and for the
+        // end of the list
+        let offset = |n: u64| {
+            filedata
+                .split_inclusive('\n')
+                .take(n as usize)
+                .map(|l| l.len() as u64)
+                .sum::<u64>()
+        };
+        assert_eq!(
+            ima_ml
+                .checkpoints
+                .iter()
+                .map(|(e, o)| (*e, *o))
+                .collect::<Vec<_>>(),
+            [1024, 2048, 3000].map(|n| (n, offset(n)))
+        );
+        assert_eq!(ima_ml.find(2047), (1024, offset(1024)));
+        assert_eq!(ima_ml.find(2048), (2048, offset(2048)));
+
+        // Reading from the checkpoints gives the same entries
+        let (ml, nth_entry, _) =
+            ima_ml.read_bounded(&mut ima_file, 2050, Some(2)).unwrap(); //#[allow_ci]
+        assert_eq!(ml, "2050-entry\n2051-entry\n");
+        assert_eq!(nth_entry, 2050);
+
+        // Only the last end of the list is kept as the list grows
+        let mut tf_file = tf.reopen().unwrap(); //#[allow_ci]
+        let _ = tf_file.seek(SeekFrom::End(0)).unwrap(); //#[allow_ci]
+        tf_file.write_all(b"3000-entry\n").unwrap(); //#[allow_ci]
+        let (ml, _, num_entries) = ima_ml.read(&mut ima_file, 3000).unwrap(); //#[allow_ci]
+        assert_eq!(ml, "3000-entry\n");
+        assert_eq!(num_entries, 3001);
+        assert_eq!(
+            ima_ml.checkpoints.keys().copied().collect::<Vec<_>>(),
+            [1024, 2048, 3001]
+        );
+
+        ima_ml.reset();
+        assert!(ima_ml.checkpoints.is_empty());
+    }
+
+    #[test]
+    fn measurement_list_reader_test() {
+        let mut ima_ml = MeasurementList::new();
+
+        // The last line is incomplete
+        let filedata = "0-entry\n1-entry\n2-entry\n3-en";
+        let mut tf = NamedTempFile::new().unwrap(); //#[allow_ci]
+        tf.write_all(filedata.as_bytes()).unwrap(); //#[allow_ci]
+        tf.flush().unwrap(); //#[allow_ci]
+
+        let mut ima_file = File::open(tf.path()).unwrap(); //#[allow_ci]
+
+        let mut reader = ima_ml.reader(&mut ima_file, 1, None).unwrap(); //#[allow_ci]
+        assert_eq!(reader.nth_entry(), 1);
+        assert_eq!(reader.next().unwrap().unwrap(), "1-entry\n"); //#[allow_ci]
+        assert_eq!(reader.next().unwrap().unwrap(), "2-entry\n"); //#[allow_ci]
+        assert!(reader.next().is_none());
+        assert_eq!(reader.finish().unwrap(), 3); //#[allow_ci]
+
+        // The reader can be finished before all the entries are read
+        let mut reader = ima_ml.reader(&mut ima_file, 0, Some(2)).unwrap(); //#[allow_ci]
+        assert_eq!(reader.next().unwrap().unwrap(), "0-entry\n"); //#[allow_ci]
+        assert_eq!(reader.finish().unwrap(), 3); //#[allow_ci]
+
+        // The entry beyond the next one reads the list from the beginning
+        let reader = ima_ml.reader(&mut ima_file, 5, None).unwrap(); //#[allow_ci]
+        assert_eq!(reader.nth_entry(), 0);
+        assert_eq!(
+            reader.collect::<Result<String, Error>>().unwrap(), //#[allow_ci]
+            "0-entry\n1-entry\n2-entry\n"
+        );
+    }
+
+    #[test]
+    fn measurement_list_cursor_test() {
+        let mut ima_ml = MeasurementList::new();
+
+        let filedata = "0-entry\n1-entry\n2-entry\n3-entry\n4-en";
+        let mut tf = NamedTempFile::new().unwrap(); //#[allow_ci]
+        tf.write_all(filedata.as_bytes()).unwrap(); //#[allow_ci]
+        tf.flush().unwrap(); //#[allow_ci]
+
+        let mut ima_file = File::open(tf.path()).unwrap(); //#[allow_ci]
+
+        let mut cursor =
+            ima_ml.cursor(&mut ima_file, 1, Some(10), false).unwrap(); //#[allow_ci]
+        assert_eq!(cursor.nth_entry(), 1);
+        assert_eq!(cursor.entry_count(), 3);
+        assert_eq!(cursor.num_entries(), 4);
+
+        // The entries are read in batches until the cursor is done
+        let mut batches = Vec::new();
+        loop {
+            let batch =
+                ima_ml.read_batch(&mut ima_file, &mut cursor, 2).unwrap(); //#[allow_ci]
+            if batch.is_empty() {
+                break;
+            }
+            batches.push(batch);
+        }
+        assert_eq!(
+            batches,
+            [vec!["1-entry\n", "2-entry\n"], vec!["3-entry\n"]]
+        );
+        assert!(cursor.is_done());
+
+        // Entries added after the cursor was created are not read
+        let mut tf_file = tf.reopen().unwrap(); //#[allow_ci]
+        let _ = tf_file.seek(SeekFrom::End(0)).unwrap(); //#[allow_ci]
+        tf_file.write_all(b"try\n").unwrap(); //#[allow_ci]
+        let mut cursor =
+            ima_ml.cursor(&mut ima_file, 3, None, false).unwrap(); //#[allow_ci]
+        assert_eq!(cursor.entry_count(), 2);
+        tf_file.write_all(b"5-entry\n").unwrap(); //#[allow_ci]
+        assert_eq!(
+            ima_ml.read_batch(&mut ima_file, &mut cursor, 10).unwrap(), //#[allow_ci]
+            ["3-entry\n", "4-entry\n"]
+        );
+        assert!(ima_ml
+            .read_batch(&mut ima_file, &mut cursor, 10)
+            .unwrap() //#[allow_ci]
+            .is_empty());
+
+        // The entry beyond the next one reads the list from the beginning
+        let cursor = ima_ml.cursor(&mut ima_file, 9, Some(1), false).unwrap(); //#[allow_ci]
+        assert_eq!(cursor.nth_entry(), 0);
+        assert_eq!(cursor.entry_count(), 1);
+        assert_eq!(cursor.num_entries(), 6);
+
+        // Reading fails if the list ends before the entries of the cursor
+        let mut cursor =
+            ima_ml.cursor(&mut ima_file, 0, None, false).unwrap(); //#[allow_ci]
+        tf_file.set_len(16).unwrap(); //#[allow_ci]
+        assert_eq!(
+            ima_ml.read_batch(&mut ima_file, &mut cursor, 4).unwrap(), //#[allow_ci]
+            ["0-entry\n", "1-entry\n"]
+        );
+        let e = ima_ml
+            .read_batch(&mut ima_file, &mut cursor, 4)
+            .unwrap_err(); //#[allow_ci]
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn measurement_list_entries_test() {
+        let mut ima_ml = MeasurementList::new();
+
+        let filedata = "0-entry\n1-\"entry\"\n2-entry\\\n";
+        let mut tf = NamedTempFile::new().unwrap(); //#[allow_ci]
+        tf.write_all(filedata.as_bytes()).unwrap(); //#[allow_ci]
+        tf.flush().unwrap(); //#[allow_ci]
+
+        let mut ima_file = File::open(tf.path()).unwrap(); //#[allow_ci]
+
+        // The entries are escaped as a single string
+        let mut cursor =
+            ima_ml.cursor(&mut ima_file, 0, None, false).unwrap(); //#[allow_ci]
+        let entries = MeasurementListEntries::new(|| {
+            ima_ml.read_batch(&mut ima_file, &mut cursor, 1)
+        });
+        assert_eq!(
+            serde_json::to_string(&entries).unwrap(), //#[allow_ci]
+            serde_json::to_string(filedata).unwrap()  //#[allow_ci]
+        );
+        assert!(entries.take_error().is_none());
+
+        // A read error ends the string early with the entries read so far,
+        // the error being returned by take_error()
+        let mut cursor =
+            ima_ml.cursor(&mut ima_file, 0, None, false).unwrap(); //#[allow_ci]
+        let mut batches = 0;
+        let entries = MeasurementListEntries::new(|| {
+            batches += 1;
+            if batches > 1 {
+                return Err(Error::other("read error"));
+            }
+            ima_ml.read_batch(&mut ima_file, &mut cursor, 1)
+        });
+        assert_eq!(
+            serde_json::to_string(&entries).unwrap(), //#[allow_ci]
+            "\"0-entry\\n\""
+        );
+        assert_eq!(
+            entries.take_error().map(|e| e.to_string()),
+            Some("read error".to_string())
+        );
+    }
 }
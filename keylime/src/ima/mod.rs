@@ -1,5 +1,9 @@
 mod entry;
+mod filter;
 mod measurement_list;
+mod replay;
 
 pub use entry::*;
+pub use filter::*;
 pub use measurement_list::*;
+pub use replay::*;
@@ -0,0 +1,618 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+
+// Replay of the IMA measurement list to calculate the expected PCR 10 values.
+
+use super::{BinaryEntries, Digest, Entry, ExcludedEntry};
+use crate::algorithms::HashAlgorithm;
+use openssl::hash::{hash, Hasher, MessageDigest};
+use std::convert::TryFrom;
+use std::io::{BufRead, Read};
+use thiserror::Error;
+
+/// The PCR extended by IMA
+pub const IMA_PCR: u32 = 10;
+
+#[derive(Error, Debug)]
+pub enum ImaReplayError {
+    /// An entry of the measurement list could not be parsed
+    #[error("Failed to parse measurement list entry {index}")]
+    Parse {
+        index: u64,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The template hash of an entry does not match its event data
+    #[error("Template hash of measurement list entry {index} ({path}) does not match: expected {expected}, calculated {calculated}")]
+    TemplateHashMismatch {
+        index: u64,
+        path: String,
+        expected: String,
+        calculated: String,
+    },
+
+    /// An entry excluded by the agent filter does not carry the template
+    /// hash for the bank
+    #[error("Measurement list entry {index} was excluded without a template hash for the {hash_alg} bank")]
+    MissingBankDigest { index: u64, hash_alg: HashAlgorithm },
+
+    /// The bank was not replayed
+    #[error("PCR {IMA_PCR} bank {0} was not replayed")]
+    UnknownBank(HashAlgorithm),
+
+    /// The value of PCR 10 does not match the measurement list
+    #[error("PCR {IMA_PCR} ({hash_alg}) does not match the measurement list after {entries} entries: replayed {replayed}, quoted {quoted}")]
+    PcrMismatch {
+        hash_alg: HashAlgorithm,
+        entries: u64,
+        replayed: String,
+        quoted: String,
+    },
+
+    /// Failed to calculate a digest
+    #[error("Failed to calculate digest")]
+    Hash(#[from] openssl::error::ErrorStack),
+
+    /// Failed to read the measurement list
+    #[error("Failed to read measurement list")]
+    Io(#[from] std::io::Error),
+}
+
+/// Replays the entries of the IMA measurement list, calculating the value of
+/// PCR 10 for each of the PCR banks
+///
+/// The template hash of each entry is validated against its event data
+/// before it is extended, so that the replay stops at the first entry which
+/// was tampered with.
+#[derive(Clone, Debug)]
+pub struct ImaReplay {
+    ima_hash_alg: HashAlgorithm,
+    pcrs: Vec<(HashAlgorithm, Vec<u8>)>,
+    entries: u64,
+    skipped: u64,
+}
+
+impl ImaReplay {
+    /// Creates a replay starting from the initial value of PCR 10
+    ///
+    /// # Arguments
+    ///
+    /// * ima_hash_alg (HashAlgorithm): The algorithm of the template hashes
+    ///   in the measurement list
+    /// * pcr_hash_algs (&[HashAlgorithm]): The PCR banks to replay
+    pub fn new(
+        ima_hash_alg: HashAlgorithm,
+        pcr_hash_algs: &[HashAlgorithm],
+    ) -> Self {
+        let mut pcrs: Vec<(HashAlgorithm, Vec<u8>)> = Vec::new();
+        for alg in pcr_hash_algs {
+            if !pcrs.iter().any(|(a, _)| a == alg) {
+                pcrs.push((*alg, Digest::start(*alg).value().to_vec()));
+            }
+        }
+        ImaReplay {
+            ima_hash_alg,
+            pcrs,
+            entries: 0,
+            skipped: 0,
+        }
+    }
+
+    /// The number of entries replayed, including the skipped ones
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// The number of entries skipped because they were measured into a PCR
+    /// other than PCR 10
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Skips the entry if it was not measured into PCR 10
+    fn skip(&mut self, pcr_index: u32) -> bool {
+        if pcr_index == IMA_PCR {
+            return false;
+        }
+        self.entries += 1;
+        self.skipped += 1;
+        true
+    }
+
+    /// The replayed value of PCR 10 for the given bank
+    pub fn pcr_value(&self, hash_alg: HashAlgorithm) -> Option<&[u8]> {
+        self.pcrs
+            .iter()
+            .find(|(alg, _)| *alg == hash_alg)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// The replayed values of PCR 10 for all the banks
+    pub fn pcr_values(&self) -> &[(HashAlgorithm, Vec<u8>)] {
+        &self.pcrs
+    }
+
+    /// Validates the template hash of the entry and extends it into every
+    /// bank
+    ///
+    /// # Returns
+    ///
+    /// The template hash extended into each bank, in the order the banks
+    /// were given. The entry is skipped, and nothing is returned, if it was
+    /// not measured into PCR 10
+    pub fn extend(
+        &mut self,
+        entry: &Entry,
+    ) -> Result<Vec<(HashAlgorithm, Digest)>, ImaReplayError> {
+        if self.skip(entry.pcr_index) {
+            return Ok(Vec::new());
+        }
+        let hashes = template_hashes(
+            entry,
+            self.entries,
+            self.ima_hash_alg,
+            self.pcrs.iter().map(|(alg, _)| *alg),
+        )?;
+
+        self.extend_hashes(&hashes)?;
+        Ok(hashes)
+    }
+
+    /// Extends an entry excluded by the agent filter into every bank, using
+    /// the template hashes carried by the entry. The entry is skipped if it
+    /// was not measured into PCR 10
+    pub fn extend_excluded(
+        &mut self,
+        entry: &ExcludedEntry,
+    ) -> Result<(), ImaReplayError> {
+        if self.skip(entry.pcr_index) {
+            return Ok(());
+        }
+        let hashes = self
+            .pcrs
+            .iter()
+            .map(|(alg, _)| {
+                entry
+                    .pcr_template_hashes
+                    .iter()
+                    .chain(std::iter::once(&entry.template_hash))
+                    .find(|digest| digest.algorithm == *alg)
+                    .map(|digest| (*alg, digest.clone()))
+                    .ok_or(ImaReplayError::MissingBankDigest {
+                        index: self.entries,
+                        hash_alg: *alg,
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.extend_hashes(&hashes)
+    }
+
+    fn extend_hashes(
+        &mut self,
+        hashes: &[(HashAlgorithm, Digest)],
+    ) -> Result<(), ImaReplayError> {
+        for ((alg, value), (_, template_hash)) in
+            self.pcrs.iter_mut().zip(hashes.iter())
+        {
+            let mut hasher = Hasher::new(MessageDigest::from(*alg))?;
+            hasher.update(value)?;
+            hasher.update(template_hash.value())?;
+            *value = hasher.finish()?.to_vec();
+        }
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Parses and extends a line of the ASCII measurement list. Empty lines
+    /// are ignored, and entries excluded by the agent filter are extended
+    /// using the template hashes they carry
+    ///
+    /// # Returns
+    ///
+    /// The parsed entry, if the line was neither empty nor excluded
+    pub fn extend_ascii(
+        &mut self,
+        line: &str,
+    ) -> Result<Option<Entry>, ImaReplayError> {
+        if line.is_empty() {
+            return Ok(None);
+        }
+        if let Some(excluded) = ExcludedEntry::parse(line) {
+            let excluded =
+                excluded.map_err(|source| ImaReplayError::Parse {
+                    index: self.entries,
+                    source,
+                })?;
+            self.extend_excluded(&excluded)?;
+            return Ok(None);
+        }
+        let entry = Entry::try_from(line).map_err(|source| {
+            ImaReplayError::Parse {
+                index: self.entries,
+                source,
+            }
+        })?;
+        let _ = self.extend(&entry)?;
+        Ok(Some(entry))
+    }
+
+    /// Replays all the entries of an ASCII measurement list
+    pub fn replay_ascii<R: BufRead>(
+        &mut self,
+        reader: R,
+    ) -> Result<(), ImaReplayError> {
+        for line in reader.lines() {
+            let _ = self.extend_ascii(&line?)?;
+        }
+        Ok(())
+    }
+
+    /// Replays all the entries of a binary measurement list
+    pub fn replay_binary<R: Read>(
+        &mut self,
+        reader: R,
+    ) -> Result<(), ImaReplayError> {
+        for entry in BinaryEntries::new(reader) {
+            let entry = entry.map_err(|source| ImaReplayError::Parse {
+                index: self.entries,
+                source,
+            })?;
+            let _ = self.extend(&entry)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the value of PCR 10, e.g. obtained from a quote, against the
+    /// replayed value for the bank
+    pub fn check_pcr(
+        &self,
+        hash_alg: HashAlgorithm,
+        quoted: &[u8],
+    ) -> Result<(), ImaReplayError> {
+        let replayed = self
+            .pcr_value(hash_alg)
+            .ok_or(ImaReplayError::UnknownBank(hash_alg))?;
+        if replayed != quoted {
+            return Err(ImaReplayError::PcrMismatch {
+                hash_alg,
+                entries: self.entries,
+                replayed: hex::encode(replayed),
+                quoted: hex::encode(quoted),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Calculates the template hash of the entry extended into each bank
+///
+/// The template hash in the entry is validated against the event data using
+/// ima_hash_alg. Entries recording a violation, which have a template hash of
+/// zeros, extend a digest of 0xff bytes instead.
+fn template_hashes(
+    entry: &Entry,
+    index: u64,
+    ima_hash_alg: HashAlgorithm,
+    pcr_hash_algs: impl IntoIterator<Item = HashAlgorithm>,
+) -> Result<Vec<(HashAlgorithm, Digest)>, ImaReplayError> {
+    // Set correct hash for time of measure, time of use (ToMToU) errors
+    // and if a file is already opened for write.
+    // https://elixir.bootlin.com/linux/v5.12.12/source/security/integrity/ima/ima_main.c#L101
+    if entry.template_hash.value().iter().all(|b| *b == 0) {
+        return Ok(pcr_hash_algs
+            .into_iter()
+            .map(|alg| (alg, Digest::ff(alg)))
+            .collect());
+    }
+
+    let event_data = entry.template_data();
+    let ima_event_hash = hash(ima_hash_alg.into(), event_data)?;
+    if ima_event_hash.as_ref() != entry.template_hash.value() {
+        return Err(ImaReplayError::TemplateHashMismatch {
+            index,
+            path: entry.event_data.path().to_string(),
+            expected: hex::encode(entry.template_hash.value()),
+            calculated: hex::encode(ima_event_hash),
+        });
+    }
+
+    pcr_hash_algs
+        .into_iter()
+        .map(|alg| {
+            let value = hash(alg.into(), event_data)?;
+            Ok((alg, Digest::new(alg, &value)?))
+        })
+        .collect()
+}
+
+/// Replays an ASCII measurement list, calculating the expected value of
+/// PCR 10 for each of the given banks
+///
+/// # Arguments
+///
+/// * reader (BufRead): The ASCII measurement list
+/// * ima_hash_alg (HashAlgorithm): The algorithm of the template hashes
+/// * pcr_hash_algs (&[HashAlgorithm]): The PCR banks to replay
+pub fn replay_ascii_measurement_list<R: BufRead>(
+    reader: R,
+    ima_hash_alg: HashAlgorithm,
+    pcr_hash_algs: &[HashAlgorithm],
+) -> Result<ImaReplay, ImaReplayError> {
+    let mut replay = ImaReplay::new(ima_hash_alg, pcr_hash_algs);
+    replay.replay_ascii(reader)?;
+    Ok(replay)
+}
+
+/// Replays a binary measurement list, calculating the expected value of
+/// PCR 10 for each of the given banks
+///
+/// # Arguments
+///
+/// * reader (Read): The binary measurement list
+/// * ima_hash_alg (HashAlgorithm): The algorithm of the template hashes
+/// * pcr_hash_algs (&[HashAlgorithm]): The PCR banks to replay
+pub fn replay_binary_measurement_list<R: Read>(
+    reader: R,
+    ima_hash_alg: HashAlgorithm,
+    pcr_hash_algs: &[HashAlgorithm],
+) -> Result<ImaReplay, ImaReplayError> {
+    let mut replay = ImaReplay::new(ima_hash_alg, pcr_hash_algs);
+    replay.replay_binary(reader)?;
+    Ok(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ima::ImaFilter;
+    use std::{fs::File, io::BufReader, path::Path};
+
+    fn sample_list() -> BufReader<File> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data/ima/ascii_runtime_measurements");
+        BufReader::new(File::open(path).unwrap()) //#[allow_ci]
+    }
+
+    #[test]
+    fn replay_test() {
+        let replay = replay_ascii_measurement_list(
+            sample_list(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha1, HashAlgorithm::Sha256],
+        )
+        .unwrap(); //#[allow_ci]
+        assert_eq!(replay.entries(), 826);
+        assert_eq!(
+            replay.pcr_value(HashAlgorithm::Sha1),
+            Some(
+                hex::decode("82231c67a69da98dc5b3aa10f6343d33109225fc")
+                    .unwrap() //#[allow_ci]
+                    .as_slice()
+            )
+        );
+        let sha256 = hex::decode(
+            "c4a065637fc6a7c55f2811dd06cb45dd037133be2b3dc5c3e6fbe6bf061db724",
+        )
+        .unwrap(); //#[allow_ci]
+        assert!(replay.check_pcr(HashAlgorithm::Sha256, &sha256).is_ok());
+
+        let result = replay.check_pcr(HashAlgorithm::Sha1, &sha256[..20]);
+        assert!(matches!(
+            result,
+            Err(ImaReplayError::PcrMismatch { entries: 826, .. })
+        ));
+        let result = replay.check_pcr(HashAlgorithm::Sha384, &[0u8; 48]);
+        assert!(matches!(
+            result,
+            Err(ImaReplayError::UnknownBank(HashAlgorithm::Sha384))
+        ));
+    }
+
+    #[test]
+    fn replay_binary_test() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data/ima/binary_runtime_measurements");
+        let binary = replay_binary_measurement_list(
+            BufReader::new(File::open(path).unwrap()), //#[allow_ci]
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha1, HashAlgorithm::Sha256],
+        )
+        .unwrap(); //#[allow_ci]
+        let ascii = replay_ascii_measurement_list(
+            sample_list(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha1, HashAlgorithm::Sha256],
+        )
+        .unwrap(); //#[allow_ci]
+        assert_eq!(binary.entries(), 826);
+        assert_eq!(binary.pcr_values(), ascii.pcr_values());
+    }
+
+    #[test]
+    fn replay_incremental_test() {
+        let mut lines = sample_list().lines();
+        let mut replay =
+            ImaReplay::new(HashAlgorithm::Sha1, &[HashAlgorithm::Sha1]);
+        let first = lines.next().unwrap().unwrap(); //#[allow_ci]
+        let entry = replay.extend_ascii(&first).unwrap().unwrap(); //#[allow_ci]
+        assert_eq!(entry.event_data.path(), "boot_aggregate");
+        assert!(replay.extend_ascii("").unwrap().is_none()); //#[allow_ci]
+        assert_eq!(replay.entries(), 1);
+
+        // Continuing the replay gives the same result as a full replay
+        for line in lines {
+            let _ = replay.extend_ascii(&line.unwrap()).unwrap(); //#[allow_ci]
+        }
+        let full = replay_ascii_measurement_list(
+            sample_list(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha1],
+        )
+        .unwrap(); //#[allow_ci]
+        assert_eq!(replay.pcr_values(), full.pcr_values());
+    }
+
+    #[test]
+    fn replay_filtered_test() {
+        let filter = ImaFilter::new(
+            &["/usr/lib/**", "/etc/*"],
+            &[],
+            &[HashAlgorithm::Sha256],
+        )
+        .unwrap(); //#[allow_ci]
+        let filtered = sample_list()
+            .lines()
+            .map(|line| filter.apply(&line.unwrap()).into_owned()) //#[allow_ci]
+            .collect::<Vec<String>>()
+            .join("\n");
+        assert!(filtered.contains(" excluded sha256:"));
+
+        // The filtered list replays to the same values for the SHA-1 bank
+        // and the bank the filter was created for
+        let replay = replay_ascii_measurement_list(
+            filtered.as_bytes(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha1, HashAlgorithm::Sha256],
+        )
+        .unwrap(); //#[allow_ci]
+        let full = replay_ascii_measurement_list(
+            sample_list(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha1, HashAlgorithm::Sha256],
+        )
+        .unwrap(); //#[allow_ci]
+        assert_eq!(replay.entries(), 826);
+        assert_eq!(replay.pcr_values(), full.pcr_values());
+
+        // Other banks cannot be replayed
+        let result = replay_ascii_measurement_list(
+            filtered.as_bytes(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha384],
+        );
+        assert!(matches!(
+            result,
+            Err(ImaReplayError::MissingBankDigest {
+                hash_alg: HashAlgorithm::Sha384,
+                ..
+            })
+        ));
+
+        // Unless the filter is created for them as well
+        let filter = ImaFilter::new(
+            &["/usr/lib/**", "/etc/*"],
+            &[],
+            &[HashAlgorithm::Sha256, HashAlgorithm::Sha384],
+        )
+        .unwrap(); //#[allow_ci]
+        let filtered = sample_list()
+            .lines()
+            .map(|line| filter.apply(&line.unwrap()).into_owned()) //#[allow_ci]
+            .collect::<Vec<String>>()
+            .join("\n");
+        let banks = [
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+        ];
+        let replay = replay_ascii_measurement_list(
+            filtered.as_bytes(),
+            HashAlgorithm::Sha1,
+            &banks,
+        )
+        .unwrap(); //#[allow_ci]
+        let full = replay_ascii_measurement_list(
+            sample_list(),
+            HashAlgorithm::Sha1,
+            &banks,
+        )
+        .unwrap(); //#[allow_ci]
+        assert_eq!(replay.pcr_values(), full.pcr_values());
+    }
+
+    #[test]
+    fn replay_other_pcr_test() {
+        let lines = sample_list()
+            .lines()
+            .map(|line| line.unwrap()) //#[allow_ci]
+            .collect::<Vec<String>>();
+        let mut other_pcr = lines.clone();
+        other_pcr[1] = other_pcr[1].replacen("10 ", "11 ", 1);
+
+        // The entry measured into PCR 11 is not extended into PCR 10
+        let replay = replay_ascii_measurement_list(
+            other_pcr.join("\n").as_bytes(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha1],
+        )
+        .unwrap(); //#[allow_ci]
+        assert_eq!(replay.entries(), 826);
+        assert_eq!(replay.skipped(), 1);
+
+        let mut without = lines;
+        let _ = without.remove(1);
+        let expected = replay_ascii_measurement_list(
+            without.join("\n").as_bytes(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha1],
+        )
+        .unwrap(); //#[allow_ci]
+        assert_eq!(expected.skipped(), 0);
+        assert_eq!(replay.pcr_values(), expected.pcr_values());
+    }
+
+    #[test]
+    fn template_hash_mismatch_test() {
+        let tampered = sample_list()
+            .lines()
+            .map(|line| line.unwrap()) //#[allow_ci]
+            .map(|line| {
+                line.replace(
+                    "sha1:375198810bb39e6593a968fcbcf6556789026743",
+                    "sha1:0000000000000000000000000000000000000000",
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        let result = replay_ascii_measurement_list(
+            tampered.as_bytes(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha256],
+        );
+        let Err(ImaReplayError::TemplateHashMismatch { index, path, .. }) =
+            result
+        else {
+            panic!("Expected a template hash mismatch"); //#[allow_ci]
+        };
+        assert_eq!(index, 5);
+        assert_eq!(path, "/lib/x86_64-linux-gnu/libc-2.23.so");
+
+        let result = replay_ascii_measurement_list(
+            "10 1d8d532d463c9f8c205d0df7787669a85f93e260 ima-foo".as_bytes(),
+            HashAlgorithm::Sha1,
+            &[HashAlgorithm::Sha1],
+        );
+        assert!(matches!(
+            result,
+            Err(ImaReplayError::Parse { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn violation_test() {
+        // Violations are extended as 0xff regardless of the event data
+        let entry = Entry::try_from("10 0000000000000000000000000000000000000000 ima-ng sha1:0000000000000000000000000000000000000000 /etc/passwd").unwrap(); //#[allow_ci]
+        let hashes = template_hashes(
+            &entry,
+            0,
+            HashAlgorithm::Sha1,
+            [HashAlgorithm::Sha256],
+        )
+        .unwrap(); //#[allow_ci]
+        assert_eq!(
+            hashes,
+            vec![(HashAlgorithm::Sha256, Digest::ff(HashAlgorithm::Sha256))]
+        );
+    }
+}
@@ -1,14 +1,19 @@
+pub mod agent_data;
+pub mod agent_registration;
 pub mod algorithms;
+pub mod config;
 pub mod crypto;
 pub mod device_id;
 pub mod hostname_parser;
 pub mod ima;
 pub mod ip_parser;
 pub mod list_parser;
+pub mod permissions;
 pub mod registrar_client;
 pub mod serialization;
 pub mod structures;
 pub mod tpm;
+pub mod uefi;
 pub mod version;
 
 #[macro_use]
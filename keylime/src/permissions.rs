@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2021 Keylime Authors
+
+use libc::{gid_t, uid_t};
+
+pub fn get_gid() -> gid_t {
+    unsafe { libc::getgid() }
+}
+
+pub fn get_uid() -> uid_t {
+    unsafe { libc::getuid() }
+}
+
+pub fn get_euid() -> uid_t {
+    unsafe { libc::geteuid() }
+}
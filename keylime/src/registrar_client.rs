@@ -23,14 +23,6 @@ pub enum RegistrarClientBuilderError {
     #[error("Agent public AK not set")]
     AKPubNotSet,
 
-    /// Agent contact IP or hostname not set
-    #[error("Agent contact IP or hostname not set")]
-    AgentContactIPNotSet,
-
-    /// Agent port not set
-    #[error("Agent port not set")]
-    AgentPortNotSet,
-
     /// Agent UUID not set
     #[error("Agent UUID not set")]
     AgentUUIDNotSet,
@@ -205,7 +197,8 @@ impl<'a> RegistrarClientBuilder<'a> {
 
     /// Set the Agent contact IP to include in the registration request
     ///
-    /// This is the Agent IP or hostname to be contacted when making requests
+    /// This is the Agent IP or hostname to be contacted when making requests.
+    /// It is not sent if not set, like for agents which do not serve requests
     ///
     /// # Arguments:
     ///
@@ -373,14 +366,6 @@ impl<'a> RegistrarClientBuilder<'a> {
             return Err(RegistrarClientBuilderError::AKPubNotSet);
         };
 
-        let Some(ip) = self.ip.take() else {
-            return Err(RegistrarClientBuilderError::AgentContactIPNotSet);
-        };
-
-        let Some(port) = self.port else {
-            return Err(RegistrarClientBuilderError::AgentPortNotSet);
-        };
-
         let Some(uuid) = self.uuid.take() else {
             return Err(RegistrarClientBuilderError::AgentUUIDNotSet);
         };
@@ -452,9 +437,9 @@ impl<'a> RegistrarClientBuilder<'a> {
             iak_pub: self.iak_pub,
             idevid_cert,
             idevid_pub: self.idevid_pub,
-            ip,
+            ip: self.ip.take(),
             mtls_cert,
-            port,
+            port: self.port,
             registrar_ip,
             registrar_port,
             uuid,
@@ -498,9 +483,9 @@ pub struct RegistrarClient<'a> {
     iak_sign: Option<Vec<u8>>,
     idevid_cert: Option<Vec<u8>>,
     idevid_pub: Option<&'a [u8]>,
-    ip: String,
+    ip: Option<String>,
     mtls_cert: Option<String>,
-    port: u32,
+    port: Option<u32>,
     registrar_ip: String,
     registrar_port: u32,
     uuid: &'a str,
@@ -585,9 +570,9 @@ impl RegistrarClient<'_> {
             iak_tpm: self.iak_pub,
             idevid_cert: self.idevid_cert.clone(),
             idevid_tpm: self.idevid_pub,
-            ip: Some(self.ip.clone()),
+            ip: self.ip.clone(),
             mtls_cert: self.mtls_cert.clone(),
-            port: Some(self.port),
+            port: self.port,
         };
 
         let addr = format!(
@@ -817,6 +802,55 @@ mod tests {
         assert!(response.is_ok());
     }
 
+    #[actix_rt::test]
+    async fn test_register_agent_without_contact() {
+        let response: Response<RegisterResponseResults> = Response {
+            code: 200.into(),
+            status: "OK".to_string(),
+            results: RegisterResponseResults { blob: None },
+        };
+
+        // The registration request must not carry the contact IP and port
+        let mock_server = MockServer::start().await;
+        let mock = Mock::given(method("POST"))
+            .and(path("/v1.2/agents/uuid"))
+            .and(|req: &wiremock::Request| {
+                match serde_json::from_slice::<serde_json::Value>(&req.body) {
+                    Ok(body) => {
+                        body.get("ip").is_none() && body.get("port").is_none()
+                    }
+                    Err(_) => false,
+                }
+            })
+            .respond_with(ResponseTemplate::new(200).set_body_json(response))
+            .expect(1);
+        mock_server.register(mock).await;
+
+        let uri = mock_server.uri();
+        let uri = uri.split("//").collect::<Vec<&str>>()[1]
+            .split(':')
+            .collect::<Vec<&str>>();
+        assert_eq!(uri.len(), 2);
+
+        let ip = uri[0];
+        let port = uri[1].parse().unwrap(); //#[allow_ci]
+
+        let mock_data = [0u8; 1];
+        let response = RegistrarClientBuilder::new()
+            .ak_pub(&mock_data)
+            .ek_pub(&mock_data)
+            .enabled_api_versions(vec!["1.2"])
+            .registrar_ip(ip.to_string())
+            .registrar_port(port)
+            .uuid("uuid")
+            .build()
+            .await;
+        assert!(response.is_ok(), "error: {:?}", response);
+        let mut registrar_client = response.unwrap(); //#[allow_ci]
+        let response = registrar_client.register_agent().await;
+        assert!(response.is_ok(), "error: {:?}", response);
+    }
+
     #[actix_rt::test]
     async fn test_register_agent_with_old_registrar() {
         // Setup mock server with only the registration endpoint
@@ -1368,8 +1402,6 @@ mod tests {
             "ak_pub",
             "ek_pub",
             "enabled_api_versions",
-            "ip",
-            "port",
             "registrar_ip",
             "registrar_port",
             "uuid",
@@ -1393,14 +1425,6 @@ mod tests {
                 builder = builder.enabled_api_versions(vec!["1.2"]);
             }
 
-            if to_add.contains(&"ip") {
-                builder = builder.ip("1.2.3.4".to_string());
-            }
-
-            if to_add.contains(&"port") {
-                builder = builder.port(0);
-            }
-
             if to_add.contains(&"registrar_ip") {
                 builder = builder.registrar_ip("1.2.3.5".to_string());
             }
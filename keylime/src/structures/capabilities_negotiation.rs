@@ -51,7 +51,7 @@ pub struct ImaEntries {
     pub evidence_class: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Capabilities {
     pub component_version: String,
     pub hash_algorithms: Vec<String>,
@@ -60,11 +60,13 @@ pub struct Capabilities {
     pub certification_keys: Vec<CertificationKey>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 // Do not serialize the struct name, only the fields
 pub struct ShaValues {
     pub sha1: Vec<u8>,
     pub sha256: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sha384: Vec<u8>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -88,6 +90,8 @@ pub struct AttestationResponse {
 pub struct ResponseData {
     #[serde(rename(serialize = "type", deserialize = "type"))]
     pub type_: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub attributes: ResponseAttributes,
 }
 
@@ -192,6 +196,7 @@ mod tests {
                                 available_subjects: ShaValues {
                                     sha1: vec![0x01, 0x02, 0x03],
                                     sha256: vec![0x04, 0x05, 0x06],
+                                    ..Default::default()
                                 },
                                 certification_keys: vec![
                                     CertificationKey {
@@ -352,6 +357,7 @@ mod tests {
                                 available_subjects: ShaValues {
                                     sha1: vec![0x01, 0x02, 0x03],
                                     sha256: vec![0x04, 0x05, 0x06],
+                                    ..Default::default()
                                 },
                                 certification_keys: vec![
                                     CertificationKey {
@@ -533,6 +539,7 @@ mod tests {
                     capabilities.available_subjects.sha256
                         == vec![0x04, 0x05, 0x06]
                 );
+                assert!(capabilities.available_subjects.sha384.is_empty());
                 let some_certification_keys =
                     capabilities.certification_keys.first();
                 assert!(some_certification_keys.is_some());
@@ -651,6 +658,7 @@ mod tests {
         let response = AttestationResponse {
             data: ResponseData {
                 type_: "attestation".to_string(),
+                id: None,
                 attributes: ResponseAttributes {
                     stage : "awaiting_evidence".to_string(),
                     evidence_requested: vec![
@@ -662,6 +670,7 @@ mod tests {
                                 selected_subjects: Some(ShaValues {
                                     sha1: vec![0x01, 0x02, 0x03],
                                     sha256: vec![0x04, 0x05, 0x06],
+                                    ..Default::default()
                                 }),
                                 hash_algorithm: Some("sha384".to_string()),
                                 signature_scheme: Some("rsassa".to_string()),
@@ -726,6 +735,7 @@ mod tests {
         let response = AttestationResponse {
             data: ResponseData {
                 type_: "attestation".to_string(),
+                id: None,
                 attributes: ResponseAttributes {
                     stage : "awaiting_evidence".to_string(),
                     evidence_requested: vec![
@@ -737,6 +747,7 @@ mod tests {
                                 selected_subjects: Some(ShaValues {
                                     sha1: vec![0x01, 0x02, 0x03],
                                     sha256: vec![0x04, 0x05, 0x06],
+                                    ..Default::default()
                                 }),
                                 hash_algorithm: Some("sha384".to_string()),
                                 signature_scheme: Some("rsassa".to_string()),
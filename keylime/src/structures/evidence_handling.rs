@@ -0,0 +1,432 @@
+use serde::{Deserialize, Serialize};
+
+// Define the structure for the EvidenceHandlingRequest:
+// The request is generic over the type of the IMA log entries, so that they
+// can be serialized while being read from the log, see
+// ima::MeasurementListEntries
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EvidenceHandlingRequest<E = String> {
+    #[serde(rename(serialize = "data", deserialize = "data"))]
+    pub data: EvidenceHandlingRequestData<E>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EvidenceHandlingRequestData<E = String> {
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    pub type_: String,
+    pub attributes: EvidenceHandlingRequestAttributes<E>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EvidenceHandlingRequestAttributes<E = String> {
+    pub evidence_collected: Vec<EvidenceCollected<E>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EvidenceCollected<E = String> {
+    pub evidence_class: String,
+    #[serde(flatten)]
+    pub data: EvidenceData<E>,
+}
+
+// The evidence data is serialized in the data field, with the variant given
+// by the sibling evidence_type field
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "evidence_type", content = "data", rename_all = "snake_case")]
+pub enum EvidenceData<E = String> {
+    TpmQuote {
+        subject_data: String,
+        message: String,
+        signature: String,
+    },
+    ImaLog {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        starting_offset: Option<u64>,
+        entry_count: u64,
+        entries: E,
+    },
+    UefiLog {
+        entries: String,
+    },
+}
+
+// Define the structure for the EvidenceHandlingResponse:
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EvidenceHandlingResponse {
+    #[serde(rename(serialize = "data", deserialize = "data"))]
+    pub data: EvidenceHandlingResponseData,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EvidenceHandlingResponseData {
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    pub type_: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub attributes: EvidenceHandlingResponseAttributes,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EvidenceHandlingResponseAttributes {
+    pub stage: String,
+    pub evaluation: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities_received_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenges_expire_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evidence_received_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification_completed_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seconds_to_next_attestation: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn serialize_request() {
+        // Create a new EvidenceHandlingRequest object and serialize it to JSON
+        let request = EvidenceHandlingRequest {
+            data: EvidenceHandlingRequestData {
+                type_: "attestation".to_string(),
+                attributes: EvidenceHandlingRequestAttributes {
+                    evidence_collected: vec![
+                        EvidenceCollected {
+                            evidence_class: "certification".to_string(),
+                            data: EvidenceData::TpmQuote {
+                                subject_data: "subject_data".to_string(),
+                                message: "message".to_string(),
+                                signature: "signature".to_string(),
+                            },
+                        },
+                        EvidenceCollected {
+                            evidence_class: "log".to_string(),
+                            data: EvidenceData::UefiLog {
+                                entries: "uefi_log_entries".to_string(),
+                            },
+                        },
+                        EvidenceCollected {
+                            evidence_class: "log".to_string(),
+                            data: EvidenceData::ImaLog {
+                                starting_offset: Some(25),
+                                entry_count: 2,
+                                entries: "ima_log_entries".to_string(),
+                            },
+                        },
+                    ],
+                },
+            },
+        };
+        let json = serde_json::to_string_pretty(&request).unwrap(); //#[allow_ci]
+        assert_eq!(
+            json,
+            r#"{
+  "data": {
+    "type": "attestation",
+    "attributes": {
+      "evidence_collected": [
+        {
+          "evidence_class": "certification",
+          "evidence_type": "tpm_quote",
+          "data": {
+            "subject_data": "subject_data",
+            "message": "message",
+            "signature": "signature"
+          }
+        },
+        {
+          "evidence_class": "log",
+          "evidence_type": "uefi_log",
+          "data": {
+            "entries": "uefi_log_entries"
+          }
+        },
+        {
+          "evidence_class": "log",
+          "evidence_type": "ima_log",
+          "data": {
+            "starting_offset": 25,
+            "entry_count": 2,
+            "entries": "ima_log_entries"
+          }
+        }
+      ]
+    }
+  }
+}"#
+        );
+    }
+
+    #[test]
+    fn deserialize_request() {
+        // Create a JSON string and deserialize it to an EvidenceHandlingRequest object
+        let json = r#"
+        {
+            "data": {
+                "type":"attestation",
+                "attributes": {
+                    "evidence_collected":[{"evidence_class":"certification",
+                                           "evidence_type":"tpm_quote",
+                                           "data":{"subject_data":"subject_data",
+                                                   "message":"message",
+                                                   "signature":"signature"}},
+                                          {"evidence_class":"log",
+                                           "evidence_type":"uefi_log",
+                                           "data":{"entries":"uefi_log_entries"}},
+                                          {"evidence_class":"log",
+                                           "evidence_type":"ima_log",
+                                           "data":{"entry_count":2,
+                                                   "entries":"ima_log_entries"}}]
+                }
+            }
+        }"#;
+        let request: EvidenceHandlingRequest =
+            serde_json::from_str(json).unwrap(); //#[allow_ci]
+        assert_eq!(request.data.type_, "attestation");
+        let evidence_collected = &request.data.attributes.evidence_collected;
+        assert_eq!(evidence_collected.len(), 3);
+        assert_eq!(evidence_collected[0].evidence_class, "certification");
+        assert_eq!(
+            evidence_collected[0].data,
+            EvidenceData::TpmQuote {
+                subject_data: "subject_data".to_string(),
+                message: "message".to_string(),
+                signature: "signature".to_string(),
+            }
+        );
+        assert_eq!(
+            evidence_collected[1].data,
+            EvidenceData::UefiLog {
+                entries: "uefi_log_entries".to_string(),
+            }
+        );
+        assert_eq!(
+            evidence_collected[2].data,
+            EvidenceData::ImaLog {
+                starting_offset: None,
+                entry_count: 2,
+                entries: "ima_log_entries".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_error_request() {
+        // Create a JSON string and deserialize it to an EvidenceHandlingRequest object
+        let json = r#"
+        {
+            "data": {
+                "type":"attestation",
+                "attributes": {
+                    "evidence_collected":[{"evidence_class":"certification",
+                                           "evidence_type":"tpm_quote",
+                                           "data":{"unexpected_data":"data"}}]
+                }
+            }
+        }"#;
+        // Deserialize the JSON string to an EvidenceHandlingRequest object and check there is an error
+        match serde_json::from_str::<EvidenceHandlingRequest>(json) {
+            Ok(_) => panic!("Expected an error"), //#[allow_ci]
+            Err(e) => {
+                assert_ne!(e.to_string().len(), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn deserialize_mismatched_request() {
+        // The data of an IMA log without the entry count is not taken for a
+        // UEFI log
+        let json = r#"
+        {
+            "data": {
+                "type":"attestation",
+                "attributes": {
+                    "evidence_collected":[{"evidence_class":"log",
+                                           "evidence_type":"ima_log",
+                                           "data":{"entries":"ima_log_entries"}}]
+                }
+            }
+        }"#;
+        assert!(
+            serde_json::from_str::<EvidenceHandlingRequest>(json).is_err()
+        );
+
+        // The data of a UEFI log with extra fields is not taken for an IMA
+        // log
+        let json = r#"
+        {
+            "evidence_class":"log",
+            "evidence_type":"uefi_log",
+            "data":{"entry_count":2, "entries":"uefi_log_entries"}
+        }"#;
+        let evidence: EvidenceCollected = serde_json::from_str(json).unwrap(); //#[allow_ci]
+        assert_eq!(
+            evidence.data,
+            EvidenceData::UefiLog {
+                entries: "uefi_log_entries".to_string(),
+            }
+        );
+
+        // Unknown evidence types are rejected
+        let json = r#"
+        {
+            "evidence_class":"log",
+            "evidence_type":"unknown_log",
+            "data":{"entries":"entries"}
+        }"#;
+        assert!(serde_json::from_str::<EvidenceCollected>(json).is_err());
+    }
+
+    #[test]
+    fn round_trip_request() {
+        // Every variant is deserialized from its serialization
+        let evidence_collected = vec![
+            EvidenceCollected {
+                evidence_class: "certification".to_string(),
+                data: EvidenceData::TpmQuote {
+                    subject_data: "subject_data".to_string(),
+                    message: "message".to_string(),
+                    signature: "signature".to_string(),
+                },
+            },
+            EvidenceCollected {
+                evidence_class: "log".to_string(),
+                data: EvidenceData::UefiLog {
+                    entries: "uefi_log_entries".to_string(),
+                },
+            },
+            EvidenceCollected {
+                evidence_class: "log".to_string(),
+                data: EvidenceData::ImaLog {
+                    starting_offset: Some(25),
+                    entry_count: 2,
+                    entries: "ima_log_entries".to_string(),
+                },
+            },
+            EvidenceCollected {
+                evidence_class: "log".to_string(),
+                data: EvidenceData::ImaLog {
+                    starting_offset: None,
+                    entry_count: 0,
+                    entries: "".to_string(),
+                },
+            },
+        ];
+        for evidence in evidence_collected {
+            let json = serde_json::to_string(&evidence).unwrap(); //#[allow_ci]
+            let deserialized: EvidenceCollected =
+                serde_json::from_str(&json).unwrap(); //#[allow_ci]
+            assert_eq!(deserialized.evidence_class, evidence.evidence_class);
+            assert_eq!(deserialized.data, evidence.data);
+        }
+    }
+
+    #[test]
+    fn serialize_response() {
+        // Create a new EvidenceHandlingResponse object and serialize it to JSON
+        let response = EvidenceHandlingResponse {
+            data: EvidenceHandlingResponseData {
+                type_: "attestation".to_string(),
+                id: Some("1".to_string()),
+                attributes: EvidenceHandlingResponseAttributes {
+                    stage: "evaluating_evidence".to_string(),
+                    evaluation: "pending".to_string(),
+                    failure_reason: None,
+                    capabilities_received_at: Some(
+                        "2025-02-26T12:32:41Z".to_string(),
+                    ),
+                    challenges_expire_at: Some(
+                        "2025-02-26T12:37:41Z".to_string(),
+                    ),
+                    evidence_received_at: Some(
+                        "2025-02-26T12:32:45Z".to_string(),
+                    ),
+                    verification_completed_at: None,
+                    seconds_to_next_attestation: None,
+                },
+            },
+        };
+        let json = serde_json::to_string_pretty(&response).unwrap(); //#[allow_ci]
+        assert_eq!(
+            json,
+            r#"{
+  "data": {
+    "type": "attestation",
+    "id": "1",
+    "attributes": {
+      "stage": "evaluating_evidence",
+      "evaluation": "pending",
+      "capabilities_received_at": "2025-02-26T12:32:41Z",
+      "challenges_expire_at": "2025-02-26T12:37:41Z",
+      "evidence_received_at": "2025-02-26T12:32:45Z"
+    }
+  }
+}"#
+        );
+    }
+
+    #[test]
+    fn deserialize_response() {
+        // Create a JSON string and deserialize it to an EvidenceHandlingResponse object
+        let json = r#"
+        {
+            "data": {
+                "type":"attestation",
+                "attributes": {
+                    "stage":"verification_complete",
+                    "evaluation":"fail",
+                    "failure_reason":"PCR 10 mismatch",
+                    "evidence_received_at":"2025-02-26T12:32:45Z",
+                    "verification_completed_at":"2025-02-26T12:32:46Z",
+                    "seconds_to_next_attestation":120
+                }
+            }
+        }"#;
+        let response: EvidenceHandlingResponse =
+            serde_json::from_str(json).unwrap(); //#[allow_ci]
+        assert_eq!(response.data.type_, "attestation");
+        let attributes = &response.data.attributes;
+        assert_eq!(attributes.stage, "verification_complete");
+        assert_eq!(attributes.evaluation, "fail");
+        assert_eq!(
+            attributes.failure_reason,
+            Some("PCR 10 mismatch".to_string())
+        );
+        assert!(attributes.capabilities_received_at.is_none());
+        assert!(attributes.challenges_expire_at.is_none());
+        assert_eq!(
+            attributes.verification_completed_at,
+            Some("2025-02-26T12:32:46Z".to_string())
+        );
+        assert_eq!(attributes.seconds_to_next_attestation, Some(120));
+        assert!(response.data.id.is_none());
+    }
+
+    #[test]
+    fn deserialize_error_response() {
+        // Create a JSON string and deserialize it to an EvidenceHandlingResponse object
+        let json = r#"
+        {
+            "data": {
+                "type":"attestation",
+                "attributes": {
+                    "stage":"verification_complete"
+                }
+            }
+        }"#;
+        // The evaluation is mandatory, check there is an error
+        match serde_json::from_str::<EvidenceHandlingResponse>(json) {
+            Ok(_) => panic!("Expected an error"), //#[allow_ci]
+            Err(e) => {
+                assert_ne!(e.to_string().len(), 0);
+            }
+        }
+    }
+}
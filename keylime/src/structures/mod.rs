@@ -1,3 +1,5 @@
 mod capabilities_negotiation;
+mod evidence_handling;
 
 pub use capabilities_negotiation::*;
+pub use evidence_handling::*;
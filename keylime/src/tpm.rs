@@ -10,6 +10,7 @@ use crate::{
 use base64::{engine::general_purpose, Engine as _};
 use log::*;
 use std::{
+    collections::BTreeMap,
     convert::{TryFrom, TryInto},
     io::Read,
     str::FromStr,
@@ -18,9 +19,14 @@ use std::{
 use thiserror::Error;
 
 use openssl::{
+    bn::{BigNum, BigNumContext},
+    ec::EcPoint,
+    ecdsa::EcdsaSig,
     hash::{Hasher, MessageDigest},
     memcmp,
-    pkey::{HasPublic, Id, PKeyRef, Public},
+    pkey::{HasPublic, Id, PKey, PKeyRef, Public},
+    rsa::Padding,
+    sign::{RsaPssSaltlen, Verifier},
     x509::X509,
 };
 
@@ -44,7 +50,10 @@ use tss_esapi::{
         SessionHandle, TpmHandle,
     },
     interface_types::{
-        algorithm::{AsymmetricAlgorithm, HashingAlgorithm, PublicAlgorithm},
+        algorithm::{
+            AsymmetricAlgorithm, HashingAlgorithm, PublicAlgorithm,
+            SignatureSchemeAlgorithm,
+        },
         ecc::EccCurve,
         key_bits::RsaKeyBits,
         resource_handles::{Hierarchy, NvAuth},
@@ -52,21 +61,27 @@ use tss_esapi::{
         structure_tags::AttestationType,
     },
     structures::{
-        Attest, AttestInfo, Auth, CapabilityData, Data, Digest, DigestValues,
-        EccParameter, EccPoint, EccScheme, EncryptedSecret, HashScheme,
-        IdObject, KeyDerivationFunctionScheme, Name, PcrSelectionList,
-        PcrSelectionListBuilder, PcrSlot, Private as TssPrivate,
-        Public as TssPublic, PublicBuilder, PublicEccParametersBuilder,
-        PublicKeyRsa, PublicRsaParametersBuilder, RsaExponent, RsaScheme,
-        Signature, SignatureScheme, SymmetricDefinitionObject, Ticket,
-        VerifiedTicket,
+        AlgorithmPropertyList, Attest, AttestBuffer, AttestInfo, Auth,
+        CapabilityData, ClockInfo, Data, Digest, DigestList, DigestValues,
+        EccParameter, EccPoint, EccScheme, EccSignature, EncryptedSecret,
+        HashScheme, IdObject, KeyDerivationFunctionScheme, Name,
+        PcrSelectionList, PcrSelectionListBuilder, PcrSlot,
+        Private as TssPrivate, Public as TssPublic, PublicBuffer,
+        PublicBuilder, PublicEccParametersBuilder, PublicKeyRsa,
+        PublicRsaParametersBuilder, RsaExponent, RsaScheme, Signature,
+        SignatureScheme, SymmetricDefinitionObject, Ticket, VerifiedTicket,
     },
     tcti_ldr::TctiNameConf,
-    traits::Marshall,
-    tss2_esys::{TPML_DIGEST, TPML_PCR_SELECTION},
+    traits::{Marshall, UnMarshall},
+    tss2_esys::{
+        TPM2B_DIGEST, TPML_DIGEST, TPML_PCR_SELECTION, TPMS_PCR_SELECTION,
+    },
     Error::Tss2Error,
 };
 
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
+
 /// Maximum size of nonce used in `quote`.
 pub const MAX_NONCE_SIZE: usize = 64;
 const TPML_DIGEST_SIZE: usize = std::mem::size_of::<TPML_DIGEST>();
@@ -272,6 +287,21 @@ pub enum TpmError {
     #[error("Error verifying signature")]
     TSSVerifySign { source: tss_esapi::Error },
 
+    /// Error getting TPM capability
+    #[error("Error getting TPM capability {capability:?}")]
+    TSSGetCapabilityError {
+        capability: CapabilityType,
+        source: tss_esapi::Error,
+    },
+
+    /// Unexpected capability data returned by the TPM
+    #[error("Unexpected capability data: expected {expected:?}")]
+    UnexpectedCapabilityData { expected: CapabilityType },
+
+    /// Unsupported key type
+    #[error("Unsupported key type: {0}")]
+    UnsupportedKeyType(String),
+
     /// Unexpected attested type in quote
     #[error("Unexpected attested type in quote: expected {expected:?} got {got:?}")]
     UnexpectedAttestedType {
@@ -283,6 +313,29 @@ pub enum TpmError {
     #[error("Consistent race condition: PCR data and attestation data mismatched on all {attempts} attempts")]
     TooManyAttestationMismatches { attempts: i32 },
 
+    /// The PCR bank selected for a quote is not allocated in the TPM
+    #[error("PCR bank {0:?} is not allocated in the TPM")]
+    UnallocatedPcrBank(HashingAlgorithm),
+
+    /// The quote is not signed with the expected hash algorithm
+    #[error("Quote signed with {got:?}, expected {expected}")]
+    QuoteHashAlgorithmMismatch {
+        expected: HashAlgorithm,
+        got: HashingAlgorithm,
+    },
+
+    /// The quote signature does not match the attestation key
+    #[error("Quote signature does not match the attestation key")]
+    QuoteSignatureMismatch,
+
+    /// The nonce in the quote does not match the expected nonce
+    #[error("Quote nonce does not match")]
+    QuoteNonceMismatch,
+
+    /// The PCR digest in the quote does not match the PCR values
+    #[error("Quote PCR digest does not match the PCR values")]
+    QuotePcrDigestMismatch,
+
     /// Error converting nonce to the Data structure
     #[error("Error converting nonce to Data structure")]
     DataFromNonce,
@@ -355,6 +408,14 @@ pub enum TpmError {
     #[error("Error encoding public key in PEM format")]
     OpenSSLPublicKeyToPEM { source: openssl::error::ErrorStack },
 
+    /// Error decoding PEM public key
+    #[error("Error decoding public key in PEM format")]
+    OpenSSLPublicKeyFromPEM { source: openssl::error::ErrorStack },
+
+    /// Error verifying a signature with OpenSSL
+    #[error("Error verifying signature")]
+    OpenSSLVerify { source: openssl::error::ErrorStack },
+
     /// Error creating Hasher
     #[error("Error creating Hasher")]
     OpenSSLHasherNew { source: openssl::error::ErrorStack },
@@ -367,6 +428,22 @@ pub enum TpmError {
     #[error("Error finishing Hasher")]
     OpenSSLHasherFinish { source: openssl::error::ErrorStack },
 
+    /// Error signing with OpenSSL
+    #[error("Error signing data")]
+    OpenSSLSign { source: openssl::error::ErrorStack },
+
+    /// Error encrypting or decrypting with OpenSSL
+    #[error("Error encrypting or decrypting data")]
+    OpenSSLCipher { source: openssl::error::ErrorStack },
+
+    /// Unknown object handle in the mock TPM
+    #[error("Unknown object handle {0:#x}")]
+    UnknownHandle(u32),
+
+    /// Integrity check of the MakeCredential credential blob failed
+    #[error("Integrity check of the credential blob failed")]
+    CredentialIntegrityMismatch,
+
     /// Error when trying to decode the EK certificate
     #[error("EK certificate parsing error")]
     EKCertParsing(#[from] picky_asn1_der::Asn1DerError),
@@ -483,6 +560,13 @@ impl EKResult {
     }
 }
 
+/// Holds the PCR indexes allocated in a PCR bank.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PcrBank {
+    pub hash_alg: HashAlgorithm,
+    pub pcrs: Vec<u8>,
+}
+
 /// Holds the output of create_ak.
 #[derive(Clone, Debug)]
 pub struct AKResult {
@@ -519,7 +603,7 @@ pub struct IAKPublic {
 }
 
 /// Wrapper around tss_esapi::Context.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Context<'a> {
     inner: &'a Arc<Mutex<tss_esapi::Context>>,
 }
@@ -546,21 +630,29 @@ impl Context<'_> {
             }
         })?;
 
-        let ctx = TPM_CTX.get_or_init(||
-        {
-            let mut tpmctx = tss_esapi::Context::new(tcti).map_err(|error| {
-                TpmError::TSSTctiContextError { source: error }}).expect("Failed to create TPM context");
-
-            //  Retrieve the TPM Vendor, this allows us to warn if someone is using a
-            // Software TPM ("SW")
-            if tss_esapi::utils::get_tpm_vendor(&mut tpmctx).unwrap().contains("SW") { //#[allow_ci]
-                warn!("INSECURE: Keylime is currently using a software TPM emulator rather than a real hardware TPM.");
-                warn!("INSECURE: The security of Keylime is NOT linked to a hardware root of trust.");
-                warn!("INSECURE: Only use Keylime in this mode for testing or debugging purposes.");
-            }
+        let ctx = match TPM_CTX.get() {
+            Some(ctx) => ctx,
+            None => {
+                // Create the context outside of get_or_init() so that a
+                // missing TPM is reported as an error instead of a panic
+                let mut tpmctx =
+                    tss_esapi::Context::new(tcti).map_err(|error| {
+                        TpmError::TSSTctiContextError { source: error }
+                    })?;
 
-            Arc::new(Mutex::new(tpmctx))
-        });
+                //  Retrieve the TPM Vendor, this allows us to warn if someone is using a
+                // Software TPM ("SW")
+                if tss_esapi::utils::get_tpm_vendor(&mut tpmctx)?
+                    .contains("SW")
+                {
+                    warn!("INSECURE: Keylime is currently using a software TPM emulator rather than a real hardware TPM.");
+                    warn!("INSECURE: The security of Keylime is NOT linked to a hardware root of trust.");
+                    warn!("INSECURE: Only use Keylime in this mode for testing or debugging purposes.");
+                }
+
+                TPM_CTX.get_or_init(|| Arc::new(Mutex::new(tpmctx)))
+            }
+        };
 
         Ok(Self { inner: ctx })
     }
@@ -1373,12 +1465,12 @@ impl Context<'_> {
     }
 
     /// This function extends PCR#16 with the digest, then creates a PcrList
-    /// from the given mask and PCR#16.
+    /// from the given selection and PCR#16.
     fn build_pcr_list(
         &mut self,
         digest: DigestValues,
-        mask: u32,
-        hash_alg: HashingAlgorithm,
+        selection: &[(HashAlgorithm, u32)],
+        hash_alg: HashAlgorithm,
     ) -> Result<PcrSelectionList> {
         // extend digest into pcr16
         self.inner
@@ -1389,32 +1481,20 @@ impl Context<'_> {
                 ctx.pcr_extend(PcrHandle::Pcr16, digest.to_owned())
             })?;
 
-        // translate mask to vec of pcrs
-        let mut pcrs = read_mask(mask)?;
-
-        // add pcr16 if it isn't in the vec already
-        if !pcrs.iter().any(|&pcr| pcr == PcrSlot::Slot16) {
-            let mut slot16 = vec![PcrSlot::Slot16];
-            pcrs.append(&mut slot16);
-        }
-
-        let mut pcrlist = PcrSelectionListBuilder::new();
-        pcrlist = pcrlist.with_selection(hash_alg, &pcrs);
-        let pcrlist = pcrlist.build()?;
-
-        Ok(pcrlist)
+        quote_pcr_selection(selection, hash_alg)
     }
 
-    /// Calculates a TPM quote of `nonce` over PCRs indicated with `mask`.
+    /// Calculates a TPM quote of `nonce` over the PCRs indicated with a mask
+    /// for each bank in `selection`.
     ///
-    /// `mask` is a `u32` value, e.g., 0x408000, translating bits that
+    /// Each mask is a `u32` value, e.g., 0x408000, translating bits that
     /// are set to PCRs to include in the list. The LSB in the mask
-    /// corresponds to PCR#0. Note that PCR#16 is always included even
-    /// if the bit is not set in `mask`.
+    /// corresponds to PCR#0. Note that PCR#16 of the bank of `hash_alg` is
+    /// always included even if the bank or the bit is not in `selection`.
     pub fn quote(
         &mut self,
         nonce: &[u8],
-        mask: u32,
+        selection: &[(HashAlgorithm, u32)],
         pubkey: &PKeyRef<Public>,
         ak_handle: KeyHandle,
         hash_alg: HashAlgorithm,
@@ -1422,8 +1502,50 @@ impl Context<'_> {
     ) -> Result<String> {
         let nk_digest = pubkey_to_tpm_digest(pubkey, hash_alg)?;
 
-        let pcrlist =
-            self.build_pcr_list(nk_digest, mask, hash_alg.into())?;
+        let pcrlist = self.build_pcr_list(nk_digest, selection, hash_alg)?;
+
+        self.quote_pcr_list(nonce, pcrlist, ak_handle, hash_alg, sign_alg)
+    }
+
+    /// Calculates a TPM quote of `nonce` over exactly the PCRs indicated
+    /// with a mask for each bank in `selection`.
+    ///
+    /// Unlike `quote`, PCR#16 is not extended with the NK digest nor added
+    /// to the selection. This is used by the push model agent, where no NK
+    /// is bound to the quote.
+    pub fn quote_without_nk(
+        &mut self,
+        nonce: &[u8],
+        selection: &[(HashAlgorithm, u32)],
+        ak_handle: KeyHandle,
+        hash_alg: HashAlgorithm,
+        sign_alg: SignAlgorithm,
+    ) -> Result<String> {
+        let pcrlist = build_pcr_selection(selection)?;
+
+        self.quote_pcr_list(nonce, pcrlist, ak_handle, hash_alg, sign_alg)
+    }
+
+    /// Quotes the PCRs in `pcrlist` and encodes the result as a quote string
+    fn quote_pcr_list(
+        &mut self,
+        nonce: &[u8],
+        pcrlist: PcrSelectionList,
+        ak_handle: KeyHandle,
+        hash_alg: HashAlgorithm,
+        sign_alg: SignAlgorithm,
+    ) -> Result<String> {
+        // The values of the PCRs of a bank not allocated in the TPM are never
+        // returned, so reading them would not complete
+        let allocated = self.get_pcr_banks()?;
+        for selection in pcrlist.get_selections() {
+            let bank = selection.hashing_algorithm();
+            if !allocated.iter().any(|pcr_bank| {
+                HashingAlgorithm::from(pcr_bank.hash_alg) == bank
+            }) {
+                return Err(TpmError::UnallocatedPcrBank(bank));
+            }
+        }
 
         let mut ctx = self.inner.lock().unwrap(); //#[allow_ci]
 
@@ -1442,6 +1564,17 @@ impl Context<'_> {
         encode_quote_string(attestation, sig, pcrs_read, pcr_data)
     }
 
+    /// Reads the public area of a loaded object
+    pub fn read_public(&mut self, handle: KeyHandle) -> Result<TssPublic> {
+        let (public, _, _) = self
+            .inner
+            .lock()
+            .unwrap() //#[allow_ci]
+            .read_public(handle)
+            .map_err(|source| TpmError::TSSReadPublicError { source })?;
+        Ok(public)
+    }
+
     /// Get the name of the object
     pub fn get_name(&mut self, handle: ObjectHandle) -> Result<Name> {
         self.inner
@@ -1451,6 +1584,94 @@ impl Context<'_> {
             .map_err(|source| TpmError::TSSGetNameError { source })
     }
 
+    /// Gets the PCR banks allocated in the TPM
+    ///
+    /// # Returns
+    ///
+    /// A list of `PcrBank` with the hash algorithm and the allocated PCR
+    /// indexes of each bank, a TPMError otherwise. Banks using a hash
+    /// algorithm not supported by Keylime are skipped.
+    pub fn get_pcr_banks(&mut self) -> Result<Vec<PcrBank>> {
+        let (capabilities, _) = self
+            .inner
+            .lock()
+            .unwrap() //#[allow_ci]
+            .get_capability(CapabilityType::AssignedPcr, 0, 1)
+            .map_err(|source| TpmError::TSSGetCapabilityError {
+                capability: CapabilityType::AssignedPcr,
+                source,
+            })?;
+
+        let CapabilityData::AssignedPcr(selection_list) = capabilities else {
+            return Err(TpmError::UnexpectedCapabilityData {
+                expected: CapabilityType::AssignedPcr,
+            });
+        };
+
+        let mut banks = Vec::new();
+        for selection in selection_list.get_selections() {
+            let pcrs: Vec<u8> = selection
+                .selected()
+                .into_iter()
+                .map(|slot| u32::from(slot).trailing_zeros() as u8)
+                .collect();
+
+            if pcrs.is_empty() {
+                continue;
+            }
+
+            match HashAlgorithm::try_from(selection.hashing_algorithm()) {
+                Ok(hash_alg) => banks.push(PcrBank { hash_alg, pcrs }),
+                Err(e) => debug!("Skipping PCR bank: {e}"),
+            }
+        }
+        Ok(banks)
+    }
+
+    /// Gets the signing schemes implemented by the TPM
+    ///
+    /// # Returns
+    ///
+    /// The list of `SignAlgorithm` supported by both Keylime and the TPM, a
+    /// TPMError otherwise.
+    pub fn get_supported_sign_algorithms(
+        &mut self,
+    ) -> Result<Vec<SignAlgorithm>> {
+        let (capabilities, _) = self
+            .inner
+            .lock()
+            .unwrap() //#[allow_ci]
+            .get_capability(
+                CapabilityType::Algorithms,
+                0,
+                AlgorithmPropertyList::MAX_SIZE as u32,
+            )
+            .map_err(|source| TpmError::TSSGetCapabilityError {
+                capability: CapabilityType::Algorithms,
+                source,
+            })?;
+
+        let CapabilityData::Algorithms(algorithms) = capabilities else {
+            return Err(TpmError::UnexpectedCapabilityData {
+                expected: CapabilityType::Algorithms,
+            });
+        };
+
+        Ok([
+            SignAlgorithm::RsaSsa,
+            SignAlgorithm::RsaPss,
+            SignAlgorithm::EcDsa,
+            SignAlgorithm::EcSchnorr,
+        ]
+        .into_iter()
+        .filter(|alg| {
+            algorithms
+                .find(SignatureSchemeAlgorithm::from(*alg).into())
+                .is_some()
+        })
+        .collect())
+    }
+
     /// Make credential: encrypt a challenge which can only be decrypted using the corresponding
     /// private EK and AK name
     ///
@@ -1474,26 +1695,7 @@ impl Context<'_> {
             .make_credential(ek_handle, credential, name)
             .map_err(|source| TpmError::TSSMakeCredentialError { source })?;
 
-        let mut blob = Vec::new();
-
-        // tpm2-tools specific header, added to keep compatibility
-        blob.extend(TSS_MAGIC.to_be_bytes());
-        // blob version number, should be 1
-        blob.extend(u32::to_be_bytes(1));
-
-        // Append big endian encoded credential length followed by the credential
-        let cred_len: u16 =
-            credential.len().try_into().map_err(TpmError::TryFromInt)?;
-        blob.extend(cred_len.to_be_bytes());
-        blob.extend(credential.as_slice());
-
-        // Append big endian encoded secret length followed by the secret
-        let secret_len: u16 =
-            secret.len().try_into().map_err(TpmError::TryFromInt)?;
-        blob.extend(secret_len.to_be_bytes());
-        blob.extend(secret.as_slice());
-
-        Ok(blob)
+        make_credential_blob(&credential, &secret)
     }
 
     /// Flush object handle context
@@ -1548,6 +1750,143 @@ impl Context<'_> {
     }
 }
 
+/// TPM operations used by the agent
+///
+/// This is implemented by `Context` on top of the TSS ESAPI, and by
+/// `mock::MockTpm` in software, which allows testing the agent without a
+/// TPM. See the methods of `Context` for the description of each operation.
+pub trait TpmBackend: std::fmt::Debug {
+    fn create_ek(
+        &mut self,
+        alg: EncryptionAlgorithm,
+        handle: Option<&str>,
+    ) -> Result<EKResult>;
+
+    fn create_ak(
+        &mut self,
+        handle: KeyHandle,
+        hash_alg: HashAlgorithm,
+        sign_alg: SignAlgorithm,
+    ) -> Result<AKResult>;
+
+    fn load_ak(
+        &mut self,
+        handle: KeyHandle,
+        ak: &AKResult,
+    ) -> Result<KeyHandle>;
+
+    fn read_public(&mut self, handle: KeyHandle) -> Result<TssPublic>;
+
+    fn quote(
+        &mut self,
+        nonce: &[u8],
+        selection: &[(HashAlgorithm, u32)],
+        pubkey: &PKeyRef<Public>,
+        ak_handle: KeyHandle,
+        hash_alg: HashAlgorithm,
+        sign_alg: SignAlgorithm,
+    ) -> Result<String>;
+
+    fn activate_credential(
+        &mut self,
+        keyblob: Vec<u8>,
+        ak: KeyHandle,
+        ek: KeyHandle,
+    ) -> Result<Digest>;
+
+    fn certify_credential_with_iak(
+        &mut self,
+        qualifying_data: Data,
+        ak: KeyHandle,
+        iak: KeyHandle,
+    ) -> Result<(Attest, Signature)>;
+
+    fn make_credential(
+        &mut self,
+        ek_handle: KeyHandle,
+        credential: Digest,
+        name: Name,
+    ) -> Result<Vec<u8>>;
+
+    fn flush_context(&mut self, handle: ObjectHandle) -> Result<()>;
+}
+
+impl TpmBackend for Context<'_> {
+    fn create_ek(
+        &mut self,
+        alg: EncryptionAlgorithm,
+        handle: Option<&str>,
+    ) -> Result<EKResult> {
+        Context::create_ek(self, alg, handle)
+    }
+
+    fn create_ak(
+        &mut self,
+        handle: KeyHandle,
+        hash_alg: HashAlgorithm,
+        sign_alg: SignAlgorithm,
+    ) -> Result<AKResult> {
+        Context::create_ak(self, handle, hash_alg, sign_alg)
+    }
+
+    fn load_ak(
+        &mut self,
+        handle: KeyHandle,
+        ak: &AKResult,
+    ) -> Result<KeyHandle> {
+        Context::load_ak(self, handle, ak)
+    }
+
+    fn read_public(&mut self, handle: KeyHandle) -> Result<TssPublic> {
+        Context::read_public(self, handle)
+    }
+
+    fn quote(
+        &mut self,
+        nonce: &[u8],
+        selection: &[(HashAlgorithm, u32)],
+        pubkey: &PKeyRef<Public>,
+        ak_handle: KeyHandle,
+        hash_alg: HashAlgorithm,
+        sign_alg: SignAlgorithm,
+    ) -> Result<String> {
+        Context::quote(
+            self, nonce, selection, pubkey, ak_handle, hash_alg, sign_alg,
+        )
+    }
+
+    fn activate_credential(
+        &mut self,
+        keyblob: Vec<u8>,
+        ak: KeyHandle,
+        ek: KeyHandle,
+    ) -> Result<Digest> {
+        Context::activate_credential(self, keyblob, ak, ek)
+    }
+
+    fn certify_credential_with_iak(
+        &mut self,
+        qualifying_data: Data,
+        ak: KeyHandle,
+        iak: KeyHandle,
+    ) -> Result<(Attest, Signature)> {
+        Context::certify_credential_with_iak(self, qualifying_data, ak, iak)
+    }
+
+    fn make_credential(
+        &mut self,
+        ek_handle: KeyHandle,
+        credential: Digest,
+        name: Name,
+    ) -> Result<Vec<u8>> {
+        Context::make_credential(self, ek_handle, credential, name)
+    }
+
+    fn flush_context(&mut self, handle: ObjectHandle) -> Result<()> {
+        Context::flush_context(self, handle)
+    }
+}
+
 // Ensure that TPML_PCR_SELECTION and TPML_DIGEST have known sizes
 assert_eq_size!(TPML_PCR_SELECTION, [u8; 132]);
 assert_eq_size!(TPML_DIGEST, [u8; 532]);
@@ -1618,6 +1957,34 @@ fn pcrdata_to_vec(
 
 const TSS_MAGIC: u32 = 3135029470;
 
+/// Creates the keyblob of a MakeCredential, in the format read by
+/// `parse_cred_and_secret`
+fn make_credential_blob(
+    credential: &IdObject,
+    secret: &EncryptedSecret,
+) -> Result<Vec<u8>> {
+    let mut blob = Vec::new();
+
+    // tpm2-tools specific header, added to keep compatibility
+    blob.extend(TSS_MAGIC.to_be_bytes());
+    // blob version number, should be 1
+    blob.extend(u32::to_be_bytes(1));
+
+    // Append big endian encoded credential length followed by the credential
+    let cred_len: u16 =
+        credential.len().try_into().map_err(TpmError::TryFromInt)?;
+    blob.extend(cred_len.to_be_bytes());
+    blob.extend(credential.as_slice());
+
+    // Append big endian encoded secret length followed by the secret
+    let secret_len: u16 =
+        secret.len().try_into().map_err(TpmError::TryFromInt)?;
+    blob.extend(secret_len.to_be_bytes());
+    blob.extend(secret.as_slice());
+
+    Ok(blob)
+}
+
 /// Parse credential and encrypted secret from the MakeCredential keyblob
 fn parse_cred_and_secret(
     keyblob: Vec<u8>,
@@ -1683,7 +2050,15 @@ fn pubkey_to_tpm_digest<T: HasPublic>(
     hash_algo: HashAlgorithm,
 ) -> Result<DigestValues> {
     let mut keydigest = DigestValues::new();
+    keydigest.set(hash_algo.into(), pubkey_digest(pubkey, hash_algo)?);
+    Ok(keydigest)
+}
 
+/// Takes a public PKey and returns the digest extended into PCR#16 for it.
+fn pubkey_digest<T: HasPublic>(
+    pubkey: &PKeyRef<T>,
+    hash_algo: HashAlgorithm,
+) -> Result<Digest> {
     let keybytes = match pubkey.id() {
         Id::RSA => pubkey
             .rsa()
@@ -1707,13 +2082,8 @@ fn pubkey_to_tpm_digest<T: HasPublic>(
     let hashvec = hasher
         .finish()
         .map_err(|source| TpmError::OpenSSLHasherFinish { source })?;
-    keydigest.set(
-        hashing_algo,
-        Digest::try_from(hashvec.as_ref())
-            .map_err(|source| TpmError::TSSDigestFromValue { source })?,
-    );
-
-    Ok(keydigest)
+    Digest::try_from(hashvec.as_ref())
+        .map_err(|source| TpmError::TSSDigestFromValue { source })
 }
 
 /// Reads a mask indicating PCRs to include in a Quote.
@@ -1774,6 +2144,118 @@ pub fn check_mask(mask: u32, pcr: &PcrSlot) -> Result<bool> {
     Ok(selected_pcrs.contains(pcr))
 }
 
+/// Gets the PCR banks to quote: the bank of `hash_alg`, followed by the other
+/// banks in `pcr_banks`, in order and without duplicates
+pub fn quote_banks(
+    hash_alg: HashAlgorithm,
+    pcr_banks: &[HashAlgorithm],
+) -> Vec<HashAlgorithm> {
+    let mut banks = vec![hash_alg];
+    for bank in pcr_banks {
+        if !banks.contains(bank) {
+            banks.push(*bank);
+        }
+    }
+    banks
+}
+
+/// Creates the selection of the PCRs quoted by `quote`, which adds PCR#16 to
+/// the bank of `hash_alg`
+fn quote_pcr_selection(
+    selection: &[(HashAlgorithm, u32)],
+    hash_alg: HashAlgorithm,
+) -> Result<PcrSelectionList> {
+    let mut selection = selection.to_vec();
+    match selection.iter_mut().find(|(bank, _)| *bank == hash_alg) {
+        Some((_, mask)) => *mask |= 1 << 16,
+        None => selection.insert(0, (hash_alg, 1 << 16)),
+    }
+    build_pcr_selection(&selection)
+}
+
+/// Creates the selection of the PCRs indicated with a mask for each bank
+///
+/// The banks keep the order of `selection`, and the banks with an empty mask
+/// are not included.
+fn build_pcr_selection(
+    selection: &[(HashAlgorithm, u32)],
+) -> Result<PcrSelectionList> {
+    // Merge the masks of repeated banks
+    let mut masks: Vec<(HashAlgorithm, u32)> = Vec::new();
+    for (hash_alg, mask) in selection {
+        match masks.iter_mut().find(|(bank, _)| bank == hash_alg) {
+            Some((_, bank_mask)) => *bank_mask |= mask,
+            None => masks.push((*hash_alg, *mask)),
+        }
+    }
+
+    // The builder does not keep the order of the banks, so the selections
+    // are built one at a time
+    let mut pcrsel = TPML_PCR_SELECTION::default();
+    for (hash_alg, mask) in masks {
+        let pcrs = read_mask(mask)?;
+        if pcrs.is_empty() {
+            continue;
+        }
+        let bank: TPML_PCR_SELECTION = PcrSelectionListBuilder::new()
+            .with_selection(hash_alg.into(), &pcrs)
+            .build()?
+            .into();
+        let slot = pcrsel
+            .pcrSelections
+            .get_mut(pcrsel.count as usize)
+            .ok_or_else(|| {
+                TpmError::InvalidRequest(format!(
+                    "Too many PCR banks selected, the maximum is {}",
+                    PcrSelectionList::MAX_SIZE
+                ))
+            })?;
+        *slot = bank.pcrSelections[0];
+        pcrsel.count += 1;
+    }
+    Ok(pcrsel.try_into()?)
+}
+
+/// Gets the algorithm and the size in bits of a TPM public key
+///
+/// # Arguments
+///
+/// `public`: The TPM public key
+///
+/// # Returns
+///
+/// A tuple with the `EncryptionAlgorithm` and the key size in bits for RSA
+/// and ECC keys, a TPMError otherwise
+pub fn get_public_key_info(
+    public: &TssPublic,
+) -> Result<(EncryptionAlgorithm, u32)> {
+    match public {
+        TssPublic::Rsa { parameters, .. } => Ok((
+            EncryptionAlgorithm::Rsa,
+            u16::from(parameters.key_bits()).into(),
+        )),
+        TssPublic::Ecc { parameters, .. } => {
+            let size = match parameters.ecc_curve() {
+                EccCurve::NistP192 => 192,
+                EccCurve::NistP224 => 224,
+                EccCurve::NistP256 | EccCurve::BnP256 | EccCurve::Sm2P256 => {
+                    256
+                }
+                EccCurve::NistP384 => 384,
+                EccCurve::NistP521 => 521,
+                EccCurve::BnP638 => 638,
+            };
+            Ok((EncryptionAlgorithm::Ecc, size))
+        }
+        TssPublic::KeyedHash { .. } => {
+            Err(TpmError::UnsupportedKeyType("keyedhash".to_string()))
+        }
+        TssPublic::SymCipher { .. } => {
+            Err(TpmError::UnsupportedKeyType("symcipher".to_string()))
+        }
+    }
+}
+
 /// This encodes a quote string as input to Python Keylime's quote checking functionality.
 /// The quote, signature, and pcr blob are concatenated with ':' separators. To match the
 /// expected format, the quote, signature, and pcr blob must be base64 encoded before concatenation.
@@ -2109,191 +2591,140 @@ pub fn read_ek_ca_chain(
     Ok(result) // Return the accumulated result
 }
 
-pub mod testing {
-    use super::*;
-    #[cfg(feature = "testing")]
-    use tokio::sync::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard};
-    use tss_esapi::{
-        constants::structure_tags::StructureTag,
-        structures::{Attest, AttestBuffer, DigestList},
-        tss2_esys::{
-            Tss2_MU_TPMT_SIGNATURE_Unmarshal, TPM2B_ATTEST, TPM2B_DIGEST,
-            TPMS_PCR_SELECTION, TPMT_SIGNATURE,
-        },
-    };
-
-    #[cfg(feature = "testing")]
-    pub static MUTEX: OnceLock<Arc<AsyncMutex<()>>> = OnceLock::new();
-
-    macro_rules! create_unmarshal_fn {
-        ($func:ident, $tpmobj:ty, $unmarshal:ident) => {
-            fn $func(val: &[u8]) -> Result<$tpmobj> {
-                let mut resp = <$tpmobj>::default();
-                let mut offset = 0;
-
-                unsafe {
-                    let res = $unmarshal(
-                        val[..].as_ptr(),
-                        val.len().try_into()?,
-                        &mut offset,
-                        &mut resp,
-                    );
-                    if res != 0 {
-                        return Err(TpmError::Other(format!(
-                            "Error converting"
-                        )));
-                    }
-                }
-                Ok(resp)
-            }
-        };
+/// Deserialize a TPML_PCR_SELECTION from a &[u8] slice.
+/// The deserialization will adjust the data endianness as necessary.
+fn deserialize_pcrsel(pcrsel_vec: &[u8]) -> Result<TPML_PCR_SELECTION> {
+    if pcrsel_vec.len() != TPML_PCR_SELECTION_SIZE {
+        return Err(TpmError::InvalidRequest(format!(
+            "Unexpected PCR selection size: Expected {} but got {}",
+            TPML_PCR_SELECTION_SIZE,
+            pcrsel_vec.len()
+        )));
     }
 
-    create_unmarshal_fn!(
-        vec_to_sig,
-        TPMT_SIGNATURE,
-        Tss2_MU_TPMT_SIGNATURE_Unmarshal
-    );
+    let mut reader = std::io::Cursor::new(pcrsel_vec);
+    let mut count_vec = [0u8; 4];
+    reader.read_exact(&mut count_vec).map_err(|source| {
+        TpmError::IoReadError {
+            what: "PCR selection count from slice".into(),
+            source,
+        }
+    })?;
+    let count = u32::from_le_bytes(count_vec);
 
-    /// Initialize testing mutex
-    #[cfg(feature = "testing")]
-    pub async fn lock_tests<'a>() -> AsyncMutexGuard<'a, ()> {
-        MUTEX
-            .get_or_init(|| Arc::new(AsyncMutex::new(())))
-            .lock()
-            .await
-    }
-
-    /// Deserialize a TPML_PCR_SELECTION from a &[u8] slice.
-    /// The deserialization will adjust the data endianness as necessary.
-    fn deserialize_pcrsel(pcrsel_vec: &[u8]) -> Result<TPML_PCR_SELECTION> {
-        if pcrsel_vec.len() != TPML_PCR_SELECTION_SIZE {
-            return Err(TpmError::InvalidRequest(format!(
-                "Unexpected PCR selection size: Expected {} but got {}",
-                TPML_PCR_SELECTION_SIZE,
-                pcrsel_vec.len()
-            )));
-        }
+    let mut pcr_selections: [TPMS_PCR_SELECTION; 16] =
+        [TPMS_PCR_SELECTION::default(); 16];
 
-        let mut reader = std::io::Cursor::new(pcrsel_vec);
-        let mut count_vec = [0u8; 4];
-        reader.read_exact(&mut count_vec).map_err(|source| {
+    for selection in &mut pcr_selections {
+        let mut hash_vec = [0u8; 2];
+        reader.read_exact(&mut hash_vec).map_err(|source| {
             TpmError::IoReadError {
-                what: "PCR selection count from slice".into(),
+                what: "PCR selection hash from slice".into(),
                 source,
             }
         })?;
-        let count = u32::from_le_bytes(count_vec);
+        selection.hash = u16::from_le_bytes(hash_vec);
 
-        let mut pcr_selections: [TPMS_PCR_SELECTION; 16] =
-            [TPMS_PCR_SELECTION::default(); 16];
-
-        for selection in &mut pcr_selections {
-            let mut hash_vec = [0u8; 2];
-            reader.read_exact(&mut hash_vec).map_err(|source| {
-                TpmError::IoReadError {
-                    what: "PCR selection hash from slice".into(),
-                    source,
-                }
-            })?;
-            selection.hash = u16::from_le_bytes(hash_vec);
+        let mut size_vec = [0u8; 1];
+        reader.read_exact(&mut size_vec).map_err(|source| {
+            TpmError::IoReadError {
+                what: "PCR selection size from slice".into(),
+                source,
+            }
+        })?;
+        selection.sizeofSelect = u8::from_le_bytes(size_vec);
 
-            let mut size_vec = [0u8; 1];
-            reader.read_exact(&mut size_vec).map_err(|source| {
-                TpmError::IoReadError {
-                    what: "PCR selection size from slice".into(),
-                    source,
-                }
+        reader
+            .read_exact(&mut selection.pcrSelect)
+            .map_err(|source| TpmError::IoReadError {
+                what: "PCR selection from slice".into(),
+                source,
             })?;
-            selection.sizeofSelect = u8::from_le_bytes(size_vec);
-
-            reader.read_exact(&mut selection.pcrSelect).map_err(
-                |source| TpmError::IoReadError {
-                    what: "PCR selection from slice".into(),
-                    source,
-                },
-            )?;
-        }
-
-        Ok(TPML_PCR_SELECTION {
-            count,
-            pcrSelections: pcr_selections,
-        })
-    }
 
-    // Deserialize a TPML_DIGEST from a &[u8] slice.
-    // The deserialization will adjust the data endianness as necessary.
-    fn deserialize_digest(digest_vec: &[u8]) -> Result<TPML_DIGEST> {
-        if digest_vec.len() != TPML_DIGEST_SIZE {
-            return Err(TpmError::InvalidRequest(format!(
-                "Unexpected digest size: Expected {} but got {}",
-                TPML_DIGEST_SIZE,
-                digest_vec.len()
-            )));
-        }
-
-        let mut reader = std::io::Cursor::new(digest_vec);
-        let mut count_vec = [0u8; 4];
-
-        reader.read_exact(&mut count_vec).map_err(|source| {
+        // skip the padding added to keep the memory alignment
+        let mut padding = [0u8; 1];
+        reader.read_exact(&mut padding).map_err(|source| {
             TpmError::IoReadError {
-                what: "Digest count from slice".into(),
+                what: "PCR selection padding from slice".into(),
                 source,
             }
         })?;
-        let count = u32::from_le_bytes(count_vec);
+    }
 
-        let mut digests: [TPM2B_DIGEST; 8] = [TPM2B_DIGEST::default(); 8];
+    Ok(TPML_PCR_SELECTION {
+        count,
+        pcrSelections: pcr_selections,
+    })
+}
 
-        for digest in &mut digests {
-            let mut size_vec = [0u8; 2];
-            reader.read_exact(&mut size_vec).map_err(|source| {
-                TpmError::IoReadError {
-                    what: "Digest size from slice".into(),
-                    source,
-                }
-            })?;
-            digest.size = u16::from_le_bytes(size_vec);
-            reader.read_exact(&mut digest.buffer).map_err(|source| {
-                TpmError::IoReadError {
-                    what: "Digest from slice".into(),
-                    source,
-                }
-            })?;
+// Deserialize a TPML_DIGEST from a &[u8] slice.
+// The deserialization will adjust the data endianness as necessary.
+fn deserialize_digest(digest_vec: &[u8]) -> Result<TPML_DIGEST> {
+    if digest_vec.len() != TPML_DIGEST_SIZE {
+        return Err(TpmError::InvalidRequest(format!(
+            "Unexpected digest size: Expected {} but got {}",
+            TPML_DIGEST_SIZE,
+            digest_vec.len()
+        )));
+    }
+
+    let mut reader = std::io::Cursor::new(digest_vec);
+    let mut count_vec = [0u8; 4];
+
+    reader.read_exact(&mut count_vec).map_err(|source| {
+        TpmError::IoReadError {
+            what: "Digest count from slice".into(),
+            source,
         }
+    })?;
+    let count = u32::from_le_bytes(count_vec);
 
-        Ok(TPML_DIGEST { count, digests })
-    }
+    let mut digests: [TPM2B_DIGEST; 8] = [TPM2B_DIGEST::default(); 8];
 
-    fn vec_to_pcrdata(val: &[u8]) -> Result<(PcrSelectionList, PcrData)> {
-        let mut reader = std::io::Cursor::new(val);
-        let mut pcrsel_vec = [0u8; TPML_PCR_SELECTION_SIZE];
-        reader.read_exact(&mut pcrsel_vec).map_err(|source| {
+    for digest in &mut digests {
+        let mut size_vec = [0u8; 2];
+        reader.read_exact(&mut size_vec).map_err(|source| {
             TpmError::IoReadError {
-                what: "PCR selection size from slice".into(),
+                what: "Digest size from slice".into(),
                 source,
             }
         })?;
-
-        let pcrsel = deserialize_pcrsel(&pcrsel_vec)?;
-        let pcrlist: PcrSelectionList = pcrsel.try_into()?;
-
-        let mut count_vec = [0u8; 4];
-        reader.read_exact(&mut count_vec).map_err(|source| {
+        digest.size = u16::from_le_bytes(size_vec);
+        reader.read_exact(&mut digest.buffer).map_err(|source| {
             TpmError::IoReadError {
-                what: "PCR selection count from slice".into(),
+                what: "Digest from slice".into(),
                 source,
             }
         })?;
-        let count = u32::from_le_bytes(count_vec);
-        // Always 1 PCR digest should follow
-        if count != 1 {
-            return Err(TpmError::InvalidRequest(format!(
-                "Expected 1 PCR digest, got {}",
-                count
-            )));
+    }
+
+    Ok(TPML_DIGEST { count, digests })
+}
+
+fn vec_to_pcrdata(val: &[u8]) -> Result<(PcrSelectionList, PcrData)> {
+    let mut reader = std::io::Cursor::new(val);
+    let mut pcrsel_vec = [0u8; TPML_PCR_SELECTION_SIZE];
+    reader.read_exact(&mut pcrsel_vec).map_err(|source| {
+        TpmError::IoReadError {
+            what: "PCR selection size from slice".into(),
+            source,
+        }
+    })?;
+
+    let pcrsel = deserialize_pcrsel(&pcrsel_vec)?;
+    let pcrlist: PcrSelectionList = pcrsel.try_into()?;
+
+    let mut count_vec = [0u8; 4];
+    reader.read_exact(&mut count_vec).map_err(|source| {
+        TpmError::IoReadError {
+            what: "PCR selection count from slice".into(),
+            source,
         }
+    })?;
+    let count = u32::from_le_bytes(count_vec);
 
+    let mut digests = Vec::new();
+    for _ in 0..count {
         let mut digest_vec = [0u8; TPML_DIGEST_SIZE];
         reader.read_exact(&mut digest_vec).map_err(|source| {
             TpmError::IoReadError {
@@ -2302,126 +2733,391 @@ pub mod testing {
             }
         })?;
         let digest = deserialize_digest(&digest_vec)?;
-        let mut digest_list = DigestList::new();
-        for i in 0..digest.count {
-            digest_list.add(digest.digests[i as usize].try_into()?)?;
+        for d in digest.digests.iter().take(digest.count as usize) {
+            digests.push(Digest::try_from(*d)?);
         }
-
-        let pcrdata = PcrData::create(&pcrlist, &digest_list)?;
-        Ok((pcrlist, pcrdata))
     }
 
-    pub fn decode_quote_string(
-        quote: &str,
-    ) -> Result<(AttestBuffer, Signature, PcrSelectionList, PcrData)> {
-        if !quote.starts_with('r') {
-            return Err(TpmError::InvalidRequest(
-                "Quote string should start with 'r'".into(),
-            ));
+    let pcrdata = pcr_data_from_digests(&pcrlist, digests)?;
+    Ok((pcrlist, pcrdata))
+}
+
+/// Creates the PcrData with the values of the PCRs in `pcrlist`, given in the
+/// order of the selection
+fn pcr_data_from_digests(
+    pcrlist: &PcrSelectionList,
+    digests: Vec<Digest>,
+) -> Result<PcrData> {
+    let mut digests = digests.into_iter();
+    let mut pcrdata = PcrData::new();
+    for selection in pcrlist.get_selections() {
+        // A DigestList holds up to 8 digests
+        for slots in selection.selected().chunks(DigestList::MAX_SIZE) {
+            let mut digest_list = DigestList::new();
+            for digest in digests.by_ref().take(slots.len()) {
+                digest_list.add(digest)?;
+            }
+            let chunk = PcrSelectionListBuilder::new()
+                .with_selection(selection.hashing_algorithm(), slots)
+                .build()?;
+            pcrdata.add(&chunk, &digest_list)?;
         }
-        // extract components from the concatenated string
-        let mut split = quote[1..].split(':');
-        let att_str = split.next().ok_or(TpmError::InvalidRequest(
-            "Malformed quote string, could not parse quote".into(),
-        ))?;
-        let sig_str = split.next().ok_or(TpmError::InvalidRequest(
-            "Malformed quote string, could not parse signature".into(),
-        ))?;
-        let pcr_str = split.next().ok_or(TpmError::InvalidRequest(
-            "Malformed quote string, could not parse the PCR blob".into(),
-        ))?;
-
-        // base64 decoding
-        let att_comp_finished = general_purpose::STANDARD.decode(att_str)?;
-        let sig_comp_finished = general_purpose::STANDARD.decode(sig_str)?;
-        let pcr_comp_finished = general_purpose::STANDARD.decode(pcr_str)?;
-
-        let sig: Signature = vec_to_sig(&sig_comp_finished)?.try_into()?;
-        let (pcrsel, pcrdata) = vec_to_pcrdata(&pcr_comp_finished)?;
-
-        let mut att = TPM2B_ATTEST {
-            size: att_comp_finished.len().try_into()?,
-            ..Default::default()
-        };
-        att.attestationData[0..att_comp_finished.len()]
-            .copy_from_slice(&att_comp_finished);
-        Ok((att.try_into()?, sig, pcrsel, pcrdata))
     }
+    if digests.next().is_some() {
+        return Err(TpmError::InvalidRequest(
+            "More PCR values than selected PCRs".into(),
+        ));
+    }
+    Ok(pcrdata)
+}
 
-    /// This performs the same checks as in tpm2_checkquote, namely:
-    /// signature, nonce, and PCR digests from the quote.
-    ///
-    /// Reference:
-    /// https://github.com/tpm2-software/tpm2-tools/blob/master/tools/tpm2_checkquote.c
-    pub fn check_quote(
-        context: &mut Context,
-        ak_handle: KeyHandle,
-        quote: &str,
-        nonce: &[u8],
-    ) -> Result<()> {
-        let (att, sig, pcrsel, pcrdata) = decode_quote_string(quote)?;
+/// PCR values of a bank, indexed by the PCR number
+pub type PcrValues = BTreeMap<u32, Vec<u8>>;
 
-        // Verify the signature matches message digest. We do not
-        // bother unmarshalling the AK to OpenSSL PKey, but just use
-        // Esys_VerifySignature with loaded AK
-        let mut hasher = Hasher::new(MessageDigest::sha256())
-            .map_err(|source| TpmError::OpenSSLHasherNew { source })?;
-        hasher
-            .update(att.value())
-            .map_err(|source| TpmError::OpenSSLHasherUpdate { source })?;
-        let digest = hasher
-            .finish()
-            .map_err(|source| TpmError::OpenSSLHasherFinish { source })?;
-        let digest: Digest = digest.as_ref().try_into().unwrap(); //#[allow_ci]
-        match context.verify_signature(ak_handle, digest, sig) {
-            Ok(ticket) if ticket.tag() == StructureTag::Verified => {}
-            _ => {
-                return Err(TpmError::Other(
-                    "unable to verify quote signature".to_string(),
-                ))
-            }
-        }
+/// Gets the PCR values included in a quote string generated by `quote`
+///
+/// # Returns
+///
+/// The PCR values of each bank included in the quote, indexed by the PCR
+/// number, a TPMError otherwise.
+pub fn get_quoted_pcrs(
+    quote: &str,
+) -> Result<Vec<(HashAlgorithm, PcrValues)>> {
+    let pcr_str = quote
+        .strip_prefix('r')
+        .and_then(|q| q.split(':').nth(2))
+        .ok_or(TpmError::InvalidRequest(
+        "Malformed quote string, could not parse the PCR blob".into(),
+    ))?;
+    let pcr_blob = general_purpose::STANDARD.decode(pcr_str)?;
+    let (_, pcr_data) = vec_to_pcrdata(&pcr_blob)?;
+
+    let mut banks = Vec::new();
+    for (hashing_alg, bank) in pcr_data {
+        let hash_alg = HashAlgorithm::try_from(hashing_alg)?;
+        let values = bank
+            .into_iter()
+            .map(|(slot, digest)| {
+                (u32::from(*slot).trailing_zeros(), digest.value().to_vec())
+            })
+            .collect();
+        banks.push((hash_alg, values));
+    }
+    Ok(banks)
+}
+
+/// Gets the clock information of the TPM when a quote string generated by
+/// `quote` was created
+///
+/// # Returns
+///
+/// The clock, reset count and restart count of the TPM from the attestation
+/// in the quote, a TPMError otherwise.
+pub fn get_quote_clock_info(quote: &str) -> Result<ClockInfo> {
+    let (att, _, _, _) = decode_quote_string(quote)?;
+    let attestation: Attest = att.try_into()?;
+    Ok(*attestation.clock_info())
+}
+
+/// Decodes a quote string generated by `quote`, in the
+/// `r<attestation>:<signature>:<PCR blob>` format
+///
+/// # Returns
+///
+/// The marshalled attestation, the signature, and the selection and values
+/// of the quoted PCRs
+pub fn decode_quote_string(
+    quote: &str,
+) -> Result<(AttestBuffer, Signature, PcrSelectionList, PcrData)> {
+    if !quote.starts_with('r') {
+        return Err(TpmError::InvalidRequest(
+            "Quote string should start with 'r'".into(),
+        ));
+    }
+    // extract components from the concatenated string
+    let mut split = quote[1..].split(':');
+    let att_str = split.next().ok_or(TpmError::InvalidRequest(
+        "Malformed quote string, could not parse quote".into(),
+    ))?;
+    let sig_str = split.next().ok_or(TpmError::InvalidRequest(
+        "Malformed quote string, could not parse signature".into(),
+    ))?;
+    let pcr_str = split.next().ok_or(TpmError::InvalidRequest(
+        "Malformed quote string, could not parse the PCR blob".into(),
+    ))?;
+
+    // base64 decoding
+    let att_comp_finished = general_purpose::STANDARD.decode(att_str)?;
+    let sig_comp_finished = general_purpose::STANDARD.decode(sig_str)?;
+    let pcr_comp_finished = general_purpose::STANDARD.decode(pcr_str)?;
+
+    let sig = Signature::unmarshall(&sig_comp_finished)?;
+    let (pcrsel, pcrdata) = vec_to_pcrdata(&pcr_comp_finished)?;
+    let att = AttestBuffer::try_from(att_comp_finished)?;
+    Ok((att, sig, pcrsel, pcrdata))
+}
+
+/// Converts a TPM public key to an OpenSSL public key
+pub fn tss_public_to_pkey(public: TssPublic) -> Result<PKey<Public>> {
+    let pem = crypto::tss_pubkey_to_pem(public)?;
+    PKey::public_key_from_pem(&pem)
+        .map_err(|source| TpmError::OpenSSLPublicKeyFromPEM { source })
+}
+
+/// Loads the public part of an attestation key
+///
+/// # Arguments
+///
+/// * data (&[u8]): The key in PEM format, or the marshalled TPM2B_PUBLIC
+///   structure sent by the agent to the registrar
+pub fn ak_pubkey_from_bytes(data: &[u8]) -> Result<PKey<Public>> {
+    if data.starts_with(b"-----BEGIN") {
+        return PKey::public_key_from_pem(data)
+            .map_err(|source| TpmError::OpenSSLPublicKeyFromPEM { source });
+    }
+    let public = TssPublic::try_from(PublicBuffer::unmarshall(data)?)?;
+    tss_public_to_pkey(public)
+}
+
+/// Verifies an EC-Schnorr signature as produced by the TPM, which OpenSSL
+/// does not implement
+///
+/// The signature is valid if r = H(E.x || digest), truncated to the size of
+/// the curve order, where E = [s]G - [r]Q.
+fn verify_ecschnorr(
+    ak_pub: &PKeyRef<Public>,
+    md: MessageDigest,
+    digest: &[u8],
+    signature: &EccSignature,
+) -> std::result::Result<bool, openssl::error::ErrorStack> {
+    let ec_key = ak_pub.ec_key()?;
+    let group = ec_key.group();
+    let mut ctx = BigNumContext::new()?;
+    let mut order = BigNum::new()?;
+    group.order(&mut order, &mut ctx)?;
+
+    // The TPM reduces r modulo the curve order when validating signatures
+    let raw_r = BigNum::from_slice(signature.signature_r().value())?;
+    let mut r = BigNum::new()?;
+    r.nnmod(&raw_r, &order, &mut ctx)?;
+    let s = BigNum::from_slice(signature.signature_s().value())?;
+    let zero = BigNum::new()?;
+    if r == zero || s == zero || s >= order {
+        return Ok(false);
+    }
 
-        // Ensure nonce is the same as given
-        let attestation: Attest = att.try_into()?;
-        if attestation.extra_data().value() != nonce {
-            return Err(TpmError::Other("nonce does not match".to_string()));
+    let mut s_g = EcPoint::new(group)?;
+    s_g.mul_generator(group, &s, &ctx)?;
+    let mut r_q = EcPoint::new(group)?;
+    r_q.mul(group, ec_key.public_key(), &r, &ctx)?;
+    r_q.invert(group, &ctx)?;
+    let mut e = EcPoint::new(group)?;
+    e.add(group, &s_g, &r_q, &mut ctx)?;
+    if e.is_infinity(group) {
+        return Ok(false);
+    }
+
+    let mut x = BigNum::new()?;
+    let mut y = BigNum::new()?;
+    e.affine_coordinates(group, &mut x, &mut y, &mut ctx)?;
+    let size = order.num_bytes();
+    let mut hasher = Hasher::new(md)?;
+    hasher.update(&x.to_vec_padded(size)?)?;
+    hasher.update(digest)?;
+    let hash = hasher.finish()?;
+    let hash = &hash[..hash.len().min(size as usize)];
+    let hash = BigNum::from_slice(hash)?;
+    let mut e = BigNum::new()?;
+    e.nnmod(&hash, &order, &mut ctx)?;
+    Ok(e == r)
+}
+
+/// Verifies the signature of the attestation with the attestation key
+fn verify_quote_signature(
+    ak_pub: &PKeyRef<Public>,
+    attestation: &[u8],
+    signature: &Signature,
+    hash_alg: HashAlgorithm,
+) -> Result<()> {
+    let got = match signature {
+        Signature::RsaSsa(sig) | Signature::RsaPss(sig) => {
+            sig.hashing_algorithm()
+        }
+        Signature::EcDsa(sig) | Signature::EcSchnorr(sig) => {
+            sig.hashing_algorithm()
+        }
+        other => {
+            return Err(TpmError::NotImplemented(format!(
+                "Verifying {:?} quote signatures",
+                other.algorithm()
+            )));
         }
+    };
+    if got != HashingAlgorithm::from(hash_alg) {
+        return Err(TpmError::QuoteHashAlgorithmMismatch {
+            expected: hash_alg,
+            got,
+        });
+    }
 
-        // Also ensure digest from quote matches PCR digest
-        let pcrbank = pcrdata
-            .pcr_bank(HashingAlgorithm::Sha256)
-            .ok_or_else(|| TpmError::Other("no SHA256 bank".to_string()))?;
-        let mut hasher = Hasher::new(MessageDigest::sha256())
-            .map_err(|source| TpmError::OpenSSLHasherNew { source })?;
-        for &sel in pcrsel.get_selections() {
-            for i in &sel.selected() {
-                if let Some(digest) = pcrbank.get_digest(*i) {
-                    hasher.update(digest.value()).map_err(|source| {
-                        TpmError::OpenSSLHasherUpdate { source }
-                    })?;
+    let md = hash_alg_to_message_digest(got)?;
+    let verify =
+        || -> std::result::Result<bool, openssl::error::ErrorStack> {
+            match (signature, ak_pub.id()) {
+                (Signature::RsaSsa(sig), Id::RSA) => {
+                    let mut verifier = Verifier::new(md, ak_pub)?;
+                    verifier.set_rsa_padding(Padding::PKCS1)?;
+                    verifier
+                        .verify_oneshot(sig.signature().value(), attestation)
                 }
-            }
-        }
-        let digest = hasher
-            .finish()
-            .map_err(|source| TpmError::OpenSSLHasherFinish { source })?;
-        let quote_info = match attestation.attested() {
-            AttestInfo::Quote { info } => info,
-            _ => {
-                return Err(TpmError::Other(format!(
-                    "Expected attestation type TPM2_ST_ATTEST_QUOTE, got {:?}",
-                    attestation.attestation_type()
-                )));
+                (Signature::RsaPss(sig), Id::RSA) => {
+                    let mut verifier = Verifier::new(md, ak_pub)?;
+                    verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+                    verifier.set_rsa_mgf1_md(md)?;
+                    // Detect the salt length, which depends on the TPM
+                    verifier
+                        .set_rsa_pss_saltlen(RsaPssSaltlen::MAXIMUM_LENGTH)?;
+                    verifier
+                        .verify_oneshot(sig.signature().value(), attestation)
+                }
+                (Signature::EcDsa(sig), Id::EC) => {
+                    let der = EcdsaSig::from_private_components(
+                        BigNum::from_slice(sig.signature_r().value())?,
+                        BigNum::from_slice(sig.signature_s().value())?,
+                    )?
+                    .to_der()?;
+                    let mut verifier = Verifier::new(md, ak_pub)?;
+                    verifier.verify_oneshot(&der, attestation)
+                }
+                (Signature::EcSchnorr(sig), Id::EC) => {
+                    let digest = openssl::hash::hash(md, attestation)?;
+                    verify_ecschnorr(ak_pub, md, &digest, sig)
+                }
+                _ => Ok(false),
             }
         };
-        if quote_info.pcr_digest().value() != digest.as_ref() {
-            return Err(TpmError::Other(
-                "PCR digest does not match".to_string(),
-            ));
+    match verify() {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(TpmError::QuoteSignatureMismatch),
+        Err(source) => {
+            // OpenSSL reports some invalid signatures as errors
+            debug!("Quote signature verification failed: {source}");
+            Err(TpmError::QuoteSignatureMismatch)
         }
+    }
+}
 
-        Ok(())
+/// Verifies a quote string generated by `quote` without a TPM
+///
+/// This performs the same checks as tpm2_checkquote, namely: signature,
+/// nonce, and PCR digests from the quote.
+///
+/// Reference:
+/// https://github.com/tpm2-software/tpm2-tools/blob/master/tools/tpm2_checkquote.c
+///
+/// # Arguments
+///
+/// * quote (&str): The quote string
+/// * ak_pub (&PKeyRef<Public>): The public part of the attestation key, see
+///   `ak_pubkey_from_bytes`
+/// * nonce (&[u8]): The nonce sent in the quote request
+/// * hash_alg (HashAlgorithm): The expected hash algorithm of the signature
+///   and of the PCR digest
+pub fn verify_quote(
+    quote: &str,
+    ak_pub: &PKeyRef<Public>,
+    nonce: &[u8],
+    hash_alg: HashAlgorithm,
+) -> Result<()> {
+    let (att, sig, pcrsel, pcrdata) = decode_quote_string(quote)?;
+
+    verify_quote_signature(ak_pub, att.value(), &sig, hash_alg)?;
+
+    // Ensure nonce is the same as given
+    let attestation: Attest = att.try_into()?;
+    if !memcmp_eq(attestation.extra_data().value(), nonce) {
+        return Err(TpmError::QuoteNonceMismatch);
+    }
+
+    let quote_info = match attestation.attested() {
+        AttestInfo::Quote { info } => info,
+        _ => {
+            return Err(TpmError::UnexpectedAttestedType {
+                expected: AttestationType::Quote,
+                got: attestation.attestation_type(),
+            })
+        }
+    };
+    // The PCR values must be the ones selected in the signed attestation
+    if *quote_info.pcr_selection() != pcrsel {
+        return Err(TpmError::QuotePcrDigestMismatch);
+    }
+
+    // Also ensure digest from quote matches PCR digest
+    let mut hasher =
+        Hasher::new(hash_alg_to_message_digest(hash_alg.into())?)
+            .map_err(|source| TpmError::OpenSSLHasherNew { source })?;
+    for selection in pcrsel.get_selections() {
+        let bank = pcrdata
+            .pcr_bank(selection.hashing_algorithm())
+            .ok_or_else(|| {
+                TpmError::InvalidRequest(format!(
+                    "Missing values of the {:?} PCR bank",
+                    selection.hashing_algorithm()
+                ))
+            })?;
+        for slot in selection.selected() {
+            let digest = bank.get_digest(slot).ok_or_else(|| {
+                TpmError::InvalidRequest(format!("Missing value of {slot:?}"))
+            })?;
+            hasher
+                .update(digest.value())
+                .map_err(|source| TpmError::OpenSSLHasherUpdate { source })?;
+        }
+    }
+    let digest = hasher
+        .finish()
+        .map_err(|source| TpmError::OpenSSLHasherFinish { source })?;
+    if !memcmp_eq(quote_info.pcr_digest().value(), &digest) {
+        return Err(TpmError::QuotePcrDigestMismatch);
+    }
+
+    Ok(())
+}
+
+/// Compares two byte slices in constant time, which memcmp::eq requires to
+/// have the same length
+fn memcmp_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && memcmp::eq(a, b)
+}
+
+pub mod testing {
+    use super::*;
+    #[cfg(feature = "testing")]
+    use tokio::sync::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard};
+
+    #[cfg(feature = "testing")]
+    pub static MUTEX: OnceLock<Arc<AsyncMutex<()>>> = OnceLock::new();
+
+    /// Initialize testing mutex
+    #[cfg(feature = "testing")]
+    pub async fn lock_tests<'a>() -> AsyncMutexGuard<'a, ()> {
+        MUTEX
+            .get_or_init(|| Arc::new(AsyncMutex::new(())))
+            .lock()
+            .await
+    }
+
+    /// Verifies a SHA-256 quote with the public part of the loaded AK, see
+    /// `verify_quote`
+    pub fn check_quote<T: TpmBackend + ?Sized>(
+        context: &mut T,
+        ak_handle: KeyHandle,
+        quote: &str,
+        nonce: &[u8],
+    ) -> Result<()> {
+        let ak_pub = tss_public_to_pkey(context.read_public(ak_handle)?)?;
+        verify_quote(quote, &ak_pub, nonce, HashAlgorithm::Sha256)
     }
 }
 
@@ -2524,6 +3220,58 @@ pub mod tests {
         assert!(r.is_err());
     }
 
+    #[test]
+    fn test_quote_pcr_selection() {
+        let banks = quote_banks(
+            HashAlgorithm::Sha256,
+            &[
+                HashAlgorithm::Sha1,
+                HashAlgorithm::Sha256,
+                HashAlgorithm::Sha1,
+            ],
+        );
+        assert_eq!(banks, vec![HashAlgorithm::Sha256, HashAlgorithm::Sha1]);
+
+        let pcrlist = quote_pcr_selection(
+            &[
+                (HashAlgorithm::Sha256, 1 << 10),
+                (HashAlgorithm::Sha1, 1 << 10),
+            ],
+            HashAlgorithm::Sha256,
+        )
+        .expect("failed to build the PCR selection");
+        let selections = pcrlist.get_selections();
+        assert_eq!(selections.len(), 2);
+        assert_eq!(
+            selections[0].hashing_algorithm(),
+            HashingAlgorithm::Sha256
+        );
+        assert_eq!(
+            selections[0].selected(),
+            vec![PcrSlot::Slot10, PcrSlot::Slot16]
+        );
+        assert_eq!(selections[1].hashing_algorithm(), HashingAlgorithm::Sha1);
+        assert_eq!(selections[1].selected(), vec![PcrSlot::Slot10]);
+
+        // PCR#16 is selected even if the bank is not
+        let pcrlist = quote_pcr_selection(
+            &[(HashAlgorithm::Sha1, 0), (HashAlgorithm::Sha384, 1)],
+            HashAlgorithm::Sha256,
+        )
+        .expect("failed to build the PCR selection");
+        let selections = pcrlist.get_selections();
+        assert_eq!(selections.len(), 2);
+        assert_eq!(
+            selections[0].hashing_algorithm(),
+            HashingAlgorithm::Sha256
+        );
+        assert_eq!(selections[0].selected(), vec![PcrSlot::Slot16]);
+        assert_eq!(
+            selections[1].hashing_algorithm(),
+            HashingAlgorithm::Sha384
+        );
+    }
+
     #[test]
     fn test_get_idevid_template() {
         let cases = [
@@ -2552,6 +3300,350 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_get_quoted_pcrs() {
+        let quote_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join("test-quote.txt");
+        let quote = std::fs::read_to_string(quote_path)
+            .expect("unable to read test-quote.txt");
+
+        let banks =
+            get_quoted_pcrs(quote.trim_end()).expect("unable to get PCRs");
+        assert_eq!(banks.len(), 1);
+        let (hash_alg, values) = &banks[0];
+        assert_eq!(*hash_alg, HashAlgorithm::Sha1);
+        assert!(!values.is_empty());
+        assert!(values.values().all(|v| v.len() == 20));
+
+        assert!(get_quoted_pcrs("AA==:AA==:AA==").is_err());
+        assert!(get_quoted_pcrs("rAA==:AA==").is_err());
+    }
+
+    #[test]
+    fn test_get_quote_clock_info() {
+        let quote_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join("test-quote.txt");
+        let quote = std::fs::read_to_string(quote_path)
+            .expect("unable to read test-quote.txt");
+
+        let clock_info = get_quote_clock_info(quote.trim_end())
+            .expect("unable to get the clock information");
+        assert_eq!(clock_info.clock(), 3294160);
+        assert_eq!(clock_info.reset_count(), 0);
+        assert_eq!(clock_info.restart_count(), 0);
+        assert!(clock_info.safe());
+
+        assert!(get_quote_clock_info("rAA==:AA==").is_err());
+    }
+
+    fn rsa_sign(
+        key: &PKeyRef<openssl::pkey::Private>,
+        pss: bool,
+        data: &[u8],
+    ) -> Signature {
+        use openssl::sign::Signer;
+        use tss_esapi::structures::RsaSignature;
+
+        let mut signer = Signer::new(MessageDigest::sha1(), key).unwrap(); //#[allow_ci]
+        if pss {
+            signer.set_rsa_padding(Padding::PKCS1_PSS).unwrap(); //#[allow_ci]
+            signer
+                .set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)
+                .unwrap(); //#[allow_ci]
+        }
+        let sig = RsaSignature::create(
+            HashingAlgorithm::Sha1,
+            PublicKeyRsa::try_from(signer.sign_oneshot_to_vec(data).unwrap()) //#[allow_ci]
+                .unwrap(), //#[allow_ci]
+        )
+        .unwrap(); //#[allow_ci]
+        if pss {
+            Signature::RsaPss(sig)
+        } else {
+            Signature::RsaSsa(sig)
+        }
+    }
+
+    fn public_only(key: &PKeyRef<openssl::pkey::Private>) -> PKey<Public> {
+        PKey::public_key_from_der(&key.public_key_to_der().unwrap()) //#[allow_ci]
+            .unwrap() //#[allow_ci]
+    }
+
+    #[test]
+    fn test_verify_quote_signature_rsa() {
+        use openssl::rsa::Rsa;
+
+        let data = b"attestation";
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap(); //#[allow_ci]
+        let other = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap(); //#[allow_ci]
+        let ak_pub = public_only(&key);
+
+        for pss in [false, true] {
+            let sig = rsa_sign(&key, pss, data);
+            let r = verify_quote_signature(
+                &ak_pub,
+                data,
+                &sig,
+                HashAlgorithm::Sha1,
+            );
+            assert!(r.is_ok(), "Result: {r:?}");
+
+            let r = verify_quote_signature(
+                &ak_pub,
+                data,
+                &sig,
+                HashAlgorithm::Sha256,
+            );
+            assert!(matches!(
+                r,
+                Err(TpmError::QuoteHashAlgorithmMismatch { .. })
+            ));
+
+            let r = verify_quote_signature(
+                &public_only(&other),
+                data,
+                &sig,
+                HashAlgorithm::Sha1,
+            );
+            assert!(matches!(r, Err(TpmError::QuoteSignatureMismatch)));
+
+            let r = verify_quote_signature(
+                &ak_pub,
+                b"other data",
+                &sig,
+                HashAlgorithm::Sha1,
+            );
+            assert!(matches!(r, Err(TpmError::QuoteSignatureMismatch)));
+        }
+    }
+
+    #[test]
+    fn test_verify_quote_signature_ecc() {
+        use openssl::{
+            bn::BigNumRef,
+            ec::{EcGroup, EcKey},
+            nid::Nid,
+        };
+
+        let data = b"attestation";
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap(); //#[allow_ci]
+        let key = EcKey::generate(&group).unwrap(); //#[allow_ci]
+        let ak_pub = public_only(&PKey::from_ec_key(key.clone()).unwrap()); //#[allow_ci]
+        let digest =
+            openssl::hash::hash(MessageDigest::sha1(), data).unwrap(); //#[allow_ci]
+        let ecc_signature = |r: &BigNumRef, s: &BigNumRef| {
+            EccSignature::create(
+                HashingAlgorithm::Sha1,
+                EccParameter::try_from(r.to_vec()).unwrap(), //#[allow_ci]
+                EccParameter::try_from(s.to_vec()).unwrap(), //#[allow_ci]
+            )
+            .unwrap() //#[allow_ci]
+        };
+
+        let sig = EcdsaSig::sign(&digest, &key).unwrap(); //#[allow_ci]
+        let sig = Signature::EcDsa(ecc_signature(sig.r(), sig.s()));
+        let r =
+            verify_quote_signature(&ak_pub, data, &sig, HashAlgorithm::Sha1);
+        assert!(r.is_ok(), "Result: {r:?}");
+        let r = verify_quote_signature(
+            &ak_pub,
+            b"other data",
+            &sig,
+            HashAlgorithm::Sha1,
+        );
+        assert!(matches!(r, Err(TpmError::QuoteSignatureMismatch)));
+
+        // EC-Schnorr: r = H(R.x || digest), s = k + r * d (mod n)
+        let mut ctx = BigNumContext::new().unwrap(); //#[allow_ci]
+        let mut order = BigNum::new().unwrap(); //#[allow_ci]
+        group.order(&mut order, &mut ctx).unwrap(); //#[allow_ci]
+        let k = EcKey::generate(&group).unwrap(); //#[allow_ci]
+        let mut x = BigNum::new().unwrap(); //#[allow_ci]
+        let mut y = BigNum::new().unwrap(); //#[allow_ci]
+        k.public_key()
+            .affine_coordinates(&group, &mut x, &mut y, &mut ctx)
+            .unwrap(); //#[allow_ci]
+        let mut hasher = Hasher::new(MessageDigest::sha1()).unwrap(); //#[allow_ci]
+        hasher
+            .update(&x.to_vec_padded(order.num_bytes()).unwrap()) //#[allow_ci]
+            .unwrap(); //#[allow_ci]
+        hasher.update(&digest).unwrap(); //#[allow_ci]
+        let r = BigNum::from_slice(&hasher.finish().unwrap()).unwrap(); //#[allow_ci]
+        let mut rd = BigNum::new().unwrap(); //#[allow_ci]
+        rd.mod_mul(&r, key.private_key(), &order, &mut ctx).unwrap(); //#[allow_ci]
+        let mut s = BigNum::new().unwrap(); //#[allow_ci]
+        s.mod_add(k.private_key(), &rd, &order, &mut ctx).unwrap(); //#[allow_ci]
+        let sig = Signature::EcSchnorr(ecc_signature(&r, &s));
+        let r =
+            verify_quote_signature(&ak_pub, data, &sig, HashAlgorithm::Sha1);
+        assert!(r.is_ok(), "Result: {r:?}");
+        let r = verify_quote_signature(
+            &ak_pub,
+            b"other data",
+            &sig,
+            HashAlgorithm::Sha1,
+        );
+        assert!(matches!(r, Err(TpmError::QuoteSignatureMismatch)));
+
+        // The signature scheme must match the key
+        let rsa = openssl::rsa::Rsa::generate(2048).unwrap(); //#[allow_ci]
+        let r = verify_quote_signature(
+            &public_only(&PKey::from_rsa(rsa).unwrap()), //#[allow_ci]
+            data,
+            &sig,
+            HashAlgorithm::Sha1,
+        );
+        assert!(matches!(r, Err(TpmError::QuoteSignatureMismatch)));
+    }
+
+    #[test]
+    fn test_verify_ecschnorr_known_answer() {
+        use openssl::{
+            ec::{EcGroup, EcKey},
+            nid::Nid,
+        };
+
+        // P-256 key and signature of SHA-256("attestation") computed as in
+        // the TPM reference implementation: r = H(R.x || digest) mod n and
+        // s = k + r * d mod n
+        let hex_bn = |h: &str| BigNum::from_hex_str(h).unwrap(); //#[allow_ci]
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap(); //#[allow_ci]
+        let key = EcKey::from_public_key_affine_coordinates(
+            &group,
+            &hex_bn("500fc614aa694460b727beb03f21913ec13ed451f051ba62c8300aa041e6ba19"),
+            &hex_bn("86d5d1942bb4aaf2ad500a58be69c09e84a58695e2ab669cf27b450fb105d3d6"),
+        )
+        .unwrap(); //#[allow_ci]
+        let ak_pub = PKey::from_ec_key(key).unwrap(); //#[allow_ci]
+        let r = "2568a7d410d502ce7baf58cacaa00ef0ac2c9e0418a4e4cc9eedb1b769d3ab1e";
+        let s = "8d50cb9707f716b3903ac1595f5616da1432760edc9158579775f287f9a36d79";
+        let signature = |r: &str, s: &str| {
+            Signature::EcSchnorr(
+                EccSignature::create(
+                    HashingAlgorithm::Sha256,
+                    EccParameter::try_from(hex::decode(r).unwrap()).unwrap(), //#[allow_ci]
+                    EccParameter::try_from(hex::decode(s).unwrap()).unwrap(), //#[allow_ci]
+                )
+                .unwrap(), //#[allow_ci]
+            )
+        };
+
+        let sig = signature(r, s);
+        let res = verify_quote_signature(
+            &ak_pub,
+            b"attestation",
+            &sig,
+            HashAlgorithm::Sha256,
+        );
+        assert!(res.is_ok(), "Result: {res:?}");
+        let res = verify_quote_signature(
+            &ak_pub,
+            b"other data",
+            &sig,
+            HashAlgorithm::Sha256,
+        );
+        assert!(matches!(res, Err(TpmError::QuoteSignatureMismatch)));
+
+        // r is reduced modulo the curve order: r + n is the same signature
+        let sig = signature(
+            "012568a7d310d502cf7baf58cacaa00ef0691398b1bfbc835192a77c7a6636d06f",
+            s,
+        );
+        let res = verify_quote_signature(
+            &ak_pub,
+            b"attestation",
+            &sig,
+            HashAlgorithm::Sha256,
+        );
+        assert!(res.is_ok(), "Result: {res:?}");
+    }
+
+    #[test]
+    fn test_verify_quote() {
+        use openssl::rsa::Rsa;
+
+        // The quote in test-data quotes PCR 0 of the SHA-1 bank with the
+        // nonce "TEST". Replace its signature with one from a new key
+        let quote_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join("test-quote.txt");
+        let quote = std::fs::read_to_string(quote_path)
+            .expect("unable to read test-quote.txt");
+        let parts: Vec<&str> = quote.trim_end()[1..].split(':').collect();
+        let att = general_purpose::STANDARD.decode(parts[0]).unwrap(); //#[allow_ci]
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap(); //#[allow_ci]
+        let ak_pub = public_only(&key);
+        let sig = rsa_sign(&key, false, &att).marshall().unwrap(); //#[allow_ci]
+        let sig = general_purpose::STANDARD.encode(sig);
+
+        let quote = format!("r{}:{sig}:{}", parts[0], parts[2]);
+        let r = verify_quote(&quote, &ak_pub, b"TEST", HashAlgorithm::Sha1);
+        assert!(r.is_ok(), "Result: {r:?}");
+
+        let r = verify_quote(&quote, &ak_pub, b"TSET", HashAlgorithm::Sha1);
+        assert!(matches!(r, Err(TpmError::QuoteNonceMismatch)));
+
+        // Modify the value of PCR 0 in the PCR blob, which is not signed
+        let mut pcrs = general_purpose::STANDARD.decode(parts[2]).unwrap(); //#[allow_ci]
+        pcrs[TPML_PCR_SELECTION_SIZE + 10] ^= 0xff;
+        let quote = format!(
+            "r{}:{sig}:{}",
+            parts[0],
+            general_purpose::STANDARD.encode(pcrs)
+        );
+        let r = verify_quote(&quote, &ak_pub, b"TEST", HashAlgorithm::Sha1);
+        assert!(matches!(r, Err(TpmError::QuotePcrDigestMismatch)));
+    }
+
+    #[test]
+    fn test_ak_pubkey_from_pem() {
+        let rsa = openssl::rsa::Rsa::generate(2048).unwrap(); //#[allow_ci]
+        let key = PKey::from_rsa(rsa).unwrap(); //#[allow_ci]
+
+        let pem = key.public_key_to_pem().unwrap(); //#[allow_ci]
+        let ak_pub = ak_pubkey_from_bytes(&pem).unwrap(); //#[allow_ci]
+        assert!(ak_pub.public_eq(&key));
+
+        assert!(ak_pubkey_from_bytes(b"-----BEGIN invalid").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "testing")]
+    fn test_ak_pubkey_from_tpm2b_public() {
+        let rsa = openssl::rsa::Rsa::generate(2048).unwrap(); //#[allow_ci]
+        let public = PublicBuilder::new()
+            .with_public_algorithm(PublicAlgorithm::Rsa)
+            .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+            .with_object_attributes(
+                ObjectAttributesBuilder::new()
+                    .with_sign_encrypt(true)
+                    .build()
+                    .unwrap(), //#[allow_ci]
+            )
+            .with_rsa_parameters(
+                PublicRsaParametersBuilder::new()
+                    .with_scheme(RsaScheme::Null)
+                    .with_key_bits(RsaKeyBits::Rsa2048)
+                    .with_exponent(RsaExponent::default())
+                    .with_is_signing_key(true)
+                    .build()
+                    .unwrap(), //#[allow_ci]
+            )
+            .with_rsa_unique_identifier(
+                PublicKeyRsa::try_from(rsa.n().to_vec()).unwrap(), //#[allow_ci]
+            )
+            .build()
+            .unwrap(); //#[allow_ci]
+        let tpm2b =
+            PublicBuffer::try_from(public).unwrap().marshall().unwrap(); //#[allow_ci]
+
+        let ak_pub = ak_pubkey_from_bytes(&tpm2b).unwrap(); //#[allow_ci]
+        assert!(ak_pub.public_eq(&PKey::from_rsa(rsa).unwrap())); //#[allow_ci]
+        assert!(ak_pubkey_from_bytes(b"invalid").is_err());
+    }
+
     #[test]
     #[cfg(feature = "testing")]
     fn test_quote_encode_decode() {
@@ -2566,8 +3658,8 @@ pub mod tests {
         let _ = f.read_line(&mut buf).expect("unable to read quote");
         let buf = buf.trim_end();
 
-        let (att, sig, pcrsel, pcrdata) = testing::decode_quote_string(buf)
-            .expect("unable to decode quote");
+        let (att, sig, pcrsel, pcrdata) =
+            decode_quote_string(buf).expect("unable to decode quote");
 
         let attestation: Attest =
             att.try_into().expect("unable to unmarshal attestation");
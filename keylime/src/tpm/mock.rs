@@ -0,0 +1,959 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+
+// Software implementation of the TPM operations used by the agent.
+
+use super::{
+    encode_quote_string, hash_alg_to_message_digest, make_credential_blob,
+    memcmp_eq, parse_cred_and_secret, pcr_data_from_digests, pubkey_digest,
+    quote_pcr_selection, AKResult, EKResult, Result, TpmBackend, TpmError,
+};
+use crate::{
+    algorithms::{EncryptionAlgorithm, HashAlgorithm, SignAlgorithm},
+    crypto,
+};
+use openssl::{
+    bn::BigNum,
+    ec::EcGroup,
+    ecdsa::EcdsaSig,
+    error::ErrorStack,
+    hash::{hash, MessageDigest},
+    md::Md,
+    nid::Nid,
+    pkey::{Id, PKey, PKeyRef, Private, Public},
+    pkey_ctx::PkeyCtx,
+    rand::rand_bytes,
+    rsa::Padding,
+    sign::{RsaPssSaltlen, Signer},
+    symm::{decrypt, encrypt, Cipher},
+};
+use std::{collections::HashMap, convert::TryFrom, time::Instant};
+use tss_esapi::{
+    abstraction::{ek, DefaultKey},
+    attributes::ObjectAttributesBuilder,
+    constants::tss::{
+        TPM2_GENERATED_VALUE, TPM2_ST_ATTEST_CERTIFY, TPM2_ST_ATTEST_QUOTE,
+    },
+    handles::{KeyHandle, ObjectHandle},
+    interface_types::{
+        algorithm::{HashingAlgorithm, PublicAlgorithm, RsaSchemeAlgorithm},
+        ecc::EccCurve,
+        key_bits::RsaKeyBits,
+    },
+    structures::{
+        Attest, Data, Digest, EccParameter, EccPoint, EccScheme,
+        EccSignature, EncryptedSecret, HashScheme, IdObject,
+        KeyDerivationFunctionScheme, Name, Private as TssPrivate,
+        Public as TssPublic, PublicBuilder, PublicEccParametersBuilder,
+        PublicKeyRsa, PublicRsaParametersBuilder, RsaExponent, RsaScheme,
+        RsaSignature, Signature, SymmetricDefinitionObject,
+    },
+    traits::Marshall,
+    tss2_esys::{
+        ESYS_TR, TPMS_ATTEST, TPMS_CERTIFY_INFO, TPMS_CLOCK_INFO,
+        TPMS_QUOTE_INFO, TPMU_ATTEST,
+    },
+};
+
+/// First handle assigned to the objects loaded in the mock TPM, in the range
+/// of the transient objects
+const FIRST_HANDLE: ESYS_TR = 0x8000_0000;
+
+/// Number of PCRs in each bank
+const NUM_PCRS: usize = 24;
+
+/// Size in bits of the symmetric key protecting the credentials, which is
+/// the AES-128 of the default EK templates
+const CREDENTIAL_KEY_BITS: u32 = 128;
+
+/// Label of the encrypted seed of MakeCredential, including the terminating
+/// NULL octet
+const IDENTITY_LABEL: &[u8] = b"IDENTITY\0";
+
+/// Object loaded in the mock TPM
+#[derive(Debug)]
+struct MockObject {
+    key: PKey<Private>,
+    public: TssPublic,
+}
+
+/// Software TPM implementing the operations used by the agent, for testing
+/// it without a TPM
+///
+/// The keys are generated with OpenSSL and only exist in memory, and the
+/// PCRs of every bank start zeroed. Quotes and credential blobs use the same
+/// formats as a TPM, so quotes can be checked with `verify_quote`, and the
+/// credentials made for the EK can only be activated by this mock TPM.
+///
+/// Only RSA EKs can be used to make and activate credentials, and EC-Schnorr
+/// signatures are not supported.
+#[derive(Debug)]
+pub struct MockTpm {
+    objects: HashMap<ESYS_TR, MockObject>,
+    next_handle: ESYS_TR,
+    pcrs: HashMap<HashingAlgorithm, Vec<Vec<u8>>>,
+    started: Instant,
+}
+
+impl Default for MockTpm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockTpm {
+    /// Creates a mock TPM without objects and with zeroed PCRs
+    pub fn new() -> Self {
+        MockTpm {
+            objects: HashMap::new(),
+            next_handle: FIRST_HANDLE,
+            pcrs: HashMap::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Extends the PCR with the given index of a bank with `digest`
+    pub fn pcr_extend(
+        &mut self,
+        hash_alg: HashAlgorithm,
+        index: usize,
+        digest: &[u8],
+    ) -> Result<()> {
+        let md = hash_alg_to_message_digest(hash_alg.into())?;
+        let pcr =
+            self.bank(hash_alg.into())?.get_mut(index).ok_or_else(|| {
+                TpmError::InvalidRequest(format!("Invalid PCR index {index}"))
+            })?;
+        let extended = hash(md, &[pcr.as_slice(), digest].concat())
+            .map_err(|source| TpmError::OpenSSLHasherFinish { source })?;
+        *pcr = extended.to_vec();
+        Ok(())
+    }
+
+    /// Gets the PCRs of a bank, which is zeroed on first use
+    fn bank(
+        &mut self,
+        hash_alg: HashingAlgorithm,
+    ) -> Result<&mut Vec<Vec<u8>>> {
+        let size = hash_alg_to_message_digest(hash_alg)?.size();
+        Ok(self
+            .pcrs
+            .entry(hash_alg)
+            .or_insert_with(|| vec![vec![0; size]; NUM_PCRS]))
+    }
+
+    fn object(&self, handle: KeyHandle) -> Result<&MockObject> {
+        let handle = ESYS_TR::from(handle);
+        self.objects
+            .get(&handle)
+            .ok_or(TpmError::UnknownHandle(handle))
+    }
+
+    fn load(&mut self, object: MockObject) -> KeyHandle {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        let _ = self.objects.insert(handle, object);
+        KeyHandle::from(handle)
+    }
+
+    /// Builds and signs an attestation structure
+    fn attest(
+        &self,
+        signer: KeyHandle,
+        extra_data: &[u8],
+        attested: (u16, TPMU_ATTEST),
+        hash_alg: HashAlgorithm,
+        sign_alg: SignAlgorithm,
+    ) -> Result<(Attest, Signature)> {
+        let signer = self.object(signer)?;
+        let (type_, attested) = attested;
+        let attestation = Attest::try_from(TPMS_ATTEST {
+            magic: TPM2_GENERATED_VALUE,
+            type_,
+            qualifiedSigner: object_name(&signer.public)?.into(),
+            extraData: Data::try_from(extra_data)
+                .map_err(|_| TpmError::DataFromNonce)?
+                .into(),
+            clockInfo: TPMS_CLOCK_INFO {
+                clock: u64::try_from(self.started.elapsed().as_millis())?,
+                resetCount: 0,
+                restartCount: 0,
+                safe: 1,
+            },
+            firmwareVersion: 0,
+            attested,
+        })?;
+        let data = attestation
+            .marshall()
+            .map_err(|source| TpmError::TSSMarshallAttestError { source })?;
+        let signature = sign(&signer.key, &data, hash_alg, sign_alg)?;
+        Ok((attestation, signature))
+    }
+}
+
+impl TpmBackend for MockTpm {
+    fn create_ek(
+        &mut self,
+        alg: EncryptionAlgorithm,
+        handle: Option<&str>,
+    ) -> Result<EKResult> {
+        if handle.is_some_and(|handle| !handle.is_empty()) {
+            return Err(TpmError::NotImplemented(
+                "Using a persisted EK in the mock TPM".to_string(),
+            ));
+        }
+        let template = ek::create_ek_public_from_default_template(
+            alg.into(),
+            DefaultKey,
+        )
+        .map_err(|source| TpmError::TSSCreateEKError { source })?;
+        let object = generate_object(template)?;
+        let public = object.public.clone();
+        Ok(EKResult {
+            key_handle: self.load(object),
+            ek_cert: None,
+            public,
+            ek_chain: None,
+        })
+    }
+
+    fn create_ak(
+        &mut self,
+        handle: KeyHandle,
+        hash_alg: HashAlgorithm,
+        sign_alg: SignAlgorithm,
+    ) -> Result<AKResult> {
+        let _ = self.object(handle)?;
+        let object = generate_object(ak_template(hash_alg, sign_alg)?)?;
+        let der = object
+            .key
+            .private_key_to_der()
+            .map_err(|source| TpmError::OpenSSLCipher { source })?;
+        Ok(AKResult {
+            public: object.public,
+            private: TssPrivate::try_from(der)?,
+        })
+    }
+
+    fn load_ak(
+        &mut self,
+        handle: KeyHandle,
+        ak: &AKResult,
+    ) -> Result<KeyHandle> {
+        let _ = self.object(handle)?;
+        let key = PKey::private_key_from_der(ak.private.value())
+            .map_err(|source| TpmError::OpenSSLCipher { source })?;
+        if with_unique(ak.public.clone(), &key)? != ak.public {
+            return Err(TpmError::InvalidRequest(
+                "The private and public parts of the AK do not match"
+                    .to_string(),
+            ));
+        }
+        Ok(self.load(MockObject {
+            key,
+            public: ak.public.clone(),
+        }))
+    }
+
+    fn read_public(&mut self, handle: KeyHandle) -> Result<TssPublic> {
+        Ok(self.object(handle)?.public.clone())
+    }
+
+    fn quote(
+        &mut self,
+        nonce: &[u8],
+        selection: &[(HashAlgorithm, u32)],
+        pubkey: &PKeyRef<Public>,
+        ak_handle: KeyHandle,
+        hash_alg: HashAlgorithm,
+        sign_alg: SignAlgorithm,
+    ) -> Result<String> {
+        // Reset PCR#16 and extend the NK digest into it, like a TPM quote
+        let nk_digest = pubkey_digest(pubkey, hash_alg)?;
+        let size = nk_digest.len();
+        self.bank(hash_alg.into())?[16] = vec![0; size];
+        self.pcr_extend(hash_alg, 16, nk_digest.value())?;
+
+        let pcrlist = quote_pcr_selection(selection, hash_alg)?;
+        let md = hash_alg_to_message_digest(hash_alg.into())?;
+        let mut quoted = Vec::new();
+        let mut digests = Vec::new();
+        for selection in pcrlist.get_selections() {
+            let bank = self.bank(selection.hashing_algorithm())?;
+            for slot in selection.selected() {
+                let index = u32::from(slot).trailing_zeros() as usize;
+                quoted.extend(&bank[index]);
+                digests.push(Digest::try_from(bank[index].as_slice())?);
+            }
+        }
+        let pcr_data = pcr_data_from_digests(&pcrlist, digests)?;
+        let pcr_digest = hash(md, &quoted)
+            .map_err(|source| TpmError::OpenSSLHasherFinish { source })?;
+
+        let quote_info = TPMU_ATTEST {
+            quote: TPMS_QUOTE_INFO {
+                pcrSelect: pcrlist.clone().into(),
+                pcrDigest: Digest::try_from(pcr_digest.as_ref())?.into(),
+            },
+        };
+        let (attestation, signature) = self.attest(
+            ak_handle,
+            nonce,
+            (TPM2_ST_ATTEST_QUOTE, quote_info),
+            hash_alg,
+            sign_alg,
+        )?;
+        encode_quote_string(attestation, signature, pcrlist, pcr_data)
+    }
+
+    fn activate_credential(
+        &mut self,
+        keyblob: Vec<u8>,
+        ak: KeyHandle,
+        ek: KeyHandle,
+    ) -> Result<Digest> {
+        let (credential, secret) = parse_cred_and_secret(keyblob)?;
+        let ek = self.object(ek)?;
+        let md = credential_digest(ek)?;
+        let name = object_name(&self.object(ak)?.public)?;
+
+        let mut ctx = PkeyCtx::new(&ek.key)
+            .map_err(|source| TpmError::OpenSSLCipher { source })?;
+        let mut seed = Vec::new();
+        ctx.decrypt_init()
+            .and_then(|_| set_identity_padding(&mut ctx, md))
+            .and_then(|_| ctx.decrypt_to_vec(secret.value(), &mut seed))
+            .map_err(|source| TpmError::OpenSSLCipher { source })?;
+
+        // The credential is a TPM2B_DIGEST with the HMAC of the encrypted
+        // identity, followed by the encrypted identity
+        let id_object = credential.value();
+        let (hmac, enc_identity) = id_object
+            .get(..2)
+            .map(|size| u16::from_be_bytes([size[0], size[1]]) as usize)
+            .and_then(|size| id_object[2..].split_at_checked(size))
+            .ok_or(TpmError::KeyblobParseCredential)?;
+        let (sym_key, hmac_key) = credential_keys(md, &seed, &name)
+            .map_err(|source| TpmError::OpenSSLSign { source })?;
+        let expected_hmac =
+            hmac_of(md, &hmac_key, &[enc_identity, name.value()])
+                .map_err(|source| TpmError::OpenSSLSign { source })?;
+        if !memcmp_eq(&expected_hmac, hmac) {
+            return Err(TpmError::CredentialIntegrityMismatch);
+        }
+
+        let identity = decrypt(
+            Cipher::aes_128_cfb128(),
+            &sym_key,
+            Some(&[0; 16]),
+            enc_identity,
+        )
+        .map_err(|source| TpmError::OpenSSLCipher { source })?;
+        let digest = identity
+            .get(..2)
+            .map(|size| u16::from_be_bytes([size[0], size[1]]) as usize)
+            .and_then(|size| identity.get(2..2 + size))
+            .ok_or(TpmError::KeyblobParseCredential)?;
+        Ok(Digest::try_from(digest)?)
+    }
+
+    fn certify_credential_with_iak(
+        &mut self,
+        qualifying_data: Data,
+        ak: KeyHandle,
+        iak: KeyHandle,
+    ) -> Result<(Attest, Signature)> {
+        let name = object_name(&self.object(ak)?.public)?;
+        let iak_object = self.object(iak)?;
+        let hash_alg = HashAlgorithm::try_from(
+            iak_object.public.name_hashing_algorithm(),
+        )?;
+        let sign_alg = match iak_object.key.id() {
+            Id::RSA => SignAlgorithm::RsaSsa,
+            _ => SignAlgorithm::EcDsa,
+        };
+        // The mock TPM has no hierarchies, so the qualified name of the AK
+        // is its name
+        let certify_info = TPMU_ATTEST {
+            certify: TPMS_CERTIFY_INFO {
+                name: name.clone().into(),
+                qualifiedName: name.into(),
+            },
+        };
+        self.attest(
+            iak,
+            qualifying_data.value(),
+            (TPM2_ST_ATTEST_CERTIFY, certify_info),
+            hash_alg,
+            sign_alg,
+        )
+    }
+
+    fn make_credential(
+        &mut self,
+        ek_handle: KeyHandle,
+        credential: Digest,
+        name: Name,
+    ) -> Result<Vec<u8>> {
+        let ek = self.object(ek_handle)?;
+        let md = credential_digest(ek)?;
+
+        let mut seed = vec![0; md.size()];
+        rand_bytes(&mut seed)
+            .map_err(|source| TpmError::OpenSSLCipher { source })?;
+        let mut ctx = PkeyCtx::new(&ek.key)
+            .map_err(|source| TpmError::OpenSSLCipher { source })?;
+        let mut secret = Vec::new();
+        ctx.encrypt_init()
+            .and_then(|_| set_identity_padding(&mut ctx, md))
+            .and_then(|_| ctx.encrypt_to_vec(&seed, &mut secret))
+            .map_err(|source| TpmError::OpenSSLCipher { source })?;
+
+        // The identity is the credential as a TPM2B_DIGEST
+        let size = u16::try_from(credential.len())?;
+        let identity = [&size.to_be_bytes(), credential.value()].concat();
+        let (sym_key, hmac_key) = credential_keys(md, &seed, &name)
+            .map_err(|source| TpmError::OpenSSLSign { source })?;
+        let enc_identity = encrypt(
+            Cipher::aes_128_cfb128(),
+            &sym_key,
+            Some(&[0; 16]),
+            &identity,
+        )
+        .map_err(|source| TpmError::OpenSSLCipher { source })?;
+        let hmac = hmac_of(md, &hmac_key, &[&enc_identity, name.value()])
+            .map_err(|source| TpmError::OpenSSLSign { source })?;
+
+        let hmac_size = u16::try_from(hmac.len())?;
+        let id_object =
+            [&hmac_size.to_be_bytes(), hmac.as_slice(), &enc_identity]
+                .concat();
+        make_credential_blob(
+            &IdObject::try_from(id_object)?,
+            &EncryptedSecret::try_from(secret)?,
+        )
+    }
+
+    fn flush_context(&mut self, handle: ObjectHandle) -> Result<()> {
+        let handle = ESYS_TR::from(handle);
+        match self.objects.remove(&handle) {
+            Some(_) => Ok(()),
+            None => Err(TpmError::UnknownHandle(handle)),
+        }
+    }
+}
+
+/// Gets the template of an AK, like the one of
+/// `tss_esapi::abstraction::ak::create_ak` but using the NIST P-256 curve for
+/// ECC keys
+fn ak_template(
+    hash_alg: HashAlgorithm,
+    sign_alg: SignAlgorithm,
+) -> Result<TssPublic> {
+    let obj_attrs = ObjectAttributesBuilder::new()
+        .with_restricted(true)
+        .with_user_with_auth(true)
+        .with_sign_encrypt(true)
+        .with_decrypt(false)
+        .with_fixed_tpm(true)
+        .with_fixed_parent(true)
+        .with_sensitive_data_origin(true)
+        .build()
+        .map_err(|source| TpmError::TSSObjectAttributesBuildError {
+            source,
+        })?;
+    let hash_alg = HashingAlgorithm::from(hash_alg);
+
+    let builder = match sign_alg {
+        SignAlgorithm::RsaSsa | SignAlgorithm::RsaPss => {
+            let scheme = if sign_alg == SignAlgorithm::RsaSsa {
+                RsaSchemeAlgorithm::RsaSsa
+            } else {
+                RsaSchemeAlgorithm::RsaPss
+            };
+            PublicBuilder::new()
+                .with_public_algorithm(PublicAlgorithm::Rsa)
+                .with_rsa_parameters(
+                    PublicRsaParametersBuilder::new()
+                        .with_scheme(RsaScheme::create(
+                            scheme,
+                            Some(hash_alg),
+                        )?)
+                        .with_key_bits(RsaKeyBits::Rsa2048)
+                        .with_exponent(RsaExponent::default())
+                        .with_is_signing_key(true)
+                        .with_restricted(true)
+                        .build()
+                        .map_err(|source| {
+                            TpmError::TSSPublicRSAParametersBuildError {
+                                source,
+                            }
+                        })?,
+                )
+                .with_rsa_unique_identifier(PublicKeyRsa::default())
+        }
+        SignAlgorithm::EcDsa => PublicBuilder::new()
+            .with_public_algorithm(PublicAlgorithm::Ecc)
+            .with_ecc_parameters(
+                PublicEccParametersBuilder::new()
+                    .with_symmetric(SymmetricDefinitionObject::Null)
+                    .with_ecc_scheme(EccScheme::EcDsa(HashScheme::new(
+                        hash_alg,
+                    )))
+                    .with_curve(EccCurve::NistP256)
+                    .with_key_derivation_function_scheme(
+                        KeyDerivationFunctionScheme::Null,
+                    )
+                    .with_is_signing_key(true)
+                    .with_restricted(true)
+                    .build()
+                    .map_err(|source| {
+                        TpmError::TSSPublicECCParametersBuildError { source }
+                    })?,
+            )
+            .with_ecc_unique_identifier(EccPoint::default()),
+        other => {
+            return Err(TpmError::NotImplemented(format!(
+                "{other} AKs in the mock TPM"
+            )));
+        }
+    };
+
+    builder
+        .with_name_hashing_algorithm(hash_alg)
+        .with_object_attributes(obj_attrs)
+        .build()
+        .map_err(|source| TpmError::TSSCreateAKError { source })
+}
+
+/// Generates a key for the template and sets its public part as the unique
+/// identifier
+fn generate_object(template: TssPublic) -> Result<MockObject> {
+    let (_, key) = match &template {
+        TssPublic::Rsa { parameters, .. } => crypto::rsa_generate_pair(
+            u32::from(u16::from(parameters.key_bits())),
+        )?,
+        TssPublic::Ecc { parameters, .. } => {
+            let nid = match parameters.ecc_curve() {
+                EccCurve::NistP256 => Nid::X9_62_PRIME256V1,
+                EccCurve::NistP384 => Nid::SECP384R1,
+                other => {
+                    return Err(TpmError::NotImplemented(format!(
+                        "{other:?} keys in the mock TPM"
+                    )));
+                }
+            };
+            let group = EcGroup::from_curve_name(nid).map_err(|source| {
+                crypto::CryptoError::ECGeneratePrivateKeyError(source)
+            })?;
+            crypto::ecc_generate_pair(&group)?
+        }
+        _ => {
+            return Err(TpmError::UnsupportedKeyType(
+                "only RSA and ECC keys are supported".to_string(),
+            ));
+        }
+    };
+    let public = with_unique(template, &key)?;
+    Ok(MockObject { key, public })
+}
+
+/// Sets the public part of the key as the unique identifier of the template
+fn with_unique(
+    template: TssPublic,
+    key: &PKeyRef<Private>,
+) -> Result<TssPublic> {
+    match template {
+        TssPublic::Rsa {
+            object_attributes,
+            name_hashing_algorithm,
+            auth_policy,
+            parameters,
+            ..
+        } => {
+            let rsa = key
+                .rsa()
+                .map_err(|source| TpmError::OpenSSLRSAFromPKey { source })?;
+            Ok(TssPublic::Rsa {
+                object_attributes,
+                name_hashing_algorithm,
+                auth_policy,
+                parameters,
+                unique: PublicKeyRsa::try_from(rsa.n().to_vec())?,
+            })
+        }
+        TssPublic::Ecc {
+            object_attributes,
+            name_hashing_algorithm,
+            auth_policy,
+            parameters,
+            ..
+        } => {
+            let (x, y) = ec_coordinates(key)
+                .map_err(|source| TpmError::OpenSSLCipher { source })?;
+            Ok(TssPublic::Ecc {
+                object_attributes,
+                name_hashing_algorithm,
+                auth_policy,
+                parameters,
+                unique: EccPoint::new(
+                    EccParameter::try_from(x)?,
+                    EccParameter::try_from(y)?,
+                ),
+            })
+        }
+        _ => Err(TpmError::UnsupportedKeyType(
+            "only RSA and ECC keys are supported".to_string(),
+        )),
+    }
+}
+
+/// Gets the affine coordinates of the public point of an EC key, padded to
+/// the size of the curve
+fn ec_coordinates(
+    key: &PKeyRef<Private>,
+) -> std::result::Result<(Vec<u8>, Vec<u8>), openssl::error::ErrorStack> {
+    let ec_key = key.ec_key()?;
+    let group = ec_key.group();
+    let size = group.degree().div_ceil(8) as i32;
+    let mut ctx = openssl::bn::BigNumContext::new()?;
+    let mut x = BigNum::new()?;
+    let mut y = BigNum::new()?;
+    ec_key
+        .public_key()
+        .affine_coordinates(group, &mut x, &mut y, &mut ctx)?;
+    Ok((x.to_vec_padded(size)?, y.to_vec_padded(size)?))
+}
+
+/// Computes the name of an object, which is the digest of its public area
+/// prefixed with the name algorithm
+fn object_name(public: &TssPublic) -> Result<Name> {
+    let name_alg = public.name_hashing_algorithm();
+    let md = hash_alg_to_message_digest(name_alg)?;
+    let public_area = public.marshall()?;
+    let digest = hash(md, &public_area)
+        .map_err(|source| TpmError::OpenSSLHasherFinish { source })?;
+    let alg_id: u16 = name_alg.into();
+    Ok(Name::try_from(
+        [alg_id.to_be_bytes().as_slice(), &digest].concat(),
+    )?)
+}
+
+/// Signs data with a key like the TPM does for attestations
+fn sign(
+    key: &PKeyRef<Private>,
+    data: &[u8],
+    hash_alg: HashAlgorithm,
+    sign_alg: SignAlgorithm,
+) -> Result<Signature> {
+    let hashing_alg = HashingAlgorithm::from(hash_alg);
+    let md = hash_alg_to_message_digest(hashing_alg)?;
+    match (sign_alg, key.id()) {
+        (SignAlgorithm::RsaSsa, Id::RSA) => {
+            let sig = rsa_sign(key, md, Padding::PKCS1, data)
+                .map_err(|source| TpmError::OpenSSLSign { source })?;
+            Ok(Signature::RsaSsa(RsaSignature::create(
+                hashing_alg,
+                PublicKeyRsa::try_from(sig)?,
+            )?))
+        }
+        (SignAlgorithm::RsaPss, Id::RSA) => {
+            let sig = rsa_sign(key, md, Padding::PKCS1_PSS, data)
+                .map_err(|source| TpmError::OpenSSLSign { source })?;
+            Ok(Signature::RsaPss(RsaSignature::create(
+                hashing_alg,
+                PublicKeyRsa::try_from(sig)?,
+            )?))
+        }
+        (SignAlgorithm::EcDsa, Id::EC) => {
+            let (r, s) = ecdsa_sign(key, md, data)
+                .map_err(|source| TpmError::OpenSSLSign { source })?;
+            Ok(Signature::EcDsa(EccSignature::create(
+                hashing_alg,
+                EccParameter::try_from(r)?,
+                EccParameter::try_from(s)?,
+            )?))
+        }
+        (other, _) => Err(TpmError::NotImplemented(format!(
+            "Signing with {other} in the mock TPM"
+        ))),
+    }
+}
+
+/// Signs data with an RSA key, using a salt of the size of the digest for
+/// RSA-PSS like the TPM
+fn rsa_sign(
+    key: &PKeyRef<Private>,
+    md: MessageDigest,
+    padding: Padding,
+    data: &[u8],
+) -> std::result::Result<Vec<u8>, ErrorStack> {
+    let mut signer = Signer::new(md, key)?;
+    signer.set_rsa_padding(padding)?;
+    if padding == Padding::PKCS1_PSS {
+        signer.set_rsa_mgf1_md(md)?;
+        signer.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+    }
+    signer.sign_oneshot_to_vec(data)
+}
+
+/// Signs data with an EC key, returning the r and s values padded to the
+/// size of the curve
+fn ecdsa_sign(
+    key: &PKeyRef<Private>,
+    md: MessageDigest,
+    data: &[u8],
+) -> std::result::Result<(Vec<u8>, Vec<u8>), ErrorStack> {
+    let ec_key = key.ec_key()?;
+    let size = ec_key.group().degree().div_ceil(8) as i32;
+    let sig = EcdsaSig::sign(&hash(md, data)?, &ec_key)?;
+    Ok((sig.r().to_vec_padded(size)?, sig.s().to_vec_padded(size)?))
+}
+
+/// Computes an HMAC over the concatenation of `data`
+fn hmac_of(
+    md: MessageDigest,
+    key: &[u8],
+    data: &[&[u8]],
+) -> std::result::Result<Vec<u8>, ErrorStack> {
+    let pkey = PKey::hmac(key)?;
+    let mut signer = Signer::new(md, &pkey)?;
+    for d in data {
+        signer.update(d)?;
+    }
+    signer.sign_to_vec()
+}
+
+/// Gets the name algorithm of the EK, used to protect the credentials
+fn credential_digest(ek: &MockObject) -> Result<MessageDigest> {
+    if ek.key.id() != Id::RSA {
+        return Err(TpmError::NotImplemented(
+            "Credentials for ECC EKs in the mock TPM".to_string(),
+        ));
+    }
+    hash_alg_to_message_digest(ek.public.name_hashing_algorithm())
+}
+
+/// Sets the RSA-OAEP padding with the IDENTITY label used to encrypt the
+/// seed of the credentials
+fn set_identity_padding<T>(
+    ctx: &mut PkeyCtx<T>,
+    md: MessageDigest,
+) -> std::result::Result<(), ErrorStack> {
+    let md = Md::from_nid(md.type_()).ok_or_else(ErrorStack::get)?;
+    ctx.set_rsa_padding(Padding::PKCS1_OAEP)?;
+    ctx.set_rsa_oaep_md(md)?;
+    ctx.set_rsa_mgf1_md(md)?;
+    ctx.set_rsa_oaep_label(IDENTITY_LABEL)
+}
+
+/// Derives the keys protecting a credential from the seed, as described in
+/// the TPM 2.0 specification part 1, section 24
+///
+/// # Returns
+///
+/// The symmetric key encrypting the identity and the key of the HMAC over
+/// the encrypted identity and the name
+fn credential_keys(
+    md: MessageDigest,
+    seed: &[u8],
+    name: &Name,
+) -> std::result::Result<(Vec<u8>, Vec<u8>), ErrorStack> {
+    let sym_key =
+        kdfa(md, seed, b"STORAGE", name.value(), CREDENTIAL_KEY_BITS)?;
+    let hmac_key = kdfa(md, seed, b"INTEGRITY", &[], md.size() as u32 * 8)?;
+    Ok((sym_key, hmac_key))
+}
+
+/// Key derivation function of the TPM, the counter mode KDF of NIST SP
+/// 800-108 with HMAC
+fn kdfa(
+    md: MessageDigest,
+    key: &[u8],
+    label: &[u8],
+    context: &[u8],
+    bits: u32,
+) -> std::result::Result<Vec<u8>, ErrorStack> {
+    let size = bits.div_ceil(8) as usize;
+    let mut derived = Vec::with_capacity(size + md.size());
+    let mut counter: u32 = 1;
+    while derived.len() < size {
+        derived.extend(hmac_of(
+            md,
+            key,
+            &[
+                &counter.to_be_bytes(),
+                label,
+                &[0],
+                context,
+                &bits.to_be_bytes(),
+            ],
+        )?);
+        counter += 1;
+    }
+    derived.truncate(size);
+    Ok(derived)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{get_quoted_pcrs, tss_public_to_pkey, verify_quote};
+    use std::path::Path;
+
+    fn nk_pub() -> PKey<Public> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join("test-rsa.pem");
+        crypto::testing::rsa_import_pair(path).unwrap().0 //#[allow_ci]
+    }
+
+    #[test]
+    fn test_quote() {
+        let mut tpm = MockTpm::new();
+        let ek = tpm.create_ek(EncryptionAlgorithm::Rsa, None).unwrap(); //#[allow_ci]
+        let nk_pub = nk_pub();
+        let digest = [0xaa; 32];
+        tpm.pcr_extend(HashAlgorithm::Sha256, 10, &digest).unwrap(); //#[allow_ci]
+
+        for sign_alg in [
+            SignAlgorithm::RsaSsa,
+            SignAlgorithm::RsaPss,
+            SignAlgorithm::EcDsa,
+        ] {
+            let ak = tpm
+                .create_ak(ek.key_handle, HashAlgorithm::Sha256, sign_alg)
+                .unwrap(); //#[allow_ci]
+            let ak_handle = tpm.load_ak(ek.key_handle, &ak).unwrap(); //#[allow_ci]
+            let ak_pub = tss_public_to_pkey(ak.public).unwrap(); //#[allow_ci]
+
+            let quote = tpm
+                .quote(
+                    b"nonce",
+                    &[(HashAlgorithm::Sha256, 1 << 10)],
+                    &nk_pub,
+                    ak_handle,
+                    HashAlgorithm::Sha256,
+                    sign_alg,
+                )
+                .unwrap(); //#[allow_ci]
+            verify_quote(&quote, &ak_pub, b"nonce", HashAlgorithm::Sha256)
+                .unwrap(); //#[allow_ci]
+            assert!(matches!(
+                verify_quote(
+                    &quote,
+                    &ak_pub,
+                    b"other",
+                    HashAlgorithm::Sha256
+                ),
+                Err(TpmError::QuoteNonceMismatch)
+            ));
+
+            let banks = get_quoted_pcrs(&quote).unwrap(); //#[allow_ci]
+            let (hash_alg, pcrs) = &banks[0];
+            assert_eq!(*hash_alg, HashAlgorithm::Sha256);
+            let expected =
+                hash(MessageDigest::sha256(), &[[0; 32], digest].concat())
+                    .unwrap(); //#[allow_ci]
+            assert_eq!(pcrs[&10], expected.to_vec());
+            assert!(pcrs.contains_key(&16));
+
+            tpm.flush_context(ak_handle.into()).unwrap(); //#[allow_ci]
+        }
+        assert!(matches!(
+            tpm.read_public(KeyHandle::from(FIRST_HANDLE + 1)),
+            Err(TpmError::UnknownHandle(_))
+        ));
+    }
+
+    #[test]
+    fn test_quote_banks() {
+        let mut tpm = MockTpm::new();
+        let ek = tpm.create_ek(EncryptionAlgorithm::Rsa, None).unwrap(); //#[allow_ci]
+        let ak = tpm
+            .create_ak(
+                ek.key_handle,
+                HashAlgorithm::Sha256,
+                SignAlgorithm::RsaSsa,
+            )
+            .unwrap(); //#[allow_ci]
+        let ak_handle = tpm.load_ak(ek.key_handle, &ak).unwrap(); //#[allow_ci]
+        let ak_pub = tss_public_to_pkey(ak.public).unwrap(); //#[allow_ci]
+        tpm.pcr_extend(HashAlgorithm::Sha1, 10, &[0xaa; 20])
+            .unwrap(); //#[allow_ci]
+
+        // Quote all the PCRs, which need several TPML_DIGEST in the PCR blob
+        let quote = tpm
+            .quote(
+                b"nonce",
+                &[
+                    (HashAlgorithm::Sha256, 0xffffff),
+                    (HashAlgorithm::Sha1, 0xffffff),
+                    (HashAlgorithm::Sha384, 0xffffff),
+                ],
+                &nk_pub(),
+                ak_handle,
+                HashAlgorithm::Sha256,
+                SignAlgorithm::RsaSsa,
+            )
+            .unwrap(); //#[allow_ci]
+        verify_quote(&quote, &ak_pub, b"nonce", HashAlgorithm::Sha256)
+            .unwrap(); //#[allow_ci]
+
+        let banks = get_quoted_pcrs(&quote).unwrap(); //#[allow_ci]
+        let hash_algs = banks.iter().map(|(alg, _)| *alg).collect::<Vec<_>>();
+        assert_eq!(
+            hash_algs,
+            vec![
+                HashAlgorithm::Sha256,
+                HashAlgorithm::Sha1,
+                HashAlgorithm::Sha384
+            ]
+        );
+        for (_, pcrs) in &banks {
+            assert_eq!(pcrs.len(), NUM_PCRS);
+        }
+        // Only the bank of the quote hash algorithm has the NK in PCR#16
+        assert_ne!(banks[0].1[&16], vec![0; 32]);
+        assert_eq!(banks[1].1[&16], vec![0; 20]);
+        assert_ne!(banks[1].1[&10], vec![0; 20]);
+        assert_eq!(banks[2].1[&16], vec![0; 48]);
+    }
+
+    #[test]
+    fn test_activate_credential() {
+        let mut tpm = MockTpm::new();
+        let ek = tpm.create_ek(EncryptionAlgorithm::Rsa, None).unwrap(); //#[allow_ci]
+        let ak = tpm
+            .create_ak(
+                ek.key_handle,
+                HashAlgorithm::Sha256,
+                SignAlgorithm::RsaSsa,
+            )
+            .unwrap(); //#[allow_ci]
+        let ak_handle = tpm.load_ak(ek.key_handle, &ak).unwrap(); //#[allow_ci]
+        let name = object_name(&ak.public).unwrap(); //#[allow_ci]
+        let credential = Digest::try_from(b"credential".as_slice()).unwrap(); //#[allow_ci]
+
+        let keyblob = tpm
+            .make_credential(ek.key_handle, credential.clone(), name)
+            .unwrap(); //#[allow_ci]
+        let activated = tpm
+            .activate_credential(keyblob, ak_handle, ek.key_handle)
+            .unwrap(); //#[allow_ci]
+        assert_eq!(activated, credential);
+
+        // A credential made for another object cannot be activated
+        let keyblob = tpm
+            .make_credential(
+                ek.key_handle,
+                credential,
+                object_name(&ek.public).unwrap(), //#[allow_ci]
+            )
+            .unwrap(); //#[allow_ci]
+        assert!(matches!(
+            tpm.activate_credential(keyblob, ak_handle, ek.key_handle),
+            Err(TpmError::CredentialIntegrityMismatch)
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+
+// Typed event data for the events of the UEFI event log.
+//
+// Implements the structures defined in:
+// TCG PC Client Platform Firmware Profile Specification, Version 1.05,
+// Section 10.2 "Event Descriptions"
+
+use super::{EventLogError, EventType, Reader};
+use uuid::Uuid;
+
+/// Data of the EFI variable events (UEFI_VARIABLE_DATA)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UefiVariableData {
+    /// The vendor GUID of the variable
+    pub variable_name: Uuid,
+    /// The name of the variable
+    pub unicode_name: String,
+    /// The contents of the variable
+    pub variable_data: Vec<u8>,
+}
+
+/// Data of the events measuring PE/COFF images (UEFI_IMAGE_LOAD_EVENT)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UefiImageLoadEvent {
+    pub image_location_in_memory: u64,
+    pub image_length_in_memory: u64,
+    pub image_link_time_address: u64,
+    /// The UEFI device path of the image, not decoded
+    pub device_path: Vec<u8>,
+}
+
+/// Data of the events measuring firmware blobs (UEFI_PLATFORM_FIRMWARE_BLOB)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UefiPlatformFirmwareBlob {
+    pub blob_base: u64,
+    pub blob_length: u64,
+}
+
+/// The event data interpreted according to the event type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventData {
+    /// EV_EFI_VARIABLE_DRIVER_CONFIG, EV_EFI_VARIABLE_BOOT,
+    /// EV_EFI_VARIABLE_BOOT2 and EV_EFI_VARIABLE_AUTHORITY
+    EfiVariable(UefiVariableData),
+    /// EV_EFI_BOOT_SERVICES_APPLICATION, EV_EFI_BOOT_SERVICES_DRIVER and
+    /// EV_EFI_RUNTIME_SERVICES_DRIVER
+    ImageLoad(UefiImageLoadEvent),
+    /// EV_EFI_PLATFORM_FIRMWARE_BLOB
+    PlatformFirmwareBlob(UefiPlatformFirmwareBlob),
+    /// EV_EFI_ACTION and EV_ACTION
+    Action(String),
+    /// EV_IPL, usually a NUL terminated string written by the boot loader
+    Ipl(Vec<u8>),
+    /// EV_SEPARATOR
+    Separator(Vec<u8>),
+    /// Any other event type, not interpreted
+    Raw(Vec<u8>),
+}
+
+impl EventData {
+    /// Interprets the data of an event of the given type
+    pub fn parse(
+        event_type: EventType,
+        data: &[u8],
+    ) -> Result<Self, EventLogError> {
+        let invalid = |reason: &str| EventLogError::InvalidEventData {
+            event_type,
+            reason: reason.to_string(),
+        };
+        match event_type {
+            EventType::EfiVariableDriverConfig
+            | EventType::EfiVariableBoot
+            | EventType::EfiVariableBoot2
+            | EventType::EfiVariableAuthority => {
+                let mut reader = Reader::new(data);
+                let guid: [u8; 16] = reader
+                    .read_bytes(16)?
+                    .try_into()
+                    .map_err(|_| invalid("invalid variable GUID"))?;
+                let name_length = reader.read_u64()?;
+                let data_length = reader.read_u64()?;
+                let name = reader.read_bytes(
+                    to_usize(name_length)?
+                        .checked_mul(2)
+                        .ok_or_else(|| invalid("variable name too long"))?,
+                )?;
+                let name = name
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<u16>>();
+                let unicode_name = String::from_utf16(&name)
+                    .map_err(|_| invalid("invalid variable name"))?;
+                let variable_data =
+                    reader.read_bytes(to_usize(data_length)?)?.to_vec();
+                if !reader.is_empty() {
+                    return Err(invalid("unexpected data after variable"));
+                }
+                Ok(EventData::EfiVariable(UefiVariableData {
+                    variable_name: Uuid::from_bytes_le(guid),
+                    unicode_name,
+                    variable_data,
+                }))
+            }
+            EventType::EfiBootServicesApplication
+            | EventType::EfiBootServicesDriver
+            | EventType::EfiRuntimeServicesDriver => {
+                let mut reader = Reader::new(data);
+                let image_location_in_memory = reader.read_u64()?;
+                let image_length_in_memory = reader.read_u64()?;
+                let image_link_time_address = reader.read_u64()?;
+                let device_path_length = reader.read_u64()?;
+                let device_path = reader
+                    .read_bytes(to_usize(device_path_length)?)?
+                    .to_vec();
+                if !reader.is_empty() {
+                    return Err(invalid("unexpected data after device path"));
+                }
+                Ok(EventData::ImageLoad(UefiImageLoadEvent {
+                    image_location_in_memory,
+                    image_length_in_memory,
+                    image_link_time_address,
+                    device_path,
+                }))
+            }
+            EventType::EfiPlatformFirmwareBlob => {
+                let mut reader = Reader::new(data);
+                let blob_base = reader.read_u64()?;
+                let blob_length = reader.read_u64()?;
+                if !reader.is_empty() {
+                    return Err(invalid("unexpected data after blob length"));
+                }
+                Ok(EventData::PlatformFirmwareBlob(
+                    UefiPlatformFirmwareBlob {
+                        blob_base,
+                        blob_length,
+                    },
+                ))
+            }
+            EventType::EfiAction | EventType::Action => {
+                let action = std::str::from_utf8(data)
+                    .map_err(|_| invalid("action is not a valid string"))?;
+                Ok(EventData::Action(action.to_string()))
+            }
+            EventType::Ipl => Ok(EventData::Ipl(data.to_vec())),
+            EventType::Separator => Ok(EventData::Separator(data.to_vec())),
+            _ => Ok(EventData::Raw(data.to_vec())),
+        }
+    }
+
+    /// Encodes the event data as stored in the event log
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            EventData::EfiVariable(variable) => {
+                let name = variable
+                    .unicode_name
+                    .encode_utf16()
+                    .collect::<Vec<u16>>();
+                let mut out = Vec::new();
+                out.extend_from_slice(&variable.variable_name.to_bytes_le());
+                out.extend_from_slice(&(name.len() as u64).to_le_bytes());
+                out.extend_from_slice(
+                    &(variable.variable_data.len() as u64).to_le_bytes(),
+                );
+                for c in name {
+                    out.extend_from_slice(&c.to_le_bytes());
+                }
+                out.extend_from_slice(&variable.variable_data);
+                out
+            }
+            EventData::ImageLoad(image) => {
+                let mut out = Vec::new();
+                out.extend_from_slice(
+                    &image.image_location_in_memory.to_le_bytes(),
+                );
+                out.extend_from_slice(
+                    &image.image_length_in_memory.to_le_bytes(),
+                );
+                out.extend_from_slice(
+                    &image.image_link_time_address.to_le_bytes(),
+                );
+                out.extend_from_slice(
+                    &(image.device_path.len() as u64).to_le_bytes(),
+                );
+                out.extend_from_slice(&image.device_path);
+                out
+            }
+            EventData::PlatformFirmwareBlob(blob) => {
+                let mut out = Vec::new();
+                out.extend_from_slice(&blob.blob_base.to_le_bytes());
+                out.extend_from_slice(&blob.blob_length.to_le_bytes());
+                out
+            }
+            EventData::Action(action) => action.as_bytes().to_vec(),
+            EventData::Ipl(data)
+            | EventData::Separator(data)
+            | EventData::Raw(data) => data.clone(),
+        }
+    }
+}
+
+fn to_usize(value: u64) -> Result<usize, EventLogError> {
+    usize::try_from(value).map_err(|_| EventLogError::TooLarge(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_data_test() {
+        let variable = EventData::EfiVariable(UefiVariableData {
+            variable_name: Uuid::parse_str(
+                "8be4df61-93ca-11d2-aa0d-00e098032b8c",
+            )
+            .unwrap(), //#[allow_ci]
+            unicode_name: "SecureBoot".to_string(),
+            variable_data: vec![0x01],
+        });
+        let bytes = variable.to_bytes();
+        // GUID, name and data lengths, name in UCS-2 and data
+        assert_eq!(bytes.len(), 16 + 8 + 8 + 20 + 1);
+        assert_eq!(&bytes[..4], &[0x61, 0xdf, 0xe4, 0x8b]);
+        let parsed =
+            EventData::parse(EventType::EfiVariableDriverConfig, &bytes)
+                .unwrap(); //#[allow_ci]
+        assert_eq!(parsed, variable);
+
+        // Truncated and trailing data are rejected
+        assert!(EventData::parse(
+            EventType::EfiVariableDriverConfig,
+            &bytes[..bytes.len() - 1]
+        )
+        .is_err());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(
+            EventData::parse(EventType::EfiVariableBoot, &trailing).is_err()
+        );
+    }
+
+    #[test]
+    fn image_load_test() {
+        let image = EventData::ImageLoad(UefiImageLoadEvent {
+            image_location_in_memory: 0x7e5a0000,
+            image_length_in_memory: 0x1b000,
+            image_link_time_address: 0,
+            device_path: vec![0x7f, 0xff, 0x04, 0x00],
+        });
+        let bytes = image.to_bytes();
+        assert_eq!(bytes.len(), 36);
+        let parsed =
+            EventData::parse(EventType::EfiBootServicesApplication, &bytes)
+                .unwrap(); //#[allow_ci]
+        assert_eq!(parsed, image);
+    }
+
+    #[test]
+    fn other_data_test() {
+        let parsed =
+            EventData::parse(EventType::EfiAction, b"Exit Boot Services")
+                .unwrap(); //#[allow_ci]
+        assert_eq!(parsed, EventData::Action("Exit Boot Services".into()));
+        assert!(EventData::parse(EventType::EfiAction, &[0xff]).is_err());
+
+        let parsed =
+            EventData::parse(EventType::Unknown(0x1234), &[1, 2]).unwrap(); //#[allow_ci]
+        assert_eq!(parsed, EventData::Raw(vec![1, 2]));
+        assert_eq!(parsed.to_bytes(), vec![1, 2]);
+    }
+}
@@ -0,0 +1,629 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+
+// Parser for the binary UEFI event log in the crypto agile format.
+//
+// Implements the structures defined in:
+// TCG PC Client Platform Firmware Profile Specification, Version 1.05,
+// Section 10 "Event Logging"
+
+use super::{EventData, PcrMismatch};
+use crate::algorithms::HashAlgorithm;
+use std::{fmt, fs, path::Path};
+use thiserror::Error;
+
+/// Signature of the Spec ID event of crypto agile logs
+const SPEC_ID_EVENT_SIGNATURE: &[u8; 16] = b"Spec ID Event03\0";
+/// Size of the SHA-1 digest in the TCG_PCR_EVENT header
+const SHA1_DIGEST_SIZE: usize = 20;
+
+#[derive(Error, Debug)]
+pub enum EventLogError {
+    /// Failed to read the event log file
+    #[error("Failed to read event log")]
+    Io(#[from] std::io::Error),
+
+    /// The event log ended in the middle of a structure
+    #[error("Unexpected end of event log at offset {offset}: {needed} bytes needed")]
+    Truncated { offset: usize, needed: usize },
+
+    /// A size in the event log cannot be handled in this platform
+    #[error("Size {0} is too large")]
+    TooLarge(u64),
+
+    /// The first event is not a valid Spec ID event
+    #[error("The event log is not in the crypto agile format: {0}")]
+    NotCryptoAgile(String),
+
+    /// A digest uses an algorithm not declared in the Spec ID event
+    #[error("Digest algorithm {0:#06x} not declared in the Spec ID event")]
+    UndeclaredAlgorithm(u16),
+
+    /// The event data does not match the event type
+    #[error("Invalid event data for {event_type}: {reason}")]
+    InvalidEventData {
+        event_type: EventType,
+        reason: String,
+    },
+
+    /// An event lacks the digest for the bank being replayed
+    #[error("Event #{index} has no {hash_alg} digest")]
+    MissingDigest {
+        index: usize,
+        hash_alg: HashAlgorithm,
+    },
+
+    /// Failed to calculate a digest while replaying the event log
+    #[error("Failed to calculate digest")]
+    Hash(#[source] openssl::error::ErrorStack),
+
+    /// A PCR value is not consistent with the event log
+    #[error("{0}")]
+    PcrMismatch(Box<PcrMismatch>),
+}
+
+/// Reads little endian values from the event log
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Reader { data, offset: 0 }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.offset == self.data.len()
+    }
+
+    pub(crate) fn read_bytes(
+        &mut self,
+        size: usize,
+    ) -> Result<&'a [u8], EventLogError> {
+        let remaining = self.data.len() - self.offset;
+        if size > remaining {
+            return Err(EventLogError::Truncated {
+                offset: self.offset,
+                needed: size - remaining,
+            });
+        }
+        let bytes = &self.data[self.offset..self.offset + size];
+        self.offset += size;
+        Ok(bytes)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, EventLogError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, EventLogError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, EventLogError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, EventLogError> {
+        let mut value = [0u8; 8];
+        value.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_le_bytes(value))
+    }
+}
+
+macro_rules! event_types {
+    ($($variant:ident = $value:expr => $name:expr,)*) => {
+        /// The type of an event in the event log
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum EventType {
+            $($variant,)*
+            Unknown(u32),
+        }
+
+        impl From<u32> for EventType {
+            fn from(value: u32) -> Self {
+                match value {
+                    $($value => EventType::$variant,)*
+                    other => EventType::Unknown(other),
+                }
+            }
+        }
+
+        impl From<EventType> for u32 {
+            fn from(event_type: EventType) -> Self {
+                match event_type {
+                    $(EventType::$variant => $value,)*
+                    EventType::Unknown(value) => value,
+                }
+            }
+        }
+
+        impl fmt::Display for EventType {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    $(EventType::$variant => write!(f, $name),)*
+                    EventType::Unknown(value) => write!(f, "{value:#010x}"),
+                }
+            }
+        }
+    };
+}
+
+event_types! {
+    PrebootCert = 0x0000_0000 => "EV_PREBOOT_CERT",
+    PostCode = 0x0000_0001 => "EV_POST_CODE",
+    Unused = 0x0000_0002 => "EV_UNUSED",
+    NoAction = 0x0000_0003 => "EV_NO_ACTION",
+    Separator = 0x0000_0004 => "EV_SEPARATOR",
+    Action = 0x0000_0005 => "EV_ACTION",
+    EventTag = 0x0000_0006 => "EV_EVENT_TAG",
+    SCrtmContents = 0x0000_0007 => "EV_S_CRTM_CONTENTS",
+    SCrtmVersion = 0x0000_0008 => "EV_S_CRTM_VERSION",
+    CpuMicrocode = 0x0000_0009 => "EV_CPU_MICROCODE",
+    PlatformConfigFlags = 0x0000_000a => "EV_PLATFORM_CONFIG_FLAGS",
+    TableOfDevices = 0x0000_000b => "EV_TABLE_OF_DEVICES",
+    CompactHash = 0x0000_000c => "EV_COMPACT_HASH",
+    Ipl = 0x0000_000d => "EV_IPL",
+    IplPartitionData = 0x0000_000e => "EV_IPL_PARTITION_DATA",
+    NonhostCode = 0x0000_000f => "EV_NONHOST_CODE",
+    NonhostConfig = 0x0000_0010 => "EV_NONHOST_CONFIG",
+    NonhostInfo = 0x0000_0011 => "EV_NONHOST_INFO",
+    OmitBootDeviceEvents = 0x0000_0012 => "EV_OMIT_BOOT_DEVICE_EVENTS",
+    EfiVariableDriverConfig = 0x8000_0001 => "EV_EFI_VARIABLE_DRIVER_CONFIG",
+    EfiVariableBoot = 0x8000_0002 => "EV_EFI_VARIABLE_BOOT",
+    EfiBootServicesApplication = 0x8000_0003 => "EV_EFI_BOOT_SERVICES_APPLICATION",
+    EfiBootServicesDriver = 0x8000_0004 => "EV_EFI_BOOT_SERVICES_DRIVER",
+    EfiRuntimeServicesDriver = 0x8000_0005 => "EV_EFI_RUNTIME_SERVICES_DRIVER",
+    EfiGptEvent = 0x8000_0006 => "EV_EFI_GPT_EVENT",
+    EfiAction = 0x8000_0007 => "EV_EFI_ACTION",
+    EfiPlatformFirmwareBlob = 0x8000_0008 => "EV_EFI_PLATFORM_FIRMWARE_BLOB",
+    EfiHandoffTables = 0x8000_0009 => "EV_EFI_HANDOFF_TABLES",
+    EfiPlatformFirmwareBlob2 = 0x8000_000a => "EV_EFI_PLATFORM_FIRMWARE_BLOB2",
+    EfiHandoffTables2 = 0x8000_000b => "EV_EFI_HANDOFF_TABLES2",
+    EfiVariableBoot2 = 0x8000_000c => "EV_EFI_VARIABLE_BOOT2",
+    EfiHcrtmEvent = 0x8000_0010 => "EV_EFI_HCRTM_EVENT",
+    EfiVariableAuthority = 0x8000_00e0 => "EV_EFI_VARIABLE_AUTHORITY",
+    EfiSpdmFirmwareBlob = 0x8000_00e1 => "EV_EFI_SPDM_FIRMWARE_BLOB",
+    EfiSpdmFirmwareConfig = 0x8000_00e2 => "EV_EFI_SPDM_FIRMWARE_CONFIG",
+}
+
+/// Gets the hash algorithm for a TPM_ALG_ID, if supported
+pub fn hash_algorithm_from_id(algorithm_id: u16) -> Option<HashAlgorithm> {
+    match algorithm_id {
+        0x0004 => Some(HashAlgorithm::Sha1),
+        0x000b => Some(HashAlgorithm::Sha256),
+        0x000c => Some(HashAlgorithm::Sha384),
+        0x000d => Some(HashAlgorithm::Sha512),
+        0x0012 => Some(HashAlgorithm::Sm3_256),
+        _ => None,
+    }
+}
+
+/// Gets the TPM_ALG_ID of a hash algorithm
+pub fn hash_algorithm_id(algorithm: HashAlgorithm) -> u16 {
+    match algorithm {
+        HashAlgorithm::Sha1 => 0x0004,
+        HashAlgorithm::Sha256 => 0x000b,
+        HashAlgorithm::Sha384 => 0x000c,
+        HashAlgorithm::Sha512 => 0x000d,
+        HashAlgorithm::Sm3_256 => 0x0012,
+    }
+}
+
+/// A digest algorithm used in the log and its digest size
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlgorithmSize {
+    pub algorithm_id: u16,
+    pub digest_size: u16,
+}
+
+/// The Spec ID event (TCG_EfiSpecIDEventStruct), which declares the digest
+/// algorithms used in the log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecIdEvent {
+    pub platform_class: u32,
+    pub spec_version_minor: u8,
+    pub spec_version_major: u8,
+    pub spec_errata: u8,
+    pub uintn_size: u8,
+    pub digest_sizes: Vec<AlgorithmSize>,
+    pub vendor_info: Vec<u8>,
+}
+
+impl SpecIdEvent {
+    fn parse(data: &[u8]) -> Result<Self, EventLogError> {
+        let mut reader = Reader::new(data);
+        if reader.read_bytes(SPEC_ID_EVENT_SIGNATURE.len())?
+            != SPEC_ID_EVENT_SIGNATURE
+        {
+            return Err(EventLogError::NotCryptoAgile(
+                "invalid Spec ID event signature".to_string(),
+            ));
+        }
+        let platform_class = reader.read_u32()?;
+        let spec_version_minor = reader.read_u8()?;
+        let spec_version_major = reader.read_u8()?;
+        let spec_errata = reader.read_u8()?;
+        let uintn_size = reader.read_u8()?;
+        let number_of_algorithms = reader.read_u32()?;
+        let mut digest_sizes = Vec::new();
+        for _ in 0..number_of_algorithms {
+            digest_sizes.push(AlgorithmSize {
+                algorithm_id: reader.read_u16()?,
+                digest_size: reader.read_u16()?,
+            });
+        }
+        let vendor_info_size = reader.read_u8()?;
+        let vendor_info =
+            reader.read_bytes(vendor_info_size as usize)?.to_vec();
+        if !reader.is_empty() {
+            return Err(EventLogError::NotCryptoAgile(
+                "unexpected data after the Spec ID event".to_string(),
+            ));
+        }
+        Ok(SpecIdEvent {
+            platform_class,
+            spec_version_minor,
+            spec_version_major,
+            spec_errata,
+            uintn_size,
+            digest_sizes,
+            vendor_info,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = SPEC_ID_EVENT_SIGNATURE.to_vec();
+        out.extend_from_slice(&self.platform_class.to_le_bytes());
+        out.extend_from_slice(&[
+            self.spec_version_minor,
+            self.spec_version_major,
+            self.spec_errata,
+            self.uintn_size,
+        ]);
+        out.extend_from_slice(
+            &(self.digest_sizes.len() as u32).to_le_bytes(),
+        );
+        for size in &self.digest_sizes {
+            out.extend_from_slice(&size.algorithm_id.to_le_bytes());
+            out.extend_from_slice(&size.digest_size.to_le_bytes());
+        }
+        out.push(self.vendor_info.len() as u8);
+        out.extend_from_slice(&self.vendor_info);
+        out
+    }
+
+    /// Gets the digest size of an algorithm declared in the log
+    pub fn digest_size(&self, algorithm_id: u16) -> Option<usize> {
+        self.digest_sizes
+            .iter()
+            .find(|s| s.algorithm_id == algorithm_id)
+            .map(|s| s.digest_size as usize)
+    }
+}
+
+/// A digest of an event (TPMT_HA)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventDigest {
+    pub algorithm_id: u16,
+    pub value: Vec<u8>,
+}
+
+impl EventDigest {
+    /// Gets the hash algorithm of the digest, if supported
+    pub fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        hash_algorithm_from_id(self.algorithm_id)
+    }
+}
+
+/// An event of the crypto agile log (TCG_PCR_EVENT2)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub pcr_index: u32,
+    pub event_type: EventType,
+    pub digests: Vec<EventDigest>,
+    /// The raw event data, see event_data() for its interpretation
+    pub data: Vec<u8>,
+}
+
+impl Event {
+    fn parse(
+        reader: &mut Reader,
+        spec_id: &SpecIdEvent,
+    ) -> Result<Self, EventLogError> {
+        let pcr_index = reader.read_u32()?;
+        let event_type = EventType::from(reader.read_u32()?);
+        let count = reader.read_u32()?;
+        let mut digests = Vec::new();
+        for _ in 0..count {
+            let algorithm_id = reader.read_u16()?;
+            let size = spec_id
+                .digest_size(algorithm_id)
+                .ok_or(EventLogError::UndeclaredAlgorithm(algorithm_id))?;
+            digests.push(EventDigest {
+                algorithm_id,
+                value: reader.read_bytes(size)?.to_vec(),
+            });
+        }
+        let size = reader.read_u32()?;
+        let data = reader.read_bytes(size as usize)?.to_vec();
+        Ok(Event {
+            pcr_index,
+            event_type,
+            digests,
+            data,
+        })
+    }
+
+    fn to_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.pcr_index.to_le_bytes());
+        out.extend_from_slice(&u32::from(self.event_type).to_le_bytes());
+        out.extend_from_slice(&(self.digests.len() as u32).to_le_bytes());
+        for digest in &self.digests {
+            out.extend_from_slice(&digest.algorithm_id.to_le_bytes());
+            out.extend_from_slice(&digest.value);
+        }
+        out.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.data);
+    }
+
+    /// Gets the digest of the event for the given algorithm
+    pub fn digest(&self, algorithm: HashAlgorithm) -> Option<&[u8]> {
+        let algorithm_id = hash_algorithm_id(algorithm);
+        self.digests
+            .iter()
+            .find(|d| d.algorithm_id == algorithm_id)
+            .map(|d| d.value.as_slice())
+    }
+
+    /// Interprets the event data according to the event type
+    pub fn event_data(&self) -> Result<EventData, EventLogError> {
+        EventData::parse(self.event_type, &self.data)
+    }
+}
+
+/// The UEFI event log in the crypto agile format
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventLog {
+    /// The PCR index of the Spec ID event, usually 0
+    pub spec_id_pcr_index: u32,
+    /// The Spec ID event, stored in the first event of the log
+    pub spec_id: SpecIdEvent,
+    /// The events following the Spec ID event
+    pub events: Vec<Event>,
+}
+
+impl EventLog {
+    /// Parses the binary event log, e.g. the contents of
+    /// /sys/kernel/security/tpm0/binary_bios_measurements
+    pub fn parse(data: &[u8]) -> Result<Self, EventLogError> {
+        let mut reader = Reader::new(data);
+
+        // The first event uses the TCG_PCR_EVENT format with a SHA-1 digest
+        let spec_id_pcr_index = reader.read_u32()?;
+        let event_type = EventType::from(reader.read_u32()?);
+        if event_type != EventType::NoAction {
+            return Err(EventLogError::NotCryptoAgile(format!(
+                "first event has type {event_type}"
+            )));
+        }
+        if reader.read_bytes(SHA1_DIGEST_SIZE)?.iter().any(|b| *b != 0) {
+            return Err(EventLogError::NotCryptoAgile(
+                "first event digest is not zero".to_string(),
+            ));
+        }
+        let size = reader.read_u32()?;
+        let spec_id = SpecIdEvent::parse(reader.read_bytes(size as usize)?)?;
+
+        let mut events = Vec::new();
+        while !reader.is_empty() {
+            events.push(Event::parse(&mut reader, &spec_id)?);
+        }
+
+        Ok(EventLog {
+            spec_id_pcr_index,
+            spec_id,
+            events,
+        })
+    }
+
+    /// Reads and parses the binary event log from a file
+    pub fn from_file(path: &Path) -> Result<Self, EventLogError> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    /// Encodes the event log in the binary format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let spec_id = self.spec_id.to_bytes();
+        out.extend_from_slice(&self.spec_id_pcr_index.to_le_bytes());
+        out.extend_from_slice(&u32::from(EventType::NoAction).to_le_bytes());
+        out.extend_from_slice(&[0u8; SHA1_DIGEST_SIZE]);
+        out.extend_from_slice(&(spec_id.len() as u32).to_le_bytes());
+        out.extend_from_slice(&spec_id);
+        for event in &self.events {
+            event.to_bytes(&mut out);
+        }
+        out
+    }
+
+    /// Gets the hash algorithms declared in the log
+    pub fn hash_algorithms(&self) -> Vec<HashAlgorithm> {
+        self.spec_id
+            .digest_sizes
+            .iter()
+            .filter_map(|s| hash_algorithm_from_id(s.algorithm_id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uefi::{UefiImageLoadEvent, UefiVariableData};
+    use std::path::PathBuf;
+
+    fn sample_log_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data/uefi")
+            .join(name)
+    }
+
+    #[test]
+    fn parse_sample_log_test() {
+        let log =
+            EventLog::from_file(&sample_log_path("binary_bios_measurements"))
+                .unwrap(); //#[allow_ci]
+        assert_eq!(log.spec_id.spec_version_major, 2);
+        assert_eq!(log.spec_id.uintn_size, 2);
+        assert_eq!(
+            log.hash_algorithms(),
+            vec![HashAlgorithm::Sha1, HashAlgorithm::Sha256]
+        );
+        assert_eq!(log.events.len(), 24);
+
+        // Every event has a digest for each declared algorithm
+        for event in &log.events {
+            assert_eq!(event.digest(HashAlgorithm::Sha1).unwrap().len(), 20); //#[allow_ci]
+            assert_eq!(
+                event.digest(HashAlgorithm::Sha256).unwrap().len(), //#[allow_ci]
+                32
+            );
+            assert!(event.digest(HashAlgorithm::Sha384).is_none());
+            assert!(event.event_data().is_ok());
+        }
+
+        let secure_boot = &log.events[3];
+        assert_eq!(secure_boot.pcr_index, 7);
+        assert_eq!(
+            secure_boot.event_type,
+            EventType::EfiVariableDriverConfig
+        );
+        assert_eq!(
+            secure_boot.event_data().unwrap(), //#[allow_ci]
+            EventData::EfiVariable(UefiVariableData {
+                variable_name: uuid::Uuid::parse_str(
+                    "8be4df61-93ca-11d2-aa0d-00e098032b8c"
+                )
+                .unwrap(), //#[allow_ci]
+                unicode_name: "SecureBoot".to_string(),
+                variable_data: vec![1],
+            })
+        );
+
+        let separators = log
+            .events
+            .iter()
+            .filter(|e| e.event_type == EventType::Separator)
+            .map(|e| e.pcr_index)
+            .collect::<Vec<u32>>();
+        assert_eq!(separators, (0..8).collect::<Vec<u32>>());
+
+        let image = log
+            .events
+            .iter()
+            .find(|e| e.event_type == EventType::EfiBootServicesApplication)
+            .unwrap(); //#[allow_ci]
+        let EventData::ImageLoad(UefiImageLoadEvent {
+            image_location_in_memory,
+            device_path,
+            ..
+        }) = image.event_data().unwrap()
+        //#[allow_ci]
+        else {
+            panic!("Unexpected event data"); //#[allow_ci]
+        };
+        assert_eq!(image_location_in_memory, 0x7e5a0000);
+        assert_eq!(
+            &device_path[device_path.len() - 4..],
+            &[0x7f, 0xff, 4, 0]
+        );
+
+        let last = log.events.last().unwrap(); //#[allow_ci]
+        assert_eq!(last.pcr_index, 9);
+        assert_eq!(
+            last.event_data().unwrap(), //#[allow_ci]
+            EventData::Ipl(b"/boot/initrd.img\0".to_vec())
+        );
+    }
+
+    #[test]
+    fn round_trip_test() {
+        for name in [
+            "binary_bios_measurements",
+            "binary_bios_measurements_sha256",
+        ] {
+            let data = fs::read(sample_log_path(name)).unwrap(); //#[allow_ci]
+            let log = EventLog::parse(&data).unwrap(); //#[allow_ci]
+            assert_eq!(log.to_bytes(), data, "{name}");
+
+            // The typed event data is encoded back to the same bytes
+            for event in &log.events {
+                let event_data = event.event_data().unwrap(); //#[allow_ci]
+                assert_eq!(event_data.to_bytes(), event.data, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn sha256_log_test() {
+        let log = EventLog::from_file(&sample_log_path(
+            "binary_bios_measurements_sha256",
+        ))
+        .unwrap(); //#[allow_ci]
+        assert_eq!(log.hash_algorithms(), vec![HashAlgorithm::Sha256]);
+        assert!(log.events.iter().all(|e| e.digests.len() == 1
+            && e.digest(HashAlgorithm::Sha1).is_none()));
+    }
+
+    #[test]
+    fn invalid_log_test() {
+        let data =
+            fs::read(sample_log_path("binary_bios_measurements")).unwrap(); //#[allow_ci]
+
+        // Truncated log
+        assert!(matches!(
+            EventLog::parse(&data[..data.len() - 1]),
+            Err(EventLogError::Truncated { needed: 1, .. })
+        ));
+        assert!(matches!(
+            EventLog::parse(&[]),
+            Err(EventLogError::Truncated { offset: 0, .. })
+        ));
+
+        // The Spec ID event signature is checked
+        let mut legacy = data.clone();
+        legacy[32] = b'X';
+        assert!(matches!(
+            EventLog::parse(&legacy),
+            Err(EventLogError::NotCryptoAgile(_))
+        ));
+
+        // A digest algorithm which was not declared cannot be parsed
+        let mut undeclared = data.clone();
+        let offset = EventLog::parse(&data).unwrap().spec_id.to_bytes().len() //#[allow_ci]
+            + 32
+            + 12;
+        undeclared[offset] = 0x0c;
+        assert!(matches!(
+            EventLog::parse(&undeclared),
+            Err(EventLogError::UndeclaredAlgorithm(0x000c))
+        ));
+    }
+
+    #[test]
+    fn event_type_test() {
+        assert_eq!(
+            EventType::from(0x8000_0001),
+            EventType::EfiVariableDriverConfig
+        );
+        assert_eq!(u32::from(EventType::Ipl), 0xd);
+        assert_eq!(EventType::Separator.to_string(), "EV_SEPARATOR");
+        assert_eq!(EventType::from(0x1234), EventType::Unknown(0x1234));
+        assert_eq!(EventType::Unknown(0x1234).to_string(), "0x00001234");
+        assert_eq!(u32::from(EventType::Unknown(0x1234)), 0x1234);
+    }
+}
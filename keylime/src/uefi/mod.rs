@@ -0,0 +1,7 @@
+mod event_data;
+mod event_log;
+mod replay;
+
+pub use event_data::*;
+pub use event_log::*;
+pub use replay::*;
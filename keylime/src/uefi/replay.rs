@@ -0,0 +1,305 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright 2025 Keylime Authors
+
+// Replay of the UEFI event log to calculate the expected PCR values.
+
+use super::{EventLog, EventLogError, EventType};
+use crate::algorithms::HashAlgorithm;
+use openssl::hash::{hash, MessageDigest};
+use std::{collections::BTreeMap, fmt};
+
+/// Signature of the EV_NO_ACTION event setting the initial value of PCR 0
+const STARTUP_LOCALITY_SIGNATURE: &[u8] = b"StartupLocality\0";
+
+/// Describes the first PCR whose value does not match the event log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PcrMismatch {
+    pub hash_alg: HashAlgorithm,
+    pub pcr_index: u32,
+    /// The value obtained replaying the event log
+    pub replayed: Vec<u8>,
+    /// The value of the PCR
+    pub quoted: Vec<u8>,
+    /// The index in the log and the type of the first event which is not
+    /// reflected in the PCR value, if it could be identified
+    pub event: Option<(usize, EventType)>,
+}
+
+impl fmt::Display for PcrMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PCR {} ({}) does not match the event log: replayed {}, quoted {}",
+            self.pcr_index,
+            self.hash_alg,
+            hex::encode(&self.replayed),
+            hex::encode(&self.quoted)
+        )?;
+        match self.event {
+            Some((index, event_type)) => write!(
+                f,
+                "; first inconsistent event is #{index} ({event_type})"
+            ),
+            None => write!(
+                f,
+                "; no prefix of the log matches, events may be missing"
+            ),
+        }
+    }
+}
+
+impl EventLog {
+    /// Gets the locality from which the TPM was started, recorded by the
+    /// StartupLocality EV_NO_ACTION event. PCR 0 starts with the locality in
+    /// its last byte instead of zeros
+    pub fn startup_locality(&self) -> Option<u8> {
+        self.events.iter().find_map(|event| {
+            match (event.event_type, event.data.split_last()) {
+                (EventType::NoAction, Some((locality, signature)))
+                    if event.pcr_index == 0
+                        && signature == STARTUP_LOCALITY_SIGNATURE =>
+                {
+                    Some(*locality)
+                }
+                _ => None,
+            }
+        })
+    }
+}
+
+/// Gets the initial PCR values for the given algorithm. All PCRs start with
+/// zeros, except PCR 0 when the log records the locality of the startup
+fn initial_values(
+    log: &EventLog,
+    hash_alg: HashAlgorithm,
+) -> BTreeMap<u32, Vec<u8>> {
+    let size = MessageDigest::from(hash_alg).size();
+    let mut values = BTreeMap::new();
+    if let Some(locality) = log.startup_locality() {
+        let mut pcr0 = vec![0u8; size];
+        pcr0[size - 1] = locality;
+        let _ = values.insert(0, pcr0);
+    }
+    values
+}
+
+/// Replays the event log, yielding the index of each event extended into a
+/// PCR and the PCR value after extending it
+fn replay_events(
+    log: &EventLog,
+    hash_alg: HashAlgorithm,
+    mut on_extend: impl FnMut(usize, u32, &[u8]),
+) -> Result<BTreeMap<u32, Vec<u8>>, EventLogError> {
+    let digest = MessageDigest::from(hash_alg);
+    let mut values = initial_values(log, hash_alg);
+    for (index, event) in log.events.iter().enumerate() {
+        // EV_NO_ACTION events are informative and not extended
+        if event.event_type == EventType::NoAction {
+            continue;
+        }
+        let event_digest = event
+            .digest(hash_alg)
+            .ok_or(EventLogError::MissingDigest { index, hash_alg })?;
+        let value = values
+            .entry(event.pcr_index)
+            .or_insert_with(|| vec![0u8; digest.size()]);
+        let mut data = value.clone();
+        data.extend_from_slice(event_digest);
+        *value = hash(digest, &data).map_err(EventLogError::Hash)?.to_vec();
+        on_extend(index, event.pcr_index, value);
+    }
+    Ok(values)
+}
+
+/// Calculates the PCR values expected from the event log for the given
+/// algorithm. Only the PCRs with events in the log are included
+pub fn replay(
+    log: &EventLog,
+    hash_alg: HashAlgorithm,
+) -> Result<BTreeMap<u32, Vec<u8>>, EventLogError> {
+    let initial = initial_values(log, hash_alg);
+    let mut extended = Vec::new();
+    let mut values = replay_events(log, hash_alg, |_, pcr_index, _| {
+        extended.push(pcr_index)
+    })?;
+    // PCRs with only the initial value set have no events
+    values.retain(|pcr_index, _| {
+        extended.contains(pcr_index) || !initial.contains_key(pcr_index)
+    });
+    Ok(values)
+}
+
+/// Checks the PCR values, e.g. obtained from a quote, against the event log
+///
+/// Only the PCRs with events in the log are checked, as the other PCRs may
+/// be extended by other components, like IMA.
+///
+/// # Arguments
+///
+/// * log (&EventLog): The event log
+/// * hash_alg (HashAlgorithm): The algorithm of the PCR bank
+/// * pcrs (&BTreeMap<u32, Vec<u8>>): The PCR values, indexed by PCR number
+///
+/// # Returns
+///
+/// EventLogError::PcrMismatch describing the first PCR, in ascending order,
+/// which does not match the event log
+pub fn check_pcrs(
+    log: &EventLog,
+    hash_alg: HashAlgorithm,
+    pcrs: &BTreeMap<u32, Vec<u8>>,
+) -> Result<(), EventLogError> {
+    // Record the last event after which each PCR matched the quoted value
+    let mut matched_at: BTreeMap<u32, usize> = BTreeMap::new();
+    let mut first_event: BTreeMap<u32, usize> = BTreeMap::new();
+    let replayed = replay(log, hash_alg)?;
+    let initial = initial_values(log, hash_alg);
+    let _ = replay_events(log, hash_alg, |index, pcr_index, value| {
+        let _ = first_event.entry(pcr_index).or_insert(index);
+        if pcrs.get(&pcr_index).map(Vec::as_slice) == Some(value) {
+            let _ = matched_at.insert(pcr_index, index);
+        }
+    })?;
+
+    for (pcr_index, replayed) in replayed {
+        let Some(quoted) = pcrs.get(&pcr_index) else {
+            continue;
+        };
+        if *quoted == replayed {
+            continue;
+        }
+
+        // If the quoted value was reached in the middle of the replay, the
+        // events following that point are not reflected in the PCR
+        let zeros = vec![0u8; replayed.len()];
+        let initial = initial.get(&pcr_index).unwrap_or(&zeros);
+        let start = match matched_at.get(&pcr_index) {
+            Some(index) => Some(index + 1),
+            _ if quoted == initial => first_event.get(&pcr_index).copied(),
+            _ => None,
+        };
+        let event = start.and_then(|start| {
+            log.events
+                .iter()
+                .enumerate()
+                .skip(start)
+                .find(|(_, e)| {
+                    e.pcr_index == pcr_index
+                        && e.event_type != EventType::NoAction
+                })
+                .map(|(index, e)| (index, e.event_type))
+        });
+
+        return Err(EventLogError::PcrMismatch(Box::new(PcrMismatch {
+            hash_alg,
+            pcr_index,
+            replayed,
+            quoted: quoted.clone(),
+            event,
+        })));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn sample_log() -> EventLog {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data/uefi/binary_bios_measurements");
+        EventLog::from_file(&path).unwrap() //#[allow_ci]
+    }
+
+    fn decode(value: &str) -> Vec<u8> {
+        hex::decode(value).unwrap() //#[allow_ci]
+    }
+
+    #[test]
+    fn replay_test() {
+        let log = sample_log();
+        assert_eq!(log.startup_locality(), Some(0));
+        let pcrs = replay(&log, HashAlgorithm::Sha256).unwrap(); //#[allow_ci]
+        assert_eq!(
+            pcrs.keys().copied().collect::<Vec<u32>>(),
+            vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
+        );
+        assert_eq!(
+            pcrs[&0],
+            decode(
+                "e7be83695d565f0b25aee11c206322f3c3b59f3d7cef15f89ad06340cc65bee2"
+            )
+        );
+        assert_eq!(
+            pcrs[&7],
+            decode(
+                "fb34927acf2fa52529831f02ca7977a65adb11c86e70271d99eb745059aa86d7"
+            )
+        );
+
+        let pcrs = replay(&log, HashAlgorithm::Sha1).unwrap(); //#[allow_ci]
+        assert_eq!(
+            pcrs[&2],
+            decode("b2a83b0ebf2f8374299a5b2bdfc31ea955ad7236")
+        );
+
+        // The bank must be present in the log
+        assert!(matches!(
+            replay(&log, HashAlgorithm::Sha384),
+            Err(EventLogError::MissingDigest { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn check_pcrs_test() {
+        let log = sample_log();
+        let mut pcrs = replay(&log, HashAlgorithm::Sha256).unwrap(); //#[allow_ci]
+                                                                     // PCRs without events in the log are ignored
+        let _ = pcrs.insert(10, vec![0xaa; 32]);
+        assert!(check_pcrs(&log, HashAlgorithm::Sha256, &pcrs).is_ok());
+
+        // Drop the last event of the log, so the log has fewer events than
+        // the PCR reflects and no prefix matches
+        let mut short_log = log.clone();
+        let _ = short_log.events.pop();
+        let Err(EventLogError::PcrMismatch(mismatch)) =
+            check_pcrs(&short_log, HashAlgorithm::Sha256, &pcrs)
+        else {
+            panic!("Expected a PCR mismatch"); //#[allow_ci]
+        };
+        assert_eq!(mismatch.pcr_index, 9);
+        assert_eq!(mismatch.event, None);
+
+        // An event in the log which was not extended into the PCR is
+        // identified, as the PCR matches an intermediate value
+        let mut extra_log = log.clone();
+        let mut extra = log.events[22].clone();
+        extra.digests.iter_mut().for_each(|d| d.value[0] ^= 0xff);
+        extra_log.events.push(extra);
+        let Err(EventLogError::PcrMismatch(mismatch)) =
+            check_pcrs(&extra_log, HashAlgorithm::Sha256, &pcrs)
+        else {
+            panic!("Expected a PCR mismatch"); //#[allow_ci]
+        };
+        assert_eq!(mismatch.pcr_index, 9);
+        assert_eq!(mismatch.event, Some((24, EventType::Ipl)));
+        assert_eq!(mismatch.quoted, pcrs[&9]);
+        assert!(mismatch
+            .to_string()
+            .contains("first inconsistent event is #24 (EV_IPL)"));
+
+        // The first mismatching PCR is reported
+        let mut wrong = pcrs.clone();
+        let _ = wrong.insert(1, vec![0u8; 32]);
+        let _ = wrong.insert(4, vec![0u8; 32]);
+        let Err(EventLogError::PcrMismatch(mismatch)) =
+            check_pcrs(&log, HashAlgorithm::Sha256, &wrong)
+        else {
+            panic!("Expected a PCR mismatch"); //#[allow_ci]
+        };
+        assert_eq!(mismatch.pcr_index, 1);
+        // The PCR was never extended, so all its events are inconsistent
+        assert_eq!(mismatch.event, Some((9, EventType::Separator)));
+    }
+}
@@ -39,6 +39,23 @@ function start_swtpm {
     SWTPM_PID=$!
 }
 
+function wait_for_swtpm {
+    # Wait for swtpm to accept connections on its control and server ports
+    for _ in $(seq 50); do
+        if ! kill -0 "$SWTPM_PID" 2>/dev/null; then
+            echo "swtpm exited before accepting connections"
+            return 1
+        fi
+        if (exec 3<>/dev/tcp/127.0.0.1/2322) 2>/dev/null && \
+            (exec 3<>/dev/tcp/127.0.0.1/2321) 2>/dev/null; then
+            return 0
+        fi
+        sleep 0.1
+    done
+    echo "Timed out waiting for swtpm to accept connections"
+    return 1
+}
+
 function stop_swtpm {
     # Stop swtpm if running
     if [[ -n "$SWTPM_PID" ]]; then
@@ -65,4 +82,28 @@ export TPM2TOOLS_TCTI=swtpm
 export TPM2OPENSSL_TCTI=swtpm
 
 start_swtpm
+wait_for_swtpm
+
+# Optionally seed PCR 10 with a recorded IMA measurement list, given as the
+# IMA_LOG environment variable
+if [[ -n "${IMA_LOG:-}" ]]; then
+    echo "-------- Replaying IMA measurement list ${IMA_LOG}"
+    cargo run --bin keylime_ima_emulator -- \
+        --mode once \
+        --hash_algs sha256 \
+        --ima_log "${IMA_LOG}"
+fi
+
+# Optionally seed the measured boot PCRs with a recorded UEFI event log,
+# given as the MB_LOG environment variable. The log is copied to
+# MB_LOG_OUTPUT, which the agent is pointed at as its measured boot log
+if [[ -n "${MB_LOG:-}" ]]; then
+    echo "-------- Replaying UEFI event log ${MB_LOG}"
+    MB_LOG_OUTPUT="${MB_LOG_OUTPUT:-${TEMPDIR:-/tmp}/binary_bios_measurements}"
+    cargo run --bin keylime_ima_emulator -- measured-boot \
+        --event-log "${MB_LOG}" \
+        --output "${MB_LOG_OUTPUT}"
+    export KEYLIME_AGENT_MEASUREDBOOT_ML_PATH="${MB_LOG_OUTPUT}"
+fi
+
 bash